@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE link_clicks (
+    id SERIAL PRIMARY KEY,
+    link_id INTEGER NOT NULL,
+    domain_id INTEGER NOT NULL,
+    referrer TEXT,
+    user_agent TEXT,
+    ip_hash VARCHAR(64),
+    clicked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (link_id) REFERENCES links(id) ON DELETE CASCADE,
+    FOREIGN KEY (domain_id) REFERENCES domains(id) ON DELETE CASCADE
+);
+
+CREATE INDEX link_clicks_link_id_clicked_at_idx ON link_clicks (link_id, clicked_at);
@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE links
+    DROP COLUMN IF EXISTS expires_at,
+    DROP COLUMN IF EXISTS max_clicks,
+    DROP COLUMN IF EXISTS click_count,
+    DROP COLUMN IF EXISTS archived_at;
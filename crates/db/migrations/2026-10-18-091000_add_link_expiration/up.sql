@@ -0,0 +1,7 @@
+-- Your SQL goes here
+
+ALTER TABLE links
+    ADD COLUMN expires_at TIMESTAMP DEFAULT NULL,
+    ADD COLUMN max_clicks INTEGER DEFAULT NULL,
+    ADD COLUMN click_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN archived_at TIMESTAMP DEFAULT NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE links DROP COLUMN IF EXISTS password_hash;
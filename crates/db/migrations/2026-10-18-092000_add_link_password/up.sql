@@ -0,0 +1,3 @@
+-- Your SQL goes here
+
+ALTER TABLE links ADD COLUMN password_hash TEXT DEFAULT NULL;
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+
+DROP TRIGGER IF EXISTS check_link_slug_collision ON links;
+DROP FUNCTION IF EXISTS check_link_slug_collision();
+
+DROP INDEX IF EXISTS links_domain_id_slug_idx;
+DROP INDEX IF EXISTS links_domain_id_custom_slug_idx;
+
+ALTER TABLE links ADD CONSTRAINT links_domain_id_slug_key UNIQUE (domain_id, slug);
+ALTER TABLE links ADD CONSTRAINT links_domain_id_custom_slug_key UNIQUE (domain_id, custom_slug);
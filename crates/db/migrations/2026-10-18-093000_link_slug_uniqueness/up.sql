@@ -0,0 +1,31 @@
+-- Your SQL goes here
+
+-- Replace the anonymous table constraints with named unique indexes
+ALTER TABLE links DROP CONSTRAINT IF EXISTS links_domain_id_slug_key;
+ALTER TABLE links DROP CONSTRAINT IF EXISTS links_domain_id_custom_slug_key;
+
+CREATE UNIQUE INDEX IF NOT EXISTS links_domain_id_slug_idx ON links (domain_id, slug);
+CREATE UNIQUE INDEX IF NOT EXISTS links_domain_id_custom_slug_idx ON links (domain_id, custom_slug);
+
+-- Slugs and custom slugs share a namespace per domain, which a unique index can't express.
+-- Reject collisions between the two columns with the same error code as the indexes.
+CREATE OR REPLACE FUNCTION check_link_slug_collision()
+RETURNS trigger AS $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM links
+        WHERE domain_id = NEW.domain_id
+        AND id <> NEW.id
+        AND (slug = NEW.custom_slug OR custom_slug = NEW.slug)
+    ) THEN
+        RAISE EXCEPTION 'Slug already exists on domain %', NEW.domain_id
+            USING ERRCODE = 'unique_violation';
+    END IF;
+
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER check_link_slug_collision
+    BEFORE INSERT OR UPDATE OF domain_id, slug, custom_slug ON links
+    FOR EACH ROW EXECUTE PROCEDURE check_link_slug_collision();
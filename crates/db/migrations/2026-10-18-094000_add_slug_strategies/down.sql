@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP SEQUENCE IF EXISTS link_slug_seq;
+
+ALTER TABLE domains DROP COLUMN IF EXISTS slug_strategy;
@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+ALTER TABLE domains ADD COLUMN slug_strategy VARCHAR(32) DEFAULT NULL;
+
+-- Source of unique numbers for sequential slugs
+CREATE SEQUENCE IF NOT EXISTS link_slug_seq;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE links DROP COLUMN IF EXISTS redirect_type;
+ALTER TABLE domains DROP COLUMN IF EXISTS redirect_type;
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+
+-- NULL inherits the redirect type from the domain, and then from the app config
+ALTER TABLE domains ADD COLUMN redirect_type INTEGER DEFAULT NULL
+    CHECK (redirect_type IN (301, 302, 307, 308));
+
+ALTER TABLE links ADD COLUMN redirect_type INTEGER DEFAULT NULL
+    CHECK (redirect_type IN (301, 302, 307, 308));
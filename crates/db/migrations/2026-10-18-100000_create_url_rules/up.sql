@@ -0,0 +1,10 @@
+-- Your SQL goes here
+
+-- Destination domains that links may (allow) or may not (block) point to, on top of the ones in the config
+CREATE TABLE url_rules (
+    id SERIAL PRIMARY KEY,
+    domain VARCHAR(255) NOT NULL,
+    kind VARCHAR(16) NOT NULL CHECK (kind IN ('block', 'allow')),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (domain, kind)
+);
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+
+CREATE TABLE api_keys (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    -- SHA-256 of the key, the key itself is only shown once on creation
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    -- Start of the key, so users can tell their keys apart
+    token_prefix VARCHAR(16) NOT NULL,
+    -- Space separated, e.g. "links:read links:write"
+    scopes TEXT NOT NULL,
+    last_used_at TIMESTAMP,
+    expires_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX api_keys_user_id_idx ON api_keys (user_id);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+
+CREATE TABLE email_change_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    -- The address the user wants to switch to, it only replaces users.email once confirmed
+    new_email VARCHAR(255) NOT NULL,
+    -- SHA-256 of the token, the token itself is only sent by email
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX email_change_tokens_user_id_idx ON email_change_tokens (user_id);
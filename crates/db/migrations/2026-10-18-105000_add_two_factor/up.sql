@@ -0,0 +1,21 @@
+-- Your SQL goes here
+
+-- Base32 TOTP secret. It's set when enrollment starts, but 2FA is only on once totp_enabled_at is set.
+ALTER TABLE users ADD COLUMN totp_secret VARCHAR(64);
+ALTER TABLE users ADD COLUMN totp_enabled_at TIMESTAMP;
+-- Last accepted TOTP time step, so a code can't be used twice
+ALTER TABLE users ADD COLUMN totp_last_step BIGINT;
+-- Codes tried since the last accepted one, too many lock 2FA logins until totp_locked_until
+ALTER TABLE users ADD COLUMN totp_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN totp_locked_until TIMESTAMP;
+
+CREATE TABLE recovery_codes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the normalized code
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (user_id, code_hash)
+);
@@ -0,0 +1,50 @@
+-- Your SQL goes here
+
+CREATE TABLE roles (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL UNIQUE,
+    description TEXT,
+    -- Built-in roles can't be deleted
+    built_in BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE role_permissions (
+    role_id INTEGER NOT NULL,
+    -- e.g. link.create or domain.manage
+    permission VARCHAR(64) NOT NULL,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission)
+);
+
+CREATE TABLE user_roles (
+    user_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, role_id)
+);
+
+CREATE INDEX user_roles_role_id_idx ON user_roles (role_id);
+
+INSERT INTO roles (name, description, built_in) VALUES
+    ('admin', 'Can do everything', TRUE),
+    ('user', 'Can shorten links', TRUE);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, permission
+FROM roles, unnest(ARRAY['link.create', 'link.manage_any', 'domain.manage', 'user.manage', 'settings.manage']) AS permission
+WHERE roles.name = 'admin';
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, 'link.create' FROM roles WHERE roles.name = 'user';
+
+-- Everyone can keep shortening links, and admins keep their rights
+INSERT INTO user_roles (user_id, role_id)
+SELECT users.id, roles.id FROM users, roles WHERE roles.name = 'user';
+
+INSERT INTO user_roles (user_id, role_id)
+SELECT users.id, roles.id FROM users, roles WHERE roles.name = 'admin' AND users.is_admin;
+
+ALTER TABLE users DROP COLUMN is_admin;
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+
+ALTER TABLE domains DROP COLUMN team_id;
+
+DROP INDEX IF EXISTS links_team_id_idx;
+ALTER TABLE links DROP CONSTRAINT links_single_owner;
+ALTER TABLE links DROP COLUMN team_id;
+
+DROP TABLE IF EXISTS team_members;
+DROP TABLE IF EXISTS teams;
@@ -0,0 +1,30 @@
+-- Your SQL goes here
+
+CREATE TABLE teams (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE team_members (
+    team_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    -- owner, admin or member
+    role VARCHAR(16) NOT NULL DEFAULT 'member',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (team_id, user_id)
+);
+
+CREATE INDEX team_members_user_id_idx ON team_members (user_id);
+
+-- Team links have no user owner, so they stay when the member who created them leaves
+ALTER TABLE links ADD COLUMN team_id INTEGER REFERENCES teams(id) ON DELETE CASCADE;
+ALTER TABLE links ADD CONSTRAINT links_single_owner CHECK (owner_id IS NULL OR team_id IS NULL);
+
+CREATE INDEX links_team_id_idx ON links (team_id);
+
+-- Members of the team can create links on its private domains
+ALTER TABLE domains ADD COLUMN team_id INTEGER REFERENCES teams(id) ON DELETE SET NULL;
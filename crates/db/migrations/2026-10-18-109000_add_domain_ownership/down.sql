@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS domains_owner_id_idx;
+
+ALTER TABLE domains DROP COLUMN verified_at;
+ALTER TABLE domains DROP COLUMN verification_token;
+ALTER TABLE domains DROP COLUMN owner_id;
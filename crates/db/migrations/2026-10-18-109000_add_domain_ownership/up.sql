@@ -0,0 +1,11 @@
+-- Your SQL goes here
+
+-- Domains brought by users, who have to prove they control them before using them
+ALTER TABLE domains ADD COLUMN owner_id INTEGER REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE domains ADD COLUMN verification_token VARCHAR(64);
+ALTER TABLE domains ADD COLUMN verified_at TIMESTAMP;
+
+-- Domains added by admins don't need to be verified
+UPDATE domains SET verified_at = created_at;
+
+CREATE INDEX domains_owner_id_idx ON domains (owner_id);
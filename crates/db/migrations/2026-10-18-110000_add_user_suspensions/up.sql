@@ -0,0 +1,6 @@
+-- Your SQL goes here
+
+ALTER TABLE users ADD COLUMN suspended_at TIMESTAMP;
+-- A suspension without an end is a ban
+ALTER TABLE users ADD COLUMN suspended_until TIMESTAMP;
+ALTER TABLE users ADD COLUMN suspension_reason TEXT;
@@ -0,0 +1,20 @@
+-- This file should undo anything in `up.sql`
+
+DROP TABLE IF EXISTS team_members;
+DROP TABLE IF EXISTS user_roles;
+DROP TABLE IF EXISTS role_permissions;
+DROP TABLE IF EXISTS roles;
+DROP TABLE IF EXISTS user_identities;
+DROP TABLE IF EXISTS recovery_codes;
+DROP TABLE IF EXISTS email_change_tokens;
+DROP TABLE IF EXISTS password_reset_tokens;
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS api_keys;
+DROP TABLE IF EXISTS url_rules;
+DROP TABLE IF EXISTS verification_tokens;
+DROP TABLE IF EXISTS link_clicks;
+DROP TABLE IF EXISTS link_slug_seq;
+DROP TABLE IF EXISTS links;
+DROP TABLE IF EXISTS domains;
+DROP TABLE IF EXISTS users;
+DROP TABLE IF EXISTS teams;
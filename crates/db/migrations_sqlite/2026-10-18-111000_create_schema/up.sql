@@ -0,0 +1,291 @@
+-- SQLite gets the whole schema in one go, it was added after the Postgres migrations had settled.
+-- Keep it in step with the Postgres migrations in ../migrations.
+
+CREATE TABLE teams (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE users (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username VARCHAR(255) NOT NULL UNIQUE,
+    email VARCHAR(255) NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    verified_at TIMESTAMP DEFAULT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT NULL,
+    -- Base32 TOTP secret. It's set when enrollment starts, but 2FA is only on once totp_enabled_at is set.
+    totp_secret VARCHAR(64),
+    totp_enabled_at TIMESTAMP,
+    -- Last accepted TOTP time step, so a code can't be used twice
+    totp_last_step BIGINT,
+    -- Codes tried since the last accepted one, too many lock 2FA logins until totp_locked_until
+    totp_attempts INTEGER NOT NULL DEFAULT 0,
+    totp_locked_until TIMESTAMP,
+    suspended_at TIMESTAMP,
+    -- A suspension without an end is a ban
+    suspended_until TIMESTAMP,
+    suspension_reason TEXT
+);
+
+CREATE TABLE domains (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    domain VARCHAR(255) UNIQUE NOT NULL,
+    public BOOLEAN DEFAULT false NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    slug_strategy VARCHAR(32) DEFAULT NULL,
+    -- NULL inherits the redirect type from the app config
+    redirect_type INTEGER DEFAULT NULL CHECK (redirect_type IN (301, 302, 307, 308)),
+    -- Members of the team can create links on its private domains
+    team_id INTEGER REFERENCES teams(id) ON DELETE SET NULL,
+    -- Domains brought by users, who have to prove they control them before using them
+    owner_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    verification_token VARCHAR(64),
+    verified_at TIMESTAMP
+);
+
+CREATE INDEX domains_owner_id_idx ON domains (owner_id);
+
+CREATE TABLE links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    domain_id INTEGER NOT NULL,
+    slug VARCHAR(255) NOT NULL,
+    custom_slug VARCHAR(255),
+    original_link TEXT NOT NULL,
+    owner_id INTEGER,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    deleted_at TIMESTAMP DEFAULT NULL,
+    expires_at TIMESTAMP DEFAULT NULL,
+    max_clicks INTEGER DEFAULT NULL,
+    click_count INTEGER NOT NULL DEFAULT 0,
+    archived_at TIMESTAMP DEFAULT NULL,
+    password_hash TEXT DEFAULT NULL,
+    -- NULL inherits the redirect type from the domain, and then from the app config
+    redirect_type INTEGER DEFAULT NULL CHECK (redirect_type IN (301, 302, 307, 308)),
+    -- Team links have no user owner, so they stay when the member who created them leaves
+    team_id INTEGER REFERENCES teams(id) ON DELETE CASCADE,
+    FOREIGN KEY (owner_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (domain_id) REFERENCES domains(id) ON DELETE CASCADE,
+    CONSTRAINT links_single_owner CHECK (owner_id IS NULL OR team_id IS NULL)
+);
+
+CREATE UNIQUE INDEX links_domain_id_slug_idx ON links (domain_id, slug);
+CREATE UNIQUE INDEX links_domain_id_custom_slug_idx ON links (domain_id, custom_slug);
+CREATE INDEX links_team_id_idx ON links (team_id);
+
+-- Slugs and custom slugs share a namespace per domain, which a unique index can't express.
+-- The message is matched by db::is_unique_violation, SQLite has no way to raise a unique violation from a trigger.
+CREATE TRIGGER check_link_slug_collision_insert
+    BEFORE INSERT ON links
+    FOR EACH ROW
+    WHEN EXISTS (
+        SELECT 1 FROM links
+        WHERE domain_id = NEW.domain_id
+        AND (slug = NEW.custom_slug OR custom_slug = NEW.slug)
+    )
+BEGIN
+    SELECT RAISE(ABORT, 'Slug already exists on domain');
+END;
+
+CREATE TRIGGER check_link_slug_collision_update
+    BEFORE UPDATE OF domain_id, slug, custom_slug ON links
+    FOR EACH ROW
+    WHEN EXISTS (
+        SELECT 1 FROM links
+        WHERE domain_id = NEW.domain_id
+        AND id <> NEW.id
+        AND (slug = NEW.custom_slug OR custom_slug = NEW.slug)
+    )
+BEGIN
+    SELECT RAISE(ABORT, 'Slug already exists on domain');
+END;
+
+-- Source of unique numbers for sequential slugs, SQLite has no sequences
+CREATE TABLE link_slug_seq (
+    value INTEGER PRIMARY KEY AUTOINCREMENT
+);
+
+CREATE TABLE link_clicks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    link_id INTEGER NOT NULL,
+    domain_id INTEGER NOT NULL,
+    referrer TEXT,
+    user_agent TEXT,
+    ip_hash VARCHAR(64),
+    clicked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (link_id) REFERENCES links(id) ON DELETE CASCADE,
+    FOREIGN KEY (domain_id) REFERENCES domains(id) ON DELETE CASCADE
+);
+
+CREATE INDEX link_clicks_link_id_clicked_at_idx ON link_clicks (link_id, clicked_at);
+
+CREATE TABLE verification_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    token VARCHAR(255) NOT NULL UNIQUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Destination domains that links may (allow) or may not (block) point to, on top of the ones in the config
+CREATE TABLE url_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    domain VARCHAR(255) NOT NULL,
+    kind VARCHAR(16) NOT NULL CHECK (kind IN ('block', 'allow')),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    UNIQUE (domain, kind)
+);
+
+CREATE TABLE api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    -- SHA-256 of the key, the key itself is only shown once on creation
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    -- Start of the key, so users can tell their keys apart
+    token_prefix VARCHAR(16) NOT NULL,
+    -- Space separated, e.g. "links:read links:write"
+    scopes TEXT NOT NULL,
+    last_used_at TIMESTAMP,
+    expires_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX api_keys_user_id_idx ON api_keys (user_id);
+
+CREATE TABLE sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the current refresh token, rotated on every refresh
+    refresh_token_hash VARCHAR(64) NOT NULL UNIQUE,
+    -- SHA-256 of the refresh token that was rotated away, seeing it again means it was stolen
+    previous_token_hash VARCHAR(64),
+    user_agent TEXT,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_seen_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
+CREATE INDEX sessions_previous_token_hash_idx ON sessions (previous_token_hash);
+
+CREATE TABLE password_reset_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the token, the token itself is only sent by email
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX password_reset_tokens_user_id_idx ON password_reset_tokens (user_id);
+
+CREATE TABLE email_change_tokens (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    -- The address the user wants to switch to, it only replaces users.email once confirmed
+    new_email VARCHAR(255) NOT NULL,
+    -- SHA-256 of the token, the token itself is only sent by email
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX email_change_tokens_user_id_idx ON email_change_tokens (user_id);
+
+CREATE TABLE recovery_codes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    -- SHA-256 of the normalized code
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMP,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (user_id, code_hash)
+);
+
+-- Accounts at external OpenID Connect providers, linked to local users
+CREATE TABLE user_identities (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    -- Issuer URL of the provider, the subject is only unique per issuer
+    issuer VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    -- Email the provider reported at the last login
+    email VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_login_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX user_identities_user_id_idx ON user_identities (user_id);
+
+CREATE TABLE roles (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name VARCHAR(64) NOT NULL UNIQUE,
+    description TEXT,
+    -- Built-in roles can't be deleted
+    built_in BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE role_permissions (
+    role_id INTEGER NOT NULL,
+    -- e.g. link.create or domain.manage
+    permission VARCHAR(64) NOT NULL,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (role_id, permission)
+);
+
+CREATE TABLE user_roles (
+    user_id INTEGER NOT NULL,
+    role_id INTEGER NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, role_id)
+);
+
+CREATE INDEX user_roles_role_id_idx ON user_roles (role_id);
+
+INSERT INTO roles (name, description, built_in) VALUES
+    ('admin', 'Can do everything', TRUE),
+    ('user', 'Can shorten links', TRUE);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, permissions.permission
+FROM roles, (
+    SELECT 'link.create' AS permission
+    UNION ALL SELECT 'link.manage_any'
+    UNION ALL SELECT 'domain.manage'
+    UNION ALL SELECT 'user.manage'
+    UNION ALL SELECT 'settings.manage'
+) AS permissions
+WHERE roles.name = 'admin';
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT roles.id, 'link.create' FROM roles WHERE roles.name = 'user';
+
+CREATE TABLE team_members (
+    team_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    -- owner, admin or member
+    role VARCHAR(16) NOT NULL DEFAULT 'member',
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    FOREIGN KEY (team_id) REFERENCES teams(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (team_id, user_id)
+);
+
+CREATE INDEX team_members_user_id_idx ON team_members (user_id);
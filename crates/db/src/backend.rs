@@ -0,0 +1,125 @@
+use std::{fmt, time::Duration};
+
+use diesel::pg::PgConnection;
+use diesel::r2d2::{self, ManageConnection, R2D2Connection};
+use diesel::sqlite::SqliteConnection;
+use diesel::{Connection, RunQueryDsl};
+
+/// A connection to whichever database `db.url` points at. Queries built with the DSL run on both, backend specific
+/// SQL has to match on the variant.
+#[derive(diesel::MultiConnection)]
+pub enum AnyConnection {
+	Postgresql(PgConnection),
+	Sqlite(SqliteConnection),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+	Postgres,
+	Sqlite,
+}
+
+impl BackendKind {
+	/// Picks the backend from the scheme of a database URL, `postgres://` or `sqlite://`
+	pub fn from_url(database_url: &str) -> Option<Self> {
+		if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+			Some(BackendKind::Postgres)
+		} else if database_url.starts_with("sqlite:") {
+			Some(BackendKind::Sqlite)
+		} else {
+			None
+		}
+	}
+}
+
+impl fmt::Display for BackendKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			BackendKind::Postgres => write!(f, "PostgreSQL"),
+			BackendKind::Sqlite => write!(f, "SQLite"),
+		}
+	}
+}
+
+/// Opens connections for the pool, on the backend picked from the URL scheme.
+///
+/// `AnyConnection::establish` would try every backend in turn, so a Postgres server being down would end up
+/// creating a SQLite file named after the URL instead.
+#[derive(Debug)]
+pub struct ConnectionManager {
+	database_url: String,
+	kind: BackendKind,
+	busy_timeout: Duration,
+}
+
+impl ConnectionManager {
+	pub fn new(database_url: &str, busy_timeout: Duration) -> Option<Self> {
+		let kind = BackendKind::from_url(database_url)?;
+
+		Some(Self {
+			database_url: database_url.to_string(),
+			kind,
+			busy_timeout,
+		})
+	}
+
+	pub fn kind(&self) -> BackendKind {
+		self.kind
+	}
+
+	fn sqlite_path(&self) -> &str {
+		let path = self.database_url.trim_start_matches("sqlite:");
+
+		path.strip_prefix("//").unwrap_or(path)
+	}
+}
+
+impl ManageConnection for ConnectionManager {
+	type Connection = AnyConnection;
+	type Error = r2d2::Error;
+
+	fn connect(&self) -> Result<AnyConnection, r2d2::Error> {
+		match self.kind {
+			BackendKind::Postgres => PgConnection::establish(&self.database_url)
+				.map(AnyConnection::Postgresql)
+				.map_err(r2d2::Error::ConnectionError),
+			BackendKind::Sqlite => {
+				let mut conn = SqliteConnection::establish(self.sqlite_path()).map_err(r2d2::Error::ConnectionError)?;
+
+				// Foreign keys are off by default, and without a busy timeout concurrent writers fail right away
+				diesel::sql_query("PRAGMA foreign_keys = ON")
+					.execute(&mut conn)
+					.map_err(r2d2::Error::QueryError)?;
+				diesel::sql_query(format!("PRAGMA busy_timeout = {}", self.busy_timeout.as_millis()))
+					.execute(&mut conn)
+					.map_err(r2d2::Error::QueryError)?;
+				diesel::sql_query("PRAGMA journal_mode = WAL")
+					.execute(&mut conn)
+					.map_err(r2d2::Error::QueryError)?;
+
+				Ok(AnyConnection::Sqlite(conn))
+			}
+		}
+	}
+
+	fn is_valid(&self, conn: &mut AnyConnection) -> Result<(), r2d2::Error> {
+		conn.ping().map_err(r2d2::Error::QueryError)
+	}
+
+	fn has_broken(&self, conn: &mut AnyConnection) -> bool {
+		std::thread::panicking() || conn.is_broken()
+	}
+}
+
+/// Runs the same query on the connection of each backend, for queries `AnyConnection` can't build itself, like batch
+/// inserts and upserts
+macro_rules! on_backend {
+	($conn:expr, |$inner:ident| $query:expr) => {
+		match $conn {
+			$crate::AnyConnection::Postgresql($inner) => $query,
+			$crate::AnyConnection::Sqlite($inner) => $query,
+		}
+	};
+}
+
+pub(crate) use on_backend;
@@ -1,23 +1,387 @@
+mod backend;
 pub mod models;
+pub mod repository;
 pub mod schema;
 
-use diesel::pg::PgConnection;
-use diesel::r2d2::{self, ConnectionManager};
+use std::{fmt, time::Duration};
+
+use diesel::r2d2::{self, CustomizeConnection};
+use diesel::{Connection, RunQueryDsl};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 
-pub type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
-pub type DbConnection = r2d2::PooledConnection<ConnectionManager<PgConnection>>;
+pub use backend::{AnyConnection, BackendKind, ConnectionManager};
+
+pub type DbPool = r2d2::Pool<ConnectionManager>;
+pub type DbConnection = r2d2::PooledConnection<ConnectionManager>;
+pub type DbError = diesel::result::Error;
+
+const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_sqlite");
+
+/// Raised by the SQLite trigger that keeps slugs and custom slugs from colliding, Postgres raises a unique violation
+/// for it instead
+const SQLITE_SLUG_COLLISION: &str = "Slug already exists on domain";
+
+/// Checks if a query failed because it violated a unique constraint
+pub fn is_unique_violation(error: &DbError) -> bool {
+	match error {
+		diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _) => true,
+		diesel::result::Error::DatabaseError(_, info) => info.message().starts_with(SQLITE_SLUG_COLLISION),
+		_ => false,
+	}
+}
+
+/// Runs the work in a transaction, which is rolled back if the work fails
+pub fn transaction<T, F>(conn: &mut DbConnection, work: F) -> Result<T, DbError>
+where
+	F: FnOnce(&mut DbConnection) -> Result<T, DbError>,
+{
+	conn.transaction(work)
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+	/// Most connections open at once
+	pub max_size: u32,
+	/// Connections kept open while idle, defaults to `max_size`
+	pub min_idle: Option<u32>,
+	/// How long to wait for a free connection before giving up
+	pub connection_timeout: Duration,
+	/// Queries running longer than this are cancelled by the database
+	pub statement_timeout: Option<Duration>,
+	/// How long SQLite waits for another connection to release a lock before failing the query
+	pub sqlite_busy_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+	fn default() -> Self {
+		Self {
+			max_size: 10,
+			min_idle: None,
+			connection_timeout: Duration::from_secs(30),
+			statement_timeout: None,
+			sqlite_busy_timeout: Duration::from_secs(5),
+		}
+	}
+}
+
+#[derive(Debug)]
+struct StatementTimeout(Duration);
+
+impl CustomizeConnection<AnyConnection, r2d2::Error> for StatementTimeout {
+	fn on_acquire(&self, conn: &mut AnyConnection) -> Result<(), r2d2::Error> {
+		// SQLite has no statement timeout
+		let AnyConnection::Postgresql(conn) = conn else {
+			return Ok(());
+		};
+
+		diesel::sql_query(format!("SET statement_timeout = {}", self.0.as_millis()))
+			.execute(conn)
+			.map(|_| ())
+			.map_err(r2d2::Error::QueryError)
+	}
+}
+
+/// Creates a connection pool for a `postgres://` or `sqlite://` URL
+pub fn create_pool(database_url: &str, options: &PoolOptions) -> DbPool {
+	let manager = ConnectionManager::new(database_url, options.sqlite_busy_timeout)
+		.expect("Unsupported database URL, expected postgres:// or sqlite://");
+	let mut builder = r2d2::Pool::builder()
+		.max_size(options.max_size)
+		.min_idle(options.min_idle)
+		.connection_timeout(options.connection_timeout);
 
-const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+	if let Some(timeout) = options.statement_timeout {
+		builder = builder.connection_customizer(Box::new(StatementTimeout(timeout)));
+	}
 
-pub fn create_pool(database_url: &str) -> DbPool {
-	let manager = ConnectionManager::<PgConnection>::new(database_url);
-	r2d2::Pool::builder().build(manager).expect("Failed to create pool.")
+	builder.build(manager).expect("Failed to create pool.")
 }
 
 pub fn run_migrations(pool: &DbPool) {
 	let mut conn = pool.get().expect("Failed to get a DB connection from the pool");
 
-	conn.run_pending_migrations(MIGRATIONS)
-		.expect("Failed to run migrations");
+	let result = match &mut *conn {
+		AnyConnection::Postgresql(conn) => conn.run_pending_migrations(POSTGRES_MIGRATIONS).map(|_| ()),
+		AnyConnection::Sqlite(conn) => conn.run_pending_migrations(SQLITE_MIGRATIONS).map(|_| ()),
+	};
+
+	result.expect("Failed to run migrations");
+}
+
+/// Why database work passed to [`run`] didn't get to finish
+#[derive(Debug)]
+pub enum RunError {
+	/// No connection became free in time, or a new one couldn't be opened
+	Pool(r2d2::PoolError),
+	/// The work panicked, or the runtime shut down before it ran
+	Aborted,
+}
+
+impl fmt::Display for RunError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			RunError::Pool(e) => write!(f, "Failed to get a DB connection from the pool: {}", e),
+			RunError::Aborted => write!(f, "Database work was aborted"),
+		}
+	}
+}
+
+/// Runs blocking database work on Tokio's blocking threads, so queries and waiting for a connection don't hold up
+/// the async workers. Async code should reach the database only through this.
+pub async fn run<T, F>(pool: &DbPool, work: F) -> Result<T, RunError>
+where
+	F: FnOnce(&mut DbConnection) -> T + Send + 'static,
+	T: Send + 'static,
+{
+	let pool = pool.clone();
+
+	tokio::task::spawn_blocking(move || {
+		let mut conn = pool.get().map_err(RunError::Pool)?;
+
+		Ok(work(&mut conn))
+	})
+	.await
+	.map_err(|_| RunError::Aborted)?
+}
+
+#[cfg(test)]
+mod test {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use chrono::{NaiveDateTime, Utc};
+
+	use super::*;
+	use crate::models::{Domain, Link, LinkClick, NewDomain, NewLink, NewLinkClick, NewUser, Role, User, USER_ROLE};
+
+	static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+	/// Something unique to this test run, so tests sharing a Postgres database don't collide
+	fn unique(prefix: &str) -> String {
+		format!("{}{}x{}", prefix, std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed))
+	}
+
+	/// A migrated pool for every backend under test. SQLite always runs in a fresh file, Postgres only when
+	/// `SHURLIX_TEST_POSTGRES_URL` points at a database the tests may write to.
+	fn pools() -> Vec<(BackendKind, DbPool)> {
+		let sqlite_path = std::env::temp_dir().join(format!("{}.db", unique("shurlix-test-")));
+		let _ = std::fs::remove_file(&sqlite_path);
+
+		let mut urls = vec![format!("sqlite://{}", sqlite_path.display())];
+
+		if let Ok(url) = std::env::var("SHURLIX_TEST_POSTGRES_URL") {
+			urls.push(url);
+		}
+
+		urls.into_iter()
+			.map(|url| {
+				let pool = create_pool(&url, &PoolOptions::default());
+				run_migrations(&pool);
+
+				(BackendKind::from_url(&url).unwrap(), pool)
+			})
+			.collect()
+	}
+
+	fn new_domain(conn: &mut DbConnection) -> Domain {
+		NewDomain {
+			domain: format!("{}.example", unique("d")),
+			public: Some(true),
+			slug_strategy: None,
+			redirect_type: None,
+			owner_id: None,
+			verification_token: None,
+			verified_at: None,
+		}
+		.insert(conn)
+		.unwrap()
+	}
+
+	fn new_link(domain_id: i32, slug: &str, custom_slug: Option<&str>) -> NewLink {
+		NewLink {
+			slug: slug.to_string(),
+			domain_id,
+			custom_slug: custom_slug.map(str::to_string),
+			original_link: "https://example.com".to_string(),
+			owner_id: None,
+			expires_at: None,
+			max_clicks: None,
+			password_hash: None,
+			redirect_type: None,
+			team_id: None,
+		}
+	}
+
+	#[test]
+	fn backend_from_url() {
+		assert_eq!(BackendKind::from_url("postgres://localhost/shurlix"), Some(BackendKind::Postgres));
+		assert_eq!(BackendKind::from_url("postgresql://localhost/shurlix"), Some(BackendKind::Postgres));
+		assert_eq!(BackendKind::from_url("sqlite://shurlix.db"), Some(BackendKind::Sqlite));
+		assert_eq!(BackendKind::from_url("mysql://localhost/shurlix"), None);
+	}
+
+	#[test]
+	fn users_are_matched_case_insensitively() {
+		for (backend, pool) in pools() {
+			let conn = &mut pool.get().unwrap();
+			let username = unique("Alice");
+
+			NewUser {
+				username: username.clone(),
+				password_hash: "hash".to_string(),
+				email: format!("{}@Example.com", username),
+			}
+			.insert(conn);
+
+			assert!(User::username_exists(&username.to_uppercase(), conn), "{}", backend);
+			assert!(User::email_exists(&format!("{}@example.com", username.to_lowercase()), conn), "{}", backend);
+			assert_eq!(User::search_count(Some(&username.to_lowercase()), conn).unwrap(), 1, "{}", backend);
+			// Wildcards in the search are taken literally
+			assert_eq!(User::search_count(Some("%"), conn).unwrap(), 0, "{}", backend);
+		}
+	}
+
+	#[test]
+	fn slug_collisions_are_unique_violations() {
+		for (backend, pool) in pools() {
+			let conn = &mut pool.get().unwrap();
+			let domain = new_domain(conn);
+
+			new_link(domain.id, "abc", Some("custom")).insert(conn).unwrap();
+
+			let same_slug = new_link(domain.id, "abc", None).insert(conn).unwrap_err();
+			assert!(is_unique_violation(&same_slug), "{}: {:?}", backend, same_slug);
+
+			let slug_of_custom = new_link(domain.id, "custom", None).insert(conn).unwrap_err();
+			assert!(is_unique_violation(&slug_of_custom), "{}: {:?}", backend, slug_of_custom);
+
+			let custom_of_slug = new_link(domain.id, "def", Some("abc")).insert(conn).unwrap_err();
+			assert!(is_unique_violation(&custom_of_slug), "{}: {:?}", backend, custom_of_slug);
+		}
+	}
+
+	#[test]
+	fn expired_domain_claims_are_deleted() {
+		for (backend, pool) in pools() {
+			let conn = &mut pool.get().unwrap();
+			let username = unique("claimer");
+			let owner = NewUser {
+				username: username.clone(),
+				password_hash: "hash".to_string(),
+				email: format!("{}@example.com", username),
+			}
+			.insert(conn);
+			let claim = |verified_at: Option<NaiveDateTime>, conn: &mut DbConnection| {
+				NewDomain {
+					domain: format!("{}.example", unique("c")),
+					public: Some(false),
+					slug_strategy: None,
+					redirect_type: None,
+					owner_id: Some(owner.id),
+					verification_token: Some("token".to_string()),
+					verified_at,
+				}
+				.insert(conn)
+				.unwrap()
+			};
+
+			let admin_domain = new_domain(conn);
+			let unverified = claim(None, conn);
+			let verified = claim(Some(Utc::now().naive_utc()), conn);
+
+			let before = (Utc::now() + chrono::Duration::minutes(1)).naive_utc();
+			Domain::delete_unverified_claims_before(before, conn).unwrap();
+
+			assert!(Domain::get_by_id(admin_domain.id, conn).is_ok(), "{}", backend);
+			assert!(Domain::get_by_id(unverified.id, conn).is_err(), "{}", backend);
+			assert!(Domain::get_by_id(verified.id, conn).is_ok(), "{}", backend);
+		}
+	}
+
+	#[test]
+	fn archived_and_purged_links_are_returned() {
+		for (backend, pool) in pools() {
+			let conn = &mut pool.get().unwrap();
+			let domain = new_domain(conn);
+
+			let expired = NewLink {
+				expires_at: Some((Utc::now() - chrono::Duration::minutes(1)).naive_utc()),
+				..new_link(domain.id, "expired", None)
+			}
+			.insert(conn)
+			.unwrap();
+
+			let archived = Link::archive_expired(conn).unwrap();
+			assert!(archived.iter().any(|link| link.id == expired.id), "{}", backend);
+
+			expired.delete(conn).unwrap();
+
+			let before = (Utc::now() + chrono::Duration::minutes(1)).naive_utc();
+			let purged = Link::purge_trashed_before(before, conn).unwrap();
+			assert!(purged.iter().any(|link| link.id == expired.id), "{}", backend);
+		}
+	}
+
+	#[test]
+	fn slug_sequence_increases() {
+		for (backend, pool) in pools() {
+			let conn = &mut pool.get().unwrap();
+
+			let first = Link::next_slug_sequence(conn).unwrap();
+			let second = Link::next_slug_sequence(conn).unwrap();
+
+			assert!(second > first, "{}", backend);
+		}
+	}
+
+	#[test]
+	fn clicks_are_bucketed() {
+		for (backend, pool) in pools() {
+			let conn = &mut pool.get().unwrap();
+			let domain = new_domain(conn);
+			let link = new_link(domain.id, &unique("s"), None).insert(conn).unwrap();
+			let day = Utc::now().date_naive().and_hms_opt(12, 0, 0).unwrap();
+
+			let clicks: Vec<NewLinkClick> = [day, day + chrono::Duration::minutes(5), day + chrono::Duration::days(1)]
+				.into_iter()
+				.map(|clicked_at| NewLinkClick {
+					link_id: link.id,
+					domain_id: domain.id,
+					referrer: None,
+					user_agent: None,
+					ip_hash: None,
+					clicked_at,
+				})
+				.collect();
+
+			assert_eq!(NewLinkClick::insert_batch(&clicks, conn).unwrap(), 3, "{}", backend);
+
+			let buckets = LinkClick::get_time_series(link.id, "day", day - chrono::Duration::days(1), conn).unwrap();
+
+			assert_eq!(buckets.len(), 2, "{}", backend);
+			assert_eq!(buckets[0].bucket, day.date().and_hms_opt(0, 0, 0).unwrap(), "{}", backend);
+			assert_eq!(buckets[0].clicks, 2, "{}", backend);
+			assert_eq!(buckets[1].clicks, 1, "{}", backend);
+		}
+	}
+
+	#[test]
+	fn assigning_a_role_twice_is_a_no_op() {
+		for (backend, pool) in pools() {
+			let conn = &mut pool.get().unwrap();
+			let username = unique("bob");
+			let user = NewUser {
+				username: username.clone(),
+				password_hash: "hash".to_string(),
+				email: format!("{}@example.com", username),
+			}
+			.insert(conn);
+
+			Role::assign_by_name(USER_ROLE, user.id, conn).unwrap();
+			Role::assign_by_name(USER_ROLE, user.id, conn).unwrap();
+
+			assert_eq!(Role::get_for_user(user.id, conn).unwrap().len(), 1, "{}", backend);
+			assert_eq!(Role::permissions_for_user(user.id, conn).unwrap(), vec!["link.create"], "{}", backend);
+		}
+	}
 }
@@ -0,0 +1,101 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{
+	schema::{api_keys, users},
+	DbConnection,
+};
+
+use super::User;
+
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::api_keys)]
+pub struct ApiKey {
+	pub id: i32,
+	pub user_id: i32,
+	pub name: String,
+	#[serde(skip_serializing)]
+	pub token_hash: String,
+	pub token_prefix: String,
+	pub scopes: String,
+	pub last_used_at: Option<NaiveDateTime>,
+	pub expires_at: Option<NaiveDateTime>,
+	pub created_at: NaiveDateTime,
+}
+
+#[derive(AsChangeset, Clone, Debug)]
+#[diesel(table_name = crate::schema::api_keys)]
+pub struct UpdateApiKey {
+	pub name: Option<String>,
+	pub scopes: Option<String>,
+	pub expires_at: Option<Option<NaiveDateTime>>,
+}
+
+impl ApiKey {
+	pub fn get_by_id(id: i32, conn: &mut DbConnection) -> Result<ApiKey, diesel::result::Error> {
+		api_keys::table.find(id).first(conn)
+	}
+
+	pub fn get_by_user_id(user_id: i32, conn: &mut DbConnection) -> Result<Vec<ApiKey>, diesel::result::Error> {
+		api_keys::table
+			.filter(api_keys::user_id.eq(user_id))
+			.order_by(api_keys::created_at.desc())
+			.load::<ApiKey>(conn)
+	}
+
+	/// Gets a key and its owner by the hash of the key
+	pub fn get_by_token_hash(
+		token_hash: &str,
+		conn: &mut DbConnection,
+	) -> Result<(ApiKey, User), diesel::result::Error> {
+		api_keys::table
+			.filter(api_keys::token_hash.eq(token_hash))
+			.inner_join(users::table)
+			.select((api_keys::all_columns, users::all_columns))
+			.first::<(ApiKey, User)>(conn)
+	}
+
+	pub fn scopes(&self) -> Vec<&str> {
+		self.scopes.split_whitespace().collect()
+	}
+
+	pub fn is_expired(&self) -> bool {
+		self.expires_at
+			.is_some_and(|expires_at| expires_at <= Utc::now().naive_utc())
+	}
+
+	/// Records that the key was just used
+	pub fn touch(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(api_keys::table.find(self.id))
+			.set(api_keys::last_used_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+	}
+
+	pub fn update(&self, values: UpdateApiKey, conn: &mut DbConnection) -> Result<ApiKey, diesel::result::Error> {
+		diesel::update(api_keys::table.find(self.id))
+			.set(&values)
+			.get_result(conn)
+	}
+
+	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(api_keys::table.find(self.id)).execute(conn)
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::api_keys)]
+pub struct NewApiKey {
+	pub user_id: i32,
+	pub name: String,
+	pub token_hash: String,
+	pub token_prefix: String,
+	pub scopes: String,
+	pub expires_at: Option<NaiveDateTime>,
+}
+
+impl NewApiKey {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<ApiKey, diesel::result::Error> {
+		diesel::insert_into(api_keys::table).values(self).get_result(conn)
+	}
+}
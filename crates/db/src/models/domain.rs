@@ -1,10 +1,13 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{schema::domains, DbConnection};
+use crate::{
+	schema::{domains, team_members},
+	DbConnection, DbPool,
+};
 
-#[derive(Debug, Queryable, Selectable, Serialize)]
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
 #[diesel(table_name = crate::schema::domains)]
 pub struct Domain {
 	pub id: i32,
@@ -12,6 +15,14 @@ pub struct Domain {
 	pub public: bool,
 	pub created_at: NaiveDateTime,
 	pub updated_at: NaiveDateTime,
+	pub slug_strategy: Option<String>,
+	pub redirect_type: Option<i32>,
+	pub team_id: Option<i32>,
+	/// The user who brought the domain. Domains added by admins have no owner.
+	pub owner_id: Option<i32>,
+	#[serde(skip_serializing)]
+	pub verification_token: Option<String>,
+	pub verified_at: Option<NaiveDateTime>,
 }
 
 #[derive(AsChangeset, Clone, Debug, Deserialize)]
@@ -19,6 +30,8 @@ pub struct Domain {
 pub struct UpdateDomain {
 	pub domain: Option<String>,
 	pub public: Option<bool>,
+	pub slug_strategy: Option<String>,
+	pub redirect_type: Option<i32>,
 }
 
 impl Domain {
@@ -34,6 +47,33 @@ impl Domain {
 		diesel::delete(domains::table.filter(domains::id.eq(id))).execute(conn)
 	}
 
+	/// Deletes claims that weren't verified before the given time. Domains added by admins have no owner and are kept.
+	pub fn delete_unverified_claims_before(
+		before: NaiveDateTime,
+		conn: &mut DbConnection,
+	) -> Result<usize, diesel::result::Error> {
+		diesel::delete(
+			domains::table
+				.filter(domains::owner_id.is_not_null())
+				.filter(domains::verified_at.is_null())
+				.filter(domains::created_at.lt(before)),
+		)
+		.execute(conn)
+	}
+
+	pub async fn delete_unverified_claims_before_pooled(
+		before: NaiveDateTime,
+		pool: &DbPool,
+	) -> Result<usize, diesel::result::Error> {
+		match crate::run(pool, move |conn| Self::delete_unverified_claims_before(before, conn)).await {
+			Ok(result) => result,
+			Err(e) => {
+				log::error!("{}", e);
+				Ok(0)
+			}
+		}
+	}
+
 	pub fn get_public(conn: &mut DbConnection) -> Result<Vec<Domain>, diesel::result::Error> {
 		domains::table
 			.order_by(domains::created_at.desc())
@@ -41,6 +81,41 @@ impl Domain {
 			.load::<Domain>(conn)
 	}
 
+	/// Gets the public domains, the private ones of the user's teams and the verified ones the user owns
+	pub fn get_usable_by_user(user_id: i32, conn: &mut DbConnection) -> Result<Vec<Domain>, diesel::result::Error> {
+		let team_ids = team_members::table
+			.filter(team_members::user_id.eq(user_id))
+			.select(team_members::team_id);
+
+		domains::table
+			.order_by(domains::created_at.desc())
+			.filter(
+				domains::public
+					.eq(true)
+					.or(domains::team_id.eq_any(team_ids.nullable()))
+					.or(domains::owner_id.eq(user_id).and(domains::verified_at.is_not_null())),
+			)
+			.load::<Domain>(conn)
+	}
+
+	/// Gets the domains a user brought, verified or not
+	pub fn get_by_owner_id(owner_id: i32, conn: &mut DbConnection) -> Result<Vec<Domain>, diesel::result::Error> {
+		domains::table
+			.filter(domains::owner_id.eq(owner_id))
+			.order_by(domains::created_at.desc())
+			.load::<Domain>(conn)
+	}
+
+	pub fn is_verified(&self) -> bool {
+		self.verified_at.is_some()
+	}
+
+	pub fn set_verified(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(domains::table.find(self.id))
+			.set(domains::verified_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+	}
+
 	pub fn get_all(conn: &mut DbConnection) -> Result<Vec<Domain>, diesel::result::Error> {
 		domains::table
 			.order_by(domains::created_at.desc())
@@ -76,6 +151,13 @@ impl Domain {
 			.execute(conn)
 	}
 
+	/// Gives the domain to a team, or takes it away with `None`
+	pub fn set_team(&self, team_id: Option<i32>, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(domains::table.find(self.id))
+			.set(domains::team_id.eq(team_id))
+			.execute(conn)
+	}
+
 	pub fn update(&self, values: UpdateDomain, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
 		diesel::update(domains::table.find(self.id)).set(&values).execute(conn)
 	}
@@ -86,13 +168,15 @@ impl Domain {
 pub struct NewDomain {
 	pub domain: String,
 	pub public: Option<bool>,
+	pub slug_strategy: Option<String>,
+	pub redirect_type: Option<i32>,
+	pub owner_id: Option<i32>,
+	pub verification_token: Option<String>,
+	pub verified_at: Option<NaiveDateTime>,
 }
 
 impl NewDomain {
 	pub fn insert(&self, conn: &mut DbConnection) -> Result<Domain, diesel::result::Error> {
-		diesel::insert_into(domains::table)
-			.values(self)
-			.returning(Domain::as_returning())
-			.get_result(conn)
+		diesel::insert_into(domains::table).values(self).get_result(conn)
 	}
 }
@@ -0,0 +1,78 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{
+	schema::{email_change_tokens, users},
+	DbConnection, DbPool,
+};
+
+use super::User;
+
+/// A pending email change, waiting for the new address to be confirmed
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::email_change_tokens)]
+pub struct EmailChangeToken {
+	pub id: i32,
+	pub user_id: i32,
+	pub new_email: String,
+	#[serde(skip_serializing)]
+	pub token_hash: String,
+	pub created_at: NaiveDateTime,
+	pub expires_at: NaiveDateTime,
+}
+
+impl EmailChangeToken {
+	/// Gets a token and the user it belongs to by the hash of the token
+	pub fn get_by_token_hash(
+		token_hash: &str,
+		conn: &mut DbConnection,
+	) -> Result<(EmailChangeToken, User), diesel::result::Error> {
+		email_change_tokens::table
+			.filter(email_change_tokens::token_hash.eq(token_hash))
+			.inner_join(users::table)
+			.select((email_change_tokens::all_columns, users::all_columns))
+			.first::<(EmailChangeToken, User)>(conn)
+	}
+
+	pub fn is_expired(&self) -> bool {
+		self.expires_at <= Utc::now().naive_utc()
+	}
+
+	/// Deletes every pending change of a user, so only the most recently requested one can be confirmed
+	pub fn delete_for_user(user_id: i32, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(email_change_tokens::table.filter(email_change_tokens::user_id.eq(user_id))).execute(conn)
+	}
+
+	pub fn delete_expired(conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(email_change_tokens::table.filter(email_change_tokens::expires_at.lt(Utc::now().naive_utc())))
+			.execute(conn)
+	}
+
+	pub async fn delete_expired_pooled(pool: &DbPool) -> Result<usize, diesel::result::Error> {
+		match crate::run(pool, Self::delete_expired).await {
+			Ok(result) => result,
+			Err(e) => {
+				log::error!("{}", e);
+				Ok(0)
+			}
+		}
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::email_change_tokens)]
+pub struct NewEmailChangeToken {
+	pub user_id: i32,
+	pub new_email: String,
+	pub token_hash: String,
+	pub expires_at: NaiveDateTime,
+}
+
+impl NewEmailChangeToken {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<EmailChangeToken, diesel::result::Error> {
+		diesel::insert_into(email_change_tokens::table)
+			.values(self)
+			.get_result(conn)
+	}
+}
@@ -1,8 +1,11 @@
-use chrono::NaiveDateTime;
-use diesel::prelude::*;
+use chrono::{NaiveDateTime, Utc};
+use diesel::{prelude::*, sql_types::BigInt};
 use serde::Serialize;
 
-use crate::{schema::{domains, links}, DbConnection};
+use crate::{
+	schema::{domains, links},
+	AnyConnection, DbConnection, DbPool,
+};
 
 #[derive(Debug, Queryable, Selectable, Serialize, Clone)]
 #[diesel(table_name = crate::schema::links)]
@@ -16,6 +19,34 @@ pub struct Link {
 	pub created_at: NaiveDateTime,
 	pub updated_at: NaiveDateTime,
 	pub deleted_at: Option<NaiveDateTime>,
+	pub expires_at: Option<NaiveDateTime>,
+	pub max_clicks: Option<i32>,
+	pub click_count: i32,
+	pub archived_at: Option<NaiveDateTime>,
+	#[serde(skip_serializing)]
+	pub password_hash: Option<String>,
+	pub redirect_type: Option<i32>,
+	pub team_id: Option<i32>,
+}
+
+#[derive(QueryableByName)]
+struct SequenceValue {
+	#[diesel(sql_type = BigInt)]
+	value: i64,
+}
+
+/// Changes to a link. `Some(None)` clears a nullable column.
+#[derive(AsChangeset, Clone, Debug)]
+#[diesel(table_name = crate::schema::links)]
+pub struct UpdateLink {
+	pub original_link: Option<String>,
+	pub custom_slug: Option<Option<String>>,
+	pub domain_id: Option<i32>,
+	pub expires_at: Option<Option<NaiveDateTime>>,
+	pub max_clicks: Option<Option<i32>>,
+	pub password_hash: Option<Option<String>>,
+	pub archived_at: Option<Option<NaiveDateTime>>,
+	pub redirect_type: Option<Option<i32>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -30,6 +61,13 @@ pub struct LinkWithDomain {
 	pub created_at: NaiveDateTime,
 	pub updated_at: NaiveDateTime,
 	pub deleted_at: Option<NaiveDateTime>,
+	pub expires_at: Option<NaiveDateTime>,
+	pub max_clicks: Option<i32>,
+	pub click_count: i32,
+	pub archived_at: Option<NaiveDateTime>,
+	pub is_protected: bool,
+	pub redirect_type: Option<i32>,
+	pub team_id: Option<i32>,
 }
 
 impl LinkWithDomain {
@@ -45,15 +83,34 @@ impl LinkWithDomain {
             created_at: link.created_at,
             updated_at: link.updated_at,
             deleted_at: link.deleted_at,
+            expires_at: link.expires_at,
+            max_clicks: link.max_clicks,
+            click_count: link.click_count,
+            archived_at: link.archived_at,
+            is_protected: link.password_hash.is_some(),
+            redirect_type: link.redirect_type,
+            team_id: link.team_id,
 		}
 	}
 }
 
 impl Link {
+	pub fn get_by_id(id: i32, conn: &mut DbConnection) -> Result<Link, diesel::result::Error> {
+		links::table.find(id).filter(links::deleted_at.is_null()).first(conn)
+	}
+
+	/// Gets a link that is in the trash
+	pub fn get_trashed_by_id(id: i32, conn: &mut DbConnection) -> Result<Link, diesel::result::Error> {
+		links::table.find(id).filter(links::deleted_at.is_not_null()).first(conn)
+	}
+
 	/// Gets a link by custom slug
 	///
 	pub fn get_by_custom_slug(slug: String, conn: &mut DbConnection) -> Result<Vec<Link>, diesel::result::Error> {
-		let links = links::table.filter(links::custom_slug.eq(slug)).load::<Link>(conn);
+		let links = links::table
+			.filter(links::custom_slug.eq(slug))
+			.filter(links::deleted_at.is_null())
+			.load::<Link>(conn);
 
 		links
 	}
@@ -62,6 +119,7 @@ impl Link {
 	pub fn get_by_slug(slug: &String, conn: &mut DbConnection) -> Result<Vec<Link>, diesel::result::Error> {
 		let links = links::table
 			.filter(links::custom_slug.eq(slug).or(links::slug.eq(slug)))
+			.filter(links::deleted_at.is_null())
 			.load::<Link>(conn);
 
 		links
@@ -79,12 +137,26 @@ impl Link {
 					.or(links::slug.eq(slug))
 					.and(links::domain_id.eq(domain_id)),
 			)
+			.filter(links::deleted_at.is_null())
+			.load::<Link>(conn)
+	}
+
+	/// Gets links by any slug on a domain, including links in the trash. Trashed links still hold on to their slug.
+	pub fn get_by_domain_slug_with_trashed(
+		domain_id: i32,
+		slug: &String,
+		conn: &mut DbConnection,
+	) -> Result<Vec<Link>, diesel::result::Error> {
+		links::table
+			.filter(links::domain_id.eq(domain_id))
+			.filter(links::custom_slug.eq(slug).or(links::slug.eq(slug)))
 			.load::<Link>(conn)
 	}
 
 	pub fn get_by_owner_id(owner: i32, conn: &mut DbConnection) -> Result<Vec<Link>, diesel::result::Error> {
 		links::table
 			.filter(links::owner_id.eq(owner))
+			.filter(links::deleted_at.is_null())
 			.order_by(links::created_at.desc())
 			.load::<Link>(conn)
 	}
@@ -98,6 +170,7 @@ impl Link {
 		let offset_value = (page - 1) * per_page;
 		links::table
 			.filter(links::owner_id.eq(owner))
+			.filter(links::deleted_at.is_null())
 			.inner_join(domains::table)
 			.select((links::all_columns, domains::domain))
 			.order_by(links::created_at.desc())
@@ -106,12 +179,224 @@ impl Link {
 			.load::<(Link, String)>(conn)
 	}
 
+	/// Counts all links on a domain, including trashed ones, as they still hold on to their slug
+	pub fn get_domain_count(domain_id: i32, conn: &mut DbConnection) -> QueryResult<i64> {
+		links::table.filter(links::domain_id.eq(domain_id)).count().get_result(conn)
+	}
+
+	/// Gets the next number for sequential slugs
+	pub fn next_slug_sequence(conn: &mut DbConnection) -> QueryResult<i64> {
+		match &mut **conn {
+			AnyConnection::Postgresql(conn) => diesel::sql_query("SELECT nextval('link_slug_seq') AS value")
+				.get_result::<SequenceValue>(conn)
+				.map(|sequence| sequence.value),
+			// AUTOINCREMENT never hands out a number twice, even after its row is gone
+			AnyConnection::Sqlite(conn) => conn.transaction(|conn| {
+				diesel::sql_query("DELETE FROM link_slug_seq").execute(conn)?;
+				diesel::sql_query("INSERT INTO link_slug_seq DEFAULT VALUES RETURNING value")
+					.get_result::<SequenceValue>(conn)
+					.map(|sequence| sequence.value)
+			}),
+		}
+	}
+
 	pub fn get_total_count(owner: i32, conn: &mut DbConnection) -> QueryResult<i64> {
-		links::table.filter(links::owner_id.eq(owner)).count().get_result(conn)
+		links::table
+			.filter(links::owner_id.eq(owner))
+			.filter(links::deleted_at.is_null())
+			.count()
+			.get_result(conn)
+	}
+
+	pub fn get_trashed_by_owner_id_paginated(
+		owner: i32,
+		page: i64,
+		per_page: i64,
+		conn: &mut DbConnection,
+	) -> Result<Vec<(Link, String)>, diesel::result::Error> {
+		let offset_value = (page - 1) * per_page;
+		links::table
+			.filter(links::owner_id.eq(owner))
+			.filter(links::deleted_at.is_not_null())
+			.inner_join(domains::table)
+			.select((links::all_columns, domains::domain))
+			.order_by(links::deleted_at.desc())
+			.limit(per_page)
+			.offset(offset_value)
+			.load::<(Link, String)>(conn)
+	}
+
+	pub fn get_trashed_total_count(owner: i32, conn: &mut DbConnection) -> QueryResult<i64> {
+		links::table
+			.filter(links::owner_id.eq(owner))
+			.filter(links::deleted_at.is_not_null())
+			.count()
+			.get_result(conn)
+	}
+
+	pub fn get_by_team_id_paginated(
+		team_id: i32,
+		page: i64,
+		per_page: i64,
+		conn: &mut DbConnection,
+	) -> Result<Vec<(Link, String)>, diesel::result::Error> {
+		let offset_value = (page - 1) * per_page;
+		links::table
+			.filter(links::team_id.eq(team_id))
+			.filter(links::deleted_at.is_null())
+			.inner_join(domains::table)
+			.select((links::all_columns, domains::domain))
+			.order_by(links::created_at.desc())
+			.limit(per_page)
+			.offset(offset_value)
+			.load::<(Link, String)>(conn)
+	}
+
+	pub fn get_team_total_count(team_id: i32, conn: &mut DbConnection) -> QueryResult<i64> {
+		links::table
+			.filter(links::team_id.eq(team_id))
+			.filter(links::deleted_at.is_null())
+			.count()
+			.get_result(conn)
+	}
+
+	pub fn get_trashed_by_team_id_paginated(
+		team_id: i32,
+		page: i64,
+		per_page: i64,
+		conn: &mut DbConnection,
+	) -> Result<Vec<(Link, String)>, diesel::result::Error> {
+		let offset_value = (page - 1) * per_page;
+		links::table
+			.filter(links::team_id.eq(team_id))
+			.filter(links::deleted_at.is_not_null())
+			.inner_join(domains::table)
+			.select((links::all_columns, domains::domain))
+			.order_by(links::deleted_at.desc())
+			.limit(per_page)
+			.offset(offset_value)
+			.load::<(Link, String)>(conn)
+	}
+
+	pub fn get_trashed_team_total_count(team_id: i32, conn: &mut DbConnection) -> QueryResult<i64> {
+		links::table
+			.filter(links::team_id.eq(team_id))
+			.filter(links::deleted_at.is_not_null())
+			.count()
+			.get_result(conn)
+	}
+
+	/// Hands the link over to a user or a team. Only one of them can own it.
+	pub fn transfer(
+		&self,
+		owner_id: Option<i32>,
+		team_id: Option<i32>,
+		conn: &mut DbConnection,
+	) -> Result<Link, diesel::result::Error> {
+		diesel::update(links::table.find(self.id))
+			.set((
+				links::owner_id.eq(owner_id),
+				links::team_id.eq(team_id),
+				links::updated_at.eq(Utc::now().naive_utc()),
+			))
+			.get_result(conn)
 	}
 
+	/// Moves the link to the trash
 	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
-		diesel::delete(links::table.filter(links::id.eq(self.id))).execute(conn)
+		diesel::update(links::table.find(self.id))
+			.set(links::deleted_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+	}
+
+	/// Takes the link out of the trash
+	pub fn restore(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(links::table.find(self.id))
+			.set(links::deleted_at.eq(None::<NaiveDateTime>))
+			.execute(conn)
+	}
+
+	/// Permanently deletes the link
+	pub fn purge(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(links::table.find(self.id)).execute(conn)
+	}
+
+	/// Permanently deletes all links that were trashed before the given time
+	/// Returns the purged links, so they can be dropped from caches
+	pub fn purge_trashed_before(
+		before: NaiveDateTime,
+		conn: &mut DbConnection,
+	) -> Result<Vec<Link>, diesel::result::Error> {
+		diesel::delete(links::table.filter(links::deleted_at.lt(before))).get_results(conn)
+	}
+
+	pub async fn purge_trashed_before_pooled(
+		before: NaiveDateTime,
+		pool: &DbPool,
+	) -> Result<Vec<Link>, diesel::result::Error> {
+		match crate::run(pool, move |conn| Self::purge_trashed_before(before, conn)).await {
+			Ok(result) => result,
+			Err(e) => {
+				log::error!("{}", e);
+				Ok(Vec::new())
+			}
+		}
+	}
+
+	pub fn update(&self, values: UpdateLink, conn: &mut DbConnection) -> Result<Link, diesel::result::Error> {
+		diesel::update(links::table.find(self.id))
+			.set((&values, links::updated_at.eq(Utc::now().naive_utc())))
+			.get_result(conn)
+	}
+
+	/// Checks if the link has been archived, passed its expiry date or used up its clicks
+	pub fn is_expired(&self) -> bool {
+		let now = Utc::now().naive_utc();
+
+		self.archived_at.is_some()
+			|| self.expires_at.is_some_and(|expires_at| now > expires_at)
+			|| self.max_clicks.is_some_and(|max_clicks| self.click_count >= max_clicks)
+	}
+
+	/// Counts a click towards `max_clicks`. Returns false if there were no clicks left.
+	pub fn consume_click(&self, conn: &mut DbConnection) -> Result<bool, diesel::result::Error> {
+		let updated = diesel::update(
+			links::table
+				.find(self.id)
+				.filter(links::max_clicks.is_null().or(links::click_count.nullable().lt(links::max_clicks))),
+		)
+		.set(links::click_count.eq(links::click_count + 1))
+		.execute(conn)?;
+
+		Ok(updated > 0)
+	}
+
+	/// Archives all links that passed their expiry date or used up their clicks, and returns them
+	pub fn archive_expired(conn: &mut DbConnection) -> Result<Vec<Link>, diesel::result::Error> {
+		let now = Utc::now().naive_utc();
+
+		diesel::update(
+			links::table
+				.filter(links::archived_at.is_null())
+				.filter(links::deleted_at.is_null())
+				.filter(
+					links::expires_at
+						.lt(now)
+						.or(links::click_count.nullable().ge(links::max_clicks)),
+				),
+		)
+		.set(links::archived_at.eq(now))
+		.get_results(conn)
+	}
+
+	pub async fn archive_expired_pooled(pool: &DbPool) -> Result<Vec<Link>, diesel::result::Error> {
+		match crate::run(pool, Self::archive_expired).await {
+			Ok(result) => result,
+			Err(e) => {
+				log::error!("{}", e);
+				Ok(Vec::new())
+			}
+		}
 	}
 }
 
@@ -123,16 +408,15 @@ pub struct NewLink {
 	pub custom_slug: Option<String>,
 	pub original_link: String,
 	pub owner_id: Option<i32>,
+	pub expires_at: Option<NaiveDateTime>,
+	pub max_clicks: Option<i32>,
+	pub password_hash: Option<String>,
+	pub redirect_type: Option<i32>,
+	pub team_id: Option<i32>,
 }
 
 impl NewLink {
-	pub fn insert(&self, conn: &mut DbConnection) -> Link {
-		let link = diesel::insert_into(links::table)
-			.values(self)
-			.returning(Link::as_returning())
-			.get_result(conn)
-			.expect("Error saving new link");
-
-		link
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<Link, diesel::result::Error> {
+		diesel::insert_into(links::table).values(self).get_result(conn)
 	}
 }
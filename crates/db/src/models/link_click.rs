@@ -0,0 +1,138 @@
+use chrono::NaiveDateTime;
+use diesel::{
+	dsl::{count, max},
+	prelude::*,
+	sql_types::{Integer, Text, Timestamp},
+};
+use serde::Serialize;
+
+use crate::{backend::on_backend, schema::link_clicks, AnyConnection, DbConnection};
+
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::link_clicks)]
+pub struct LinkClick {
+	pub id: i32,
+	pub link_id: i32,
+	pub domain_id: i32,
+	pub referrer: Option<String>,
+	pub user_agent: Option<String>,
+	pub ip_hash: Option<String>,
+	pub clicked_at: NaiveDateTime,
+}
+
+/// A single bucket of a click time series
+#[derive(Debug, QueryableByName, Serialize)]
+pub struct ClickBucket {
+	#[diesel(sql_type = Timestamp)]
+	pub bucket: NaiveDateTime,
+	#[diesel(sql_type = diesel::sql_types::BigInt)]
+	pub clicks: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReferrerCount {
+	pub referrer: Option<String>,
+	pub clicks: i64,
+}
+
+impl LinkClick {
+	pub fn get_total_count(link_id: i32, conn: &mut DbConnection) -> QueryResult<i64> {
+		link_clicks::table
+			.filter(link_clicks::link_id.eq(link_id))
+			.count()
+			.get_result(conn)
+	}
+
+	pub fn get_unique_count(link_id: i32, conn: &mut DbConnection) -> QueryResult<i64> {
+		link_clicks::table
+			.filter(link_clicks::link_id.eq(link_id))
+			.select(count(link_clicks::ip_hash).aggregate_distinct())
+			.get_result(conn)
+	}
+
+	pub fn get_last_clicked_at(link_id: i32, conn: &mut DbConnection) -> QueryResult<Option<NaiveDateTime>> {
+		link_clicks::table
+			.filter(link_clicks::link_id.eq(link_id))
+			.select(max(link_clicks::clicked_at))
+			.get_result(conn)
+	}
+
+	pub fn get_top_referrers(
+		link_id: i32,
+		limit: i64,
+		conn: &mut DbConnection,
+	) -> Result<Vec<ReferrerCount>, diesel::result::Error> {
+		let rows = link_clicks::table
+			.filter(link_clicks::link_id.eq(link_id))
+			.group_by(link_clicks::referrer)
+			.select((link_clicks::referrer, count(link_clicks::id)))
+			.order_by(count(link_clicks::id).desc())
+			.limit(limit)
+			.load::<(Option<String>, i64)>(conn)?;
+
+		Ok(rows
+			.into_iter()
+			.map(|(referrer, clicks)| ReferrerCount { referrer, clicks })
+			.collect())
+	}
+
+	/// Gets click counts grouped by `interval` (`minute`, `hour`, `day`, `month` or `year`) since the given time
+	pub fn get_time_series(
+		link_id: i32,
+		interval: &str,
+		since: NaiveDateTime,
+		conn: &mut DbConnection,
+	) -> Result<Vec<ClickBucket>, diesel::result::Error> {
+		// SQLite has no date_trunc, so the start of each bucket is formatted instead
+		let sqlite_format = bucket_format(interval)?;
+
+		match &mut **conn {
+			AnyConnection::Postgresql(conn) => diesel::sql_query(
+				"SELECT date_trunc($1, clicked_at) AS bucket, COUNT(*) AS clicks FROM link_clicks \
+				WHERE link_id = $2 AND clicked_at >= $3 GROUP BY bucket ORDER BY bucket",
+			)
+			.bind::<Text, _>(interval)
+			.bind::<Integer, _>(link_id)
+			.bind::<Timestamp, _>(since)
+			.load::<ClickBucket>(conn),
+			AnyConnection::Sqlite(conn) => diesel::sql_query(
+				"SELECT strftime(?, clicked_at) AS bucket, COUNT(*) AS clicks FROM link_clicks \
+				WHERE link_id = ? AND clicked_at >= ? GROUP BY bucket ORDER BY bucket",
+			)
+			.bind::<Text, _>(sqlite_format)
+			.bind::<Integer, _>(link_id)
+			.bind::<Timestamp, _>(since)
+			.load::<ClickBucket>(conn),
+		}
+	}
+}
+
+/// The `strftime` format that turns a click time into the start of its bucket
+pub(crate) fn bucket_format(interval: &str) -> QueryResult<&'static str> {
+	match interval {
+		"minute" => Ok("%Y-%m-%d %H:%M:00"),
+		"hour" => Ok("%Y-%m-%d %H:00:00"),
+		"day" => Ok("%Y-%m-%d 00:00:00"),
+		"month" => Ok("%Y-%m-01 00:00:00"),
+		"year" => Ok("%Y-01-01 00:00:00"),
+		_ => Err(diesel::result::Error::QueryBuilderError(format!("Unknown interval {}", interval).into())),
+	}
+}
+
+#[derive(Debug, Insertable, Clone)]
+#[diesel(table_name = crate::schema::link_clicks)]
+pub struct NewLinkClick {
+	pub link_id: i32,
+	pub domain_id: i32,
+	pub referrer: Option<String>,
+	pub user_agent: Option<String>,
+	pub ip_hash: Option<String>,
+	pub clicked_at: NaiveDateTime,
+}
+
+impl NewLinkClick {
+	/// Inserts a batch of clicks in a single statement
+	pub fn insert_batch(clicks: &[NewLinkClick], conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		on_backend!(&mut **conn, |conn| diesel::insert_into(link_clicks::table).values(clicks).execute(conn))
+	}
+}
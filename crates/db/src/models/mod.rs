@@ -1,9 +1,29 @@
+mod api_key;
 mod domain;
+mod email_change_token;
 mod link;
+mod link_click;
+mod password_reset_token;
+mod recovery_code;
+mod role;
+mod session;
+mod team;
+mod url_rule;
 mod user;
+mod user_identity;
 mod verification_tokens;
 
+pub use api_key::*;
 pub use domain::*;
+pub use email_change_token::*;
 pub use link::*;
+pub use link_click::*;
+pub use password_reset_token::*;
+pub use recovery_code::*;
+pub use role::*;
+pub use session::*;
+pub use team::*;
+pub use url_rule::*;
 pub use user::*;
+pub use user_identity::*;
 pub use verification_tokens::*;
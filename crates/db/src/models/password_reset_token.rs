@@ -0,0 +1,81 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{
+	schema::{password_reset_tokens, users},
+	DbConnection, DbPool,
+};
+
+use super::User;
+
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
+#[diesel(table_name = crate::schema::password_reset_tokens)]
+pub struct PasswordResetToken {
+	pub id: i32,
+	pub user_id: i32,
+	#[serde(skip_serializing)]
+	pub token_hash: String,
+	pub created_at: NaiveDateTime,
+	pub expires_at: NaiveDateTime,
+}
+
+impl PasswordResetToken {
+	/// Gets a token and the user it belongs to by the hash of the token
+	pub fn get_by_token_hash(
+		token_hash: &str,
+		conn: &mut DbConnection,
+	) -> Result<(PasswordResetToken, User), diesel::result::Error> {
+		password_reset_tokens::table
+			.filter(password_reset_tokens::token_hash.eq(token_hash))
+			.inner_join(users::table)
+			.select((password_reset_tokens::all_columns, users::all_columns))
+			.first::<(PasswordResetToken, User)>(conn)
+	}
+
+	pub fn is_expired(&self) -> bool {
+		self.expires_at <= Utc::now().naive_utc()
+	}
+
+	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(password_reset_tokens::table.find(self.id)).execute(conn)
+	}
+
+	/// Deletes every reset token of a user, so older links stop working once one is used or a new one is requested
+	pub fn delete_for_user(user_id: i32, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(password_reset_tokens::table.filter(password_reset_tokens::user_id.eq(user_id))).execute(conn)
+	}
+
+	pub fn delete_expired(conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(
+			password_reset_tokens::table.filter(password_reset_tokens::expires_at.lt(Utc::now().naive_utc())),
+		)
+		.execute(conn)
+	}
+
+	pub async fn delete_expired_pooled(pool: &DbPool) -> Result<usize, diesel::result::Error> {
+		match crate::run(pool, Self::delete_expired).await {
+			Ok(result) => result,
+			Err(e) => {
+				log::error!("{}", e);
+				Ok(0)
+			}
+		}
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::password_reset_tokens)]
+pub struct NewPasswordResetToken {
+	pub user_id: i32,
+	pub token_hash: String,
+	pub expires_at: NaiveDateTime,
+}
+
+impl NewPasswordResetToken {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<PasswordResetToken, diesel::result::Error> {
+		diesel::insert_into(password_reset_tokens::table)
+			.values(self)
+			.get_result(conn)
+	}
+}
@@ -0,0 +1,72 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{backend::on_backend, schema::recovery_codes, DbConnection};
+
+/// A one-time code that can be used instead of a TOTP code
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::recovery_codes)]
+pub struct RecoveryCode {
+	pub id: i32,
+	pub user_id: i32,
+	#[serde(skip_serializing)]
+	pub code_hash: String,
+	pub used_at: Option<NaiveDateTime>,
+	pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::recovery_codes)]
+struct NewRecoveryCode<'a> {
+	user_id: i32,
+	code_hash: &'a str,
+}
+
+impl RecoveryCode {
+	pub fn count_unused(user_id: i32, conn: &mut DbConnection) -> Result<i64, diesel::result::Error> {
+		recovery_codes::table
+			.filter(recovery_codes::user_id.eq(user_id))
+			.filter(recovery_codes::used_at.is_null())
+			.count()
+			.get_result(conn)
+	}
+
+	/// Marks a code as used. Returns false if the user has no such unused code.
+	pub fn redeem(user_id: i32, code_hash: &str, conn: &mut DbConnection) -> Result<bool, diesel::result::Error> {
+		let updated = diesel::update(
+			recovery_codes::table
+				.filter(recovery_codes::user_id.eq(user_id))
+				.filter(recovery_codes::code_hash.eq(code_hash))
+				.filter(recovery_codes::used_at.is_null()),
+		)
+		.set(recovery_codes::used_at.eq(Utc::now().naive_utc()))
+		.execute(conn)?;
+
+		Ok(updated == 1)
+	}
+
+	/// Swaps all codes of a user for new ones
+	pub fn replace_for_user(
+		user_id: i32,
+		code_hashes: &[String],
+		conn: &mut DbConnection,
+	) -> Result<usize, diesel::result::Error> {
+		let new_codes: Vec<NewRecoveryCode> = code_hashes
+			.iter()
+			.map(|code_hash| NewRecoveryCode { user_id, code_hash })
+			.collect();
+
+		conn.transaction(|conn| {
+			Self::delete_for_user(user_id, conn)?;
+
+			on_backend!(&mut **conn, |conn| diesel::insert_into(recovery_codes::table)
+				.values(&new_codes)
+				.execute(conn))
+		})
+	}
+
+	pub fn delete_for_user(user_id: i32, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(recovery_codes::table.filter(recovery_codes::user_id.eq(user_id))).execute(conn)
+	}
+}
@@ -0,0 +1,166 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{
+	backend::on_backend,
+	schema::{role_permissions, roles, user_roles},
+	DbConnection,
+};
+
+/// Built-in role with every permission, given to the first user
+pub const ADMIN_ROLE: &str = "admin";
+/// Built-in role every user gets when their account is created
+pub const USER_ROLE: &str = "user";
+
+/// A named set of permissions that can be given to users
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::roles)]
+pub struct Role {
+	pub id: i32,
+	pub name: String,
+	pub description: Option<String>,
+	pub built_in: bool,
+	pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::role_permissions)]
+struct NewRolePermission<'a> {
+	role_id: i32,
+	permission: &'a str,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::user_roles)]
+struct NewUserRole {
+	user_id: i32,
+	role_id: i32,
+}
+
+impl Role {
+	pub fn get_all(conn: &mut DbConnection) -> Result<Vec<Role>, diesel::result::Error> {
+		roles::table.order(roles::id.asc()).load::<Role>(conn)
+	}
+
+	pub fn get_by_id(id: i32, conn: &mut DbConnection) -> Result<Role, diesel::result::Error> {
+		roles::table.find(id).first::<Role>(conn)
+	}
+
+	pub fn get_by_name(name: &str, conn: &mut DbConnection) -> Result<Role, diesel::result::Error> {
+		roles::table.filter(roles::name.eq(name)).first::<Role>(conn)
+	}
+
+	/// Gets the roles of a user
+	pub fn get_for_user(user_id: i32, conn: &mut DbConnection) -> Result<Vec<Role>, diesel::result::Error> {
+		user_roles::table
+			.filter(user_roles::user_id.eq(user_id))
+			.inner_join(roles::table)
+			.select(Role::as_select())
+			.order(roles::id.asc())
+			.load::<Role>(conn)
+	}
+
+	/// Gets every permission a user has through any of their roles
+	pub fn permissions_for_user(user_id: i32, conn: &mut DbConnection) -> Result<Vec<String>, diesel::result::Error> {
+		user_roles::table
+			.filter(user_roles::user_id.eq(user_id))
+			.inner_join(role_permissions::table.on(role_permissions::role_id.eq(user_roles::role_id)))
+			.select(role_permissions::permission)
+			.distinct()
+			.load::<String>(conn)
+	}
+
+	pub fn permissions(&self, conn: &mut DbConnection) -> Result<Vec<String>, diesel::result::Error> {
+		role_permissions::table
+			.filter(role_permissions::role_id.eq(self.id))
+			.select(role_permissions::permission)
+			.order(role_permissions::permission.asc())
+			.load::<String>(conn)
+	}
+
+	/// Replaces the permissions of the role
+	pub fn set_permissions(
+		&self,
+		permissions: &[String],
+		conn: &mut DbConnection,
+	) -> Result<usize, diesel::result::Error> {
+		let new_permissions: Vec<NewRolePermission> = permissions
+			.iter()
+			.map(|permission| NewRolePermission {
+				role_id: self.id,
+				permission,
+			})
+			.collect();
+
+		conn.transaction(|conn| {
+			diesel::delete(role_permissions::table.filter(role_permissions::role_id.eq(self.id))).execute(conn)?;
+
+			on_backend!(&mut **conn, |conn| diesel::insert_into(role_permissions::table)
+				.values(&new_permissions)
+				.execute(conn))
+		})
+	}
+
+	pub fn update_description(
+		&self,
+		description: Option<String>,
+		conn: &mut DbConnection,
+	) -> Result<usize, diesel::result::Error> {
+		diesel::update(roles::table.find(self.id))
+			.set(roles::description.eq(description))
+			.execute(conn)
+	}
+
+	/// How many users have the role
+	pub fn count_users(&self, conn: &mut DbConnection) -> Result<i64, diesel::result::Error> {
+		user_roles::table
+			.filter(user_roles::role_id.eq(self.id))
+			.count()
+			.get_result(conn)
+	}
+
+	/// Gives the role to a user, doing nothing if they already have it
+	pub fn assign(&self, user_id: i32, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		let new_user_role = NewUserRole {
+			user_id,
+			role_id: self.id,
+		};
+
+		on_backend!(&mut **conn, |conn| diesel::insert_into(user_roles::table)
+			.values(&new_user_role)
+			.on_conflict_do_nothing()
+			.execute(conn))
+	}
+
+	pub fn unassign(&self, user_id: i32, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(
+			user_roles::table
+				.filter(user_roles::user_id.eq(user_id))
+				.filter(user_roles::role_id.eq(self.id)),
+		)
+		.execute(conn)
+	}
+
+	/// Gives a role, looked up by name, to a user
+	pub fn assign_by_name(name: &str, user_id: i32, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		Self::get_by_name(name, conn)?.assign(user_id, conn)
+	}
+
+	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(roles::table.find(self.id)).execute(conn)
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::roles)]
+pub struct NewRole {
+	pub name: String,
+	pub description: Option<String>,
+}
+
+impl NewRole {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<Role, diesel::result::Error> {
+		diesel::insert_into(roles::table).values(self).get_result(conn)
+	}
+}
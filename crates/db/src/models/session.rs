@@ -0,0 +1,145 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{schema::sessions, DbConnection, DbPool};
+
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::sessions)]
+pub struct Session {
+	pub id: i32,
+	pub user_id: i32,
+	#[serde(skip_serializing)]
+	pub refresh_token_hash: String,
+	#[serde(skip_serializing)]
+	pub previous_token_hash: Option<String>,
+	pub user_agent: Option<String>,
+	pub created_at: NaiveDateTime,
+	pub last_seen_at: NaiveDateTime,
+	pub expires_at: NaiveDateTime,
+	pub revoked_at: Option<NaiveDateTime>,
+}
+
+impl Session {
+	pub fn get_by_id(id: i32, conn: &mut DbConnection) -> Result<Session, diesel::result::Error> {
+		sessions::table.find(id).first(conn)
+	}
+
+	/// Gets the sessions of a user that haven't been revoked or expired, most recently used first
+	pub fn get_active_by_user_id(user_id: i32, conn: &mut DbConnection) -> Result<Vec<Session>, diesel::result::Error> {
+		sessions::table
+			.filter(sessions::user_id.eq(user_id))
+			.filter(sessions::revoked_at.is_null())
+			.filter(sessions::expires_at.gt(Utc::now().naive_utc()))
+			.order_by(sessions::last_seen_at.desc())
+			.load::<Session>(conn)
+	}
+
+	pub fn get_by_refresh_token_hash(
+		refresh_token_hash: &str,
+		conn: &mut DbConnection,
+	) -> Result<Session, diesel::result::Error> {
+		sessions::table
+			.filter(sessions::refresh_token_hash.eq(refresh_token_hash))
+			.first(conn)
+	}
+
+	/// Gets the session a refresh token belonged to before it was rotated
+	pub fn get_by_previous_token_hash(
+		previous_token_hash: &str,
+		conn: &mut DbConnection,
+	) -> Result<Session, diesel::result::Error> {
+		sessions::table
+			.filter(sessions::previous_token_hash.eq(previous_token_hash))
+			.first(conn)
+	}
+
+	pub fn is_active(&self) -> bool {
+		self.revoked_at.is_none() && self.expires_at > Utc::now().naive_utc()
+	}
+
+	/// Swaps the refresh token for a new one and extends the session. Returns `None` if the token was already
+	/// swapped by someone else, so each refresh token can only be used once.
+	pub fn rotate(
+		&self,
+		refresh_token_hash: String,
+		expires_at: NaiveDateTime,
+		conn: &mut DbConnection,
+	) -> Result<Option<Session>, diesel::result::Error> {
+		diesel::update(
+			sessions::table
+				.find(self.id)
+				.filter(sessions::refresh_token_hash.eq(&self.refresh_token_hash)),
+		)
+		.set((
+			sessions::previous_token_hash.eq(&self.refresh_token_hash),
+			sessions::refresh_token_hash.eq(refresh_token_hash),
+			sessions::last_seen_at.eq(Utc::now().naive_utc()),
+			sessions::expires_at.eq(expires_at),
+		))
+		.get_result(conn)
+		.optional()
+	}
+
+	pub fn touch(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(sessions::table.find(self.id))
+			.set(sessions::last_seen_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+	}
+
+	pub fn revoke(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(sessions::table.find(self.id))
+			.set(sessions::revoked_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+	}
+
+	/// Revokes every active session of a user, except the given one
+	pub fn revoke_all_for_user(
+		user_id: i32,
+		except_id: Option<i32>,
+		conn: &mut DbConnection,
+	) -> Result<usize, diesel::result::Error> {
+		diesel::update(
+			sessions::table
+				.filter(sessions::user_id.eq(user_id))
+				.filter(sessions::revoked_at.is_null())
+				.filter(sessions::id.ne(except_id.unwrap_or(-1))),
+		)
+		.set(sessions::revoked_at.eq(Utc::now().naive_utc()))
+		.execute(conn)
+	}
+
+	/// Deletes sessions that expired or were revoked before the given time
+	pub fn delete_stale_before(before: NaiveDateTime, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(sessions::table.filter(sessions::expires_at.lt(before).or(sessions::revoked_at.lt(before))))
+			.execute(conn)
+	}
+
+	pub async fn delete_stale_before_pooled(
+		before: NaiveDateTime,
+		pool: &DbPool,
+	) -> Result<usize, diesel::result::Error> {
+		match crate::run(pool, move |conn| Self::delete_stale_before(before, conn)).await {
+			Ok(result) => result,
+			Err(e) => {
+				log::error!("{}", e);
+				Ok(0)
+			}
+		}
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::sessions)]
+pub struct NewSession {
+	pub user_id: i32,
+	pub refresh_token_hash: String,
+	pub user_agent: Option<String>,
+	pub expires_at: NaiveDateTime,
+}
+
+impl NewSession {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<Session, diesel::result::Error> {
+		diesel::insert_into(sessions::table).values(self).get_result(conn)
+	}
+}
@@ -0,0 +1,174 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{
+	schema::{team_members, teams, users},
+	DbConnection,
+};
+
+use super::User;
+
+/// A group of users that share links and domains
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::teams)]
+pub struct Team {
+	pub id: i32,
+	pub name: String,
+	pub created_at: NaiveDateTime,
+	pub updated_at: NaiveDateTime,
+}
+
+/// A user's membership in a team, `role` is one of owner, admin or member
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::team_members)]
+pub struct TeamMember {
+	pub team_id: i32,
+	pub user_id: i32,
+	pub role: String,
+	pub created_at: NaiveDateTime,
+}
+
+impl Team {
+	pub fn get_by_id(id: i32, conn: &mut DbConnection) -> Result<Team, diesel::result::Error> {
+		teams::table.find(id).first::<Team>(conn)
+	}
+
+	/// Gets the teams of a user, along with their membership
+	pub fn get_for_user(
+		user_id: i32,
+		conn: &mut DbConnection,
+	) -> Result<Vec<(Team, TeamMember)>, diesel::result::Error> {
+		team_members::table
+			.filter(team_members::user_id.eq(user_id))
+			.inner_join(teams::table)
+			.select((Team::as_select(), TeamMember::as_select()))
+			.order(teams::name.asc())
+			.load::<(Team, TeamMember)>(conn)
+	}
+
+	/// Gets the members of the team, along with their users
+	pub fn members(&self, conn: &mut DbConnection) -> Result<Vec<(TeamMember, User)>, diesel::result::Error> {
+		team_members::table
+			.filter(team_members::team_id.eq(self.id))
+			.inner_join(users::table)
+			.select((TeamMember::as_select(), User::as_select()))
+			.order(team_members::created_at.asc())
+			.load::<(TeamMember, User)>(conn)
+	}
+
+	pub fn count_members(&self, conn: &mut DbConnection) -> Result<i64, diesel::result::Error> {
+		team_members::table
+			.filter(team_members::team_id.eq(self.id))
+			.count()
+			.get_result(conn)
+	}
+
+	/// Counts the members that have the given role
+	pub fn count_members_with_role(&self, role: &str, conn: &mut DbConnection) -> Result<i64, diesel::result::Error> {
+		team_members::table
+			.filter(team_members::team_id.eq(self.id))
+			.filter(team_members::role.eq(role))
+			.count()
+			.get_result(conn)
+	}
+
+	pub fn rename(&self, name: &str, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(teams::table.find(self.id))
+			.set((teams::name.eq(name), teams::updated_at.eq(Utc::now().naive_utc())))
+			.execute(conn)
+	}
+
+	/// Deletes the team along with its links. Its domains are kept, but no longer belong to a team.
+	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(teams::table.find(self.id)).execute(conn)
+	}
+}
+
+impl TeamMember {
+	/// Gets the membership of a user in a team, if they're in it
+	pub fn get(
+		team_id: i32,
+		user_id: i32,
+		conn: &mut DbConnection,
+	) -> Result<Option<TeamMember>, diesel::result::Error> {
+		team_members::table
+			.find((team_id, user_id))
+			.first::<TeamMember>(conn)
+			.optional()
+	}
+
+	/// Checks if two users are in at least one team together
+	pub fn share_team(
+		user_id: i32,
+		other_user_id: i32,
+		conn: &mut DbConnection,
+	) -> Result<bool, diesel::result::Error> {
+		let (members, others) = diesel::alias!(team_members as members, team_members as others);
+
+		diesel::select(diesel::dsl::exists(
+			members
+				.inner_join(
+					others.on(others
+						.field(team_members::team_id)
+						.eq(members.field(team_members::team_id))),
+				)
+				.filter(members.field(team_members::user_id).eq(user_id))
+				.filter(others.field(team_members::user_id).eq(other_user_id)),
+		))
+		.get_result(conn)
+	}
+
+	pub fn set_role(&self, role: &str, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(team_members::table.find((self.team_id, self.user_id)))
+			.set(team_members::role.eq(role))
+			.execute(conn)
+	}
+
+	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(team_members::table.find((self.team_id, self.user_id))).execute(conn)
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::teams)]
+pub struct NewTeam {
+	pub name: String,
+}
+
+impl NewTeam {
+	/// Creates the team with its first member
+	pub fn insert_with_member(
+		&self,
+		user_id: i32,
+		role: &str,
+		conn: &mut DbConnection,
+	) -> Result<Team, diesel::result::Error> {
+		conn.transaction(|conn| {
+			let team: Team = diesel::insert_into(teams::table).values(self).get_result(conn)?;
+
+			NewTeamMember {
+				team_id: team.id,
+				user_id,
+				role: role.to_string(),
+			}
+			.insert(conn)?;
+
+			Ok(team)
+		})
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::team_members)]
+pub struct NewTeamMember {
+	pub team_id: i32,
+	pub user_id: i32,
+	pub role: String,
+}
+
+impl NewTeamMember {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<TeamMember, diesel::result::Error> {
+		diesel::insert_into(team_members::table).values(self).get_result(conn)
+	}
+}
@@ -0,0 +1,52 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{schema::url_rules, DbConnection};
+
+pub const URL_RULE_BLOCK: &str = "block";
+pub const URL_RULE_ALLOW: &str = "allow";
+
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::url_rules)]
+pub struct UrlRule {
+	pub id: i32,
+	pub domain: String,
+	pub kind: String,
+	pub created_at: NaiveDateTime,
+}
+
+impl UrlRule {
+	pub fn get_all(conn: &mut DbConnection) -> Result<Vec<UrlRule>, diesel::result::Error> {
+		url_rules::table.order_by(url_rules::domain.asc()).load::<UrlRule>(conn)
+	}
+
+	pub fn get_by_id(id: i32, conn: &mut DbConnection) -> Result<UrlRule, diesel::result::Error> {
+		url_rules::table.find(id).first(conn)
+	}
+
+	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(url_rules::table.find(self.id)).execute(conn)
+	}
+
+	pub fn is_block(&self) -> bool {
+		self.kind == URL_RULE_BLOCK
+	}
+
+	pub fn is_allow(&self) -> bool {
+		self.kind == URL_RULE_ALLOW
+	}
+}
+
+#[derive(Debug, Insertable, Deserialize)]
+#[diesel(table_name = crate::schema::url_rules)]
+pub struct NewUrlRule {
+	pub domain: String,
+	pub kind: String,
+}
+
+impl NewUrlRule {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<UrlRule, diesel::result::Error> {
+		diesel::insert_into(url_rules::table).values(self).get_result(conn)
+	}
+}
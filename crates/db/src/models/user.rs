@@ -1,8 +1,11 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use serde::Serialize;
 
-use crate::{schema::users, DbConnection};
+use crate::{backend::MultiBackend, schema::users, DbConnection};
+
+// ILIKE is Postgres only
+define_sql_function!(fn lower(x: diesel::sql_types::Text) -> diesel::sql_types::Text);
 
 #[derive(Debug, Queryable, Selectable, Serialize, Clone)]
 #[diesel(table_name = crate::schema::users)]
@@ -12,9 +15,21 @@ pub struct User {
 	pub email: String,
 	pub password_hash: String,
 	pub verified_at: Option<NaiveDateTime>,
-	pub is_admin: bool,
 	pub created_at: NaiveDateTime,
 	pub deleted_at: Option<NaiveDateTime>,
+	#[serde(skip_serializing)]
+	pub totp_secret: Option<String>,
+	pub totp_enabled_at: Option<NaiveDateTime>,
+	#[serde(skip_serializing)]
+	pub totp_last_step: Option<i64>,
+	#[serde(skip_serializing)]
+	pub totp_attempts: i32,
+	#[serde(skip_serializing)]
+	pub totp_locked_until: Option<NaiveDateTime>,
+	pub suspended_at: Option<NaiveDateTime>,
+	/// When the suspension ends. Suspensions without an end are bans.
+	pub suspended_until: Option<NaiveDateTime>,
+	pub suspension_reason: Option<String>,
 }
 
 #[derive(AsChangeset, Clone, Debug)]
@@ -31,33 +46,30 @@ pub struct SanitizedUser {
 	pub username: String,
 	pub email: String,
 	pub verified_at: Option<NaiveDateTime>,
-	pub is_admin: bool,
 	pub created_at: NaiveDateTime,
 	pub deleted_at: Option<NaiveDateTime>,
-}
-
-impl From<&User> for SanitizedUser {
-	fn from(user: &User) -> Self {
-		Self {
-			id: user.id,
-			username: user.username.clone(),
-			email: user.email.clone(),
-			verified_at: user.verified_at,
-			created_at: user.created_at,
-			deleted_at: user.deleted_at,
-			is_admin: user.is_admin,
-		}
-	}
+	pub two_factor_enabled: bool,
+	/// What the user is allowed to do through their roles
+	pub permissions: Vec<String>,
 }
 
 impl User {
-	pub fn sanitize(&self) -> SanitizedUser {
-		SanitizedUser::from(self)
+	pub fn sanitize(&self, permissions: Vec<String>) -> SanitizedUser {
+		SanitizedUser {
+			id: self.id,
+			username: self.username.clone(),
+			email: self.email.clone(),
+			verified_at: self.verified_at,
+			created_at: self.created_at,
+			deleted_at: self.deleted_at,
+			two_factor_enabled: self.has_two_factor(),
+			permissions,
+		}
 	}
 
 	pub fn username_exists(username: &str, conn: &mut DbConnection) -> bool {
 		let count = users::table
-			.filter(users::username.ilike(username))
+			.filter(lower(users::username).eq(lower(username)))
 			.count()
 			.get_result::<i64>(conn)
 			.unwrap_or(0);
@@ -66,7 +78,7 @@ impl User {
 
 	pub fn email_exists(email: &str, conn: &mut DbConnection) -> bool {
 		let count = users::table
-			.filter(users::email.ilike(email))
+			.filter(lower(users::email).eq(lower(email)))
 			.count()
 			.get_result::<i64>(conn)
 			.unwrap_or(0);
@@ -106,10 +118,178 @@ impl User {
 			.execute(conn)
 	}
 
+	pub fn has_two_factor(&self) -> bool {
+		self.totp_enabled_at.is_some()
+	}
+
+	/// Stores a new TOTP secret. 2FA stays off until the secret is confirmed with [`User::enable_totp`].
+	pub fn set_pending_totp_secret(&self, secret: &str, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set((
+				users::totp_secret.eq(secret),
+				users::totp_enabled_at.eq(None::<NaiveDateTime>),
+				users::totp_last_step.eq(None::<i64>),
+			))
+			.execute(conn)
+	}
+
+	pub fn enable_totp(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set(users::totp_enabled_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+	}
+
+	pub fn disable_totp(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set((
+				users::totp_secret.eq(None::<String>),
+				users::totp_enabled_at.eq(None::<NaiveDateTime>),
+				users::totp_last_step.eq(None::<i64>),
+			))
+			.execute(conn)
+	}
+
+	/// Records a TOTP time step as used. Returns false if it (or a later one) was already used,
+	/// which also holds up when two requests race with the same code.
+	pub fn claim_totp_step(&self, step: i64, conn: &mut DbConnection) -> Result<bool, diesel::result::Error> {
+		let updated = diesel::update(
+			users::table
+				.find(self.id)
+				.filter(users::totp_last_step.is_null().or(users::totp_last_step.lt(step))),
+		)
+		.set(users::totp_last_step.eq(step))
+		.execute(conn)?;
+
+		Ok(updated == 1)
+	}
+
+	/// Counts a 2FA code attempt. Returns false while the user is locked out, and locks them out until
+	/// `locked_until` once they go over `max_attempts` without a code being accepted.
+	pub fn claim_totp_attempt(
+		&self,
+		max_attempts: i32,
+		locked_until: NaiveDateTime,
+		conn: &mut DbConnection,
+	) -> Result<bool, diesel::result::Error> {
+		let now = Utc::now().naive_utc();
+
+		// Counted in the database, so requests racing each other can't get more attempts
+		let attempts = diesel::update(
+			users::table
+				.find(self.id)
+				.filter(users::totp_locked_until.is_null().or(users::totp_locked_until.le(now))),
+		)
+		.set(users::totp_attempts.eq(users::totp_attempts + 1))
+		.returning(users::totp_attempts)
+		.get_result::<i32>(conn)
+		.optional()?;
+
+		match attempts {
+			Some(attempts) if attempts <= max_attempts => Ok(true),
+			Some(_) => {
+				diesel::update(users::table.find(self.id))
+					.set((users::totp_attempts.eq(0), users::totp_locked_until.eq(locked_until)))
+					.execute(conn)?;
+
+				Ok(false)
+			}
+			None => Ok(false),
+		}
+	}
+
+	/// Starts counting 2FA code attempts from zero, after a code was accepted
+	pub fn reset_totp_attempts(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set(users::totp_attempts.eq(0))
+			.execute(conn)
+	}
+
 	pub fn delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
 		diesel::delete(users::table.filter(users::id.eq(self.id))).execute(conn)
 	}
 
+	/// Marks the user as deleted, keeping their data so it can be restored
+	pub fn soft_delete(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set(users::deleted_at.eq(Utc::now().naive_utc()))
+			.execute(conn)
+	}
+
+	pub fn restore(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set(users::deleted_at.eq(None::<NaiveDateTime>))
+			.execute(conn)
+	}
+
+	/// Checks if the user is suspended right now. Suspensions that ran out don't count.
+	pub fn is_suspended(&self) -> bool {
+		self.suspended_at.is_some() && self.suspended_until.is_none_or(|until| until > Utc::now().naive_utc())
+	}
+
+	/// Suspends the user until the given time, or for good if there is none
+	pub fn suspend(
+		&self,
+		until: Option<NaiveDateTime>,
+		reason: Option<String>,
+		conn: &mut DbConnection,
+	) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set((
+				users::suspended_at.eq(Utc::now().naive_utc()),
+				users::suspended_until.eq(until),
+				users::suspension_reason.eq(reason),
+			))
+			.execute(conn)
+	}
+
+	pub fn unsuspend(&self, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(users::table.find(self.id))
+			.set((
+				users::suspended_at.eq(None::<NaiveDateTime>),
+				users::suspended_until.eq(None::<NaiveDateTime>),
+				users::suspension_reason.eq(None::<String>),
+			))
+			.execute(conn)
+	}
+
+	/// Filters users by a part of their username or email
+	fn search_query(search: Option<&str>) -> users::BoxedQuery<'static, MultiBackend> {
+		let mut query = users::table.into_boxed();
+
+		if let Some(search) = search.map(str::trim).filter(|search| !search.is_empty()) {
+			let escaped = search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+			let pattern = format!("%{}%", escaped);
+
+			query = query.filter(
+				lower(users::username)
+					.like(pattern.to_lowercase())
+					.escape('\\')
+					.or(lower(users::email).like(pattern.to_lowercase()).escape('\\')),
+			);
+		}
+
+		query
+	}
+
+	pub fn search_paginated(
+		search: Option<&str>,
+		page: i64,
+		per_page: i64,
+		conn: &mut DbConnection,
+	) -> Result<Vec<User>, diesel::result::Error> {
+		let offset_value = (page - 1) * per_page;
+
+		Self::search_query(search)
+			.order_by(users::created_at.desc())
+			.limit(per_page)
+			.offset(offset_value)
+			.load::<User>(conn)
+	}
+
+	pub fn search_count(search: Option<&str>, conn: &mut DbConnection) -> QueryResult<i64> {
+		Self::search_query(search).count().get_result(conn)
+	}
+
 	pub fn update(&self, values: UpdateUser, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
 		diesel::update(users::table.find(self.id)).set(&values).execute(conn)
 	}
@@ -125,14 +305,12 @@ pub struct NewUser {
 	pub username: String,
 	pub password_hash: String,
 	pub email: String,
-	pub is_admin: bool,
 }
 
 impl NewUser {
 	pub fn insert(&self, conn: &mut DbConnection) -> User {
 		diesel::insert_into(users::table)
 			.values(self)
-			.returning(User::as_returning())
 			.get_result(conn)
 			.expect("Error saving new user")
 	}
@@ -0,0 +1,64 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+
+use crate::{
+	schema::{user_identities, users},
+	DbConnection,
+};
+
+use super::User;
+
+/// An account at an external OpenID Connect provider that can be used to sign in as a local user
+#[derive(Debug, Queryable, Selectable, Serialize, Clone)]
+#[diesel(table_name = crate::schema::user_identities)]
+pub struct UserIdentity {
+	pub id: i32,
+	pub user_id: i32,
+	pub issuer: String,
+	pub subject: String,
+	pub email: Option<String>,
+	pub created_at: NaiveDateTime,
+	pub last_login_at: NaiveDateTime,
+}
+
+impl UserIdentity {
+	/// Gets an identity and the user it's linked to by the provider's issuer and the subject at that provider
+	pub fn get_by_issuer_subject(
+		issuer: &str,
+		subject: &str,
+		conn: &mut DbConnection,
+	) -> Result<Option<(UserIdentity, User)>, diesel::result::Error> {
+		user_identities::table
+			.filter(user_identities::issuer.eq(issuer))
+			.filter(user_identities::subject.eq(subject))
+			.inner_join(users::table)
+			.select((user_identities::all_columns, users::all_columns))
+			.first::<(UserIdentity, User)>(conn)
+			.optional()
+	}
+
+	/// Records a login through this identity, along with the email the provider currently reports
+	pub fn touch(&self, email: Option<&str>, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::update(user_identities::table.find(self.id))
+			.set((user_identities::email.eq(email), user_identities::last_login_at.eq(Utc::now().naive_utc())))
+			.execute(conn)
+	}
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = crate::schema::user_identities)]
+pub struct NewUserIdentity {
+	pub user_id: i32,
+	pub issuer: String,
+	pub subject: String,
+	pub email: Option<String>,
+}
+
+impl NewUserIdentity {
+	pub fn insert(&self, conn: &mut DbConnection) -> Result<UserIdentity, diesel::result::Error> {
+		diesel::insert_into(user_identities::table)
+			.values(self)
+			.get_result(conn)
+	}
+}
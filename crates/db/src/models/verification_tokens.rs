@@ -9,7 +9,7 @@ use crate::{
 
 use super::User;
 
-#[derive(Debug, Queryable, Selectable, Serialize)]
+#[derive(Debug, Clone, Queryable, Selectable, Serialize)]
 #[diesel(table_name = crate::schema::verification_tokens)]
 pub struct VerificationToken {
 	pub id: i32,
@@ -21,20 +21,18 @@ pub struct VerificationToken {
 
 impl VerificationToken {
 	pub fn delete_expired(conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
-		diesel::sql_query("SELECT delete_expired_tokens();").execute(conn)
+		diesel::delete(verification_tokens::table.filter(verification_tokens::expires_at.lt(Local::now().naive_utc())))
+			.execute(conn)
 	}
 
-	pub fn delete_expired_pooled(pool: &DbPool) -> Result<usize, diesel::result::Error> {
-		let mut conn: diesel::r2d2::PooledConnection<diesel::r2d2::ConnectionManager<PgConnection>> =
-			match pool.clone().get() {
-				Ok(conn) => conn,
-				Err(e) => {
-					log::error!("Failed to get conn from pool: {:#?}", e);
-					return Ok(0);
-				}
-			};
-
-		diesel::sql_query("SELECT delete_expired_tokens();").execute(&mut conn)
+	pub async fn delete_expired_pooled(pool: &DbPool) -> Result<usize, diesel::result::Error> {
+		match crate::run(pool, Self::delete_expired).await {
+			Ok(result) => result,
+			Err(e) => {
+				log::error!("{}", e);
+				Ok(0)
+			}
+		}
 	}
 
 	pub fn get_by_token(
@@ -48,6 +46,23 @@ impl VerificationToken {
 			.load::<(VerificationToken, User)>(conn)
 	}
 
+	/// Gets the most recently sent token of a user, used to rate limit resending
+	pub fn get_latest_by_user_id(
+		user_id: i32,
+		conn: &mut DbConnection,
+	) -> Result<Option<VerificationToken>, diesel::result::Error> {
+		verification_tokens::table
+			.filter(verification_tokens::user_id.eq(user_id))
+			.order_by(verification_tokens::created_at.desc())
+			.first::<VerificationToken>(conn)
+			.optional()
+	}
+
+	/// Deletes every token of a user, so only the most recently sent link works
+	pub fn delete_for_user(user_id: i32, conn: &mut DbConnection) -> Result<usize, diesel::result::Error> {
+		diesel::delete(verification_tokens::table.filter(verification_tokens::user_id.eq(user_id))).execute(conn)
+	}
+
 	pub fn is_expired(&self) -> bool {
 		let now = Local::now().naive_utc();
 		now > self.expires_at
@@ -70,7 +85,6 @@ impl NewVerificationToken {
 	pub fn insert(&self, conn: &mut DbConnection) -> VerificationToken {
 		let token = diesel::insert_into(verification_tokens::table)
 			.values(self)
-			.returning(VerificationToken::as_returning())
 			.get_result(conn)
 			.expect("Error saving new verification token");
 
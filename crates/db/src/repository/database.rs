@@ -0,0 +1,641 @@
+use chrono::NaiveDateTime;
+use diesel::{
+	connection::{Connection, TransactionManager},
+	QueryResult, RunQueryDsl,
+};
+
+use super::{
+	DomainRepository, LinkRepository, Repositories, RoleRepository, Store, TeamRepository, TokenRepository,
+	UserRepository,
+};
+use crate::{
+	models::{
+		ApiKey, ClickBucket, Domain, EmailChangeToken, Link, LinkClick, NewApiKey, NewDomain, NewEmailChangeToken,
+		NewLink, NewLinkClick, NewPasswordResetToken, NewRole, NewSession, NewTeam, NewTeamMember, NewUrlRule, NewUser,
+		NewUserIdentity, NewVerificationToken, PasswordResetToken, RecoveryCode, ReferrerCount, Role, Session, Team,
+		TeamMember, UpdateApiKey, UpdateDomain, UpdateLink, UpdateUser, UrlRule, User, UserIdentity, VerificationToken,
+	},
+	schema::{users, verification_tokens},
+	DbConnection, DbPool, RunError,
+};
+
+/// The store backed by the database, every unit of work gets a connection from the pool
+#[derive(Clone)]
+pub struct DieselStore {
+	pool: DbPool,
+}
+
+impl DieselStore {
+	pub fn new(pool: DbPool) -> Self {
+		Self { pool }
+	}
+}
+
+impl Store for DieselStore {
+	fn open(&self) -> Result<Box<dyn Repositories + '_>, RunError> {
+		let conn = self.pool.get().map_err(RunError::Pool)?;
+
+		Ok(Box::new(DieselRepositories { conn }))
+	}
+}
+
+/// The repositories on a single pooled connection, running the queries of the models
+pub struct DieselRepositories {
+	conn: DbConnection,
+}
+
+impl Repositories for DieselRepositories {
+	fn links(&mut self) -> &mut dyn LinkRepository {
+		self
+	}
+
+	fn users(&mut self) -> &mut dyn UserRepository {
+		self
+	}
+
+	fn roles(&mut self) -> &mut dyn RoleRepository {
+		self
+	}
+
+	fn teams(&mut self) -> &mut dyn TeamRepository {
+		self
+	}
+
+	fn domains(&mut self) -> &mut dyn DomainRepository {
+		self
+	}
+
+	fn tokens(&mut self) -> &mut dyn TokenRepository {
+		self
+	}
+
+	fn in_transaction(&mut self, work: &mut dyn FnMut(&mut dyn Repositories) -> QueryResult<()>) -> QueryResult<()> {
+		type Manager = <DbConnection as Connection>::TransactionManager;
+
+		// `Connection::transaction` would keep the connection borrowed, while the work needs the repositories on it
+		Manager::begin_transaction(&mut self.conn)?;
+
+		match work(self) {
+			Ok(()) => Manager::commit_transaction(&mut self.conn),
+			Err(e) => {
+				Manager::rollback_transaction(&mut self.conn)?;
+
+				Err(e)
+			}
+		}
+	}
+}
+
+impl LinkRepository for DieselRepositories {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Link> {
+		Link::get_by_id(id, &mut self.conn)
+	}
+
+	fn get_trashed_by_id(&mut self, id: i32) -> QueryResult<Link> {
+		Link::get_trashed_by_id(id, &mut self.conn)
+	}
+
+	fn get_by_domain_slug(&mut self, domain_id: i32, slug: &str) -> QueryResult<Vec<Link>> {
+		Link::get_by_domain_slug(domain_id, &slug.to_string(), &mut self.conn)
+	}
+
+	fn get_by_domain_slug_with_trashed(&mut self, domain_id: i32, slug: &str) -> QueryResult<Vec<Link>> {
+		Link::get_by_domain_slug_with_trashed(domain_id, &slug.to_string(), &mut self.conn)
+	}
+
+	fn get_by_owner_id(&mut self, owner_id: i32) -> QueryResult<Vec<Link>> {
+		Link::get_by_owner_id(owner_id, &mut self.conn)
+	}
+
+	fn get_by_owner_id_paginated(
+		&mut self,
+		owner_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>> {
+		Link::get_by_owner_id_paginated(owner_id, page, per_page, &mut self.conn)
+	}
+
+	fn get_total_count(&mut self, owner_id: i32) -> QueryResult<i64> {
+		Link::get_total_count(owner_id, &mut self.conn)
+	}
+
+	fn get_trashed_by_owner_id_paginated(
+		&mut self,
+		owner_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>> {
+		Link::get_trashed_by_owner_id_paginated(owner_id, page, per_page, &mut self.conn)
+	}
+
+	fn get_trashed_total_count(&mut self, owner_id: i32) -> QueryResult<i64> {
+		Link::get_trashed_total_count(owner_id, &mut self.conn)
+	}
+
+	fn get_by_team_id_paginated(&mut self, team_id: i32, page: i64, per_page: i64) -> QueryResult<Vec<(Link, String)>> {
+		Link::get_by_team_id_paginated(team_id, page, per_page, &mut self.conn)
+	}
+
+	fn get_team_total_count(&mut self, team_id: i32) -> QueryResult<i64> {
+		Link::get_team_total_count(team_id, &mut self.conn)
+	}
+
+	fn get_trashed_by_team_id_paginated(
+		&mut self,
+		team_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>> {
+		Link::get_trashed_by_team_id_paginated(team_id, page, per_page, &mut self.conn)
+	}
+
+	fn get_trashed_team_total_count(&mut self, team_id: i32) -> QueryResult<i64> {
+		Link::get_trashed_team_total_count(team_id, &mut self.conn)
+	}
+
+	fn get_domain_count(&mut self, domain_id: i32) -> QueryResult<i64> {
+		Link::get_domain_count(domain_id, &mut self.conn)
+	}
+
+	fn next_slug_sequence(&mut self) -> QueryResult<i64> {
+		Link::next_slug_sequence(&mut self.conn)
+	}
+
+	fn insert(&mut self, new_link: &NewLink) -> QueryResult<Link> {
+		new_link.insert(&mut self.conn)
+	}
+
+	fn update(&mut self, link: &Link, values: UpdateLink) -> QueryResult<Link> {
+		link.update(values, &mut self.conn)
+	}
+
+	fn transfer(&mut self, link: &Link, owner_id: Option<i32>, team_id: Option<i32>) -> QueryResult<Link> {
+		link.transfer(owner_id, team_id, &mut self.conn)
+	}
+
+	fn delete(&mut self, link: &Link) -> QueryResult<usize> {
+		link.delete(&mut self.conn)
+	}
+
+	fn restore(&mut self, link: &Link) -> QueryResult<usize> {
+		link.restore(&mut self.conn)
+	}
+
+	fn purge(&mut self, link: &Link) -> QueryResult<usize> {
+		link.purge(&mut self.conn)
+	}
+
+	fn consume_click(&mut self, link: &Link) -> QueryResult<bool> {
+		link.consume_click(&mut self.conn)
+	}
+
+	fn insert_clicks(&mut self, clicks: &[NewLinkClick]) -> QueryResult<usize> {
+		NewLinkClick::insert_batch(clicks, &mut self.conn)
+	}
+
+	fn get_click_count(&mut self, link_id: i32) -> QueryResult<i64> {
+		LinkClick::get_total_count(link_id, &mut self.conn)
+	}
+
+	fn get_unique_click_count(&mut self, link_id: i32) -> QueryResult<i64> {
+		LinkClick::get_unique_count(link_id, &mut self.conn)
+	}
+
+	fn get_last_clicked_at(&mut self, link_id: i32) -> QueryResult<Option<NaiveDateTime>> {
+		LinkClick::get_last_clicked_at(link_id, &mut self.conn)
+	}
+
+	fn get_top_referrers(&mut self, link_id: i32, limit: i64) -> QueryResult<Vec<ReferrerCount>> {
+		LinkClick::get_top_referrers(link_id, limit, &mut self.conn)
+	}
+
+	fn get_time_series(&mut self, link_id: i32, interval: &str, since: NaiveDateTime) -> QueryResult<Vec<ClickBucket>> {
+		LinkClick::get_time_series(link_id, interval, since, &mut self.conn)
+	}
+}
+
+impl UserRepository for DieselRepositories {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Option<User>> {
+		User::get_by_id(&id, &mut self.conn).map(|users| users.into_iter().next())
+	}
+
+	fn get_by_email(&mut self, email: &str) -> QueryResult<Option<User>> {
+		User::get_by_email(email, &mut self.conn).map(|users| users.into_iter().next())
+	}
+
+	fn username_exists(&mut self, username: &str) -> bool {
+		User::username_exists(username, &mut self.conn)
+	}
+
+	fn email_exists(&mut self, email: &str) -> bool {
+		User::email_exists(email, &mut self.conn)
+	}
+
+	fn get_total_count(&mut self) -> QueryResult<i64> {
+		User::get_total_count(&mut self.conn)
+	}
+
+	fn search_paginated(&mut self, search: Option<&str>, page: i64, per_page: i64) -> QueryResult<Vec<User>> {
+		User::search_paginated(search, page, per_page, &mut self.conn)
+	}
+
+	fn search_count(&mut self, search: Option<&str>) -> QueryResult<i64> {
+		User::search_count(search, &mut self.conn)
+	}
+
+	// `NewUser::insert` panics on failure, handlers should get to answer with an error instead
+	fn insert(&mut self, new_user: &NewUser) -> QueryResult<User> {
+		diesel::insert_into(users::table)
+			.values(new_user)
+			.get_result(&mut self.conn)
+	}
+
+	fn update(&mut self, user: &User, values: UpdateUser) -> QueryResult<usize> {
+		user.update(values, &mut self.conn)
+	}
+
+	fn update_password_hash(&mut self, user: &User, password_hash: String) -> QueryResult<usize> {
+		user.update_password_hash(password_hash, &mut self.conn)
+	}
+
+	fn set_verified_at(&mut self, user: &User, verified_at: Option<NaiveDateTime>) -> QueryResult<usize> {
+		user.set_verified_at(verified_at, &mut self.conn)
+	}
+
+	fn delete(&mut self, user: &User) -> QueryResult<usize> {
+		user.delete(&mut self.conn)
+	}
+
+	fn soft_delete(&mut self, user: &User) -> QueryResult<usize> {
+		user.soft_delete(&mut self.conn)
+	}
+
+	fn restore(&mut self, user: &User) -> QueryResult<usize> {
+		user.restore(&mut self.conn)
+	}
+
+	fn suspend(&mut self, user: &User, until: Option<NaiveDateTime>, reason: Option<String>) -> QueryResult<usize> {
+		user.suspend(until, reason, &mut self.conn)
+	}
+
+	fn unsuspend(&mut self, user: &User) -> QueryResult<usize> {
+		user.unsuspend(&mut self.conn)
+	}
+
+	fn set_pending_totp_secret(&mut self, user: &User, secret: &str) -> QueryResult<usize> {
+		user.set_pending_totp_secret(secret, &mut self.conn)
+	}
+
+	fn enable_totp(&mut self, user: &User) -> QueryResult<usize> {
+		user.enable_totp(&mut self.conn)
+	}
+
+	fn disable_totp(&mut self, user: &User) -> QueryResult<usize> {
+		user.disable_totp(&mut self.conn)
+	}
+
+	fn claim_totp_step(&mut self, user: &User, step: i64) -> QueryResult<bool> {
+		user.claim_totp_step(step, &mut self.conn)
+	}
+
+	fn claim_totp_attempt(&mut self, user: &User, max_attempts: i32, locked_until: NaiveDateTime) -> QueryResult<bool> {
+		user.claim_totp_attempt(max_attempts, locked_until, &mut self.conn)
+	}
+
+	fn reset_totp_attempts(&mut self, user: &User) -> QueryResult<usize> {
+		user.reset_totp_attempts(&mut self.conn)
+	}
+
+	fn get_permissions(&mut self, user_id: i32) -> QueryResult<Vec<String>> {
+		Role::permissions_for_user(user_id, &mut self.conn)
+	}
+
+	fn assign_role(&mut self, name: &str, user_id: i32) -> QueryResult<usize> {
+		Role::assign_by_name(name, user_id, &mut self.conn)
+	}
+
+	fn get_identity(&mut self, issuer: &str, subject: &str) -> QueryResult<Option<(UserIdentity, User)>> {
+		UserIdentity::get_by_issuer_subject(issuer, subject, &mut self.conn)
+	}
+
+	fn insert_identity(&mut self, new_identity: &NewUserIdentity) -> QueryResult<UserIdentity> {
+		new_identity.insert(&mut self.conn)
+	}
+
+	fn touch_identity(&mut self, identity: &UserIdentity, email: Option<&str>) -> QueryResult<usize> {
+		identity.touch(email, &mut self.conn)
+	}
+}
+
+impl RoleRepository for DieselRepositories {
+	fn get_all(&mut self) -> QueryResult<Vec<Role>> {
+		Role::get_all(&mut self.conn)
+	}
+
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Role> {
+		Role::get_by_id(id, &mut self.conn)
+	}
+
+	fn get_by_name(&mut self, name: &str) -> QueryResult<Role> {
+		Role::get_by_name(name, &mut self.conn)
+	}
+
+	fn get_for_user(&mut self, user_id: i32) -> QueryResult<Vec<Role>> {
+		Role::get_for_user(user_id, &mut self.conn)
+	}
+
+	fn get_permissions(&mut self, role: &Role) -> QueryResult<Vec<String>> {
+		role.permissions(&mut self.conn)
+	}
+
+	fn set_permissions(&mut self, role: &Role, permissions: &[String]) -> QueryResult<usize> {
+		role.set_permissions(permissions, &mut self.conn)
+	}
+
+	fn update_description(&mut self, role: &Role, description: Option<String>) -> QueryResult<usize> {
+		role.update_description(description, &mut self.conn)
+	}
+
+	fn count_users(&mut self, role: &Role) -> QueryResult<i64> {
+		role.count_users(&mut self.conn)
+	}
+
+	fn insert(&mut self, new_role: &NewRole) -> QueryResult<Role> {
+		new_role.insert(&mut self.conn)
+	}
+
+	fn assign(&mut self, role: &Role, user_id: i32) -> QueryResult<usize> {
+		role.assign(user_id, &mut self.conn)
+	}
+
+	fn unassign(&mut self, role: &Role, user_id: i32) -> QueryResult<usize> {
+		role.unassign(user_id, &mut self.conn)
+	}
+
+	fn delete(&mut self, role: &Role) -> QueryResult<usize> {
+		role.delete(&mut self.conn)
+	}
+}
+
+impl TeamRepository for DieselRepositories {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Team> {
+		Team::get_by_id(id, &mut self.conn)
+	}
+
+	fn get_for_user(&mut self, user_id: i32) -> QueryResult<Vec<(Team, TeamMember)>> {
+		Team::get_for_user(user_id, &mut self.conn)
+	}
+
+	fn insert_with_member(&mut self, new_team: &NewTeam, user_id: i32, role: &str) -> QueryResult<Team> {
+		new_team.insert_with_member(user_id, role, &mut self.conn)
+	}
+
+	fn rename(&mut self, team: &Team, name: &str) -> QueryResult<usize> {
+		team.rename(name, &mut self.conn)
+	}
+
+	fn delete(&mut self, team: &Team) -> QueryResult<usize> {
+		team.delete(&mut self.conn)
+	}
+
+	fn get_members(&mut self, team: &Team) -> QueryResult<Vec<(TeamMember, User)>> {
+		team.members(&mut self.conn)
+	}
+
+	fn get_member(&mut self, team_id: i32, user_id: i32) -> QueryResult<Option<TeamMember>> {
+		TeamMember::get(team_id, user_id, &mut self.conn)
+	}
+
+	fn count_members(&mut self, team: &Team) -> QueryResult<i64> {
+		team.count_members(&mut self.conn)
+	}
+
+	fn count_members_with_role(&mut self, team: &Team, role: &str) -> QueryResult<i64> {
+		team.count_members_with_role(role, &mut self.conn)
+	}
+
+	fn insert_member(&mut self, new_member: &NewTeamMember) -> QueryResult<TeamMember> {
+		new_member.insert(&mut self.conn)
+	}
+
+	fn set_member_role(&mut self, member: &TeamMember, role: &str) -> QueryResult<usize> {
+		member.set_role(role, &mut self.conn)
+	}
+
+	fn delete_member(&mut self, member: &TeamMember) -> QueryResult<usize> {
+		member.delete(&mut self.conn)
+	}
+
+	fn share_team(&mut self, user_id: i32, other_user_id: i32) -> QueryResult<bool> {
+		TeamMember::share_team(user_id, other_user_id, &mut self.conn)
+	}
+}
+
+impl DomainRepository for DieselRepositories {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Domain> {
+		Domain::get_by_id(id, &mut self.conn)
+	}
+
+	fn get_by_domain(&mut self, domain: &str) -> QueryResult<Domain> {
+		Domain::get_by_domain(domain.to_string(), &mut self.conn)
+	}
+
+	fn get_all(&mut self) -> QueryResult<Vec<Domain>> {
+		Domain::get_all(&mut self.conn)
+	}
+
+	fn get_public(&mut self) -> QueryResult<Vec<Domain>> {
+		Domain::get_public(&mut self.conn)
+	}
+
+	fn get_usable_by_user(&mut self, user_id: i32) -> QueryResult<Vec<Domain>> {
+		Domain::get_usable_by_user(user_id, &mut self.conn)
+	}
+
+	fn get_by_owner_id(&mut self, owner_id: i32) -> QueryResult<Vec<Domain>> {
+		Domain::get_by_owner_id(owner_id, &mut self.conn)
+	}
+
+	fn get_paginated(&mut self, page: i64, per_page: i64) -> QueryResult<Vec<Domain>> {
+		Domain::get_paginated(page, per_page, &mut self.conn)
+	}
+
+	fn get_total_count(&mut self) -> QueryResult<i64> {
+		Domain::get_total_count(&mut self.conn)
+	}
+
+	fn insert(&mut self, new_domain: &NewDomain) -> QueryResult<Domain> {
+		new_domain.insert(&mut self.conn)
+	}
+
+	fn update(&mut self, domain: &Domain, values: UpdateDomain) -> QueryResult<usize> {
+		domain.update(values, &mut self.conn)
+	}
+
+	fn set_verified(&mut self, domain: &Domain) -> QueryResult<usize> {
+		domain.set_verified(&mut self.conn)
+	}
+
+	fn set_team(&mut self, domain: &Domain, team_id: Option<i32>) -> QueryResult<usize> {
+		domain.set_team(team_id, &mut self.conn)
+	}
+
+	fn delete_by_id(&mut self, id: i32) -> QueryResult<usize> {
+		Domain::delete_by_id(id, &mut self.conn)
+	}
+
+	fn get_url_rules(&mut self) -> QueryResult<Vec<UrlRule>> {
+		UrlRule::get_all(&mut self.conn)
+	}
+
+	fn get_url_rule(&mut self, id: i32) -> QueryResult<UrlRule> {
+		UrlRule::get_by_id(id, &mut self.conn)
+	}
+
+	fn insert_url_rule(&mut self, new_rule: &NewUrlRule) -> QueryResult<UrlRule> {
+		new_rule.insert(&mut self.conn)
+	}
+
+	fn delete_url_rule(&mut self, rule: &UrlRule) -> QueryResult<usize> {
+		rule.delete(&mut self.conn)
+	}
+}
+
+impl TokenRepository for DieselRepositories {
+	fn get_session(&mut self, id: i32) -> QueryResult<Session> {
+		Session::get_by_id(id, &mut self.conn)
+	}
+
+	fn get_active_sessions(&mut self, user_id: i32) -> QueryResult<Vec<Session>> {
+		Session::get_active_by_user_id(user_id, &mut self.conn)
+	}
+
+	fn get_session_by_refresh_token_hash(&mut self, refresh_token_hash: &str) -> QueryResult<Session> {
+		Session::get_by_refresh_token_hash(refresh_token_hash, &mut self.conn)
+	}
+
+	fn get_session_by_previous_token_hash(&mut self, previous_token_hash: &str) -> QueryResult<Session> {
+		Session::get_by_previous_token_hash(previous_token_hash, &mut self.conn)
+	}
+
+	fn insert_session(&mut self, new_session: &NewSession) -> QueryResult<Session> {
+		new_session.insert(&mut self.conn)
+	}
+
+	fn rotate_session(
+		&mut self,
+		session: &Session,
+		refresh_token_hash: String,
+		expires_at: NaiveDateTime,
+	) -> QueryResult<Option<Session>> {
+		session.rotate(refresh_token_hash, expires_at, &mut self.conn)
+	}
+
+	fn touch_session(&mut self, session: &Session) -> QueryResult<usize> {
+		session.touch(&mut self.conn)
+	}
+
+	fn revoke_session(&mut self, session: &Session) -> QueryResult<usize> {
+		session.revoke(&mut self.conn)
+	}
+
+	fn revoke_all_sessions(&mut self, user_id: i32, except_id: Option<i32>) -> QueryResult<usize> {
+		Session::revoke_all_for_user(user_id, except_id, &mut self.conn)
+	}
+
+	fn get_api_key(&mut self, id: i32) -> QueryResult<ApiKey> {
+		ApiKey::get_by_id(id, &mut self.conn)
+	}
+
+	fn get_api_keys(&mut self, user_id: i32) -> QueryResult<Vec<ApiKey>> {
+		ApiKey::get_by_user_id(user_id, &mut self.conn)
+	}
+
+	fn get_api_key_by_token_hash(&mut self, token_hash: &str) -> QueryResult<(ApiKey, User)> {
+		ApiKey::get_by_token_hash(token_hash, &mut self.conn)
+	}
+
+	fn insert_api_key(&mut self, new_api_key: &NewApiKey) -> QueryResult<ApiKey> {
+		new_api_key.insert(&mut self.conn)
+	}
+
+	fn update_api_key(&mut self, api_key: &ApiKey, values: UpdateApiKey) -> QueryResult<ApiKey> {
+		api_key.update(values, &mut self.conn)
+	}
+
+	fn touch_api_key(&mut self, api_key: &ApiKey) -> QueryResult<usize> {
+		api_key.touch(&mut self.conn)
+	}
+
+	fn delete_api_key(&mut self, api_key: &ApiKey) -> QueryResult<usize> {
+		api_key.delete(&mut self.conn)
+	}
+
+	fn count_unused_recovery_codes(&mut self, user_id: i32) -> QueryResult<i64> {
+		RecoveryCode::count_unused(user_id, &mut self.conn)
+	}
+
+	fn redeem_recovery_code(&mut self, user_id: i32, code_hash: &str) -> QueryResult<bool> {
+		RecoveryCode::redeem(user_id, code_hash, &mut self.conn)
+	}
+
+	fn replace_recovery_codes(&mut self, user_id: i32, code_hashes: &[String]) -> QueryResult<usize> {
+		RecoveryCode::replace_for_user(user_id, code_hashes, &mut self.conn)
+	}
+
+	fn delete_recovery_codes(&mut self, user_id: i32) -> QueryResult<usize> {
+		RecoveryCode::delete_for_user(user_id, &mut self.conn)
+	}
+
+	fn get_verification_token(&mut self, token: &str) -> QueryResult<Option<(VerificationToken, User)>> {
+		VerificationToken::get_by_token(token.to_string(), &mut self.conn).map(|records| records.into_iter().next())
+	}
+
+	fn get_latest_verification_token(&mut self, user_id: i32) -> QueryResult<Option<VerificationToken>> {
+		VerificationToken::get_latest_by_user_id(user_id, &mut self.conn)
+	}
+
+	// `NewVerificationToken::insert` panics on failure
+	fn insert_verification_token(&mut self, new_token: &NewVerificationToken) -> QueryResult<VerificationToken> {
+		diesel::insert_into(verification_tokens::table)
+			.values(new_token)
+			.get_result(&mut self.conn)
+	}
+
+	fn delete_verification_token(&mut self, token: &VerificationToken) -> QueryResult<usize> {
+		token.delete(&mut self.conn)
+	}
+
+	fn delete_verification_tokens(&mut self, user_id: i32) -> QueryResult<usize> {
+		VerificationToken::delete_for_user(user_id, &mut self.conn)
+	}
+
+	fn get_password_reset_token(&mut self, token_hash: &str) -> QueryResult<(PasswordResetToken, User)> {
+		PasswordResetToken::get_by_token_hash(token_hash, &mut self.conn)
+	}
+
+	fn insert_password_reset_token(&mut self, new_token: &NewPasswordResetToken) -> QueryResult<PasswordResetToken> {
+		new_token.insert(&mut self.conn)
+	}
+
+	fn delete_password_reset_token(&mut self, token: &PasswordResetToken) -> QueryResult<usize> {
+		token.delete(&mut self.conn)
+	}
+
+	fn delete_password_reset_tokens(&mut self, user_id: i32) -> QueryResult<usize> {
+		PasswordResetToken::delete_for_user(user_id, &mut self.conn)
+	}
+
+	fn get_email_change_token(&mut self, token_hash: &str) -> QueryResult<(EmailChangeToken, User)> {
+		EmailChangeToken::get_by_token_hash(token_hash, &mut self.conn)
+	}
+
+	fn insert_email_change_token(&mut self, new_token: &NewEmailChangeToken) -> QueryResult<EmailChangeToken> {
+		new_token.insert(&mut self.conn)
+	}
+
+	fn delete_email_change_tokens(&mut self, user_id: i32) -> QueryResult<usize> {
+		EmailChangeToken::delete_for_user(user_id, &mut self.conn)
+	}
+}
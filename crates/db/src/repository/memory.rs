@@ -0,0 +1,1975 @@
+use std::{
+	cmp::Reverse,
+	collections::{HashMap, HashSet},
+	sync::{Mutex, MutexGuard},
+};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+	result::{DatabaseErrorKind, Error},
+	QueryResult,
+};
+
+use super::{
+	DomainRepository, LinkRepository, Repositories, RoleRepository, Store, TeamRepository, TokenRepository,
+	UserRepository,
+};
+use crate::{
+	models::{
+		bucket_format, ApiKey, ClickBucket, Domain, EmailChangeToken, Link, LinkClick, NewApiKey, NewDomain,
+		NewEmailChangeToken, NewLink, NewLinkClick, NewPasswordResetToken, NewRole, NewSession, NewTeam, NewTeamMember,
+		NewUrlRule, NewUser, NewUserIdentity, NewVerificationToken, PasswordResetToken, RecoveryCode, ReferrerCount,
+		Role, Session, Team, TeamMember, UpdateApiKey, UpdateDomain, UpdateLink, UpdateUser, UrlRule, User,
+		UserIdentity, VerificationToken, ADMIN_ROLE, USER_ROLE,
+	},
+	RunError,
+};
+
+/// The built-in roles and their permissions, as seeded by the migrations
+const BUILT_IN_ROLES: [(&str, &str, &[&str]); 2] = [
+	(
+		ADMIN_ROLE,
+		"Can do everything",
+		&[
+			"link.create",
+			"link.manage_any",
+			"domain.manage",
+			"user.manage",
+			"settings.manage",
+		],
+	),
+	(USER_ROLE, "Can shorten links", &["link.create"]),
+];
+
+/// A store that keeps everything in memory, for tests that shouldn't need a database.
+///
+/// It follows the constraints the schema enforces (unique slugs, usernames, emails and domains, and deleting what
+/// belongs to deleted users and domains), so handlers see the same errors they would get from the database.
+pub struct MemoryStore {
+	state: Mutex<State>,
+}
+
+impl MemoryStore {
+	pub fn new() -> Self {
+		let mut state = State::default();
+
+		for (name, description, permissions) in BUILT_IN_ROLES {
+			let id = state.next_id();
+
+			state.roles.push(Role {
+				id,
+				name: name.to_string(),
+				description: Some(description.to_string()),
+				built_in: true,
+				created_at: now(),
+			});
+			state
+				.role_permissions
+				.extend(permissions.iter().map(|permission| (id, permission.to_string())));
+		}
+
+		Self {
+			state: Mutex::new(state),
+		}
+	}
+
+	fn lock(&self) -> MutexGuard<'_, State> {
+		// A test that panicked while holding the lock leaves the state as it was, which is fine to keep using
+		self.state.lock().unwrap_or_else(|e| e.into_inner())
+	}
+}
+
+impl Default for MemoryStore {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Store for MemoryStore {
+	fn open(&self) -> Result<Box<dyn Repositories + '_>, RunError> {
+		Ok(Box::new(MemoryRepositories { state: self.lock() }))
+	}
+}
+
+#[derive(Default, Clone)]
+struct State {
+	/// IDs are unique across tables, which keeps tests from passing by mixing up IDs of different rows
+	last_id: i32,
+	slug_sequence: i64,
+	users: Vec<User>,
+	user_identities: Vec<UserIdentity>,
+	roles: Vec<Role>,
+	role_permissions: Vec<(i32, String)>,
+	user_roles: Vec<(i32, i32)>,
+	teams: Vec<Team>,
+	team_members: Vec<TeamMember>,
+	domains: Vec<Domain>,
+	url_rules: Vec<UrlRule>,
+	links: Vec<Link>,
+	link_clicks: Vec<LinkClick>,
+	sessions: Vec<Session>,
+	api_keys: Vec<ApiKey>,
+	recovery_codes: Vec<RecoveryCode>,
+	verification_tokens: Vec<VerificationToken>,
+	password_reset_tokens: Vec<PasswordResetToken>,
+	email_change_tokens: Vec<EmailChangeToken>,
+}
+
+impl State {
+	fn next_id(&mut self) -> i32 {
+		self.last_id += 1;
+		self.last_id
+	}
+
+	fn user(&self, id: i32) -> QueryResult<User> {
+		self.users
+			.iter()
+			.find(|user| user.id == id)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn user_mut(&mut self, id: i32) -> QueryResult<&mut User> {
+		self.users.iter_mut().find(|user| user.id == id).ok_or(Error::NotFound)
+	}
+
+	fn team(&self, id: i32) -> QueryResult<Team> {
+		self.teams
+			.iter()
+			.find(|team| team.id == id)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn role(&self, id: i32) -> QueryResult<Role> {
+		self.roles
+			.iter()
+			.find(|role| role.id == id)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn link_mut(&mut self, id: i32) -> QueryResult<&mut Link> {
+		self.links.iter_mut().find(|link| link.id == id).ok_or(Error::NotFound)
+	}
+
+	fn domain_name(&self, id: i32) -> String {
+		self.domains
+			.iter()
+			.find(|domain| domain.id == id)
+			.map(|domain| domain.domain.clone())
+			.unwrap_or_default()
+	}
+
+	/// Slugs and custom slugs share a namespace per domain, trashed links included
+	fn check_slugs(&self, id: Option<i32>, domain_id: i32, slug: &str, custom_slug: Option<&str>) -> QueryResult<()> {
+		let taken = self
+			.links
+			.iter()
+			.filter(|link| Some(link.id) != id && link.domain_id == domain_id)
+			.any(|link| {
+				let slugs = [Some(link.slug.as_str()), link.custom_slug.as_deref()];
+
+				slugs.contains(&Some(slug)) || (custom_slug.is_some() && slugs.contains(&custom_slug))
+			});
+
+		match taken {
+			true => Err(unique_violation("Slug already exists on domain")),
+			false => Ok(()),
+		}
+	}
+
+	fn delete_domain(&mut self, id: i32) -> usize {
+		let before = self.domains.len();
+		self.domains.retain(|domain| domain.id != id);
+
+		let link_ids: HashSet<i32> = self
+			.links
+			.iter()
+			.filter(|link| link.domain_id == id)
+			.map(|link| link.id)
+			.collect();
+		self.delete_links(&link_ids);
+
+		before - self.domains.len()
+	}
+
+	fn delete_links(&mut self, ids: &HashSet<i32>) {
+		self.links.retain(|link| !ids.contains(&link.id));
+		self.link_clicks.retain(|click| !ids.contains(&click.link_id));
+	}
+}
+
+fn now() -> NaiveDateTime {
+	Utc::now().naive_utc()
+}
+
+fn unique_violation(message: &str) -> Error {
+	Error::DatabaseError(DatabaseErrorKind::UniqueViolation, Box::new(message.to_string()))
+}
+
+/// Gets a page of rows, `page` starting at 1
+fn paginate<T>(rows: Vec<T>, page: i64, per_page: i64) -> Vec<T> {
+	let offset = ((page - 1) * per_page).max(0) as usize;
+
+	rows.into_iter().skip(offset).take(per_page.max(0) as usize).collect()
+}
+
+/// Sorts newest first, rows created in the same instant by the order they were added in
+fn newest_first<T>(rows: &mut [T], key: impl Fn(&T) -> (NaiveDateTime, i32)) {
+	rows.sort_by_key(|row| Reverse(key(row)));
+}
+
+/// The repositories on the store's state, which stays locked for the whole unit of work
+pub struct MemoryRepositories<'a> {
+	state: MutexGuard<'a, State>,
+}
+
+impl Repositories for MemoryRepositories<'_> {
+	fn links(&mut self) -> &mut dyn LinkRepository {
+		self
+	}
+
+	fn users(&mut self) -> &mut dyn UserRepository {
+		self
+	}
+
+	fn roles(&mut self) -> &mut dyn RoleRepository {
+		self
+	}
+
+	fn teams(&mut self) -> &mut dyn TeamRepository {
+		self
+	}
+
+	fn domains(&mut self) -> &mut dyn DomainRepository {
+		self
+	}
+
+	fn tokens(&mut self) -> &mut dyn TokenRepository {
+		self
+	}
+
+	fn in_transaction(&mut self, work: &mut dyn FnMut(&mut dyn Repositories) -> QueryResult<()>) -> QueryResult<()> {
+		// Nothing else can see the state while it's locked, so rolling back is putting the old state back
+		let snapshot = self.state.clone();
+		let result = work(self);
+
+		if result.is_err() {
+			*self.state = snapshot;
+		}
+
+		result
+	}
+}
+
+impl LinkRepository for MemoryRepositories<'_> {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Link> {
+		self.state
+			.links
+			.iter()
+			.find(|link| link.id == id && link.deleted_at.is_none())
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_trashed_by_id(&mut self, id: i32) -> QueryResult<Link> {
+		self.state
+			.links
+			.iter()
+			.find(|link| link.id == id && link.deleted_at.is_some())
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_by_domain_slug(&mut self, domain_id: i32, slug: &str) -> QueryResult<Vec<Link>> {
+		Ok(self
+			.get_by_domain_slug_with_trashed(domain_id, slug)?
+			.into_iter()
+			.filter(|link| link.deleted_at.is_none())
+			.collect())
+	}
+
+	fn get_by_domain_slug_with_trashed(&mut self, domain_id: i32, slug: &str) -> QueryResult<Vec<Link>> {
+		Ok(self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.domain_id == domain_id)
+			.filter(|link| link.slug == slug || link.custom_slug.as_deref() == Some(slug))
+			.cloned()
+			.collect())
+	}
+
+	fn get_by_owner_id(&mut self, owner_id: i32) -> QueryResult<Vec<Link>> {
+		let mut links: Vec<Link> = self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.owner_id == Some(owner_id) && link.deleted_at.is_none())
+			.cloned()
+			.collect();
+		newest_first(&mut links, |link| (link.created_at, link.id));
+
+		Ok(links)
+	}
+
+	fn get_by_owner_id_paginated(
+		&mut self,
+		owner_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>> {
+		let links = paginate(LinkRepository::get_by_owner_id(self, owner_id)?, page, per_page);
+
+		Ok(links
+			.into_iter()
+			.map(|link| {
+				let domain = self.state.domain_name(link.domain_id);
+				(link, domain)
+			})
+			.collect())
+	}
+
+	fn get_total_count(&mut self, owner_id: i32) -> QueryResult<i64> {
+		Ok(LinkRepository::get_by_owner_id(self, owner_id)?.len() as i64)
+	}
+
+	fn get_trashed_by_owner_id_paginated(
+		&mut self,
+		owner_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>> {
+		let mut links: Vec<Link> = self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.owner_id == Some(owner_id) && link.deleted_at.is_some())
+			.cloned()
+			.collect();
+		newest_first(&mut links, |link| (link.deleted_at.unwrap_or_default(), link.id));
+
+		Ok(paginate(links, page, per_page)
+			.into_iter()
+			.map(|link| {
+				let domain = self.state.domain_name(link.domain_id);
+				(link, domain)
+			})
+			.collect())
+	}
+
+	fn get_trashed_total_count(&mut self, owner_id: i32) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.owner_id == Some(owner_id) && link.deleted_at.is_some())
+			.count() as i64)
+	}
+
+	fn get_by_team_id_paginated(&mut self, team_id: i32, page: i64, per_page: i64) -> QueryResult<Vec<(Link, String)>> {
+		let mut links: Vec<Link> = self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.team_id == Some(team_id) && link.deleted_at.is_none())
+			.cloned()
+			.collect();
+		newest_first(&mut links, |link| (link.created_at, link.id));
+
+		Ok(paginate(links, page, per_page)
+			.into_iter()
+			.map(|link| {
+				let domain = self.state.domain_name(link.domain_id);
+				(link, domain)
+			})
+			.collect())
+	}
+
+	fn get_team_total_count(&mut self, team_id: i32) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.team_id == Some(team_id) && link.deleted_at.is_none())
+			.count() as i64)
+	}
+
+	fn get_trashed_by_team_id_paginated(
+		&mut self,
+		team_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>> {
+		let mut links: Vec<Link> = self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.team_id == Some(team_id) && link.deleted_at.is_some())
+			.cloned()
+			.collect();
+		newest_first(&mut links, |link| (link.deleted_at.unwrap_or_default(), link.id));
+
+		Ok(paginate(links, page, per_page)
+			.into_iter()
+			.map(|link| {
+				let domain = self.state.domain_name(link.domain_id);
+				(link, domain)
+			})
+			.collect())
+	}
+
+	fn get_trashed_team_total_count(&mut self, team_id: i32) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.team_id == Some(team_id) && link.deleted_at.is_some())
+			.count() as i64)
+	}
+
+	fn get_domain_count(&mut self, domain_id: i32) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.links
+			.iter()
+			.filter(|link| link.domain_id == domain_id)
+			.count() as i64)
+	}
+
+	fn next_slug_sequence(&mut self) -> QueryResult<i64> {
+		self.state.slug_sequence += 1;
+
+		Ok(self.state.slug_sequence)
+	}
+
+	fn insert(&mut self, new_link: &NewLink) -> QueryResult<Link> {
+		self.state
+			.check_slugs(None, new_link.domain_id, &new_link.slug, new_link.custom_slug.as_deref())?;
+
+		let link = Link {
+			id: self.state.next_id(),
+			domain_id: new_link.domain_id,
+			slug: new_link.slug.clone(),
+			custom_slug: new_link.custom_slug.clone(),
+			original_link: new_link.original_link.clone(),
+			owner_id: new_link.owner_id,
+			created_at: now(),
+			updated_at: now(),
+			deleted_at: None,
+			expires_at: new_link.expires_at,
+			max_clicks: new_link.max_clicks,
+			click_count: 0,
+			archived_at: None,
+			password_hash: new_link.password_hash.clone(),
+			redirect_type: new_link.redirect_type,
+			team_id: new_link.team_id,
+		};
+
+		self.state.links.push(link.clone());
+
+		Ok(link)
+	}
+
+	fn update(&mut self, link: &Link, values: UpdateLink) -> QueryResult<Link> {
+		let mut updated = self
+			.state
+			.links
+			.iter()
+			.find(|row| row.id == link.id)
+			.cloned()
+			.ok_or(Error::NotFound)?;
+
+		if let Some(original_link) = values.original_link {
+			updated.original_link = original_link;
+		}
+		if let Some(custom_slug) = values.custom_slug {
+			updated.custom_slug = custom_slug;
+		}
+		if let Some(domain_id) = values.domain_id {
+			updated.domain_id = domain_id;
+		}
+		if let Some(expires_at) = values.expires_at {
+			updated.expires_at = expires_at;
+		}
+		if let Some(max_clicks) = values.max_clicks {
+			updated.max_clicks = max_clicks;
+		}
+		if let Some(password_hash) = values.password_hash {
+			updated.password_hash = password_hash;
+		}
+		if let Some(archived_at) = values.archived_at {
+			updated.archived_at = archived_at;
+		}
+		if let Some(redirect_type) = values.redirect_type {
+			updated.redirect_type = redirect_type;
+		}
+		updated.updated_at = now();
+
+		self.state
+			.check_slugs(Some(updated.id), updated.domain_id, &updated.slug, updated.custom_slug.as_deref())?;
+		*self.state.link_mut(link.id)? = updated.clone();
+
+		Ok(updated)
+	}
+
+	fn transfer(&mut self, link: &Link, owner_id: Option<i32>, team_id: Option<i32>) -> QueryResult<Link> {
+		let row = self.state.link_mut(link.id)?;
+
+		row.owner_id = owner_id;
+		row.team_id = team_id;
+		row.updated_at = now();
+
+		Ok(row.clone())
+	}
+
+	fn delete(&mut self, link: &Link) -> QueryResult<usize> {
+		match self.state.link_mut(link.id) {
+			Ok(row) => {
+				row.deleted_at = Some(now());
+				Ok(1)
+			}
+			Err(_) => Ok(0),
+		}
+	}
+
+	fn restore(&mut self, link: &Link) -> QueryResult<usize> {
+		match self.state.link_mut(link.id) {
+			Ok(row) => {
+				row.deleted_at = None;
+				Ok(1)
+			}
+			Err(_) => Ok(0),
+		}
+	}
+
+	fn purge(&mut self, link: &Link) -> QueryResult<usize> {
+		let before = self.state.links.len();
+		self.state.delete_links(&HashSet::from([link.id]));
+
+		Ok(before - self.state.links.len())
+	}
+
+	fn consume_click(&mut self, link: &Link) -> QueryResult<bool> {
+		let Ok(row) = self.state.link_mut(link.id) else {
+			return Ok(false);
+		};
+
+		if row.max_clicks.is_some_and(|max_clicks| row.click_count >= max_clicks) {
+			return Ok(false);
+		}
+
+		row.click_count += 1;
+
+		Ok(true)
+	}
+
+	fn insert_clicks(&mut self, clicks: &[NewLinkClick]) -> QueryResult<usize> {
+		for click in clicks {
+			let id = self.state.next_id();
+
+			self.state.link_clicks.push(LinkClick {
+				id,
+				link_id: click.link_id,
+				domain_id: click.domain_id,
+				referrer: click.referrer.clone(),
+				user_agent: click.user_agent.clone(),
+				ip_hash: click.ip_hash.clone(),
+				clicked_at: click.clicked_at,
+			});
+		}
+
+		Ok(clicks.len())
+	}
+
+	fn get_click_count(&mut self, link_id: i32) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.link_clicks
+			.iter()
+			.filter(|click| click.link_id == link_id)
+			.count() as i64)
+	}
+
+	fn get_unique_click_count(&mut self, link_id: i32) -> QueryResult<i64> {
+		let ip_hashes: HashSet<&str> = self
+			.state
+			.link_clicks
+			.iter()
+			.filter(|click| click.link_id == link_id)
+			.filter_map(|click| click.ip_hash.as_deref())
+			.collect();
+
+		Ok(ip_hashes.len() as i64)
+	}
+
+	fn get_last_clicked_at(&mut self, link_id: i32) -> QueryResult<Option<NaiveDateTime>> {
+		Ok(self
+			.state
+			.link_clicks
+			.iter()
+			.filter(|click| click.link_id == link_id)
+			.map(|click| click.clicked_at)
+			.max())
+	}
+
+	fn get_top_referrers(&mut self, link_id: i32, limit: i64) -> QueryResult<Vec<ReferrerCount>> {
+		let mut counts: HashMap<Option<String>, i64> = HashMap::new();
+
+		for click in self.state.link_clicks.iter().filter(|click| click.link_id == link_id) {
+			*counts.entry(click.referrer.clone()).or_default() += 1;
+		}
+
+		let mut referrers: Vec<ReferrerCount> = counts
+			.into_iter()
+			.map(|(referrer, clicks)| ReferrerCount { referrer, clicks })
+			.collect();
+		referrers.sort_by(|a, b| b.clicks.cmp(&a.clicks).then_with(|| a.referrer.cmp(&b.referrer)));
+		referrers.truncate(limit.max(0) as usize);
+
+		Ok(referrers)
+	}
+
+	fn get_time_series(&mut self, link_id: i32, interval: &str, since: NaiveDateTime) -> QueryResult<Vec<ClickBucket>> {
+		let format = bucket_format(interval)?;
+		let mut buckets: Vec<ClickBucket> = Vec::new();
+
+		let mut clicked_at: Vec<NaiveDateTime> = self
+			.state
+			.link_clicks
+			.iter()
+			.filter(|click| click.link_id == link_id && click.clicked_at >= since)
+			.map(|click| click.clicked_at)
+			.collect();
+		clicked_at.sort();
+
+		for clicked_at in clicked_at {
+			let bucket = NaiveDateTime::parse_from_str(&clicked_at.format(format).to_string(), "%Y-%m-%d %H:%M:%S")
+				.map_err(|e| Error::DeserializationError(Box::new(e)))?;
+
+			match buckets.last_mut() {
+				Some(last) if last.bucket == bucket => last.clicks += 1,
+				_ => buckets.push(ClickBucket { bucket, clicks: 1 }),
+			}
+		}
+
+		Ok(buckets)
+	}
+}
+
+impl UserRepository for MemoryRepositories<'_> {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Option<User>> {
+		Ok(self.state.user(id).ok())
+	}
+
+	fn get_by_email(&mut self, email: &str) -> QueryResult<Option<User>> {
+		Ok(self.state.users.iter().find(|user| user.email == email).cloned())
+	}
+
+	fn username_exists(&mut self, username: &str) -> bool {
+		self.state
+			.users
+			.iter()
+			.any(|user| user.username.to_lowercase() == username.to_lowercase())
+	}
+
+	fn email_exists(&mut self, email: &str) -> bool {
+		self.state
+			.users
+			.iter()
+			.any(|user| user.email.to_lowercase() == email.to_lowercase())
+	}
+
+	fn get_total_count(&mut self) -> QueryResult<i64> {
+		Ok(self.state.users.len() as i64)
+	}
+
+	fn search_paginated(&mut self, search: Option<&str>, page: i64, per_page: i64) -> QueryResult<Vec<User>> {
+		let search = search
+			.map(|search| search.trim().to_lowercase())
+			.filter(|search| !search.is_empty());
+
+		let mut users: Vec<User> = self
+			.state
+			.users
+			.iter()
+			.filter(|user| {
+				search.as_ref().is_none_or(|search| {
+					user.username.to_lowercase().contains(search) || user.email.to_lowercase().contains(search)
+				})
+			})
+			.cloned()
+			.collect();
+		newest_first(&mut users, |user| (user.created_at, user.id));
+
+		Ok(paginate(users, page, per_page))
+	}
+
+	fn search_count(&mut self, search: Option<&str>) -> QueryResult<i64> {
+		Ok(self.search_paginated(search, 1, i64::MAX)?.len() as i64)
+	}
+
+	fn insert(&mut self, new_user: &NewUser) -> QueryResult<User> {
+		if self.state.users.iter().any(|user| user.username == new_user.username) {
+			return Err(unique_violation("Username already exists"));
+		}
+
+		if self.state.users.iter().any(|user| user.email == new_user.email) {
+			return Err(unique_violation("Email already exists"));
+		}
+
+		let user = User {
+			id: self.state.next_id(),
+			username: new_user.username.clone(),
+			email: new_user.email.clone(),
+			password_hash: new_user.password_hash.clone(),
+			verified_at: None,
+			created_at: now(),
+			deleted_at: None,
+			totp_secret: None,
+			totp_enabled_at: None,
+			totp_last_step: None,
+			totp_attempts: 0,
+			totp_locked_until: None,
+			suspended_at: None,
+			suspended_until: None,
+			suspension_reason: None,
+		};
+
+		self.state.users.push(user.clone());
+
+		Ok(user)
+	}
+
+	fn update(&mut self, user: &User, values: UpdateUser) -> QueryResult<usize> {
+		let taken = |other: &User| {
+			other.id != user.id
+				&& (values.username.as_ref() == Some(&other.username) || values.email.as_ref() == Some(&other.email))
+		};
+
+		if self.state.users.iter().any(taken) {
+			return Err(unique_violation("Username or email already exists"));
+		}
+
+		let row = self.state.user_mut(user.id)?;
+
+		if let Some(username) = values.username {
+			row.username = username;
+		}
+		if let Some(email) = values.email {
+			row.email = email;
+		}
+		if let Some(verified_at) = values.verified_at {
+			row.verified_at = Some(verified_at);
+		}
+
+		Ok(1)
+	}
+
+	fn update_password_hash(&mut self, user: &User, password_hash: String) -> QueryResult<usize> {
+		self.state.user_mut(user.id)?.password_hash = password_hash;
+
+		Ok(1)
+	}
+
+	fn set_verified_at(&mut self, user: &User, verified_at: Option<NaiveDateTime>) -> QueryResult<usize> {
+		self.state.user_mut(user.id)?.verified_at = verified_at;
+
+		Ok(1)
+	}
+
+	fn delete(&mut self, user: &User) -> QueryResult<usize> {
+		let state = &mut *self.state;
+		let before = state.users.len();
+		state.users.retain(|row| row.id != user.id);
+
+		if state.users.len() == before {
+			return Ok(0);
+		}
+
+		let owned_domains: Vec<i32> = state
+			.domains
+			.iter()
+			.filter(|domain| domain.owner_id == Some(user.id))
+			.map(|domain| domain.id)
+			.collect();
+
+		for domain_id in owned_domains {
+			state.delete_domain(domain_id);
+		}
+
+		let owned_links: HashSet<i32> = state
+			.links
+			.iter()
+			.filter(|link| link.owner_id == Some(user.id))
+			.map(|link| link.id)
+			.collect();
+		state.delete_links(&owned_links);
+
+		state.user_roles.retain(|(user_id, _)| *user_id != user.id);
+		state.user_identities.retain(|identity| identity.user_id != user.id);
+		state.team_members.retain(|member| member.user_id != user.id);
+		state.sessions.retain(|session| session.user_id != user.id);
+		state.api_keys.retain(|api_key| api_key.user_id != user.id);
+		state.recovery_codes.retain(|code| code.user_id != user.id);
+		state.verification_tokens.retain(|token| token.user_id != user.id);
+		state.password_reset_tokens.retain(|token| token.user_id != user.id);
+		state.email_change_tokens.retain(|token| token.user_id != user.id);
+
+		Ok(1)
+	}
+
+	fn soft_delete(&mut self, user: &User) -> QueryResult<usize> {
+		self.state.user_mut(user.id)?.deleted_at = Some(now());
+
+		Ok(1)
+	}
+
+	fn restore(&mut self, user: &User) -> QueryResult<usize> {
+		self.state.user_mut(user.id)?.deleted_at = None;
+
+		Ok(1)
+	}
+
+	fn suspend(&mut self, user: &User, until: Option<NaiveDateTime>, reason: Option<String>) -> QueryResult<usize> {
+		let row = self.state.user_mut(user.id)?;
+
+		row.suspended_at = Some(now());
+		row.suspended_until = until;
+		row.suspension_reason = reason;
+
+		Ok(1)
+	}
+
+	fn unsuspend(&mut self, user: &User) -> QueryResult<usize> {
+		let row = self.state.user_mut(user.id)?;
+
+		row.suspended_at = None;
+		row.suspended_until = None;
+		row.suspension_reason = None;
+
+		Ok(1)
+	}
+
+	fn set_pending_totp_secret(&mut self, user: &User, secret: &str) -> QueryResult<usize> {
+		let row = self.state.user_mut(user.id)?;
+
+		row.totp_secret = Some(secret.to_string());
+		row.totp_enabled_at = None;
+		row.totp_last_step = None;
+
+		Ok(1)
+	}
+
+	fn enable_totp(&mut self, user: &User) -> QueryResult<usize> {
+		self.state.user_mut(user.id)?.totp_enabled_at = Some(now());
+
+		Ok(1)
+	}
+
+	fn disable_totp(&mut self, user: &User) -> QueryResult<usize> {
+		let row = self.state.user_mut(user.id)?;
+
+		row.totp_secret = None;
+		row.totp_enabled_at = None;
+		row.totp_last_step = None;
+
+		Ok(1)
+	}
+
+	fn claim_totp_step(&mut self, user: &User, step: i64) -> QueryResult<bool> {
+		let row = self.state.user_mut(user.id)?;
+
+		if row.totp_last_step.is_some_and(|last_step| last_step >= step) {
+			return Ok(false);
+		}
+
+		row.totp_last_step = Some(step);
+
+		Ok(true)
+	}
+
+	fn claim_totp_attempt(&mut self, user: &User, max_attempts: i32, locked_until: NaiveDateTime) -> QueryResult<bool> {
+		let row = self.state.user_mut(user.id)?;
+
+		if row.totp_locked_until.is_some_and(|locked_until| locked_until > now()) {
+			return Ok(false);
+		}
+
+		row.totp_attempts += 1;
+
+		if row.totp_attempts > max_attempts {
+			row.totp_attempts = 0;
+			row.totp_locked_until = Some(locked_until);
+
+			return Ok(false);
+		}
+
+		Ok(true)
+	}
+
+	fn reset_totp_attempts(&mut self, user: &User) -> QueryResult<usize> {
+		self.state.user_mut(user.id)?.totp_attempts = 0;
+
+		Ok(1)
+	}
+
+	fn get_permissions(&mut self, user_id: i32) -> QueryResult<Vec<String>> {
+		let role_ids: HashSet<i32> = self
+			.state
+			.user_roles
+			.iter()
+			.filter(|(role_user_id, _)| *role_user_id == user_id)
+			.map(|(_, role_id)| *role_id)
+			.collect();
+
+		let mut permissions: Vec<String> = Vec::new();
+
+		for (_, permission) in self
+			.state
+			.role_permissions
+			.iter()
+			.filter(|(role_id, _)| role_ids.contains(role_id))
+		{
+			if !permissions.contains(permission) {
+				permissions.push(permission.clone());
+			}
+		}
+
+		Ok(permissions)
+	}
+
+	fn assign_role(&mut self, name: &str, user_id: i32) -> QueryResult<usize> {
+		let role_id = self
+			.state
+			.roles
+			.iter()
+			.find(|role| role.name == name)
+			.map(|role| role.id)
+			.ok_or(Error::NotFound)?;
+
+		if self.state.user_roles.contains(&(user_id, role_id)) {
+			return Ok(0);
+		}
+
+		self.state.user_roles.push((user_id, role_id));
+
+		Ok(1)
+	}
+
+	fn get_identity(&mut self, issuer: &str, subject: &str) -> QueryResult<Option<(UserIdentity, User)>> {
+		let Some(identity) = self
+			.state
+			.user_identities
+			.iter()
+			.find(|identity| identity.issuer == issuer && identity.subject == subject)
+			.cloned()
+		else {
+			return Ok(None);
+		};
+
+		let user = self.state.user(identity.user_id)?;
+
+		Ok(Some((identity, user)))
+	}
+
+	fn insert_identity(&mut self, new_identity: &NewUserIdentity) -> QueryResult<UserIdentity> {
+		if self
+			.get_identity(&new_identity.issuer, &new_identity.subject)?
+			.is_some()
+		{
+			return Err(unique_violation("Identity already exists"));
+		}
+
+		let identity = UserIdentity {
+			id: self.state.next_id(),
+			user_id: new_identity.user_id,
+			issuer: new_identity.issuer.clone(),
+			subject: new_identity.subject.clone(),
+			email: new_identity.email.clone(),
+			created_at: now(),
+			last_login_at: now(),
+		};
+
+		self.state.user_identities.push(identity.clone());
+
+		Ok(identity)
+	}
+
+	fn touch_identity(&mut self, identity: &UserIdentity, email: Option<&str>) -> QueryResult<usize> {
+		let Some(row) = self.state.user_identities.iter_mut().find(|row| row.id == identity.id) else {
+			return Ok(0);
+		};
+
+		row.email = email.map(|email| email.to_string());
+		row.last_login_at = now();
+
+		Ok(1)
+	}
+}
+
+impl RoleRepository for MemoryRepositories<'_> {
+	fn get_all(&mut self) -> QueryResult<Vec<Role>> {
+		Ok(self.state.roles.clone())
+	}
+
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Role> {
+		self.state.role(id)
+	}
+
+	fn get_by_name(&mut self, name: &str) -> QueryResult<Role> {
+		self.state
+			.roles
+			.iter()
+			.find(|role| role.name == name)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_for_user(&mut self, user_id: i32) -> QueryResult<Vec<Role>> {
+		Ok(self
+			.state
+			.roles
+			.iter()
+			.filter(|role| self.state.user_roles.contains(&(user_id, role.id)))
+			.cloned()
+			.collect())
+	}
+
+	fn get_permissions(&mut self, role: &Role) -> QueryResult<Vec<String>> {
+		let mut permissions: Vec<String> = self
+			.state
+			.role_permissions
+			.iter()
+			.filter(|(role_id, _)| *role_id == role.id)
+			.map(|(_, permission)| permission.clone())
+			.collect();
+		permissions.sort();
+
+		Ok(permissions)
+	}
+
+	fn set_permissions(&mut self, role: &Role, permissions: &[String]) -> QueryResult<usize> {
+		let state = &mut *self.state;
+
+		state.role_permissions.retain(|(role_id, _)| *role_id != role.id);
+		state
+			.role_permissions
+			.extend(permissions.iter().map(|permission| (role.id, permission.clone())));
+
+		Ok(permissions.len())
+	}
+
+	fn update_description(&mut self, role: &Role, description: Option<String>) -> QueryResult<usize> {
+		let Some(row) = self.state.roles.iter_mut().find(|row| row.id == role.id) else {
+			return Ok(0);
+		};
+
+		row.description = description;
+
+		Ok(1)
+	}
+
+	fn count_users(&mut self, role: &Role) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.user_roles
+			.iter()
+			.filter(|(_, role_id)| *role_id == role.id)
+			.count() as i64)
+	}
+
+	fn insert(&mut self, new_role: &NewRole) -> QueryResult<Role> {
+		if self.state.roles.iter().any(|role| role.name == new_role.name) {
+			return Err(unique_violation("Role already exists"));
+		}
+
+		let role = Role {
+			id: self.state.next_id(),
+			name: new_role.name.clone(),
+			description: new_role.description.clone(),
+			built_in: false,
+			created_at: now(),
+		};
+
+		self.state.roles.push(role.clone());
+
+		Ok(role)
+	}
+
+	fn assign(&mut self, role: &Role, user_id: i32) -> QueryResult<usize> {
+		if self.state.user_roles.contains(&(user_id, role.id)) {
+			return Ok(0);
+		}
+
+		self.state.user_roles.push((user_id, role.id));
+
+		Ok(1)
+	}
+
+	fn unassign(&mut self, role: &Role, user_id: i32) -> QueryResult<usize> {
+		let before = self.state.user_roles.len();
+		self.state
+			.user_roles
+			.retain(|user_role| *user_role != (user_id, role.id));
+
+		Ok(before - self.state.user_roles.len())
+	}
+
+	fn delete(&mut self, role: &Role) -> QueryResult<usize> {
+		let state = &mut *self.state;
+		let before = state.roles.len();
+
+		state.roles.retain(|row| row.id != role.id);
+		state.role_permissions.retain(|(role_id, _)| *role_id != role.id);
+		state.user_roles.retain(|(_, role_id)| *role_id != role.id);
+
+		Ok(before - state.roles.len())
+	}
+}
+
+impl TeamRepository for MemoryRepositories<'_> {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Team> {
+		self.state.team(id)
+	}
+
+	fn get_for_user(&mut self, user_id: i32) -> QueryResult<Vec<(Team, TeamMember)>> {
+		let mut teams: Vec<(Team, TeamMember)> = self
+			.state
+			.team_members
+			.iter()
+			.filter(|member| member.user_id == user_id)
+			.filter_map(|member| Some((self.state.team(member.team_id).ok()?, member.clone())))
+			.collect();
+		teams.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+		Ok(teams)
+	}
+
+	fn insert_with_member(&mut self, new_team: &NewTeam, user_id: i32, role: &str) -> QueryResult<Team> {
+		self.state.user(user_id)?;
+
+		let team = Team {
+			id: self.state.next_id(),
+			name: new_team.name.clone(),
+			created_at: now(),
+			updated_at: now(),
+		};
+
+		self.state.teams.push(team.clone());
+		self.state.team_members.push(TeamMember {
+			team_id: team.id,
+			user_id,
+			role: role.to_string(),
+			created_at: now(),
+		});
+
+		Ok(team)
+	}
+
+	fn rename(&mut self, team: &Team, name: &str) -> QueryResult<usize> {
+		let Some(row) = self.state.teams.iter_mut().find(|row| row.id == team.id) else {
+			return Ok(0);
+		};
+
+		row.name = name.to_string();
+		row.updated_at = now();
+
+		Ok(1)
+	}
+
+	fn delete(&mut self, team: &Team) -> QueryResult<usize> {
+		let state = &mut *self.state;
+		let before = state.teams.len();
+		state.teams.retain(|row| row.id != team.id);
+		state.team_members.retain(|member| member.team_id != team.id);
+
+		let team_links: HashSet<i32> = state
+			.links
+			.iter()
+			.filter(|link| link.team_id == Some(team.id))
+			.map(|link| link.id)
+			.collect();
+		state.delete_links(&team_links);
+
+		for domain in state
+			.domains
+			.iter_mut()
+			.filter(|domain| domain.team_id == Some(team.id))
+		{
+			domain.team_id = None;
+		}
+
+		Ok(before - state.teams.len())
+	}
+
+	fn get_members(&mut self, team: &Team) -> QueryResult<Vec<(TeamMember, User)>> {
+		let mut members: Vec<(TeamMember, User)> = self
+			.state
+			.team_members
+			.iter()
+			.filter(|member| member.team_id == team.id)
+			.filter_map(|member| Some((member.clone(), self.state.user(member.user_id).ok()?)))
+			.collect();
+		members.sort_by_key(|(member, _)| member.created_at);
+
+		Ok(members)
+	}
+
+	fn get_member(&mut self, team_id: i32, user_id: i32) -> QueryResult<Option<TeamMember>> {
+		Ok(self
+			.state
+			.team_members
+			.iter()
+			.find(|member| member.team_id == team_id && member.user_id == user_id)
+			.cloned())
+	}
+
+	fn count_members(&mut self, team: &Team) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.team_members
+			.iter()
+			.filter(|member| member.team_id == team.id)
+			.count() as i64)
+	}
+
+	fn count_members_with_role(&mut self, team: &Team, role: &str) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.team_members
+			.iter()
+			.filter(|member| member.team_id == team.id && member.role == role)
+			.count() as i64)
+	}
+
+	fn insert_member(&mut self, new_member: &NewTeamMember) -> QueryResult<TeamMember> {
+		self.state.team(new_member.team_id)?;
+		self.state.user(new_member.user_id)?;
+
+		if self.get_member(new_member.team_id, new_member.user_id)?.is_some() {
+			return Err(unique_violation("Member already exists"));
+		}
+
+		let member = TeamMember {
+			team_id: new_member.team_id,
+			user_id: new_member.user_id,
+			role: new_member.role.clone(),
+			created_at: now(),
+		};
+
+		self.state.team_members.push(member.clone());
+
+		Ok(member)
+	}
+
+	fn set_member_role(&mut self, member: &TeamMember, role: &str) -> QueryResult<usize> {
+		let Some(row) = self
+			.state
+			.team_members
+			.iter_mut()
+			.find(|row| row.team_id == member.team_id && row.user_id == member.user_id)
+		else {
+			return Ok(0);
+		};
+
+		row.role = role.to_string();
+
+		Ok(1)
+	}
+
+	fn delete_member(&mut self, member: &TeamMember) -> QueryResult<usize> {
+		let before = self.state.team_members.len();
+		self.state
+			.team_members
+			.retain(|row| !(row.team_id == member.team_id && row.user_id == member.user_id));
+
+		Ok(before - self.state.team_members.len())
+	}
+
+	fn share_team(&mut self, user_id: i32, other_user_id: i32) -> QueryResult<bool> {
+		let team_ids = |user_id: i32| -> HashSet<i32> {
+			self.state
+				.team_members
+				.iter()
+				.filter(|member| member.user_id == user_id)
+				.map(|member| member.team_id)
+				.collect()
+		};
+
+		Ok(!team_ids(user_id).is_disjoint(&team_ids(other_user_id)))
+	}
+}
+
+impl DomainRepository for MemoryRepositories<'_> {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Domain> {
+		self.state
+			.domains
+			.iter()
+			.find(|domain| domain.id == id)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_by_domain(&mut self, domain: &str) -> QueryResult<Domain> {
+		self.state
+			.domains
+			.iter()
+			.find(|row| row.domain == domain)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_all(&mut self) -> QueryResult<Vec<Domain>> {
+		let mut domains = self.state.domains.clone();
+		newest_first(&mut domains, |domain| (domain.created_at, domain.id));
+
+		Ok(domains)
+	}
+
+	fn get_public(&mut self) -> QueryResult<Vec<Domain>> {
+		Ok(DomainRepository::get_all(self)?
+			.into_iter()
+			.filter(|domain| domain.public)
+			.collect())
+	}
+
+	fn get_usable_by_user(&mut self, user_id: i32) -> QueryResult<Vec<Domain>> {
+		let team_ids: HashSet<i32> = self
+			.state
+			.team_members
+			.iter()
+			.filter(|member| member.user_id == user_id)
+			.map(|member| member.team_id)
+			.collect();
+
+		Ok(DomainRepository::get_all(self)?
+			.into_iter()
+			.filter(|domain| {
+				domain.public
+					|| domain.team_id.is_some_and(|team_id| team_ids.contains(&team_id))
+					|| (domain.owner_id == Some(user_id) && domain.is_verified())
+			})
+			.collect())
+	}
+
+	fn get_by_owner_id(&mut self, owner_id: i32) -> QueryResult<Vec<Domain>> {
+		Ok(DomainRepository::get_all(self)?
+			.into_iter()
+			.filter(|domain| domain.owner_id == Some(owner_id))
+			.collect())
+	}
+
+	fn get_paginated(&mut self, page: i64, per_page: i64) -> QueryResult<Vec<Domain>> {
+		Ok(paginate(DomainRepository::get_all(self)?, page, per_page))
+	}
+
+	fn get_total_count(&mut self) -> QueryResult<i64> {
+		Ok(self.state.domains.len() as i64)
+	}
+
+	fn insert(&mut self, new_domain: &NewDomain) -> QueryResult<Domain> {
+		if self
+			.state
+			.domains
+			.iter()
+			.any(|domain| domain.domain == new_domain.domain)
+		{
+			return Err(unique_violation("Domain already exists"));
+		}
+
+		let domain = Domain {
+			id: self.state.next_id(),
+			domain: new_domain.domain.clone(),
+			public: new_domain.public.unwrap_or(false),
+			created_at: now(),
+			updated_at: now(),
+			slug_strategy: new_domain.slug_strategy.clone(),
+			redirect_type: new_domain.redirect_type,
+			team_id: None,
+			owner_id: new_domain.owner_id,
+			verification_token: new_domain.verification_token.clone(),
+			verified_at: new_domain.verified_at,
+		};
+
+		self.state.domains.push(domain.clone());
+
+		Ok(domain)
+	}
+
+	fn update(&mut self, domain: &Domain, values: UpdateDomain) -> QueryResult<usize> {
+		if let Some(name) = &values.domain {
+			if self
+				.state
+				.domains
+				.iter()
+				.any(|row| row.id != domain.id && &row.domain == name)
+			{
+				return Err(unique_violation("Domain already exists"));
+			}
+		}
+
+		let Some(row) = self.state.domains.iter_mut().find(|row| row.id == domain.id) else {
+			return Ok(0);
+		};
+
+		if let Some(name) = values.domain {
+			row.domain = name;
+		}
+		if let Some(public) = values.public {
+			row.public = public;
+		}
+		if let Some(slug_strategy) = values.slug_strategy {
+			row.slug_strategy = Some(slug_strategy);
+		}
+		if let Some(redirect_type) = values.redirect_type {
+			row.redirect_type = Some(redirect_type);
+		}
+
+		Ok(1)
+	}
+
+	fn set_verified(&mut self, domain: &Domain) -> QueryResult<usize> {
+		let Some(row) = self.state.domains.iter_mut().find(|row| row.id == domain.id) else {
+			return Ok(0);
+		};
+
+		row.verified_at = Some(now());
+
+		Ok(1)
+	}
+
+	fn set_team(&mut self, domain: &Domain, team_id: Option<i32>) -> QueryResult<usize> {
+		let Some(row) = self.state.domains.iter_mut().find(|row| row.id == domain.id) else {
+			return Ok(0);
+		};
+
+		row.team_id = team_id;
+
+		Ok(1)
+	}
+
+	fn delete_by_id(&mut self, id: i32) -> QueryResult<usize> {
+		Ok(self.state.delete_domain(id))
+	}
+
+	fn get_url_rules(&mut self) -> QueryResult<Vec<UrlRule>> {
+		let mut rules = self.state.url_rules.clone();
+		rules.sort_by(|a, b| a.domain.cmp(&b.domain));
+
+		Ok(rules)
+	}
+
+	fn get_url_rule(&mut self, id: i32) -> QueryResult<UrlRule> {
+		self.state
+			.url_rules
+			.iter()
+			.find(|rule| rule.id == id)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn insert_url_rule(&mut self, new_rule: &NewUrlRule) -> QueryResult<UrlRule> {
+		let taken = self
+			.state
+			.url_rules
+			.iter()
+			.any(|rule| rule.domain == new_rule.domain && rule.kind == new_rule.kind);
+
+		if taken {
+			return Err(unique_violation("Rule already exists"));
+		}
+
+		let rule = UrlRule {
+			id: self.state.next_id(),
+			domain: new_rule.domain.clone(),
+			kind: new_rule.kind.clone(),
+			created_at: now(),
+		};
+
+		self.state.url_rules.push(rule.clone());
+
+		Ok(rule)
+	}
+
+	fn delete_url_rule(&mut self, rule: &UrlRule) -> QueryResult<usize> {
+		let before = self.state.url_rules.len();
+		self.state.url_rules.retain(|row| row.id != rule.id);
+
+		Ok(before - self.state.url_rules.len())
+	}
+}
+
+impl TokenRepository for MemoryRepositories<'_> {
+	fn get_session(&mut self, id: i32) -> QueryResult<Session> {
+		self.state
+			.sessions
+			.iter()
+			.find(|session| session.id == id)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_active_sessions(&mut self, user_id: i32) -> QueryResult<Vec<Session>> {
+		let mut sessions: Vec<Session> = self
+			.state
+			.sessions
+			.iter()
+			.filter(|session| session.user_id == user_id && session.is_active())
+			.cloned()
+			.collect();
+		newest_first(&mut sessions, |session| (session.last_seen_at, session.id));
+
+		Ok(sessions)
+	}
+
+	fn get_session_by_refresh_token_hash(&mut self, refresh_token_hash: &str) -> QueryResult<Session> {
+		self.state
+			.sessions
+			.iter()
+			.find(|session| session.refresh_token_hash == refresh_token_hash)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_session_by_previous_token_hash(&mut self, previous_token_hash: &str) -> QueryResult<Session> {
+		self.state
+			.sessions
+			.iter()
+			.find(|session| session.previous_token_hash.as_deref() == Some(previous_token_hash))
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn insert_session(&mut self, new_session: &NewSession) -> QueryResult<Session> {
+		let session = Session {
+			id: self.state.next_id(),
+			user_id: new_session.user_id,
+			refresh_token_hash: new_session.refresh_token_hash.clone(),
+			previous_token_hash: None,
+			user_agent: new_session.user_agent.clone(),
+			created_at: now(),
+			last_seen_at: now(),
+			expires_at: new_session.expires_at,
+			revoked_at: None,
+		};
+
+		self.state.sessions.push(session.clone());
+
+		Ok(session)
+	}
+
+	fn rotate_session(
+		&mut self,
+		session: &Session,
+		refresh_token_hash: String,
+		expires_at: NaiveDateTime,
+	) -> QueryResult<Option<Session>> {
+		let Some(row) = self
+			.state
+			.sessions
+			.iter_mut()
+			.find(|row| row.id == session.id && row.refresh_token_hash == session.refresh_token_hash)
+		else {
+			return Ok(None);
+		};
+
+		row.previous_token_hash = Some(session.refresh_token_hash.clone());
+		row.refresh_token_hash = refresh_token_hash;
+		row.last_seen_at = now();
+		row.expires_at = expires_at;
+
+		Ok(Some(row.clone()))
+	}
+
+	fn touch_session(&mut self, session: &Session) -> QueryResult<usize> {
+		let Some(row) = self.state.sessions.iter_mut().find(|row| row.id == session.id) else {
+			return Ok(0);
+		};
+
+		row.last_seen_at = now();
+
+		Ok(1)
+	}
+
+	fn revoke_session(&mut self, session: &Session) -> QueryResult<usize> {
+		let Some(row) = self.state.sessions.iter_mut().find(|row| row.id == session.id) else {
+			return Ok(0);
+		};
+
+		row.revoked_at = Some(now());
+
+		Ok(1)
+	}
+
+	fn revoke_all_sessions(&mut self, user_id: i32, except_id: Option<i32>) -> QueryResult<usize> {
+		let mut count = 0;
+
+		for session in self.state.sessions.iter_mut() {
+			if session.user_id == user_id && session.revoked_at.is_none() && Some(session.id) != except_id {
+				session.revoked_at = Some(now());
+				count += 1;
+			}
+		}
+
+		Ok(count)
+	}
+
+	fn get_api_key(&mut self, id: i32) -> QueryResult<ApiKey> {
+		self.state
+			.api_keys
+			.iter()
+			.find(|api_key| api_key.id == id)
+			.cloned()
+			.ok_or(Error::NotFound)
+	}
+
+	fn get_api_keys(&mut self, user_id: i32) -> QueryResult<Vec<ApiKey>> {
+		let mut api_keys: Vec<ApiKey> = self
+			.state
+			.api_keys
+			.iter()
+			.filter(|api_key| api_key.user_id == user_id)
+			.cloned()
+			.collect();
+		newest_first(&mut api_keys, |api_key| (api_key.created_at, api_key.id));
+
+		Ok(api_keys)
+	}
+
+	fn get_api_key_by_token_hash(&mut self, token_hash: &str) -> QueryResult<(ApiKey, User)> {
+		let api_key = self
+			.state
+			.api_keys
+			.iter()
+			.find(|api_key| api_key.token_hash == token_hash)
+			.cloned()
+			.ok_or(Error::NotFound)?;
+		let user = self.state.user(api_key.user_id)?;
+
+		Ok((api_key, user))
+	}
+
+	fn insert_api_key(&mut self, new_api_key: &NewApiKey) -> QueryResult<ApiKey> {
+		if self
+			.state
+			.api_keys
+			.iter()
+			.any(|api_key| api_key.token_hash == new_api_key.token_hash)
+		{
+			return Err(unique_violation("API key already exists"));
+		}
+
+		let api_key = ApiKey {
+			id: self.state.next_id(),
+			user_id: new_api_key.user_id,
+			name: new_api_key.name.clone(),
+			token_hash: new_api_key.token_hash.clone(),
+			token_prefix: new_api_key.token_prefix.clone(),
+			scopes: new_api_key.scopes.clone(),
+			last_used_at: None,
+			expires_at: new_api_key.expires_at,
+			created_at: now(),
+		};
+
+		self.state.api_keys.push(api_key.clone());
+
+		Ok(api_key)
+	}
+
+	fn update_api_key(&mut self, api_key: &ApiKey, values: UpdateApiKey) -> QueryResult<ApiKey> {
+		let row = self
+			.state
+			.api_keys
+			.iter_mut()
+			.find(|row| row.id == api_key.id)
+			.ok_or(Error::NotFound)?;
+
+		if let Some(name) = values.name {
+			row.name = name;
+		}
+		if let Some(scopes) = values.scopes {
+			row.scopes = scopes;
+		}
+		if let Some(expires_at) = values.expires_at {
+			row.expires_at = expires_at;
+		}
+
+		Ok(row.clone())
+	}
+
+	fn touch_api_key(&mut self, api_key: &ApiKey) -> QueryResult<usize> {
+		let Some(row) = self.state.api_keys.iter_mut().find(|row| row.id == api_key.id) else {
+			return Ok(0);
+		};
+
+		row.last_used_at = Some(now());
+
+		Ok(1)
+	}
+
+	fn delete_api_key(&mut self, api_key: &ApiKey) -> QueryResult<usize> {
+		let before = self.state.api_keys.len();
+		self.state.api_keys.retain(|row| row.id != api_key.id);
+
+		Ok(before - self.state.api_keys.len())
+	}
+
+	fn count_unused_recovery_codes(&mut self, user_id: i32) -> QueryResult<i64> {
+		Ok(self
+			.state
+			.recovery_codes
+			.iter()
+			.filter(|code| code.user_id == user_id && code.used_at.is_none())
+			.count() as i64)
+	}
+
+	fn redeem_recovery_code(&mut self, user_id: i32, code_hash: &str) -> QueryResult<bool> {
+		let code = self
+			.state
+			.recovery_codes
+			.iter_mut()
+			.find(|code| code.user_id == user_id && code.code_hash == code_hash && code.used_at.is_none());
+
+		match code {
+			Some(code) => {
+				code.used_at = Some(now());
+				Ok(true)
+			}
+			None => Ok(false),
+		}
+	}
+
+	fn replace_recovery_codes(&mut self, user_id: i32, code_hashes: &[String]) -> QueryResult<usize> {
+		self.delete_recovery_codes(user_id)?;
+
+		for code_hash in code_hashes {
+			let id = self.state.next_id();
+
+			self.state.recovery_codes.push(RecoveryCode {
+				id,
+				user_id,
+				code_hash: code_hash.clone(),
+				used_at: None,
+				created_at: now(),
+			});
+		}
+
+		Ok(code_hashes.len())
+	}
+
+	fn delete_recovery_codes(&mut self, user_id: i32) -> QueryResult<usize> {
+		let before = self.state.recovery_codes.len();
+		self.state.recovery_codes.retain(|code| code.user_id != user_id);
+
+		Ok(before - self.state.recovery_codes.len())
+	}
+
+	fn get_verification_token(&mut self, token: &str) -> QueryResult<Option<(VerificationToken, User)>> {
+		let Some(token) = self.state.verification_tokens.iter().find(|row| row.token == token) else {
+			return Ok(None);
+		};
+		let user = self.state.user(token.user_id)?;
+
+		Ok(Some((token.clone(), user)))
+	}
+
+	fn get_latest_verification_token(&mut self, user_id: i32) -> QueryResult<Option<VerificationToken>> {
+		Ok(self
+			.state
+			.verification_tokens
+			.iter()
+			.filter(|token| token.user_id == user_id)
+			.max_by_key(|token| (token.created_at, token.id))
+			.cloned())
+	}
+
+	fn insert_verification_token(&mut self, new_token: &NewVerificationToken) -> QueryResult<VerificationToken> {
+		let token = VerificationToken {
+			id: self.state.next_id(),
+			user_id: new_token.user_id,
+			token: new_token.token.clone(),
+			created_at: now(),
+			expires_at: new_token.expires_at,
+		};
+
+		self.state.verification_tokens.push(token.clone());
+
+		Ok(token)
+	}
+
+	fn delete_verification_token(&mut self, token: &VerificationToken) -> QueryResult<usize> {
+		let before = self.state.verification_tokens.len();
+		self.state.verification_tokens.retain(|row| row.id != token.id);
+
+		Ok(before - self.state.verification_tokens.len())
+	}
+
+	fn delete_verification_tokens(&mut self, user_id: i32) -> QueryResult<usize> {
+		let before = self.state.verification_tokens.len();
+		self.state.verification_tokens.retain(|token| token.user_id != user_id);
+
+		Ok(before - self.state.verification_tokens.len())
+	}
+
+	fn get_password_reset_token(&mut self, token_hash: &str) -> QueryResult<(PasswordResetToken, User)> {
+		let token = self
+			.state
+			.password_reset_tokens
+			.iter()
+			.find(|token| token.token_hash == token_hash)
+			.ok_or(Error::NotFound)?;
+		let user = self.state.user(token.user_id)?;
+
+		Ok((token.clone(), user))
+	}
+
+	fn insert_password_reset_token(&mut self, new_token: &NewPasswordResetToken) -> QueryResult<PasswordResetToken> {
+		let token = PasswordResetToken {
+			id: self.state.next_id(),
+			user_id: new_token.user_id,
+			token_hash: new_token.token_hash.clone(),
+			created_at: now(),
+			expires_at: new_token.expires_at,
+		};
+
+		self.state.password_reset_tokens.push(token.clone());
+
+		Ok(token)
+	}
+
+	fn delete_password_reset_token(&mut self, token: &PasswordResetToken) -> QueryResult<usize> {
+		let before = self.state.password_reset_tokens.len();
+		self.state.password_reset_tokens.retain(|row| row.id != token.id);
+
+		Ok(before - self.state.password_reset_tokens.len())
+	}
+
+	fn delete_password_reset_tokens(&mut self, user_id: i32) -> QueryResult<usize> {
+		let before = self.state.password_reset_tokens.len();
+		self.state
+			.password_reset_tokens
+			.retain(|token| token.user_id != user_id);
+
+		Ok(before - self.state.password_reset_tokens.len())
+	}
+
+	fn get_email_change_token(&mut self, token_hash: &str) -> QueryResult<(EmailChangeToken, User)> {
+		let token = self
+			.state
+			.email_change_tokens
+			.iter()
+			.find(|token| token.token_hash == token_hash)
+			.ok_or(Error::NotFound)?;
+		let user = self.state.user(token.user_id)?;
+
+		Ok((token.clone(), user))
+	}
+
+	fn insert_email_change_token(&mut self, new_token: &NewEmailChangeToken) -> QueryResult<EmailChangeToken> {
+		let token = EmailChangeToken {
+			id: self.state.next_id(),
+			user_id: new_token.user_id,
+			new_email: new_token.new_email.clone(),
+			token_hash: new_token.token_hash.clone(),
+			created_at: now(),
+			expires_at: new_token.expires_at,
+		};
+
+		self.state.email_change_tokens.push(token.clone());
+
+		Ok(token)
+	}
+
+	fn delete_email_change_tokens(&mut self, user_id: i32) -> QueryResult<usize> {
+		let before = self.state.email_change_tokens.len();
+		self.state.email_change_tokens.retain(|token| token.user_id != user_id);
+
+		Ok(before - self.state.email_change_tokens.len())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::{is_unique_violation, repository};
+
+	fn new_link(domain_id: i32, slug: &str, owner_id: Option<i32>) -> NewLink {
+		NewLink {
+			slug: slug.to_string(),
+			domain_id,
+			custom_slug: None,
+			original_link: "https://example.com".to_string(),
+			owner_id,
+			expires_at: None,
+			max_clicks: None,
+			password_hash: None,
+			redirect_type: None,
+			team_id: None,
+		}
+	}
+
+	#[test]
+	fn test_constraints() {
+		let store = MemoryStore::new();
+		let mut repos = store.open().unwrap();
+
+		let domain = repos
+			.domains()
+			.insert(&NewDomain {
+				domain: "sho.rt".to_string(),
+				public: Some(true),
+				slug_strategy: None,
+				redirect_type: None,
+				owner_id: None,
+				verification_token: None,
+				verified_at: None,
+			})
+			.unwrap();
+		let user = repos
+			.users()
+			.insert(&NewUser {
+				username: "alice".to_string(),
+				password_hash: "hash".to_string(),
+				email: "alice@example.com".to_string(),
+			})
+			.unwrap();
+
+		repos.users().assign_role(USER_ROLE, user.id).unwrap();
+		assert_eq!(repos.users().get_permissions(user.id).unwrap(), vec!["link.create".to_string()]);
+
+		let link = repos
+			.links()
+			.insert(&new_link(domain.id, "abc", Some(user.id)))
+			.unwrap();
+		let error = repos.links().insert(&new_link(domain.id, "abc", None)).unwrap_err();
+		assert!(is_unique_violation(&error));
+
+		// Links go with the user that owns them
+		repos.users().delete(&user).unwrap();
+		assert!(repos.links().get_by_id(link.id).is_err());
+	}
+
+	#[test]
+	fn test_transaction_rollback() {
+		let store = MemoryStore::new();
+		let mut repos = store.open().unwrap();
+		let new_user = NewUser {
+			username: "alice".to_string(),
+			password_hash: "hash".to_string(),
+			email: "alice@example.com".to_string(),
+		};
+
+		let result = repository::transaction(repos.as_mut(), |repos| {
+			let user = repos.users().insert(&new_user)?;
+			repos.users().assign_role("missing", user.id)
+		});
+		assert!(result.is_err());
+		assert!(repos.users().get_by_email("alice@example.com").unwrap().is_none());
+
+		let user = repository::transaction(repos.as_mut(), |repos| repos.users().insert(&new_user)).unwrap();
+		let found = repos.users().get_by_email("alice@example.com").unwrap();
+		assert_eq!(found.map(|found| found.id), Some(user.id));
+	}
+}
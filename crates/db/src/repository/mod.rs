@@ -0,0 +1,308 @@
+//! Data access behind traits, so handlers can run against the database or against memory in tests.
+//!
+//! A [`Store`] hands out [`Repositories`], a unit of work on one connection (or on the in-memory state) that gives
+//! access to each repository.
+
+mod database;
+mod memory;
+
+use std::sync::Arc;
+
+use chrono::NaiveDateTime;
+use diesel::QueryResult;
+
+use crate::{
+	models::{
+		ApiKey, ClickBucket, Domain, EmailChangeToken, Link, NewApiKey, NewDomain, NewEmailChangeToken, NewLink,
+		NewLinkClick, NewPasswordResetToken, NewRole, NewSession, NewTeam, NewTeamMember, NewUrlRule, NewUser,
+		NewUserIdentity, NewVerificationToken, PasswordResetToken, ReferrerCount, Role, Session, Team, TeamMember,
+		UpdateApiKey, UpdateDomain, UpdateLink, UpdateUser, UrlRule, User, UserIdentity, VerificationToken,
+	},
+	RunError,
+};
+
+pub use database::{DieselRepositories, DieselStore};
+pub use memory::MemoryStore;
+
+pub trait LinkRepository {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Link>;
+	/// Gets a link that is in the trash
+	fn get_trashed_by_id(&mut self, id: i32) -> QueryResult<Link>;
+	/// Gets the links using a slug or custom slug on a domain, leaving out trashed ones
+	fn get_by_domain_slug(&mut self, domain_id: i32, slug: &str) -> QueryResult<Vec<Link>>;
+	/// Like [`LinkRepository::get_by_domain_slug`], but trashed links still hold on to their slug
+	fn get_by_domain_slug_with_trashed(&mut self, domain_id: i32, slug: &str) -> QueryResult<Vec<Link>>;
+	fn get_by_owner_id(&mut self, owner_id: i32) -> QueryResult<Vec<Link>>;
+	/// Gets a page of the user's links, along with their domain name
+	fn get_by_owner_id_paginated(
+		&mut self,
+		owner_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>>;
+	fn get_total_count(&mut self, owner_id: i32) -> QueryResult<i64>;
+	fn get_trashed_by_owner_id_paginated(
+		&mut self,
+		owner_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>>;
+	fn get_trashed_total_count(&mut self, owner_id: i32) -> QueryResult<i64>;
+	/// Gets a page of the team's links, along with their domain name
+	fn get_by_team_id_paginated(&mut self, team_id: i32, page: i64, per_page: i64) -> QueryResult<Vec<(Link, String)>>;
+	fn get_team_total_count(&mut self, team_id: i32) -> QueryResult<i64>;
+	fn get_trashed_by_team_id_paginated(
+		&mut self,
+		team_id: i32,
+		page: i64,
+		per_page: i64,
+	) -> QueryResult<Vec<(Link, String)>>;
+	fn get_trashed_team_total_count(&mut self, team_id: i32) -> QueryResult<i64>;
+	/// Counts all links on a domain, including trashed ones
+	fn get_domain_count(&mut self, domain_id: i32) -> QueryResult<i64>;
+	/// Gets the next number for sequential slugs
+	fn next_slug_sequence(&mut self) -> QueryResult<i64>;
+	fn insert(&mut self, new_link: &NewLink) -> QueryResult<Link>;
+	fn update(&mut self, link: &Link, values: UpdateLink) -> QueryResult<Link>;
+	/// Hands the link over to a user or a team
+	fn transfer(&mut self, link: &Link, owner_id: Option<i32>, team_id: Option<i32>) -> QueryResult<Link>;
+	/// Moves the link to the trash
+	fn delete(&mut self, link: &Link) -> QueryResult<usize>;
+	fn restore(&mut self, link: &Link) -> QueryResult<usize>;
+	/// Permanently deletes the link
+	fn purge(&mut self, link: &Link) -> QueryResult<usize>;
+	/// Counts a click towards `max_clicks`. Returns false if there were no clicks left.
+	fn consume_click(&mut self, link: &Link) -> QueryResult<bool>;
+	fn insert_clicks(&mut self, clicks: &[NewLinkClick]) -> QueryResult<usize>;
+	fn get_click_count(&mut self, link_id: i32) -> QueryResult<i64>;
+	fn get_unique_click_count(&mut self, link_id: i32) -> QueryResult<i64>;
+	fn get_last_clicked_at(&mut self, link_id: i32) -> QueryResult<Option<NaiveDateTime>>;
+	fn get_top_referrers(&mut self, link_id: i32, limit: i64) -> QueryResult<Vec<ReferrerCount>>;
+	/// Gets click counts grouped by `interval` since the given time, see [`crate::models::LinkClick::get_time_series`]
+	fn get_time_series(&mut self, link_id: i32, interval: &str, since: NaiveDateTime) -> QueryResult<Vec<ClickBucket>>;
+}
+
+/// Users, along with what they're allowed to do through their roles and the identities they sign in with
+pub trait UserRepository {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Option<User>>;
+	fn get_by_email(&mut self, email: &str) -> QueryResult<Option<User>>;
+	/// Checks if a username is taken, ignoring case
+	fn username_exists(&mut self, username: &str) -> bool;
+	/// Checks if an email is taken, ignoring case
+	fn email_exists(&mut self, email: &str) -> bool;
+	fn get_total_count(&mut self) -> QueryResult<i64>;
+	/// Gets a page of users whose username or email contains `search`, newest first
+	fn search_paginated(&mut self, search: Option<&str>, page: i64, per_page: i64) -> QueryResult<Vec<User>>;
+	fn search_count(&mut self, search: Option<&str>) -> QueryResult<i64>;
+	fn insert(&mut self, new_user: &NewUser) -> QueryResult<User>;
+	fn update(&mut self, user: &User, values: UpdateUser) -> QueryResult<usize>;
+	fn update_password_hash(&mut self, user: &User, password_hash: String) -> QueryResult<usize>;
+	fn set_verified_at(&mut self, user: &User, verified_at: Option<NaiveDateTime>) -> QueryResult<usize>;
+	/// Deletes the user, along with everything they own
+	fn delete(&mut self, user: &User) -> QueryResult<usize>;
+	/// Marks the user as deleted, keeping their data so it can be restored
+	fn soft_delete(&mut self, user: &User) -> QueryResult<usize>;
+	fn restore(&mut self, user: &User) -> QueryResult<usize>;
+	/// Suspends the user until the given time, or for good if there is none
+	fn suspend(&mut self, user: &User, until: Option<NaiveDateTime>, reason: Option<String>) -> QueryResult<usize>;
+	fn unsuspend(&mut self, user: &User) -> QueryResult<usize>;
+	fn set_pending_totp_secret(&mut self, user: &User, secret: &str) -> QueryResult<usize>;
+	fn enable_totp(&mut self, user: &User) -> QueryResult<usize>;
+	fn disable_totp(&mut self, user: &User) -> QueryResult<usize>;
+	/// Records a TOTP time step as used. Returns false if it (or a later one) was already used.
+	fn claim_totp_step(&mut self, user: &User, step: i64) -> QueryResult<bool>;
+	/// Counts a 2FA code attempt. Returns false while the user is locked out, and locks them out until `locked_until`
+	/// once they go over `max_attempts`, see [`User::claim_totp_attempt`].
+	fn claim_totp_attempt(&mut self, user: &User, max_attempts: i32, locked_until: NaiveDateTime) -> QueryResult<bool>;
+	/// Starts counting 2FA code attempts from zero, after a code was accepted
+	fn reset_totp_attempts(&mut self, user: &User) -> QueryResult<usize>;
+	/// Gets every permission a user has through any of their roles
+	fn get_permissions(&mut self, user_id: i32) -> QueryResult<Vec<String>>;
+	/// Gives a role, looked up by name, to a user. Does nothing if they already have it.
+	fn assign_role(&mut self, name: &str, user_id: i32) -> QueryResult<usize>;
+	/// Gets an identity and the user it's linked to by the provider's issuer and the subject at that provider
+	fn get_identity(&mut self, issuer: &str, subject: &str) -> QueryResult<Option<(UserIdentity, User)>>;
+	fn insert_identity(&mut self, new_identity: &NewUserIdentity) -> QueryResult<UserIdentity>;
+	/// Records a login through the identity, along with the email the provider currently reports
+	fn touch_identity(&mut self, identity: &UserIdentity, email: Option<&str>) -> QueryResult<usize>;
+}
+
+/// Roles and the permissions they give
+pub trait RoleRepository {
+	fn get_all(&mut self) -> QueryResult<Vec<Role>>;
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Role>;
+	fn get_by_name(&mut self, name: &str) -> QueryResult<Role>;
+	/// Gets the roles of a user
+	fn get_for_user(&mut self, user_id: i32) -> QueryResult<Vec<Role>>;
+	fn get_permissions(&mut self, role: &Role) -> QueryResult<Vec<String>>;
+	/// Replaces the permissions of the role
+	fn set_permissions(&mut self, role: &Role, permissions: &[String]) -> QueryResult<usize>;
+	fn update_description(&mut self, role: &Role, description: Option<String>) -> QueryResult<usize>;
+	/// How many users have the role
+	fn count_users(&mut self, role: &Role) -> QueryResult<i64>;
+	fn insert(&mut self, new_role: &NewRole) -> QueryResult<Role>;
+	/// Gives the role to a user, doing nothing if they already have it
+	fn assign(&mut self, role: &Role, user_id: i32) -> QueryResult<usize>;
+	fn unassign(&mut self, role: &Role, user_id: i32) -> QueryResult<usize>;
+	fn delete(&mut self, role: &Role) -> QueryResult<usize>;
+}
+
+/// Teams and their members
+pub trait TeamRepository {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Team>;
+	/// Gets the teams of a user, along with their membership
+	fn get_for_user(&mut self, user_id: i32) -> QueryResult<Vec<(Team, TeamMember)>>;
+	/// Creates the team with its first member
+	fn insert_with_member(&mut self, new_team: &NewTeam, user_id: i32, role: &str) -> QueryResult<Team>;
+	fn rename(&mut self, team: &Team, name: &str) -> QueryResult<usize>;
+	/// Deletes the team along with its links. Its domains are kept, but no longer belong to a team.
+	fn delete(&mut self, team: &Team) -> QueryResult<usize>;
+	/// Gets the members of the team, along with their users
+	fn get_members(&mut self, team: &Team) -> QueryResult<Vec<(TeamMember, User)>>;
+	/// Gets the membership of a user in a team, if they're in it
+	fn get_member(&mut self, team_id: i32, user_id: i32) -> QueryResult<Option<TeamMember>>;
+	fn count_members(&mut self, team: &Team) -> QueryResult<i64>;
+	fn count_members_with_role(&mut self, team: &Team, role: &str) -> QueryResult<i64>;
+	fn insert_member(&mut self, new_member: &NewTeamMember) -> QueryResult<TeamMember>;
+	fn set_member_role(&mut self, member: &TeamMember, role: &str) -> QueryResult<usize>;
+	fn delete_member(&mut self, member: &TeamMember) -> QueryResult<usize>;
+	/// Checks if two users are in at least one team together
+	fn share_team(&mut self, user_id: i32, other_user_id: i32) -> QueryResult<bool>;
+}
+
+/// Short domains, along with the URL rules that decide where links on them may point to
+pub trait DomainRepository {
+	fn get_by_id(&mut self, id: i32) -> QueryResult<Domain>;
+	fn get_by_domain(&mut self, domain: &str) -> QueryResult<Domain>;
+	fn get_all(&mut self) -> QueryResult<Vec<Domain>>;
+	fn get_public(&mut self) -> QueryResult<Vec<Domain>>;
+	/// Gets the public domains, the private ones of the user's teams and the verified ones the user owns
+	fn get_usable_by_user(&mut self, user_id: i32) -> QueryResult<Vec<Domain>>;
+	fn get_by_owner_id(&mut self, owner_id: i32) -> QueryResult<Vec<Domain>>;
+	fn get_paginated(&mut self, page: i64, per_page: i64) -> QueryResult<Vec<Domain>>;
+	fn get_total_count(&mut self) -> QueryResult<i64>;
+	fn insert(&mut self, new_domain: &NewDomain) -> QueryResult<Domain>;
+	fn update(&mut self, domain: &Domain, values: UpdateDomain) -> QueryResult<usize>;
+	fn set_verified(&mut self, domain: &Domain) -> QueryResult<usize>;
+	/// Gives the domain to a team, or takes it away with `None`
+	fn set_team(&mut self, domain: &Domain, team_id: Option<i32>) -> QueryResult<usize>;
+	/// Deletes the domain along with its links
+	fn delete_by_id(&mut self, id: i32) -> QueryResult<usize>;
+	fn get_url_rules(&mut self) -> QueryResult<Vec<UrlRule>>;
+	fn get_url_rule(&mut self, id: i32) -> QueryResult<UrlRule>;
+	fn insert_url_rule(&mut self, new_rule: &NewUrlRule) -> QueryResult<UrlRule>;
+	fn delete_url_rule(&mut self, rule: &UrlRule) -> QueryResult<usize>;
+}
+
+/// Everything that proves who a user is: sessions, API keys, recovery codes and the tokens sent by email
+pub trait TokenRepository {
+	fn get_session(&mut self, id: i32) -> QueryResult<Session>;
+	/// Gets the sessions of a user that haven't been revoked or expired, most recently used first
+	fn get_active_sessions(&mut self, user_id: i32) -> QueryResult<Vec<Session>>;
+	fn get_session_by_refresh_token_hash(&mut self, refresh_token_hash: &str) -> QueryResult<Session>;
+	/// Gets the session a refresh token belonged to before it was rotated
+	fn get_session_by_previous_token_hash(&mut self, previous_token_hash: &str) -> QueryResult<Session>;
+	fn insert_session(&mut self, new_session: &NewSession) -> QueryResult<Session>;
+	/// Swaps the refresh token for a new one and extends the session. Returns `None` if the token was already swapped
+	/// by someone else, so each refresh token can only be used once.
+	fn rotate_session(
+		&mut self,
+		session: &Session,
+		refresh_token_hash: String,
+		expires_at: NaiveDateTime,
+	) -> QueryResult<Option<Session>>;
+	fn touch_session(&mut self, session: &Session) -> QueryResult<usize>;
+	fn revoke_session(&mut self, session: &Session) -> QueryResult<usize>;
+	/// Revokes every active session of a user, except the given one
+	fn revoke_all_sessions(&mut self, user_id: i32, except_id: Option<i32>) -> QueryResult<usize>;
+
+	fn get_api_key(&mut self, id: i32) -> QueryResult<ApiKey>;
+	fn get_api_keys(&mut self, user_id: i32) -> QueryResult<Vec<ApiKey>>;
+	/// Gets a key and its owner by the hash of the key
+	fn get_api_key_by_token_hash(&mut self, token_hash: &str) -> QueryResult<(ApiKey, User)>;
+	fn insert_api_key(&mut self, new_api_key: &NewApiKey) -> QueryResult<ApiKey>;
+	fn update_api_key(&mut self, api_key: &ApiKey, values: UpdateApiKey) -> QueryResult<ApiKey>;
+	/// Records that the key was just used
+	fn touch_api_key(&mut self, api_key: &ApiKey) -> QueryResult<usize>;
+	fn delete_api_key(&mut self, api_key: &ApiKey) -> QueryResult<usize>;
+
+	fn count_unused_recovery_codes(&mut self, user_id: i32) -> QueryResult<i64>;
+	/// Marks a code as used. Returns false if the user has no such unused code.
+	fn redeem_recovery_code(&mut self, user_id: i32, code_hash: &str) -> QueryResult<bool>;
+	/// Swaps all codes of a user for new ones
+	fn replace_recovery_codes(&mut self, user_id: i32, code_hashes: &[String]) -> QueryResult<usize>;
+	fn delete_recovery_codes(&mut self, user_id: i32) -> QueryResult<usize>;
+
+	fn get_verification_token(&mut self, token: &str) -> QueryResult<Option<(VerificationToken, User)>>;
+	/// Gets the most recently sent token of a user, used to rate limit resending
+	fn get_latest_verification_token(&mut self, user_id: i32) -> QueryResult<Option<VerificationToken>>;
+	fn insert_verification_token(&mut self, new_token: &NewVerificationToken) -> QueryResult<VerificationToken>;
+	fn delete_verification_token(&mut self, token: &VerificationToken) -> QueryResult<usize>;
+	fn delete_verification_tokens(&mut self, user_id: i32) -> QueryResult<usize>;
+
+	fn get_password_reset_token(&mut self, token_hash: &str) -> QueryResult<(PasswordResetToken, User)>;
+	fn insert_password_reset_token(&mut self, new_token: &NewPasswordResetToken) -> QueryResult<PasswordResetToken>;
+	fn delete_password_reset_token(&mut self, token: &PasswordResetToken) -> QueryResult<usize>;
+	fn delete_password_reset_tokens(&mut self, user_id: i32) -> QueryResult<usize>;
+
+	fn get_email_change_token(&mut self, token_hash: &str) -> QueryResult<(EmailChangeToken, User)>;
+	fn insert_email_change_token(&mut self, new_token: &NewEmailChangeToken) -> QueryResult<EmailChangeToken>;
+	fn delete_email_change_tokens(&mut self, user_id: i32) -> QueryResult<usize>;
+}
+
+/// One unit of work, giving access to every repository
+pub trait Repositories {
+	fn links(&mut self) -> &mut dyn LinkRepository;
+	fn users(&mut self) -> &mut dyn UserRepository;
+	fn roles(&mut self) -> &mut dyn RoleRepository;
+	fn teams(&mut self) -> &mut dyn TeamRepository;
+	fn domains(&mut self) -> &mut dyn DomainRepository;
+	fn tokens(&mut self) -> &mut dyn TokenRepository;
+	/// Runs the work in a transaction, undoing everything it did if it fails. Use [`transaction`], which can return
+	/// a value.
+	fn in_transaction(&mut self, work: &mut dyn FnMut(&mut dyn Repositories) -> QueryResult<()>) -> QueryResult<()>;
+}
+
+/// Runs the work in a transaction on the repositories, which is rolled back if the work fails. Like
+/// [`crate::transaction`] does on a connection.
+pub fn transaction<T, F>(repos: &mut dyn Repositories, work: F) -> QueryResult<T>
+where
+	F: FnOnce(&mut dyn Repositories) -> QueryResult<T>,
+{
+	let mut work = Some(work);
+	let mut output = None;
+
+	repos.in_transaction(&mut |repos| {
+		if let Some(work) = work.take() {
+			output = Some(work(repos)?);
+		}
+
+		Ok(())
+	})?;
+
+	Ok(output.expect("The transaction didn't run its work"))
+}
+
+/// Where the data lives. Opening it may block, so async code goes through [`run`].
+pub trait Store: Send + Sync {
+	fn open(&self) -> Result<Box<dyn Repositories + '_>, RunError>;
+}
+
+pub type SharedStore = Arc<dyn Store>;
+
+/// Runs blocking work on the repositories on Tokio's blocking threads, like [`crate::run`] does for the pool
+pub async fn run<T, F>(store: &SharedStore, work: F) -> Result<T, RunError>
+where
+	F: FnOnce(&mut dyn Repositories) -> T + Send + 'static,
+	T: Send + 'static,
+{
+	let store = store.clone();
+
+	tokio::task::spawn_blocking(move || {
+		let mut repos = store.open()?;
+
+		Ok(work(repos.as_mut()))
+	})
+	.await
+	.map_err(|_| RunError::Aborted)?
+}
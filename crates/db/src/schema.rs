@@ -1,5 +1,22 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    api_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        #[max_length = 16]
+        token_prefix -> Varchar,
+        scopes -> Text,
+        last_used_at -> Nullable<Timestamp>,
+        expires_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     domains (id) {
         id -> Int4,
@@ -8,6 +25,40 @@ diesel::table! {
         public -> Bool,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        #[max_length = 32]
+        slug_strategy -> Nullable<Varchar>,
+        redirect_type -> Nullable<Int4>,
+        team_id -> Nullable<Int4>,
+        owner_id -> Nullable<Int4>,
+        #[max_length = 64]
+        verification_token -> Nullable<Varchar>,
+        verified_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    email_change_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 255]
+        new_email -> Varchar,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    link_clicks (id) {
+        id -> Int4,
+        link_id -> Int4,
+        domain_id -> Int4,
+        referrer -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
+        #[max_length = 64]
+        ip_hash -> Nullable<Varchar>,
+        clicked_at -> Timestamp,
     }
 }
 
@@ -24,6 +75,124 @@ diesel::table! {
         created_at -> Timestamp,
         updated_at -> Timestamp,
         deleted_at -> Nullable<Timestamp>,
+        expires_at -> Nullable<Timestamp>,
+        max_clicks -> Nullable<Int4>,
+        click_count -> Int4,
+        archived_at -> Nullable<Timestamp>,
+        password_hash -> Nullable<Text>,
+        redirect_type -> Nullable<Int4>,
+        team_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    password_reset_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        token_hash -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    recovery_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    role_permissions (role_id, permission) {
+        role_id -> Int4,
+        #[max_length = 64]
+        permission -> Varchar,
+    }
+}
+
+diesel::table! {
+    roles (id) {
+        id -> Int4,
+        #[max_length = 64]
+        name -> Varchar,
+        description -> Nullable<Text>,
+        built_in -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sessions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 64]
+        refresh_token_hash -> Varchar,
+        #[max_length = 64]
+        previous_token_hash -> Nullable<Varchar>,
+        user_agent -> Nullable<Text>,
+        created_at -> Timestamp,
+        last_seen_at -> Timestamp,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    team_members (team_id, user_id) {
+        team_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 16]
+        role -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    teams (id) {
+        id -> Int4,
+        #[max_length = 64]
+        name -> Varchar,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    url_rules (id) {
+        id -> Int4,
+        #[max_length = 255]
+        domain -> Varchar,
+        #[max_length = 16]
+        kind -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_identities (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 255]
+        issuer -> Varchar,
+        #[max_length = 255]
+        subject -> Varchar,
+        #[max_length = 255]
+        email -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        last_login_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    user_roles (user_id, role_id) {
+        user_id -> Int4,
+        role_id -> Int4,
+        created_at -> Timestamp,
     }
 }
 
@@ -36,9 +205,17 @@ diesel::table! {
         email -> Varchar,
         password_hash -> Text,
         verified_at -> Nullable<Timestamp>,
-        is_admin -> Bool,
         created_at -> Timestamp,
         deleted_at -> Nullable<Timestamp>,
+        #[max_length = 64]
+        totp_secret -> Nullable<Varchar>,
+        totp_enabled_at -> Nullable<Timestamp>,
+        totp_last_step -> Nullable<Int8>,
+        totp_attempts -> Int4,
+        totp_locked_until -> Nullable<Timestamp>,
+        suspended_at -> Nullable<Timestamp>,
+        suspended_until -> Nullable<Timestamp>,
+        suspension_reason -> Nullable<Text>,
     }
 }
 
@@ -53,13 +230,42 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(api_keys -> users (user_id));
+diesel::joinable!(domains -> teams (team_id));
+diesel::joinable!(domains -> users (owner_id));
+diesel::joinable!(email_change_tokens -> users (user_id));
+diesel::joinable!(link_clicks -> domains (domain_id));
+diesel::joinable!(link_clicks -> links (link_id));
 diesel::joinable!(links -> domains (domain_id));
+diesel::joinable!(links -> teams (team_id));
 diesel::joinable!(links -> users (owner_id));
+diesel::joinable!(password_reset_tokens -> users (user_id));
+diesel::joinable!(recovery_codes -> users (user_id));
+diesel::joinable!(role_permissions -> roles (role_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(team_members -> teams (team_id));
+diesel::joinable!(team_members -> users (user_id));
+diesel::joinable!(user_identities -> users (user_id));
+diesel::joinable!(user_roles -> roles (role_id));
+diesel::joinable!(user_roles -> users (user_id));
 diesel::joinable!(verification_tokens -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    api_keys,
     domains,
+    email_change_tokens,
+    link_clicks,
     links,
+    password_reset_tokens,
+    recovery_codes,
+    role_permissions,
+    roles,
+    sessions,
+    team_members,
+    teams,
+    url_rules,
+    user_identities,
+    user_roles,
     users,
     verification_tokens,
 );
@@ -1,8 +1,8 @@
 import { useContext, useEffect } from 'preact/hooks'
-import { LoginContext } from '../../context/LoginContext'
+import { LoginContext, Permission, hasPermission } from '../../context/LoginContext'
 import { Dashboard } from '../Layout/Dashboard/Dashboard'
 
-export const RequireAdmin = (WrappedComponent: any) => {
+export const RequirePermission = (WrappedComponent: any, permission: Permission) => {
 	// const { route } = useLocation()
 
 	const isLoggedIn = localStorage.getItem('isLoggedIn') === 'true'
@@ -36,7 +36,7 @@ export const RequireAdmin = (WrappedComponent: any) => {
 			)
 		}
 
-		if (!loginContext.user.is_admin) {
+		if (!hasPermission(loginContext.user, permission)) {
 			return (
 				<Dashboard>
 					<h1>You do not have access to this page.</h1>
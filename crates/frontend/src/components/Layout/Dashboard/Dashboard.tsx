@@ -1,6 +1,6 @@
 import { ComponentChildren } from 'preact'
 import { useContext } from 'preact/hooks'
-import { LoginContext } from '../../../context/LoginContext'
+import { LoginContext, hasPermission } from '../../../context/LoginContext'
 import { ToastContainer } from 'react-toastify'
 
 import 'react-toastify/dist/ReactToastify.css'
@@ -36,7 +36,7 @@ export const Dashboard = ({
 								User settings
 							</a>
 						</li>
-						{user.is_admin && (
+						{hasPermission(user, 'domain.manage') && (
 							<>
 								<li>
 									<a href="/dash/domains" class="block px-4 py-2 hover:bg-gray-700">
@@ -45,6 +45,13 @@ export const Dashboard = ({
 								</li>
 							</>
 						)}
+						{hasPermission(user, 'user.manage') && (
+							<li>
+								<a href="/dash/users" class="block px-4 py-2 hover:bg-gray-700">
+									Users
+								</a>
+							</li>
+						)}
 						<li>
 							<a href="#" class="block px-4 py-2 hover:bg-gray-700" onClick={logoutUser}>
 								Logout
@@ -0,0 +1,130 @@
+import { useContext, useEffect, useState } from 'preact/hooks'
+import { toast } from 'react-toastify'
+import { APIError, simpleDataFetch, simpleDataPost } from '../context/contextUtils'
+import { LoginContext } from '../context/LoginContext'
+
+type TwoFactorStatus = {
+	enabled: boolean,
+	recovery_codes_left: number,
+}
+
+type TwoFactorSetup = {
+	secret: string,
+	otpauth_uri: string,
+	qr_code_svg: string,
+}
+
+type RecoveryCodes = {
+	recovery_codes: string[],
+}
+
+const inputClass = 'w-full px-4 py-2 border border-gray-300 rounded-md focus:outline-none focus:ring-2 focus:ring-indigo-500 dark:bg-gray-700 dark:border-gray-600 dark:text-gray-200 dark:focus:ring-indigo-400'
+const buttonClass = 'bg-indigo-600 text-white px-6 py-2 rounded-md hover:bg-indigo-700 focus:outline-none focus:ring-2 focus:ring-indigo-500 focus:ring-offset-2 dark:bg-indigo-700 dark:hover:bg-indigo-800 dark:focus:ring-indigo-400'
+
+const errorMessage = (e: APIError) => e.error?.message ?? e.message
+
+export const TwoFactorSettings = () => {
+	const { fetchMe } = useContext(LoginContext)
+
+	const [ status, setStatus ] = useState<TwoFactorStatus | null>(null)
+	const [ setup, setSetup ] = useState<TwoFactorSetup | null>(null)
+	const [ recoveryCodes, setRecoveryCodes ] = useState<string[] | null>(null)
+	const [ code, setCode ] = useState('')
+	const [ password, setPassword ] = useState('')
+
+	const fetchStatus = () => simpleDataFetch<TwoFactorStatus>('/api/user/me/2fa', setStatus)
+		.catch((e: APIError) => toast.error(`Failed to get 2FA status: ${errorMessage(e)}`))
+
+	useEffect(() => {
+		fetchStatus()
+	}, [])
+
+	const onRecoveryCodes = (data: RecoveryCodes) => {
+		setRecoveryCodes(data.recovery_codes)
+		setCode('')
+		fetchStatus()
+	}
+
+	const startSetup = () => simpleDataPost<TwoFactorSetup>('/api/user/me/2fa/setup', {}, setSetup)
+		.catch((e: APIError) => toast.error(`Failed to start 2FA setup: ${errorMessage(e)}`))
+
+	const enable = (e) => {
+		e.preventDefault()
+
+		simpleDataPost<RecoveryCodes>('/api/user/me/2fa/enable', { code }, (data) => {
+			toast.success('2FA enabled.')
+			setSetup(null)
+			onRecoveryCodes(data)
+			fetchMe()
+		}).catch((e: APIError) => toast.error(`Failed to enable 2FA: ${errorMessage(e)}`))
+	}
+
+	const disable = (e) => {
+		e.preventDefault()
+
+		simpleDataPost('/api/user/me/2fa/disable', { password, code }, () => {
+			toast.success('2FA disabled.')
+			setPassword('')
+			setCode('')
+			setRecoveryCodes(null)
+			fetchStatus()
+			fetchMe()
+		}).catch((e: APIError) => toast.error(`Failed to disable 2FA: ${errorMessage(e)}`))
+	}
+
+	const regenerate = () => simpleDataPost<RecoveryCodes>('/api/user/me/2fa/recovery-codes', { code }, onRecoveryCodes)
+		.catch((e: APIError) => toast.error(`Failed to create recovery codes: ${errorMessage(e)}`))
+
+	if (!status) return null
+
+	return (
+		<div>
+			{recoveryCodes && (
+				<div class="mb-6 p-4 bg-yellow-100 dark:bg-yellow-800 border border-yellow-300 dark:border-yellow-600 text-yellow-800 dark:text-yellow-200 rounded">
+					<p class="mb-2">Save these recovery codes somewhere safe. Each one can be used once if you lose your authenticator, and they won't be shown again.</p>
+					<ul class="grid grid-cols-2 gap-1 font-mono">
+						{recoveryCodes.map((recoveryCode) => <li key={recoveryCode}>{recoveryCode}</li>)}
+					</ul>
+				</div>
+			)}
+
+			{!status.enabled && !setup && (
+				<button type="button" class={buttonClass} onClick={startSetup}>Set up 2FA</button>
+			)}
+
+			{!status.enabled && setup && (
+				<form onSubmit={enable}>
+					<p class="mb-4 text-gray-600 dark:text-gray-300">Scan this QR code with your authenticator app, or enter the secret manually.</p>
+					<div class="mb-4 bg-white p-2 inline-block" dangerouslySetInnerHTML={{ __html: setup.qr_code_svg }} />
+					<p class="mb-4 font-mono text-sm text-gray-700 dark:text-gray-300 break-all">{setup.secret}</p>
+
+					<div class="mb-6">
+						<label class="block text-gray-700 dark:text-gray-300 mb-2" for="twoFactorCode">Code from the app</label>
+						<input id="twoFactorCode" autocomplete="one-time-code" class={inputClass} value={code} onChange={e => setCode(e.currentTarget.value)} />
+					</div>
+
+					<button type="submit" class={buttonClass}>Enable 2FA</button>
+				</form>
+			)}
+
+			{status.enabled && (
+				<form onSubmit={disable}>
+					<p class="mb-4 text-gray-600 dark:text-gray-300">2FA is enabled. You have {status.recovery_codes_left} recovery codes left.</p>
+
+					<div class="mb-4">
+						<label class="block text-gray-700 dark:text-gray-300 mb-2" for="twoFactorCode">Code from the app</label>
+						<input id="twoFactorCode" autocomplete="one-time-code" class={inputClass} value={code} onChange={e => setCode(e.currentTarget.value)} />
+					</div>
+
+					<div class="mb-6">
+						<label class="block text-gray-700 dark:text-gray-300 mb-2" for="twoFactorPassword">Password, to disable 2FA</label>
+						<input id="twoFactorPassword" type="password" class={inputClass} value={password} onChange={e => setPassword(e.currentTarget.value)} />
+					</div>
+
+					<button type="button" class={`${buttonClass} mr-2`} onClick={regenerate}>New recovery codes</button>
+					<button type="submit" class="bg-red-600 text-white px-6 py-2 rounded-md hover:bg-red-700 focus:outline-none focus:ring-2 focus:ring-red-500 focus:ring-offset-2">Disable 2FA</button>
+				</form>
+			)}
+		</div>
+	)
+}
@@ -10,11 +10,39 @@ export type Link = {
     custom_slug?: string;
     original_link: string;
     owner_id?: number;
+    team_id?: number | null;
     created_at: string;
     updated_at: string;
     deleted_at?: string;
+    expires_at?: string;
+    max_clicks?: number;
+    click_count: number;
+    archived_at?: string;
+    is_protected: boolean;
+    redirect_type?: 301 | 302 | 307 | 308;
 };
 
+export type LinkStats = {
+	link_id: number,
+	total_clicks: number,
+	unique_visitors: number,
+	last_clicked_at?: string,
+	top_referrers: {
+		referrer?: string,
+		clicks: number,
+	}[],
+}
+
+export type LinkTimeSeries = {
+	link_id: number,
+	interval: 'hour' | 'day',
+	since: string,
+	buckets: {
+		bucket: string,
+		clicks: number,
+	}[],
+}
+
 export type PaginatedResponse<T> = {
 	items: T[]
 	total_count: number,
@@ -33,7 +61,9 @@ export type IApiContext = {
 	createLink: (url: string, domainId: number, customSlug?: string) => Promise<void>,
 	resetLinkCreationState: () => void,
 	// eslint-disable-next-line no-unused-vars
-	deleteLink: (slug: string) => Promise<void>,
+	deleteLink: (id: number) => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	getLinkStats: (id: number) => Promise<[LinkStats, LinkTimeSeries]>,
 	// eslint-disable-next-line no-unused-vars
 	setCurrentLinkPage: (page: number) => void,
 	// eslint-disable-next-line no-unused-vars
@@ -86,11 +116,11 @@ export const ApiContextProvider = ({
 		})
 	}
 
-	const deleteLink = async (slug: string) => {
-		await simpleDelete(`/api/link/${slug}`, () => {
+	const deleteLink = async (id: number) => {
+		await simpleDelete(`/api/link/${id}`, () => {
 			const linkClone = [...links]
 
-			const newLinks = linkClone.filter(link => link.slug !== slug)
+			const newLinks = linkClone.filter(link => link.id !== id)
 
 			setLinks(newLinks)
 
@@ -102,6 +132,20 @@ export const ApiContextProvider = ({
 		})
 	} 
 
+	const getLinkStats = async (id: number): Promise<[LinkStats, LinkTimeSeries]> => {
+		let stats: LinkStats = null
+		let timeSeries: LinkTimeSeries = null
+
+		await Promise.all([
+			simpleDataFetch<LinkStats>(`/api/link/${id}/stats`, data => stats = data),
+			simpleDataFetch<LinkTimeSeries>(`/api/link/${id}/stats/timeseries?interval=day&days=30`, data => timeSeries = data),
+		]).catch((e: APIError) => {
+			toast.error(e.message)
+		})
+
+		return [ stats, timeSeries ]
+	}
+
 	useEffect(() => {
 		getMyLinks()
 	}, [ currentLinkPage, perPage ])
@@ -119,6 +163,7 @@ export const ApiContextProvider = ({
 				createLink,
 				resetLinkCreationState,
 				deleteLink,
+				getLinkStats,
 				linkCreationState,
 				error,
 			}}
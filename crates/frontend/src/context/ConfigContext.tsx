@@ -8,6 +8,8 @@ export type IConfigContext = {
 	minPasswordStrength: number,
 	baseUrl: string,
 	setupDone: boolean,
+	oidcEnabled: boolean,
+	oidcDisplayName: string | null,
 }
 
 export const ConfigContext = createContext<IConfigContext>({
@@ -16,6 +18,8 @@ export const ConfigContext = createContext<IConfigContext>({
 	minPasswordStrength: 0,
 	baseUrl: null,
 	setupDone: false,
+	oidcEnabled: false,
+	oidcDisplayName: null,
 })
 
 type ConfigResponse = {
@@ -24,6 +28,8 @@ type ConfigResponse = {
 	min_password_strength: number,
 	base_url: string,
 	setup_done: boolean,
+	oidc_enabled: boolean,
+	oidc_display_name: string | null,
 }
 
 export const ConfigContextProvider = ({
@@ -35,6 +41,8 @@ export const ConfigContextProvider = ({
 		minPasswordStrength: 0,
 		baseUrl: null,
 		setupDone: false,
+		oidcEnabled: false,
+		oidcDisplayName: null,
 	})
 
 	useEffect(() => {
@@ -45,6 +53,8 @@ export const ConfigContextProvider = ({
 				minPasswordStrength: data.min_password_strength,
 				baseUrl: data.base_url,
 				setupDone: data.setup_done,
+				oidcEnabled: data.oidc_enabled,
+				oidcDisplayName: data.oidc_display_name,
 			})
 		})
 	}, [])
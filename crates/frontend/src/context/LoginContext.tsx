@@ -7,16 +7,28 @@ import { toast } from 'react-toastify'
 export type User = {
     id: number,
     username: string,
-	is_admin: boolean,
     email: string,
     verified_at: Date | null,
+	two_factor_enabled: boolean,
+	permissions: Permission[],
     created_at: Date,
     deleted_at: Date | null
 }
 
+export type Permission = 'link.create' | 'link.manage_any' | 'domain.manage' | 'user.manage' | 'settings.manage'
+
+export const hasPermission = (user: User | null, permission: Permission) => !!user?.permissions?.includes(permission)
+
 type LoginResponse = {
 	token: String,
 	user: User,
+	two_factor_setup_required: boolean,
+}
+
+// Returned by the password step when the user still has to enter a 2FA code
+type TwoFactorChallenge = {
+	two_factor_required: true,
+	two_factor_token: string,
 }
 
 type UpdateUser = {
@@ -31,8 +43,11 @@ export type ILoginContext = {
 	loginRedirectMessage: string | null,
 	loginRedirectTo: string | null,
 	isDeletingAccount: boolean,
+	twoFactorToken: string | null,
 	// eslint-disable-next-line no-unused-vars
 	loginUser: (email: string, password: string, onLogin?: (() => void)) => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	submitTwoFactorCode: (code: string, onLogin?: (() => void)) => Promise<void>,
 	logoutUser: () => void,
 	fetchMe: () => Promise<void>,
 	deleteAccount: () => Promise<void>,
@@ -40,20 +55,24 @@ export type ILoginContext = {
 	changePassword: (currentPassword: string, newPassword: string, confirmPassword: string) => Promise<void>,
 	// eslint-disable-next-line no-unused-vars
 	updateUser: (values: UpdateUser) => Promise<void>,
+	resendVerification: () => Promise<void>,
 	// eslint-disable-next-line no-unused-vars
 	setLoginRedirectMessage: (message: string) => void,
+	// eslint-disable-next-line no-unused-vars
+	setTwoFactorToken: (token: string) => void,
 }
 
 //@ts-ignore
 export const LoginContext = createContext<ILoginContext>({
 	//@ts-ignore
 	user: {
-		is_admin: false,
+		permissions: [],
 	},
 	error: null,
 	loginRedirectMessage: null,
 	loginRedirectTo: null,
 	isDeletingAccount: false,
+	twoFactorToken: null,
 })
 
 export const LoginContextProvider = ({
@@ -64,28 +83,55 @@ export const LoginContextProvider = ({
 	const [ loginRedirectMessage, setLoginRedirectMessage ] = useState<string | null>(null)
 	const [ loginRedirectTo, setLoginRedirectTo ] = useState<string | null>(null)
 	const [ isDeletingAccount, setIsDeletingAccount ] = useState<boolean>(false)
+	const [ twoFactorToken, setTwoFactorToken ] = useState<string | null>(null)
 
 	const { route, path } = useLocation()
 
-	const loginUser = async (email: string, password: string, onLogin) => {
-		simpleDataPost<LoginResponse>('/api/user/login', { email, password }, (data) => {
-			setError(null)
-			setLoginRedirectMessage(null)
+	const onLoggedIn = (data: LoginResponse, onLogin?: (() => void)) => {
+		setError(null)
+		setLoginRedirectMessage(null)
+		setTwoFactorToken(null)
 
-			setUser(data.user)
-			localStorage.setItem('isLoggedIn', 'true')
-			if (onLogin) onLogin()
+		setUser(data.user)
+		localStorage.setItem('isLoggedIn', 'true')
+		if (onLogin) onLogin()
+
+		if (data.two_factor_setup_required) {
+			toast.warn('Enable 2FA to use your management permissions. Until then, they are paused.')
+		}
+
+		if (loginRedirectTo) {
+			route(loginRedirectTo)
+			setLoginRedirectTo(null)
+		}
+	}
 
-			if (loginRedirectTo) {
-				route(loginRedirectTo)
-				setLoginRedirectTo(null)
+	const loginUser = async (email: string, password: string, onLogin) => {
+		simpleDataPost<LoginResponse | TwoFactorChallenge>('/api/user/login', { email, password }, (data) => {
+			if ('two_factor_required' in data) {
+				setError(null)
+				setTwoFactorToken(data.two_factor_token)
+				return
 			}
 
+			onLoggedIn(data, onLogin)
 		}).catch((e: APIError) => {
 			setError(e.message)
 		})
 	}
 
+	const submitTwoFactorCode = async (code: string, onLogin) => {
+		simpleDataPost<LoginResponse>('/api/user/login/2fa', { two_factor_token: twoFactorToken, code }, (data) => {
+			onLoggedIn(data, onLogin)
+		}).catch((e: APIError) => {
+			if (e.statusCode === 401 && e.error?.reason !== 'invalid_code') {
+				setTwoFactorToken(null)
+			}
+
+			setError(e.error?.message ?? e.message)
+		})
+	}
+
 	const fetchMe = async () => {
 		simpleDataFetch<User>('/api/user/me', data => {
 			setError(null)
@@ -148,11 +194,11 @@ export const LoginContextProvider = ({
 	}
 
 	const updateUser = async (values: UpdateUser) => {
-		await simpleDataPost('/api/user/me/update', values, () => {
-			toast.success('User updated.')
+		await simpleDataPost<{ message: string }>('/api/user/me/update', values, (data) => {
+			toast.success(data.message)
+			// The email only changes once the new address is confirmed
 			setUser({
 				...user,
-				email: values.email || user.email,
 				username: values.username || user.username,
 			})
 		}).catch((e: APIError) => {
@@ -160,6 +206,14 @@ export const LoginContextProvider = ({
 		})
 	}
 
+	const resendVerification = async () => {
+		await simpleDataPost('/api/user/verify/resend', {}, () => {
+			toast.success('Verification email sent.')
+		}).catch((e: APIError) => {
+			toast.error(`Failed to send verification email: ${e.error?.message ?? e.message}`)
+		})
+	}
+
 	useEffect(() => {
 		if(!user) fetchMe()
 	}, [])
@@ -172,13 +226,17 @@ export const LoginContextProvider = ({
 				loginRedirectMessage,
 				loginRedirectTo,
 				isDeletingAccount,
+				twoFactorToken,
 				loginUser,
+				submitTwoFactorCode,
 				logoutUser,
 				fetchMe,
 				deleteAccount,
 				changePassword,
 				updateUser,
+				resendVerification,
 				setLoginRedirectMessage,
+				setTwoFactorToken,
 			}}
 		>
 			{children}
@@ -0,0 +1,163 @@
+import { createContext } from 'preact'
+import { PaginationContext } from './types'
+import { useEffect, useState } from 'preact/hooks'
+import { APIError, simpleDataFetch, simpleDataPost, simpleDelete } from './contextUtils'
+import { PaginatedResponse } from './ApiContext'
+import { toast } from 'react-toastify'
+
+export type ManagedUser = {
+	id: number,
+	username: string,
+	email: string,
+	verified_at: string | null,
+	created_at: string,
+	deleted_at: string | null,
+	two_factor_enabled: boolean,
+	suspended: boolean,
+	suspended_at: string | null,
+	suspended_until: string | null,
+	suspension_reason: string | null,
+	roles: string[],
+}
+
+export type PasswordResetLink = {
+	reset_url: string,
+	expires_at: string,
+	emailed: boolean,
+}
+
+export type IUserManagementContext = PaginationContext<ManagedUser> & {
+	search: string,
+	// eslint-disable-next-line no-unused-vars
+	setSearch: (search: string) => void,
+	getUsers: () => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	setAdmin: (id: number, isAdmin: boolean) => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	suspendUser: (id: number, until: Date | null, reason: string) => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	unsuspendUser: (id: number) => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	verifyEmail: (id: number) => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	resetPassword: (id: number) => Promise<PasswordResetLink | null>,
+	// eslint-disable-next-line no-unused-vars
+	deleteUser: (id: number) => Promise<void>,
+	// eslint-disable-next-line no-unused-vars
+	restoreUser: (id: number) => Promise<void>,
+}
+
+export const UserManagementContext = createContext<IUserManagementContext>(null)
+
+// Posts return the whole error body, deletes only its message
+const errorMessage = (e: APIError) => e.error?.message ?? e.message
+
+export const UserManagementContextProvider = ({
+	children,
+}) => {
+	const [ items, setItems ] = useState<ManagedUser[]>(null)
+	const [ totalCount, setTotalCount ] = useState(0)
+	const [ currentPage, setCurrentPage ] = useState(1)
+	const [ perPage, setPerPage ] = useState(10)
+	const [ search, setSearch ] = useState('')
+
+	const getUsers = async () => {
+		const query = new URLSearchParams({ page: `${currentPage}`, per_page: `${perPage}`, search })
+
+		simpleDataFetch<PaginatedResponse<ManagedUser>>(`/api/admin/users?${query}`, data => {
+			setTotalCount(data.total_count)
+
+			setItems(data.items)
+		})
+	}
+
+	const replaceUser = (user: ManagedUser) => {
+		setItems(items.map(item => item.id === user.id ? user : item))
+	}
+
+	// Runs an action that answers with the updated user
+	const updateUser = async (request: Promise<void>, success: string) => {
+		await request
+			.then(() => toast.success(success))
+			.catch((e: APIError) => {
+				toast.error(errorMessage(e))
+			})
+	}
+
+	const setAdmin = (id: number, isAdmin: boolean) => updateUser(
+		isAdmin
+			? simpleDataPost<ManagedUser>(`/api/admin/users/${id}/admin`, {}, replaceUser)
+			: simpleDelete<ManagedUser>(`/api/admin/users/${id}/admin`, replaceUser),
+		isAdmin ? 'User promoted to admin.' : 'User is no longer an admin.',
+	)
+
+	const suspendUser = (id: number, until: Date | null, reason: string) => updateUser(
+		until
+			? simpleDataPost<ManagedUser>(`/api/admin/users/${id}/suspend`, { until: until.toISOString(), reason }, replaceUser)
+			: simpleDataPost<ManagedUser>(`/api/admin/users/${id}/ban`, { reason }, replaceUser),
+		until ? 'User suspended.' : 'User banned.',
+	)
+
+	const unsuspendUser = (id: number) => updateUser(
+		simpleDelete<ManagedUser>(`/api/admin/users/${id}/suspension`, replaceUser),
+		'Suspension lifted.',
+	)
+
+	const verifyEmail = (id: number) => updateUser(
+		simpleDataPost<ManagedUser>(`/api/admin/users/${id}/verify-email`, {}, replaceUser),
+		'Email verified.',
+	)
+
+	const deleteUser = (id: number) => updateUser(
+		simpleDelete<ManagedUser>(`/api/admin/users/${id}`, replaceUser),
+		'User deleted.',
+	)
+
+	const restoreUser = (id: number) => updateUser(
+		simpleDataPost<ManagedUser>(`/api/admin/users/${id}/restore`, {}, replaceUser),
+		'User restored.',
+	)
+
+	const resetPassword = async (id: number) => {
+		let link: PasswordResetLink = null
+
+		await simpleDataPost<PasswordResetLink>(`/api/admin/users/${id}/reset-password`, {}, data => {
+			link = data
+		}).catch((e: APIError) => {
+			toast.error(errorMessage(e))
+		})
+
+		return link
+	}
+
+	useEffect(() => {
+		if (items) {
+			getUsers()
+		}
+	}, [ currentPage, perPage, search ])
+
+	return (
+		<UserManagementContext.Provider
+			value={{
+				items,
+				totalCount,
+				currentPage,
+				perPage,
+				search,
+				getUsers,
+				setCurrentPage,
+				setPerPage,
+				setSearch,
+				setAdmin,
+				suspendUser,
+				unsuspendUser,
+				verifyEmail,
+				resetPassword,
+				deleteUser,
+				restoreUser,
+			}}
+		>
+			{children}
+		</UserManagementContext.Provider>
+	)
+}
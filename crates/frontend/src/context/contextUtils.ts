@@ -11,8 +11,39 @@ export class APIError extends Error {
 }
 
 
+let refreshRequest: Promise<boolean> | null = null
+
+// Gets a new access token with the refresh token cookie. Concurrent callers share one request, as the refresh token rotates.
+const refreshSession = (): Promise<boolean> => {
+	if (!refreshRequest) {
+		refreshRequest = fetch('/api/user/refresh', { method: 'POST' })
+			.then((response) => response.ok)
+			.catch(() => false)
+			.finally(() => {
+				refreshRequest = null
+			})
+	}
+
+	return refreshRequest
+}
+
+// Like fetch, but refreshes the session and retries once when the access token has expired
+export const authedFetch = async (url: string, init?: RequestInit): Promise<Response> => {
+	const response = await fetch(url, init)
+
+	if (response.status !== 401 || url === '/api/user/refresh' || url.startsWith('/api/user/login')) {
+		return response
+	}
+
+	if (!(await refreshSession())) {
+		return response
+	}
+
+	return fetch(url, init)
+}
+
 export const simpleDataFetch = async <T>(url: string, setFn: (data: T) => void): Promise<void> => {
-	const request = await fetch(url, {
+	const request = await authedFetch(url, {
 		method: 'GET',
 		headers: {
 			'Content-Type': 'application/json',
@@ -33,7 +64,7 @@ export const simpleDataPost = async <T, T2 = Record<string, any>>(
 	data: T2,
 	setFn: (data: T) => void,
 ): Promise<void> => {
-	const request = await fetch(url, {
+	const request = await authedFetch(url, {
 		method: 'POST',
 		body: JSON.stringify(data),
 		headers: {
@@ -56,7 +87,7 @@ export const simpleDataPut = async <T>(
 	data: Record<string, any>,
 	setFn: (data: T) => void,
 ): Promise<void> => {
-	const request = await fetch(url, {
+	const request = await authedFetch(url, {
 		method: 'PUT',
 		body: JSON.stringify(data),
 		headers: {
@@ -76,7 +107,7 @@ export const simpleDataPut = async <T>(
 
 
 export const simpleDelete = async <T>(url: string, setFn: (data: T) => void): Promise<void> => {
-	const request = await fetch(url, {
+	const request = await authedFetch(url, {
 		method: 'DELETE',
 		headers: {
 			'Content-Type': 'application/json',
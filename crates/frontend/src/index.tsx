@@ -7,8 +7,11 @@ const Home = lazy(async () => (await import('./pages/Home/index')).Home)
 const LoginPage = lazy(async () => (await import('./pages/Login/index')).LoginPage)
 const UserPage = lazy(async () => (await import('./pages/Dash/User')).UserPage)
 const RegisterPage = lazy(async () => (await import('./pages/Register/index')).RegisterPage)
+const ForgotPasswordPage = lazy(async () => (await import('./pages/ResetPassword/index')).ForgotPasswordPage)
+const ResetPasswordPage = lazy(async () => (await import('./pages/ResetPassword/index')).ResetPasswordPage)
 const LinkList = lazy(async () => (await import('./pages/Dash/Links')).LinkList)
 const DomainsPage = lazy(async () => (await import('./pages/Dash/Domains')).DomainsPage)
+const UsersPage = lazy(async () => (await import('./pages/Dash/Users')).UsersPage)
 const SetupRouter = lazy(async () => (await import('./pages/Setup/index')).SetupRouter)
 const NotFound = lazy(async () => (await import('./pages/_404')).NotFound)
 
@@ -21,6 +24,7 @@ import { ApiContextProvider } from './context/ApiContext'
 import { ProviderComposer } from './components/ProviderComposer.js'
 import { DomainContextProvider } from './context/DomainContext.js'
 import { DomainRepositoryContextProvider } from './context/DomainRepositoryContext.js'
+import { UserManagementContextProvider } from './context/UserManagementContext.js'
 import { ThemeContextProvider } from './context/ThemeContext.js'
 
 const providers = [
@@ -31,6 +35,7 @@ const providers = [
 	ApiContextProvider,
 	DomainContextProvider,
 	DomainRepositoryContextProvider,
+	UserManagementContextProvider,
 ]
 
 export function App() {
@@ -42,8 +47,11 @@ export function App() {
 					<Route path="/dash/login" component={LoginPage} />
 					<Route path="/dash/me" component={UserPage} />
 					<Route path="/dash/register" component={RegisterPage} />
+					<Route path="/dash/forgot-password" component={ForgotPasswordPage} />
+					<Route path="/dash/reset-password/:token" component={ResetPasswordPage} />
 					<Route path="/dash/links" component={LinkList} />
 					<Route path="/dash/domains" component={DomainsPage} />
+					<Route path="/dash/users" component={UsersPage} />
 					<Route path="/setup" component={SetupRouter} />
 					<Route path="/setup/*" component={SetupRouter} />
 					<Route default component={NotFound} />
@@ -1,5 +1,5 @@
 import { useContext, useEffect, useRef, useState } from 'preact/hooks'
-import { RequireAdmin } from '../../components/HoC/RequireAdmin'
+import { RequirePermission } from '../../components/HoC/RequirePermission'
 import { Dashboard } from '../../components/Layout/Dashboard/Dashboard'
 import { Domain, DomainContext } from '../../context/DomainContext'
 import { PaginatedTable } from '../../components/PaginatedTable'
@@ -252,4 +252,4 @@ const InternalDomains = () => {
 	)
 }
 
-export const DomainsPage = RequireAdmin(InternalDomains)
+export const DomainsPage = RequirePermission(InternalDomains, 'domain.manage')
@@ -1,7 +1,7 @@
 import { useContext, useRef, useState } from 'preact/hooks'
 import { RequireLogin } from '../../components/HoC/RequireLogin'
 import { Dashboard } from '../../components/Layout/Dashboard/Dashboard'
-import { ApiContext, Link } from '../../context/ApiContext'
+import { ApiContext, Link, LinkStats, LinkTimeSeries } from '../../context/ApiContext'
 import { Modal } from '../../components/Modal'
 import { isValidUrl } from '../../util/validator'
 import { PaginatedTable } from '../../components/PaginatedTable'
@@ -11,7 +11,7 @@ import { DomainSelector } from '../../components/DomainSelector'
 const InternalLinkList = () => {
 	const {
 		links, getMyLinks, createLink, linkCreationState, resetLinkCreationState, error, deleteLink,
-		totalLinkCount, perPage, setPerPage, setCurrentLinkPage, currentLinkPage,
+		totalLinkCount, perPage, setPerPage, setCurrentLinkPage, currentLinkPage, getLinkStats,
 	} = useContext(ApiContext)
 	const createLinkForm = useRef<HTMLFormElement>(null)
 	const [ isModalOpen, setIsModalOpen ] = useState(false)
@@ -21,9 +21,13 @@ const InternalLinkList = () => {
 
 	const [ formError, setFormError ] = useState<string>(null)
 
-	const [ deleteLinkSlug, setDeleteLinkSlug ] = useState<string>(null)
+	const [ deleteLinkId, setDeleteLinkId ] = useState<number>(null)
 	const [ isDeleteModalOpen, setIsDeleteModalOpen ] = useState<boolean>(false)
 
+	const [ stats, setStats ] = useState<LinkStats>(null)
+	const [ timeSeries, setTimeSeries ] = useState<LinkTimeSeries>(null)
+	const [ isStatsModalOpen, setIsStatsModalOpen ] = useState<boolean>(false)
+
 	const [ domainId, setDomainId ] = useState(null)
 
 	// Calculate total pages
@@ -62,16 +66,33 @@ const InternalLinkList = () => {
 	}
 
 	const onCloseDeleteModal = () => {
-		setDeleteLinkSlug(null)
+		setDeleteLinkId(null)
 		setIsDeleteModalOpen(false)
 	}
 
 	const onDeleteLink = () => {
-		deleteLink(deleteLinkSlug)
-		setDeleteLinkSlug(null)
+		deleteLink(deleteLinkId)
+		setDeleteLinkId(null)
 		setIsDeleteModalOpen(false)
 	}
 
+	const onOpenStats = async (link: Link) => {
+		setStats(null)
+		setTimeSeries(null)
+		setIsStatsModalOpen(true)
+
+		const [ linkStats, linkTimeSeries ] = await getLinkStats(link.id)
+
+		setStats(linkStats)
+		setTimeSeries(linkTimeSeries)
+	}
+
+	const onCloseStatsModal = () => {
+		setIsStatsModalOpen(false)
+	}
+
+	const maxBucketClicks = Math.max(1, ...(timeSeries?.buckets.map(bucket => bucket.clicks) ?? []))
+
 	return (
 		<Dashboard>
 			<Modal
@@ -99,6 +120,53 @@ const InternalLinkList = () => {
 			>
 				Are you sure you want to delete this link?
 			</Modal>
+			<Modal
+				open={isStatsModalOpen}
+				title="Link Stats"
+				onClickOutside={onCloseStatsModal}
+				onClose={onCloseStatsModal}
+			>
+				{!stats && (
+					<div>Please wait, loading.</div>
+				)}
+
+				{stats && (
+					<div>
+						<div class="grid grid-cols-2 gap-4 mb-4">
+							<div>
+								<div class="text-sm text-gray-500">Total Clicks</div>
+								<div class="text-2xl font-semibold">{stats.total_clicks}</div>
+							</div>
+							<div>
+								<div class="text-sm text-gray-500">Unique Visitors</div>
+								<div class="text-2xl font-semibold">{stats.unique_visitors}</div>
+							</div>
+						</div>
+
+						<div class="text-sm text-gray-500 mb-1">Last 30 days</div>
+						<div class="flex items-end h-24 gap-px mb-4">
+							{timeSeries?.buckets.map(bucket => (
+								<div
+									key={bucket.bucket}
+									title={`${bucket.bucket}: ${bucket.clicks}`}
+									class="flex-1 bg-purple-600 dark:bg-purple-800"
+									style={{ height: `${(bucket.clicks / maxBucketClicks) * 100}%` }}
+								/>
+							))}
+						</div>
+
+						<div class="text-sm text-gray-500 mb-1">Top Referrers</div>
+						<ul class="text-sm">
+							{stats.top_referrers.map(referrer => (
+								<li key={referrer.referrer} class="flex justify-between">
+									<span class="truncate">{referrer.referrer ?? 'Direct'}</span>
+									<span>{referrer.clicks}</span>
+								</li>
+							))}
+						</ul>
+					</div>
+				)}
+			</Modal>
 			<Modal
 				open={isModalOpen}
 				title="Create Link"
@@ -208,16 +276,25 @@ const InternalLinkList = () => {
 					titles={[ 'Domain', 'Slug', 'Custom Slug', 'Original Link', 'Created At', 'Updated At' ]}
 					valueOrder={[ 'domain', 'slug', 'custom_slug', 'original_link', 'created_at', 'updated_at' ]}
 					action={(link: Link) => (
-						<button
-							type="button"
-							class="inline-flex items-center gap-x-2 text-sm font-semibold rounded-lg border border-transparent text-red-600 hover:text-red-800 focus:outline-none focus:text-red-800 disabled:opacity-50 disabled:pointer-events-none"
-							onClick={() => {
-								setDeleteLinkSlug(link.slug)
-								setIsDeleteModalOpen(true)
-							}}
-						>
-							Delete
-						</button>
+						<>
+							<button
+								type="button"
+								class="inline-flex items-center gap-x-2 text-sm font-semibold rounded-lg border border-transparent text-purple-600 hover:text-purple-800 focus:outline-none focus:text-purple-800 disabled:opacity-50 disabled:pointer-events-none mr-4"
+								onClick={() => onOpenStats(link)}
+							>
+								Stats
+							</button>
+							<button
+								type="button"
+								class="inline-flex items-center gap-x-2 text-sm font-semibold rounded-lg border border-transparent text-red-600 hover:text-red-800 focus:outline-none focus:text-red-800 disabled:opacity-50 disabled:pointer-events-none"
+								onClick={() => {
+									setDeleteLinkId(link.id)
+									setIsDeleteModalOpen(true)
+								}}
+							>
+								Delete
+							</button>
+						</>
 					)}
 				/>
 			</div>
@@ -3,9 +3,10 @@ import { RequireLogin } from '../../components/HoC/RequireLogin'
 import { Dashboard } from '../../components/Layout/Dashboard/Dashboard'
 import { LoginContext } from '../../context/LoginContext'
 import { Modal } from '../../components/Modal'
+import { TwoFactorSettings } from '../../components/TwoFactorSettings'
 
 const InternalUserPage = () => {
-	const { changePassword, deleteAccount, isDeletingAccount, updateUser } = useContext(LoginContext)
+	const { user, changePassword, deleteAccount, isDeletingAccount, updateUser, resendVerification } = useContext(LoginContext)
 	const [ isDeletionModalOpen, setDeletionModalOpen ] = useState(false)
 
 	const [ formData, setFormData ] = useState({
@@ -70,6 +71,12 @@ const InternalUserPage = () => {
 						<div>
 							<h2 class="text-2xl font-semibold mb-4 text-gray-900 dark:text-gray-100">Personal information</h2>
 							<p class="text-gray-600 dark:text-gray-300">Use a permanent address where you can receive mail.</p>
+							{user && !user.verified_at && (
+								<div class="mt-4 p-4 bg-yellow-100 dark:bg-yellow-800 border border-yellow-300 dark:border-yellow-600 text-yellow-800 dark:text-yellow-200 rounded">
+									Your email address isn't verified yet.&nbsp;
+									<a href="#" class="font-medium underline" onClick={() => resendVerification()}>Resend verification email</a>
+								</div>
+							)}
 						</div>
 						{/* Right Column */}
 						<div>
@@ -176,6 +183,19 @@ const InternalUserPage = () => {
 
 					<div class="h-24" />
 
+					<div class="grid grid-cols-2 gap-8">
+						{/* Left Column */}
+						<div>
+							<h2 class="text-2xl font-semibold mb-4 text-gray-900 dark:text-gray-100">Two-factor authentication</h2>
+							<p class="text-gray-600 dark:text-gray-300">Require a code from an authenticator app when signing in.</p>
+						</div>
+
+						{/* Right Column */}
+						<TwoFactorSettings />
+					</div>
+
+					<div class="h-24" />
+
 					<div class="grid grid-cols-2 gap-8">
 						{/* Left Column */}
 						<div>
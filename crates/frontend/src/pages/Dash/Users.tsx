@@ -0,0 +1,274 @@
+import { useContext, useState } from 'preact/hooks'
+import { RequirePermission } from '../../components/HoC/RequirePermission'
+import { Dashboard } from '../../components/Layout/Dashboard/Dashboard'
+import { PaginatedTable } from '../../components/PaginatedTable'
+import { Modal } from '../../components/Modal'
+import { ManagedUser, PasswordResetLink, UserManagementContext } from '../../context/UserManagementContext'
+import { LoginContext } from '../../context/LoginContext'
+
+const ADMIN_ROLE = 'admin'
+
+const statusOf = (user: ManagedUser) => {
+	if (user.deleted_at) {
+		return 'Deleted'
+	}
+
+	if (user.suspended) {
+		return user.suspended_until ? `Suspended until ${new Date(user.suspended_until + 'Z').toLocaleString()}` : 'Banned'
+	}
+
+	return user.verified_at ? 'Active' : 'Unverified'
+}
+
+const InternalUsers = () => {
+	const {
+		currentPage, getUsers, perPage, setCurrentPage, setPerPage, items, totalCount, search, setSearch,
+		setAdmin, suspendUser, unsuspendUser, verifyEmail, resetPassword, deleteUser, restoreUser,
+	} = useContext(UserManagementContext)
+	const { user: currentUser } = useContext(LoginContext)
+
+	const [ suspendItem, setSuspendItem ] = useState<ManagedUser>(null)
+	const [ suspendUntil, setSuspendUntil ] = useState('')
+	const [ suspendReason, setSuspendReason ] = useState('')
+
+	const [ deleteItem, setDeleteItem ] = useState<ManagedUser>(null)
+	const [ resetLink, setResetLink ] = useState<PasswordResetLink>(null)
+
+	const totalPages = Math.max(Math.ceil(totalCount / perPage), 1)
+
+	const onCloseSuspendModal = () => {
+		setSuspendItem(null)
+		setSuspendUntil('')
+		setSuspendReason('')
+	}
+
+	// Without an end date the suspension is a ban
+	const onSuspend = async () => {
+		await suspendUser(suspendItem.id, suspendUntil ? new Date(suspendUntil) : null, suspendReason)
+		onCloseSuspendModal()
+	}
+
+	const onDelete = async () => {
+		await deleteUser(deleteItem.id)
+		setDeleteItem(null)
+	}
+
+	const onResetPassword = async (item: ManagedUser) => {
+		setResetLink(await resetPassword(item.id))
+	}
+
+	if (!items) {
+		getUsers()
+		return (
+			<Dashboard>
+				Please wait, loading.
+			</Dashboard>
+		)
+	}
+
+	const rows = items.map(item => ({
+		...item,
+		status: statusOf(item),
+		admin: item.roles.includes(ADMIN_ROLE),
+	}))
+
+	return (
+		<Dashboard>
+			<Modal
+				open={!!deleteItem}
+				title="Delete User?"
+				onClickOutside={() => setDeleteItem(null)}
+				onClose={() => setDeleteItem(null)}
+				actionButton={(
+					<>
+						<button
+							onClick={() => setDeleteItem(null)}
+							class="bg-gray-400 text-white px-4 py-2 rounded-md mx-4"
+						>
+							Cancel
+						</button>
+
+						<button
+							onClick={onDelete}
+							class="bg-red-500 text-white px-4 py-2 rounded-md"
+						>
+							Delete
+						</button>
+					</>
+				)}
+			>
+				{deleteItem?.username} will be signed out and won't be able to sign in until restored.
+			</Modal>
+
+			<Modal
+				open={!!suspendItem}
+				title={`Suspend ${suspendItem?.username ?? ''}`}
+				onClose={onCloseSuspendModal}
+				actionButton={(
+					<button
+						onClick={onSuspend}
+						class="bg-red-500 text-white px-4 py-2 rounded-md"
+					>
+						{suspendUntil ? 'Suspend' : 'Ban'}
+					</button>
+				)}
+			>
+				<form
+					onSubmit={(e) => {
+						e.preventDefault()
+						onSuspend()
+					}}
+				>
+					<div>
+						<label for="until" class="block text-sm font-medium text-gray-700 dark:text-gray-300">Until</label>
+						<input
+							type="datetime-local"
+							id="until"
+							class="mt-1 block w-full px-4 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-300 rounded-md shadow-sm focus:ring-indigo-500 focus:border-indigo-500 sm:text-sm"
+							value={suspendUntil}
+							onChange={e => setSuspendUntil(e.currentTarget.value)}
+						/>
+						<p class="text-gray-500 mt-1 text-xs">Leave empty to ban the user.</p>
+					</div>
+
+					<div class="pt-4">
+						<label for="reason" class="block text-sm font-medium text-gray-700 dark:text-gray-300">Reason</label>
+						<input
+							type="text"
+							id="reason"
+							maxLength={512}
+							class="mt-1 block w-full px-4 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-300 rounded-md shadow-sm focus:ring-indigo-500 focus:border-indigo-500 sm:text-sm"
+							placeholder="Shown to the user"
+							value={suspendReason}
+							onChange={e => setSuspendReason(e.currentTarget.value)}
+						/>
+					</div>
+				</form>
+			</Modal>
+
+			<Modal
+				open={!!resetLink}
+				title="Password reset link"
+				onClose={() => setResetLink(null)}
+			>
+				<p class="mb-2">
+					{resetLink?.emailed
+						? 'The link has been emailed to the user. You can also pass it on yourself:'
+						: 'Email is not configured, pass this link on to the user:'}
+				</p>
+				<input
+					readOnly
+					type="text"
+					class="block w-full px-4 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-300 rounded-md sm:text-sm"
+					value={resetLink?.reset_url}
+					onFocus={e => e.currentTarget.select()}
+				/>
+				<p class="text-gray-500 mt-2 text-xs">
+					Expires {resetLink && new Date(resetLink.expires_at + 'Z').toLocaleString()}.
+				</p>
+			</Modal>
+
+			<div class="flex flex-col p-2 ">
+				<div class="flex justify-between">
+					<div>
+						<h1 class="text-2xl font-semibold mb-2">Users</h1>
+						<p class="text-gray-500 mb-6 text-sm">Everyone with an account on this instance.</p>
+					</div>
+					<div class="mt-4">
+						<input
+							type="search"
+							class="block px-4 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-300 rounded-md shadow-sm sm:text-sm"
+							placeholder="Search by username or email"
+							value={search}
+							onChange={e => {
+								setCurrentPage(1)
+								setSearch(e.currentTarget.value)
+							}}
+						/>
+					</div>
+				</div>
+				<PaginatedTable
+					perPage={perPage}
+					totalPages={totalPages}
+					setPerPage={setPerPage}
+					setCurrentPage={setCurrentPage}
+					currentPage={currentPage}
+					data={rows}
+					titles={[ 'Username', 'Email', 'Admin', 'Status', 'Created at' ]}
+					valueOrder={[ 'username', 'email', 'admin', 'status', 'created_at' ]}
+					action={(item: ManagedUser) => {
+						const isSelf = item.id === currentUser?.id
+						const isAdmin = item.roles.includes(ADMIN_ROLE)
+
+						return (
+							<div class="flex justify-around gap-x-3">
+								<button
+									type="button"
+									class="text-sm font-semibold text-blue-600 hover:text-blue-800 dark:text-blue-400 dark:hover:text-blue-600 disabled:opacity-50 disabled:pointer-events-none"
+									onClick={() => setAdmin(item.id, !isAdmin)}
+									disabled={isSelf}
+								>
+									{isAdmin ? 'Demote' : 'Promote'}
+								</button>
+								{!item.verified_at && (
+									<button
+										type="button"
+										class="text-sm font-semibold text-blue-600 hover:text-blue-800 dark:text-blue-400 dark:hover:text-blue-600"
+										onClick={() => verifyEmail(item.id)}
+									>
+										Verify email
+									</button>
+								)}
+								<button
+									type="button"
+									class="text-sm font-semibold text-blue-600 hover:text-blue-800 dark:text-blue-400 dark:hover:text-blue-600"
+									onClick={() => onResetPassword(item)}
+								>
+									Reset password
+								</button>
+								{item.suspended ? (
+									<button
+										type="button"
+										class="text-sm font-semibold text-blue-600 hover:text-blue-800 dark:text-blue-400 dark:hover:text-blue-600"
+										onClick={() => unsuspendUser(item.id)}
+									>
+										Unsuspend
+									</button>
+								) : (
+									<button
+										type="button"
+										class="text-sm font-semibold text-red-600 hover:text-red-800 disabled:opacity-50 disabled:pointer-events-none"
+										onClick={() => setSuspendItem(item)}
+										disabled={isSelf}
+									>
+										Suspend
+									</button>
+								)}
+								{item.deleted_at ? (
+									<button
+										type="button"
+										class="text-sm font-semibold text-blue-600 hover:text-blue-800 dark:text-blue-400 dark:hover:text-blue-600"
+										onClick={() => restoreUser(item.id)}
+									>
+										Restore
+									</button>
+								) : (
+									<button
+										type="button"
+										class="text-sm font-semibold text-red-600 hover:text-red-800 disabled:opacity-50 disabled:pointer-events-none"
+										onClick={() => setDeleteItem(item)}
+										disabled={isSelf}
+									>
+										Delete
+									</button>
+								)}
+							</div>
+						)
+					}}
+				/>
+			</div>
+		</Dashboard>
+	)
+}
+
+export const UsersPage = RequirePermission(InternalUsers, 'user.manage')
@@ -3,6 +3,7 @@ import './style.css'
 import { Input } from '../../components/Input'
 import { ConfigContext } from '../../context/ConfigContext'
 import { DomainSelector } from '../../components/DomainSelector'
+import { authedFetch } from '../../context/contextUtils'
 
 
 export const Home = () => {
@@ -17,7 +18,7 @@ export const Home = () => {
 	const onSubmit = async () => {
 		// Todo: Notification about having shortened
 		
-		const res = await fetch('/api/link/shorten', {
+		const res = await authedFetch('/api/link/shorten', {
 			method: 'POST',
 			headers: {
 				'Content-Type': 'application/json',
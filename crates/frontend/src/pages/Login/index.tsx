@@ -1,17 +1,25 @@
-import { useContext, useRef, useState } from 'preact/hooks'
+import { useContext, useEffect, useRef, useState } from 'preact/hooks'
 import { LoginContext } from '../../context/LoginContext'
 import { useLocation } from 'preact-iso'
 import { ConfigContext } from '../../context/ConfigContext'
 
 export const LoginPage = () => {
-	const { allowRegistering } = useContext(ConfigContext)
-	const { loginUser, user, loginRedirectMessage, error } = useContext(LoginContext)
-	const { route } = useLocation()
+	const { allowRegistering, oidcEnabled, oidcDisplayName } = useContext(ConfigContext)
+	const { loginUser, submitTwoFactorCode, twoFactorToken, setTwoFactorToken, user, loginRedirectMessage, error } = useContext(LoginContext)
+	const { route, query } = useLocation()
 
 	const loginFormRef = useRef<HTMLFormElement>(null)
 
 	const [ email, setEmail ] = useState('')
 	const [ password, setPassword ] = useState('')
+	const [ code, setCode ] = useState('')
+	// Set when single sign-on sends the user back here
+	const [ ssoError ] = useState<string | null>(query.error ?? null)
+
+	useEffect(() => {
+		if (query.two_factor_token) setTwoFactorToken(query.two_factor_token)
+		if (query.two_factor_token || query.error) route('/dash/login', true)
+	}, [])
 	
 
 	const login = async () => {
@@ -41,9 +49,9 @@ export const LoginPage = () => {
 			)}
 
 			{/* Message Box */}
-			{error && (
+			{(error || ssoError) && (
 				<div class="mb-6 p-4 bg-red-100 dark:bg-red-800 border border-red-300 dark:border-red-600 text-red-800 dark:text-red-200 rounded w-full max-w-md">
-					{error}
+					{error ?? ssoError}
 				</div>
 			)}
 
@@ -57,6 +65,25 @@ export const LoginPage = () => {
 
 				<h2 class="text-2xl font-bold text-center text-gray-800 dark:text-white mb-6">Sign in to your account</h2>
 
+				{twoFactorToken ? (
+					<form
+						class="space-y-6"
+						onSubmit={(e) => {
+							e.preventDefault()
+							submitTwoFactorCode(code)
+						}}
+					>
+						<div>
+							<label for="code" class="block text-sm font-medium text-gray-700 dark:text-gray-300">Authentication code</label>
+							<input required autoFocus autocomplete="one-time-code" id="code" class="mt-1 block w-full px-4 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-300 rounded-md shadow-sm focus:ring-indigo-500 focus:border-indigo-500 sm:text-sm" placeholder="123456" value={code} onChange={e => setCode(e.currentTarget.value)} />
+							<p class="mt-2 text-xs text-gray-500 dark:text-gray-400">Enter the code from your authenticator app, or one of your recovery codes.</p>
+						</div>
+
+						<div>
+							<button type="submit" class="w-full flex justify-center py-2 px-4 border border-transparent rounded-md shadow-sm text-sm font-medium text-white bg-indigo-600 dark:bg-indigo-500 hover:bg-indigo-700 dark:hover:bg-indigo-400 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500">Verify</button>
+						</div>
+					</form>
+				) : (
 				<form
 					class="space-y-6"
 					onSubmit={(e) => {
@@ -82,7 +109,7 @@ export const LoginPage = () => {
 						</div>
 
 						<div class="text-sm">
-							<a href="#" class="font-medium text-indigo-600 dark:text-indigo-400 hover:text-indigo-500 dark:hover:text-indigo-300">Forgot your password?</a>
+							<a href="#" class="font-medium text-indigo-600 dark:text-indigo-400 hover:text-indigo-500 dark:hover:text-indigo-300" onClick={() => route('/dash/forgot-password')}>Forgot your password?</a>
 						</div>
 					</div>
 
@@ -90,6 +117,13 @@ export const LoginPage = () => {
 						<button type="submit" class="w-full flex justify-center py-2 px-4 border border-transparent rounded-md shadow-sm text-sm font-medium text-white bg-indigo-600 dark:bg-indigo-500 hover:bg-indigo-700 dark:hover:bg-indigo-400 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500">Sign in</button>
 					</div>
 
+					{oidcEnabled && (
+						<div>
+							{/* A full page load, the server redirects to the identity provider */}
+							<a href="/api/user/oidc/login" class="w-full flex justify-center py-2 px-4 border border-gray-300 dark:border-gray-600 rounded-md shadow-sm text-sm font-medium text-gray-700 dark:text-gray-300 bg-white dark:bg-gray-700 hover:bg-gray-50 dark:hover:bg-gray-600 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500">Sign in with {oidcDisplayName}</a>
+						</div>
+					)}
+
 					{allowRegistering && (
 						<p class="text-gray-500 text-xs text-center">
           Or,&nbsp;
@@ -99,6 +133,7 @@ export const LoginPage = () => {
 						</p>
 					)}
 				</form>
+				)}
 			</div>
 		</div>
 	)
@@ -0,0 +1,124 @@
+import { useLocation, useRoute } from 'preact-iso'
+import { useState } from 'preact/hooks'
+import { JSX } from 'preact/jsx-runtime'
+
+const inputClass = 'mt-1 block w-full px-4 py-2 border border-gray-300 dark:border-gray-600 bg-white dark:bg-gray-700 text-gray-900 dark:text-gray-300 rounded-md shadow-sm focus:ring-indigo-500 focus:border-indigo-500 sm:text-sm'
+const buttonClass = 'w-full flex justify-center py-2 px-4 border border-transparent rounded-md shadow-sm text-sm font-medium text-white bg-indigo-600 dark:bg-indigo-500 hover:bg-indigo-700 dark:hover:bg-indigo-400 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-indigo-500'
+
+const MessageBox = ({ message, error }: { message: string, error: boolean }) => (
+	<div class={error
+		? 'mb-6 p-4 bg-red-100 dark:bg-red-800 border border-red-300 dark:border-red-600 text-red-800 dark:text-red-200 rounded w-full max-w-md'
+		: 'mb-6 p-4 bg-green-100 dark:bg-green-800 border border-green-300 dark:border-green-600 text-green-800 dark:text-green-200 rounded w-full max-w-md'}
+	>
+		{message}
+	</div>
+)
+
+const postJson = async (url: string, body: Record<string, string>): Promise<{ ok: boolean, message: string }> => {
+	const response = await fetch(url, {
+		method: 'POST',
+		body: JSON.stringify(body),
+		headers: {
+			'Content-Type': 'application/json',
+		},
+	})
+
+	const data = await response.json()
+
+	return { ok: response.ok, message: data.message }
+}
+
+export const ForgotPasswordPage = () => {
+	const { route } = useLocation()
+
+	const [ email, setEmail ] = useState('')
+	const [ result, setResult ] = useState<{ ok: boolean, message: string }>(null)
+
+	const onSubmit = async (e: JSX.TargetedSubmitEvent<HTMLFormElement>) => {
+		e.preventDefault()
+
+		setResult(await postJson('/api/user/password/forgot', { email }))
+	}
+
+	return (
+		<div class="bg-gray-50 dark:bg-gray-900 flex justify-center items-center h-screen flex-col">
+			{result && <MessageBox message={result.message} error={!result.ok} />}
+
+			<div class="w-full max-w-md bg-white dark:bg-gray-800 rounded-lg shadow-md p-8">
+				<h2 class="text-2xl font-bold text-center text-gray-800 dark:text-white mb-6">Forgot your password?</h2>
+
+				<form class="space-y-6" onSubmit={onSubmit}>
+					<div>
+						<label for="email" class="block text-sm font-medium text-gray-700 dark:text-gray-300">Email address</label>
+						<input required type="email" id="email" class={inputClass} placeholder="you@example.com" value={email} onChange={e => setEmail(e.currentTarget.value)} />
+					</div>
+
+					<div>
+						<button type="submit" class={buttonClass}>Send reset link</button>
+					</div>
+
+					<p class="text-gray-500 text-xs text-center">
+						<a href="#" class="text-blue-600 font-sm hover:underline" onClick={() => route('/dash/login')}>
+							Back to sign in
+						</a>
+					</p>
+				</form>
+			</div>
+		</div>
+	)
+}
+
+export const ResetPasswordPage = () => {
+	const { route } = useLocation()
+	const { params } = useRoute()
+
+	const [ password, setPassword ] = useState('')
+	const [ confirmPassword, setConfirmPassword ] = useState('')
+	const [ result, setResult ] = useState<{ ok: boolean, message: string }>(null)
+
+	const onSubmit = async (e: JSX.TargetedSubmitEvent<HTMLFormElement>) => {
+		e.preventDefault()
+
+		if (password !== confirmPassword) {
+			setResult({ ok: false, message: 'Passwords do not match.' })
+			return
+		}
+
+		const response = await postJson(`/api/user/password/reset/${params.token}`, {
+			password,
+			confirm_password: confirmPassword,
+		})
+
+		setResult(response)
+
+		if (response.ok) {
+			route('/dash/login')
+		}
+	}
+
+	return (
+		<div class="bg-gray-50 dark:bg-gray-900 flex justify-center items-center h-screen flex-col">
+			{result && <MessageBox message={result.message} error={!result.ok} />}
+
+			<div class="w-full max-w-md bg-white dark:bg-gray-800 rounded-lg shadow-md p-8">
+				<h2 class="text-2xl font-bold text-center text-gray-800 dark:text-white mb-6">Choose a new password</h2>
+
+				<form class="space-y-6" onSubmit={onSubmit}>
+					<div>
+						<label for="password" class="block text-sm font-medium text-gray-700 dark:text-gray-300">New password</label>
+						<input required type="password" id="password" class={inputClass} placeholder="********" value={password} onChange={e => setPassword(e.currentTarget.value)} />
+					</div>
+
+					<div>
+						<label for="confirm_password" class="block text-sm font-medium text-gray-700 dark:text-gray-300">Confirm new password</label>
+						<input required type="password" id="confirm_password" class={inputClass} placeholder="********" value={confirmPassword} onChange={e => setConfirmPassword(e.currentTarget.value)} />
+					</div>
+
+					<div>
+						<button type="submit" class={buttonClass}>Reset password</button>
+					</div>
+				</form>
+			</div>
+		</div>
+	)
+}
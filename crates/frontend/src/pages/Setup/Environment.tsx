@@ -23,6 +23,7 @@ export const EnvironmentSetup = () => {
 		security: {
 			jwt_secret: '',
 			min_password_strength: 3,
+			ip_hash_salt: generateSecret(), // Only hashes visitor IPs in link stats, so there's no need to show it
 		},
 		smtp: {
 			enabled: true,
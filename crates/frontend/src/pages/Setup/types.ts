@@ -14,6 +14,7 @@ interface AppConfig {
 interface SecurityConfig {
 	jwt_secret: string,
 	min_password_strength: number,
+	ip_hash_salt: string,
 }
 
 interface SmtpConfig {
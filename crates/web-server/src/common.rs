@@ -1,24 +1,61 @@
 use axum::{http::StatusCode, Json};
 use axum_extra::extract::CookieJar;
+use db::{
+	repository::{self, Repositories, SharedStore},
+	RunError,
+};
 use serde::Serialize;
 
 #[derive(Serialize, Debug)]
 pub struct GenericMessage {
 	message: String,
+	/// Machine readable reason, for errors the client may want to handle specifically
+	#[serde(skip_serializing_if = "Option::is_none")]
+	reason: Option<String>,
 }
 
 impl GenericMessage {
 	pub fn new(message: &str) -> Json<Self> {
 		Json(Self {
 			message: message.to_string(),
+			reason: None,
 		})
 	}
 
 	pub fn from_string(message: String) -> Json<Self> {
-		Json(Self { message: message })
+		Json(Self { message, reason: None })
+	}
+
+	pub fn with_reason(message: String, reason: &str) -> Json<Self> {
+		Json(Self {
+			message,
+			reason: Some(reason.to_string()),
+		})
 	}
 }
 
+pub type APIError = (StatusCode, Json<GenericMessage>);
 pub type APIResponse<T> = Result<(StatusCode, Json<T>), (StatusCode, Json<GenericMessage>)>;
 pub type APIResultWithError<T, T2> = Result<(StatusCode, Json<T>), (StatusCode, Json<T2>)>;
 pub type CookiedAPIResponse<T> = Result<(CookieJar, Json<T>), (StatusCode, Json<GenericMessage>)>;
+
+/// Runs a handler's work on the repositories through [`repository::run`], off the async workers. Fails the request
+/// if the work didn't get to run.
+pub async fn with_repos<T, F>(store: &SharedStore, work: F) -> Result<T, APIError>
+where
+	F: FnOnce(&mut dyn Repositories) -> Result<T, APIError> + Send + 'static,
+	T: Send + 'static,
+{
+	repository::run(store, work).await.unwrap_or_else(|e| Err(run_error(e)))
+}
+
+fn run_error(e: RunError) -> APIError {
+	log::error!("{}", e);
+
+	match e {
+		RunError::Pool(_) => {
+			(StatusCode::SERVICE_UNAVAILABLE, GenericMessage::new("The database is busy, try again later."))
+		}
+		_ => (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Something went wrong.")),
+	}
+}
@@ -1,70 +1,458 @@
-use std::str::FromStr;
+use std::{marker::PhantomData, str::FromStr};
 
-use axum::{async_trait, extract::FromRequestParts, Extension};
+use axum::{
+	async_trait,
+	extract::FromRequestParts,
+	http::{header, request::Parts, HeaderMap, StatusCode},
+	Extension,
+};
 use axum_extra::extract::{cookie::Cookie, CookieJar};
-use db::{models::User, DbPool};
+use chrono::{Duration, Utc};
+use db::{
+	models::User,
+	repository::{Repositories, SharedStore},
+	DbError,
+};
 
-use crate::{config::Config, util::jwt::decode_user_token};
+use crate::{
+	common::{with_repos, APIError, GenericMessage},
+	config::Config,
+	types::{ApiKeyScope, Permission, PermissionMarker},
+	util::{
+		api_key::hash_api_key,
+		jwt::{decode_user_token, UserToken},
+	},
+};
 
+/// How often the last seen time of a session gets updated
+const SESSION_SEEN_INTERVAL_MINUTES: i64 = 5;
+
+/// The logged in user, from the `auth_token` cookie or an `Authorization: Bearer <key>` API key
 #[derive(Debug, Clone)]
 pub struct AuthedUser(pub Option<User>);
 
+/// Like [`AuthedUser`], but rejects users without a verified email when `app.require_verified_email` is set.
+/// Anonymous requests are let through, handlers decide whether they need a user.
+#[derive(Debug, Clone)]
+pub struct VerifiedUser(pub Option<User>);
+
+/// What the logged in user is allowed to do, empty for anonymous requests
+#[derive(Debug, Clone)]
+pub struct Permissions(pub Vec<Permission>);
+
+impl Permissions {
+	pub fn has(&self, permission: Permission) -> bool {
+		self.0.contains(&permission)
+	}
+}
+
+/// The logged in user, if they have the permission `P`. Everyone else is rejected.
+pub struct RequirePermission<P: PermissionMarker>(pub User, pub PhantomData<P>);
+
+/// Gets the API key from the `Authorization` header
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+	headers
+		.get(header::AUTHORIZATION)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "))
+		.map(str::trim)
+}
+
+/// Authenticates an API key. Unlike the cookie, a bad key is rejected instead of treated as anonymous,
+/// so scripts don't silently act without their user.
+fn authenticate_api_key(
+	key: &str,
+	required_scope: Option<ApiKeyScope>,
+	repos: &mut dyn Repositories,
+) -> Result<User, APIError> {
+	let unauthorized = || (StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid API key."));
+
+	let Some(required_scope) = required_scope else {
+		return Err((StatusCode::FORBIDDEN, GenericMessage::new("API keys can't be used for this endpoint.")));
+	};
+
+	let (api_key, user) = repos
+		.tokens()
+		.get_api_key_by_token_hash(&hash_api_key(key))
+		.map_err(|_| unauthorized())?;
+
+	if api_key.is_expired() || user.deleted_at.is_some() {
+		return Err(unauthorized());
+	}
+
+	check_not_suspended(&user)?;
+
+	if !ApiKeyScope::parse_all(&api_key.scopes()).contains(&required_scope) {
+		return Err((
+			StatusCode::FORBIDDEN,
+			GenericMessage::from_string(format!("API key is missing the {} scope.", required_scope)),
+		));
+	}
+
+	if let Err(e) = repos.tokens().touch_api_key(&api_key) {
+		log::warn!("Failed to update API key last use: {:#?}", e);
+	}
+
+	Ok(user)
+}
+
+/// Gets the user of a session, as long as the session hasn't been revoked and the user still exists
+fn session_user(token: &UserToken, repos: &mut dyn Repositories) -> Option<User> {
+	let session = match repos.tokens().get_session(token.session_id) {
+		Ok(session) if session.is_active() && session.user_id == token.user_id => session,
+		_ => return None,
+	};
+
+	if Utc::now().naive_utc() - session.last_seen_at > Duration::minutes(SESSION_SEEN_INTERVAL_MINUTES) {
+		if let Err(e) = repos.tokens().touch_session(&session) {
+			log::warn!("Failed to update session last seen: {:#?}", e);
+		}
+	}
+
+	let user = repos.users().get_by_id(token.user_id).ok()??;
+
+	if user.deleted_at.is_some() {
+		return None;
+	}
+
+	Some(user)
+}
+
+/// Rejects suspended users, telling them why and until when
+pub fn check_not_suspended(user: &User) -> Result<(), APIError> {
+	if !user.is_suspended() {
+		return Ok(());
+	}
+
+	let mut message = match user.suspended_until {
+		Some(until) => format!("Your account is suspended until {} UTC.", until.format("%Y-%m-%d %H:%M")),
+		None => "Your account has been banned.".to_string(),
+	};
+
+	if let Some(reason) = &user.suspension_reason {
+		message = format!("{} Reason: {}", message, reason);
+	}
+
+	Err((StatusCode::FORBIDDEN, GenericMessage::with_reason(message, "account_suspended")))
+}
+
+/// Gets what a user is allowed to do through their roles
+pub fn granted_permissions(user_id: i32, repos: &mut dyn Repositories) -> Result<Vec<Permission>, DbError> {
+	repos
+		.users()
+		.get_permissions(user_id)
+		.map(|permissions| Permission::parse_all(&permissions))
+}
+
+/// Withholds admin permissions from users without 2FA when `security.require_admin_two_factor` is set,
+/// so they can still log in and enable it
+pub fn effective_permissions(granted: Vec<Permission>, user: &User, config: &Config) -> Vec<Permission> {
+	let required = config
+		.security
+		.as_ref()
+		.is_some_and(|security| security.require_admin_two_factor);
+
+	if !required || user.has_two_factor() {
+		return granted;
+	}
+
+	granted
+		.into_iter()
+		.filter(|permission| !permission.is_privileged())
+		.collect()
+}
+
 // TODO: Clean this up?
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthedUser
 where
 	S: Send + Sync,
 {
-	type Rejection = ();
-
-	async fn from_request_parts(parts: &mut axum::http::request::Parts, state: &S) -> Result<Self, Self::Rejection> {
-		// Create a CookieJar from the cookies
-		let mut cookie_jar = CookieJar::new();
-
-		let Extension(config): Extension<Config> = Extension::from_request_parts(parts, state).await.map_err(|_| ())?;
-		let Extension(pool): Extension<DbPool> = Extension::from_request_parts(parts, state).await.map_err(|_| ())?;
-
-		// Extract the Cookie header
-		if let Some(cookie_header) = parts.headers.get(axum::http::header::COOKIE) {
-			// Convert the header value to a string
-			if let Ok(cookie_str) = cookie_header.to_str() {
-				for cookie in cookie_str.split(';') {
-					if let Ok(cookie) = Cookie::from_str(cookie.trim()) {
-						// Add the cookie to the CookieJar
-						cookie_jar = cookie_jar.add(cookie);
-					}
-				}
+	type Rejection = APIError;
 
-				// Access a specific cookie if needed
-				if let Some(cookie) = cookie_jar.get("auth_token") {
-					let user_id = match decode_user_token(&cookie.value().to_string(), config.security.unwrap().jwt_secret.as_bytes()) {
-						Some(user_id) => user_id,
-						None => return Ok(AuthedUser(None)),
-					};
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		// Other extractors build on this one, so the user is only looked up once per request
+		if let Some(authed_user) = parts.extensions.get::<AuthedUser>() {
+			return Ok(authed_user.clone());
+		}
 
-					let conn = &mut pool.get();
+		let authed_user = authenticate_request(parts, state).await?;
+		parts.extensions.insert(authed_user.clone());
 
-					let conn = match conn {
-						Ok(conn) => conn,
-						Err(_) => return Ok(AuthedUser(None)),
-					};
+		Ok(authed_user)
+	}
+}
 
-					let users = match User::get_by_id(&user_id, conn) {
-						Ok(users) => users,
-						Err(_) => return Ok(AuthedUser(None)),
-					};
+async fn authenticate_request<S: Send + Sync>(parts: &mut Parts, state: &S) -> Result<AuthedUser, APIError> {
+	let internal_error = |_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error"));
 
-					if users.len() == 0 {
-						return Ok(AuthedUser(None));
-					}
+	// Create a CookieJar from the cookies
+	let mut cookie_jar = CookieJar::new();
+
+	let Extension(config): Extension<Config> = Extension::from_request_parts(parts, state)
+		.await
+		.map_err(internal_error)?;
+	let Extension(store): Extension<SharedStore> = Extension::from_request_parts(parts, state)
+		.await
+		.map_err(internal_error)?;
+
+	if let Some(key) = bearer_token(&parts.headers) {
+		let required_scope = parts.extensions.get::<ApiKeyScope>().copied();
 
-					let user = users.first().unwrap().to_owned();
+		let key = key.to_string();
 
-					return Ok(AuthedUser(Some(user)));
+		return with_repos(&store, move |repos| authenticate_api_key(&key, required_scope, repos))
+			.await
+			.map(|user| AuthedUser(Some(user)));
+	}
+
+	// Extract the Cookie header
+	if let Some(cookie_header) = parts.headers.get(axum::http::header::COOKIE) {
+		// Convert the header value to a string
+		if let Ok(cookie_str) = cookie_header.to_str() {
+			for cookie in cookie_str.split(';') {
+				if let Ok(cookie) = Cookie::from_str(cookie.trim()) {
+					// Add the cookie to the CookieJar
+					cookie_jar = cookie_jar.add(cookie);
 				}
 			}
+
+			// Access a specific cookie if needed
+			if let Some(cookie) = cookie_jar.get("auth_token") {
+				let token = match decode_user_token(
+					&cookie.value().to_string(),
+					config.security.as_ref().unwrap().jwt_secret.as_bytes(),
+				) {
+					Some(token) => token,
+					None => return Ok(AuthedUser(None)),
+				};
+
+				let user = match db::repository::run(&store, move |repos| session_user(&token, repos)).await {
+					Ok(user) => user,
+					Err(e) => {
+						log::error!("{}", e);
+						None
+					}
+				};
+
+				let Some(user) = user else {
+					return Ok(AuthedUser(None));
+				};
+
+				check_not_suspended(&user)?;
+
+				return Ok(AuthedUser(Some(user)));
+			}
+		}
+	}
+
+	Ok(AuthedUser(None))
+}
+
+/// Checks that the user verified their email, if the config requires it
+fn check_verified(user: &User, config: &Config) -> Result<(), APIError> {
+	let required = config.app.as_ref().is_some_and(|app| app.require_verified_email);
+
+	if required && user.verified_at.is_none() {
+		return Err((
+			StatusCode::FORBIDDEN,
+			GenericMessage::with_reason("Please verify your email first.".to_string(), "email_not_verified"),
+		));
+	}
+
+	Ok(())
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for VerifiedUser
+where
+	S: Send + Sync,
+{
+	type Rejection = APIError;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		let AuthedUser(user) = AuthedUser::from_request_parts(parts, state).await?;
+
+		let Extension(config): Extension<Config> = Extension::from_request_parts(parts, state)
+			.await
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+
+		if let Some(user) = &user {
+			check_verified(user, &config)?;
+		}
+
+		Ok(VerifiedUser(user))
+	}
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Permissions
+where
+	S: Send + Sync,
+{
+	type Rejection = APIError;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		let internal_error = || (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error"));
+
+		if let Some(permissions) = parts.extensions.get::<Permissions>() {
+			return Ok(permissions.clone());
+		}
+
+		let AuthedUser(user) = AuthedUser::from_request_parts(parts, state).await?;
+
+		let Some(user) = user else {
+			return Ok(Permissions(Vec::new()));
+		};
+
+		let Extension(config): Extension<Config> = Extension::from_request_parts(parts, state)
+			.await
+			.map_err(|_| internal_error())?;
+		let Extension(store): Extension<SharedStore> = Extension::from_request_parts(parts, state)
+			.await
+			.map_err(|_| internal_error())?;
+
+		let user_id = user.id;
+		let granted =
+			with_repos(&store, move |repos| granted_permissions(user_id, repos).map_err(|_| internal_error())).await?;
+
+		let permissions = Permissions(effective_permissions(granted, &user, &config));
+		parts.extensions.insert(permissions.clone());
+
+		Ok(permissions)
+	}
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+	S: Send + Sync,
+	P: PermissionMarker,
+{
+	type Rejection = APIError;
+
+	async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+		let AuthedUser(user) = AuthedUser::from_request_parts(parts, state).await?;
+
+		let user =
+			user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))?;
+
+		let permissions = Permissions::from_request_parts(parts, state).await?;
+
+		if !permissions.has(P::PERMISSION) {
+			return Err((
+				StatusCode::FORBIDDEN,
+				GenericMessage::with_reason(
+					format!("You need the {} permission to perform this action.", P::PERMISSION),
+					"missing_permission",
+				),
+			));
+		}
+
+		Ok(RequirePermission(user, PhantomData))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::config::AppConfig;
+	use chrono::NaiveDateTime;
+
+	fn user(verified_at: Option<NaiveDateTime>) -> User {
+		User {
+			id: 1,
+			username: "user".to_string(),
+			email: "user@example.com".to_string(),
+			password_hash: "hash".to_string(),
+			verified_at,
+			created_at: NaiveDateTime::default(),
+			deleted_at: None,
+			totp_secret: None,
+			totp_enabled_at: None,
+			totp_last_step: None,
+			totp_attempts: 0,
+			totp_locked_until: None,
+			suspended_at: None,
+			suspended_until: None,
+			suspension_reason: None,
 		}
+	}
+
+	fn config(require_verified_email: bool) -> Config {
+		let app: AppConfig = toml::from_str(&format!(
+			r#"
+			shortened_link_length = 8
+			allow_anonymous_shorten = true
+			allow_registering = true
+			base_url = ""
+			enable_email_verification = true
+			email_verification_ttl = "1h"
+			require_verified_email = {}
+			"#,
+			require_verified_email
+		))
+		.unwrap();
+
+		Config {
+			app: Some(app),
+			..Config::new()
+		}
+	}
+
+	#[test]
+	fn test_check_verified() {
+		let unverified = user(None);
+		let verified = user(Some(NaiveDateTime::default()));
+
+		assert!(check_verified(&unverified, &config(false)).is_ok());
+		assert!(check_verified(&verified, &config(true)).is_ok());
+
+		let (status, _) = check_verified(&unverified, &config(true)).unwrap_err();
+		assert_eq!(status, StatusCode::FORBIDDEN);
+	}
+
+	#[test]
+	fn test_check_not_suspended() {
+		let mut suspended = user(None);
+		suspended.suspended_at = Some(Utc::now().naive_utc());
+		suspended.suspended_until = Some((Utc::now() + Duration::days(1)).naive_utc());
+		suspended.suspension_reason = Some("Spam".to_string());
+
+		let mut expired = suspended.clone();
+		expired.suspended_until = Some((Utc::now() - Duration::days(1)).naive_utc());
+
+		let mut banned = suspended.clone();
+		banned.suspended_until = None;
+
+		assert!(check_not_suspended(&user(None)).is_ok());
+		assert!(check_not_suspended(&expired).is_ok());
+
+		let (status, _) = check_not_suspended(&suspended).unwrap_err();
+		assert_eq!(status, StatusCode::FORBIDDEN);
+		assert!(check_not_suspended(&banned).is_err());
+	}
+
+	#[test]
+	fn test_effective_permissions() {
+		let mut config = config(false);
+		config.security = Some(
+			toml::from_str(
+				r#"
+				jwt_secret = "secret"
+				min_password_strength = 4
+				require_admin_two_factor = true
+				"#,
+			)
+			.unwrap(),
+		);
+
+		let granted = vec![Permission::LinkCreate, Permission::DomainManage];
+		let mut with_two_factor = user(None);
+		with_two_factor.totp_enabled_at = Some(NaiveDateTime::default());
 
-		Ok(AuthedUser(None))
+		assert_eq!(effective_permissions(granted.clone(), &user(None), &config), [Permission::LinkCreate]);
+		assert_eq!(effective_permissions(granted.clone(), &with_two_factor, &config), granted);
+		assert_eq!(effective_permissions(granted.clone(), &user(None), &Config::new()), granted);
 	}
 }
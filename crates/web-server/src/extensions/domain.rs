@@ -1,10 +1,13 @@
 use axum::{async_trait, extract::FromRequestParts, http::StatusCode, Extension};
-use db::{models::Domain, DbPool};
+use db::{models::Domain, repository::SharedStore};
 
-use crate::common::{APIResponse, GenericMessage};
+use crate::{
+	common::{APIResponse, GenericMessage},
+	services::redirect_cache::RedirectCache,
+};
 
 #[derive(Debug, Clone)]
-pub struct ExtractedDomain(pub String, pub i32);
+pub struct ExtractedDomain(pub String, pub Domain);
 
 #[async_trait]
 impl<S> FromRequestParts<S> for ExtractedDomain
@@ -14,9 +17,10 @@ where
 	type Rejection = APIResponse<GenericMessage>;
 
 	async fn from_request_parts(parts: &mut axum::http::request::Parts, state: &S) -> Result<Self, Self::Rejection> {
-		let Extension(pool): Extension<DbPool> = Extension::from_request_parts(parts, state).await.map_err(|_| {
-			return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to get database extension.")));
-		})?;
+		let Extension(store): Extension<SharedStore> =
+			Extension::from_request_parts(parts, state).await.map_err(|_| {
+				Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to get database extension.")))
+			})?;
 
 		let host = parts
 			.headers
@@ -29,14 +33,22 @@ where
 				return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to parse host header.")));
 			})?;
 
-		let conn = &mut pool.get().map_err(|_| {
-			return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to get connection.")));
-		})?;
+		// Routers without the cache look every host up
+		let cache = parts.extensions.get::<RedirectCache>().cloned().unwrap_or_else(RedirectCache::disabled);
+
+		let domain = cache
+			.get_domain(host, async {
+				let host = host.to_string();
 
-		let domain = Domain::get_by_domain(host.to_string(), conn).map_err(|_| {
-			return Err((StatusCode::NOT_FOUND, GenericMessage::new("Failed to find requested host.")));
-		})?;
+				db::repository::run(&store, move |repos| repos.domains().get_by_domain(&host))
+					.await
+					.map_err(|_| {
+						Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to get connection.")))
+					})?
+					.map_err(|_| Err((StatusCode::NOT_FOUND, GenericMessage::new("Failed to find requested host."))))
+			})
+			.await?;
 
-		Ok(ExtractedDomain(host.to_string(), domain.id))
+		Ok(ExtractedDomain(host.to_string(), domain))
 	}
 }
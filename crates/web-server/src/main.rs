@@ -6,31 +6,50 @@ mod extensions;
 mod hostname_router;
 mod routes;
 mod services;
+mod slug;
+#[cfg(test)]
+mod test_app;
 mod types;
+mod unlock_page;
+mod url_policy;
 mod util;
 
 use asset::Asset;
 use axum::{
 	body::Body,
-	extract::{Path, Request},
-	http::StatusCode,
+	extract::{ConnectInfo, Form, Path, Request},
+	http::{header, HeaderMap, StatusCode},
 	middleware::{self, Next},
 	response::{IntoResponse, Redirect, Response},
 	routing::get,
-	Extension, Router,
+	Extension, Json, Router,
 };
-use common::GenericMessage;
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use common::{with_repos, GenericMessage};
 use config::{Config, LoadConfigResult};
 use db::{
-	models::{Link, VerificationToken},
+	models::{Domain, EmailChangeToken, Link, PasswordResetToken, Session, VerificationToken},
+	repository::{DieselStore, SharedStore},
 	DbPool,
 };
 use extensions::domain::ExtractedDomain;
 use hostname_router::HostnameRouter;
 use mime_guess::from_path;
 use owo_colors::OwoColorize;
-use services::email::Email;
+use routes::api::domains::DOMAIN_CLAIM_TTL_DAYS;
+use serde::Deserialize;
+use services::{
+	click_tracker::ClickTracker,
+	domain_verification::{DnsTxtResolver, DomainVerifier},
+	email::Email,
+	redirect_cache::{RedirectCache, RedirectLink},
+};
 use tokio::sync::oneshot;
+use types::RedirectType;
+use util::{
+	jwt::{encode_link_unlock_token, verify_link_unlock_token},
+	password::verify_password,
+};
 use std::{net::SocketAddr, sync::{Arc, Mutex}};
 
 use tokio_cron_scheduler::{Job, JobScheduler, JobSchedulerError};
@@ -62,14 +81,129 @@ async fn index() -> impl IntoResponse {
 	axum::response::Html(include_str!("../static/index.html")) // Serve your index.html
 }
 
-async fn create_scheduler(pool: &DbPool) -> Result<(), JobSchedulerError> {
+async fn create_scheduler(pool: &DbPool, config: &Config, cache: &RedirectCache) -> Result<(), JobSchedulerError> {
 	let scheduler = JobScheduler::new().await?;
 	let pool_clone = pool.clone();
 
 	scheduler
-		.add(Job::new("* 0 0 * * *", move |_, _| match VerificationToken::delete_expired_pooled(&pool_clone) {
-			Ok(_) => log::debug!("Deleted expired verification tokens."),
-			Err(e) => log::error!("Failed to delete expired verification tokens: {:#?}", e),
+		.add(Job::new_async("* 0 0 * * *", move |_, _| {
+			let pool = pool_clone.clone();
+
+			Box::pin(async move {
+				match VerificationToken::delete_expired_pooled(&pool).await {
+					Ok(_) => log::debug!("Deleted expired verification tokens."),
+					Err(e) => log::error!("Failed to delete expired verification tokens: {:#?}", e),
+				}
+			})
+		})?)
+		.await?;
+
+	let pool_clone = pool.clone();
+	let cache_clone = cache.clone();
+
+	scheduler
+		.add(Job::new_async("0 */10 * * * *", move |_, _| {
+			let pool = pool_clone.clone();
+			let cache = cache_clone.clone();
+
+			Box::pin(async move {
+				match Link::archive_expired_pooled(&pool).await {
+					Ok(links) => {
+						for link in &links {
+							cache.invalidate_link(link).await;
+						}
+
+						log::debug!("Archived {} expired links.", links.len());
+					}
+					Err(e) => log::error!("Failed to archive expired links: {:#?}", e),
+				}
+			})
+		})?)
+		.await?;
+
+	let pool_clone = pool.clone();
+	let cache_clone = cache.clone();
+	let trash_retention = config.app.clone().unwrap().trash_retention;
+
+	scheduler
+		.add(Job::new_async("0 0 * * * *", move |_, _| {
+			let pool = pool_clone.clone();
+			let cache = cache_clone.clone();
+			let before = (chrono::Utc::now() - trash_retention.0).naive_utc();
+
+			Box::pin(async move {
+				match Link::purge_trashed_before_pooled(before, &pool).await {
+					Ok(links) => {
+						for link in &links {
+							cache.invalidate_link(link).await;
+						}
+
+						log::debug!("Purged {} trashed links.", links.len());
+					}
+					Err(e) => log::error!("Failed to purge trashed links: {:#?}", e),
+				}
+			})
+		})?)
+		.await?;
+
+	let pool_clone = pool.clone();
+
+	scheduler
+		.add(Job::new_async("0 30 0 * * *", move |_, _| {
+			let pool = pool_clone.clone();
+
+			Box::pin(async move {
+				match Session::delete_stale_before_pooled(chrono::Utc::now().naive_utc(), &pool).await {
+					Ok(count) => log::debug!("Deleted {} expired or revoked sessions.", count),
+					Err(e) => log::error!("Failed to delete stale sessions: {:#?}", e),
+				}
+			})
+		})?)
+		.await?;
+
+	let pool_clone = pool.clone();
+
+	scheduler
+		.add(Job::new_async("0 0 * * * *", move |_, _| {
+			let pool = pool_clone.clone();
+
+			Box::pin(async move {
+				match PasswordResetToken::delete_expired_pooled(&pool).await {
+					Ok(count) => log::debug!("Deleted {} expired password reset tokens.", count),
+					Err(e) => log::error!("Failed to delete expired password reset tokens: {:#?}", e),
+				}
+			})
+		})?)
+		.await?;
+
+	let pool_clone = pool.clone();
+
+	scheduler
+		.add(Job::new_async("0 0 * * * *", move |_, _| {
+			let pool = pool_clone.clone();
+
+			Box::pin(async move {
+				match EmailChangeToken::delete_expired_pooled(&pool).await {
+					Ok(count) => log::debug!("Deleted {} expired email change tokens.", count),
+					Err(e) => log::error!("Failed to delete expired email change tokens: {:#?}", e),
+				}
+			})
+		})?)
+		.await?;
+
+	let pool_clone = pool.clone();
+
+	scheduler
+		.add(Job::new_async("0 15 * * * *", move |_, _| {
+			let pool = pool_clone.clone();
+			let before = (chrono::Utc::now() - chrono::Duration::days(DOMAIN_CLAIM_TTL_DAYS)).naive_utc();
+
+			Box::pin(async move {
+				match Domain::delete_unverified_claims_before_pooled(before, &pool).await {
+					Ok(count) => log::debug!("Deleted {} expired domain claims.", count),
+					Err(e) => log::error!("Failed to delete expired domain claims: {:#?}", e),
+				}
+			})
 		})?)
 		.await?;
 
@@ -92,11 +226,50 @@ async fn log_request(req: Request<Body>, next: Next) -> Result<Response, StatusC
 	Ok(response)
 }
 
+/// What the routers hand to handlers as extensions
+#[derive(Clone)]
+struct Services {
+	config: Config,
+	email: Email,
+	store: SharedStore,
+	click_tracker: ClickTracker,
+	redirect_cache: RedirectCache,
+	domain_verifier: DomainVerifier,
+}
+
+/// The router for the base URL, serving the dashboard, the API and redirects
+fn app_router(services: &Services) -> Router {
+	Router::new()
+		.route("/", get(index))
+		.route("/:slug", get(handle_slug).post(unlock_link))
+		.route("/dash/*path", get(index))
+		.route("/assets/*path", get(asset_handler))
+		.nest("/api", routes::api::api_router())
+		.layer(Extension(services.config.clone()))
+		.layer(Extension(services.email.clone()))
+		.layer(Extension(services.store.clone()))
+		.layer(Extension(services.click_tracker.clone()))
+		.layer(Extension(services.redirect_cache.clone()))
+		.layer(Extension(services.domain_verifier.clone()))
+		.layer(middleware::from_fn(log_request))
+}
+
+/// The router for every other domain, which only serves redirects
+fn slug_router(services: &Services) -> Router {
+	Router::new()
+		.route("/:slug", get(handle_slug).post(unlock_link))
+		.layer(Extension(services.config.clone()))
+		.layer(Extension(services.store.clone()))
+		.layer(Extension(services.click_tracker.clone()))
+		.layer(Extension(services.redirect_cache.clone()))
+		.layer(middleware::from_fn(log_request))
+}
+
 async fn start_app(config: Config) {
 	let db_config = config.db.clone().unwrap();
 	let smtp_config = config.smtp.clone();
 
-	let pool = db::create_pool(&db_config.url);
+	let pool = db::create_pool(&db_config.url, &db_config.pool_options());
 	let email: Option<Email> = smtp_config
 		.as_ref() // Avoid cloning `smtp_config` unnecessarily
 		.and_then(|config| {
@@ -115,35 +288,37 @@ async fn start_app(config: Config) {
 
 	db::run_migrations(&pool.clone());
 
-	match create_scheduler(&pool).await {
+	let store: SharedStore = Arc::new(DieselStore::new(pool.clone()));
+	let click_tracker = ClickTracker::new(store.clone(), config.security.clone().unwrap().ip_hash_salt);
+	let redirect_cache = match RedirectCache::from_config(&config.cache).await {
+		Ok(redirect_cache) => redirect_cache,
+		Err(e) => panic!("Failed to set up the redirect cache: {}", e),
+	};
+
+	match create_scheduler(&pool, &config, &redirect_cache).await {
 		Ok(_) => {}
 		Err(e) => eprintln!("Failed to create scheduler: {:#?}", e),
 	}
 
-	let app_router = Router::new()
-		.route("/", get(index))
-		.route("/:slug", get(handle_slug))
-		.route("/dash/*path", get(index))
-		.route("/assets/*path", get(asset_handler))
-		.nest("/api", routes::api::api_router())
-		.layer(Extension(config.clone()))
-		.layer(Extension(email.unwrap()))
-		.layer(Extension(pool.clone()))
-		.layer(middleware::from_fn(log_request));
-
-	let slug_router = Router::new()
-		.route("/:slug", get(handle_slug))
-		.layer(Extension(pool.clone()))
-		.layer(middleware::from_fn(log_request));
+	let services = Services {
+		config: config.clone(),
+		email: email.unwrap(),
+		store,
+		click_tracker,
+		redirect_cache,
+		domain_verifier: DomainVerifier::new(DnsTxtResolver::from_system_conf()),
+	};
 
-	let hostname_router = HostnameRouter::new(app_router, slug_router, config.clone());
+	let hostname_router = HostnameRouter::new(app_router(&services), slug_router(&services), config.clone());
 
 	let app = Router::new().fallback(hostname_router);
 
 	let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
 	log::info!("APP listening on {}", addr);
 	let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-	axum::serve(listener, app.into_make_service()).await.unwrap();
+	axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+		.await
+		.unwrap();
 }
 
 pub async fn start_setup(config: config::Config, shutdown_tx: Arc<Mutex<Option<oneshot::Sender<()>>>>, shutdown_rx: oneshot::Receiver<()>) {
@@ -205,28 +380,285 @@ async fn main() {
 	};
 }
 
+#[derive(Deserialize)]
+struct UnlockLinkRequest {
+	password: String,
+}
+
+/// Finds the link for a slug on the given domain, rejecting expired links
+async fn find_link(
+	config: &Config,
+	cache: &RedirectCache,
+	domain_id: i32,
+	slug: &str,
+	store: &SharedStore,
+) -> Result<RedirectLink, (StatusCode, Json<GenericMessage>)> {
+	let load_slug = slug.to_string();
+
+	let existing_link = cache
+		.get_link(
+			domain_id,
+			slug,
+			with_repos(store, move |repos| {
+				repos
+					.links()
+					.get_by_domain_slug(domain_id, &load_slug)
+					.map(|links| links.into_iter().next())
+					.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))
+			}),
+		)
+		.await?;
+
+	let link = match existing_link {
+		Some(link) => link,
+		None => return Err((StatusCode::NOT_FOUND, GenericMessage::new("Slug not found."))),
+	};
+
+	if link.link.is_expired() {
+		return Err(gone(config));
+	}
+
+	Ok(link)
+}
+
+fn gone(config: &Config) -> (StatusCode, Json<GenericMessage>) {
+	(StatusCode::GONE, GenericMessage::from_string(config.app.clone().unwrap().expired_link_message))
+}
+
+/// Counts the click towards the link's limit and records it for the stats
+async fn track_click(
+	link: &Link,
+	config: &Config,
+	click_tracker: &ClickTracker,
+	headers: &HeaderMap,
+	remote_addr: Option<SocketAddr>,
+	store: &SharedStore,
+) -> Result<(), (StatusCode, Json<GenericMessage>)> {
+	if link.max_clicks.is_some() {
+		let link = link.clone();
+		let consumed = with_repos(store, move |repos| {
+			repos
+				.links()
+				.consume_click(&link)
+				.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))
+		})
+		.await?;
+
+		if !consumed {
+			return Err(gone(config));
+		}
+	}
+
+	let header_value = |name: header::HeaderName| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+
+	click_tracker.record(
+		link.id,
+		link.domain_id,
+		header_value(header::REFERER),
+		header_value(header::USER_AGENT),
+		util::client_ip(headers, remote_addr, config.app.as_ref().is_some_and(|app| app.behind_proxy)),
+	);
+
+	Ok(())
+}
+
+fn link_unlock_cookie_name(link: &Link) -> String {
+	format!("link_unlock_{}", link.id)
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn handle_slug(
-	Extension(pool): Extension<DbPool>,
-	ExtractedDomain(_domain, domain_id): ExtractedDomain,
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+	Extension(click_tracker): Extension<ClickTracker>,
+	Extension(cache): Extension<RedirectCache>,
+	ExtractedDomain(_host, domain): ExtractedDomain,
+	connect_info: Option<ConnectInfo<SocketAddr>>,
+	headers: HeaderMap,
+	jar: CookieJar,
+	Path(slug): Path<String>,
+) -> Result<Response, (StatusCode, Json<GenericMessage>)> {
+	let RedirectLink { link, has_password } = find_link(&config, &cache, domain.id, &slug, &store).await?;
+
+	if has_password {
+		let jwt_secret = config.security.clone().unwrap().jwt_secret;
+
+		let is_unlocked = jar
+			.get(&link_unlock_cookie_name(&link))
+			.is_some_and(|cookie| verify_link_unlock_token(cookie.value(), link.id, jwt_secret.as_bytes()));
+
+		if !is_unlocked {
+			return Ok(unlock_page::render(None).into_response());
+		}
+	}
+
+	track_click(&link, &config, &click_tracker, &headers, connect_info.map(|ConnectInfo(addr)| addr), &store).await?;
+
+	let redirect_type = RedirectType::resolve(
+		link.redirect_type,
+		domain.redirect_type,
+		config.app.clone().unwrap().default_redirect_type,
+	);
+
+	// A cached redirect would skip the password, expiry and click limit checks
+	if has_password || link.expires_at.is_some() || link.max_clicks.is_some() {
+		return Ok(redirect_type.redirect_uncached(&link.original_link));
+	}
+
+	Ok(redirect_type.redirect(&link.original_link))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn unlock_link(
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+	Extension(click_tracker): Extension<ClickTracker>,
+	Extension(cache): Extension<RedirectCache>,
+	ExtractedDomain(_host, domain): ExtractedDomain,
+	connect_info: Option<ConnectInfo<SocketAddr>>,
+	headers: HeaderMap,
+	mut jar: CookieJar,
 	Path(slug): Path<String>,
-) -> impl IntoResponse {
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+	Form(payload): Form<UnlockLinkRequest>,
+) -> Result<Response, (StatusCode, Json<GenericMessage>)> {
+	let app_config = config.app.clone().unwrap();
+	let security_config = config.security.clone().unwrap();
+
+	let RedirectLink { link, has_password } = find_link(&config, &cache, domain.id, &slug, &store).await?;
+
+	// The cache only knows if the link has a password, the hash itself comes from the database
+	let password_hash = if has_password {
+		let link_id = link.id;
+
+		with_repos(&store, move |repos| {
+			repos
+				.links()
+				.get_by_id(link_id)
+				.map(|link| link.password_hash)
+				.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Slug not found.")))
+		})
+		.await?
+	} else {
+		None
+	};
+
+	if let Some(password_hash) = &password_hash {
+		let is_valid = verify_password(&payload.password, password_hash)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+
+		if !is_valid {
+			return Ok((StatusCode::UNAUTHORIZED, unlock_page::render(Some("Incorrect password."))).into_response());
+		}
+
+		let ttl = time::Duration::seconds(app_config.link_unlock_ttl.0.num_seconds());
+		let token = encode_link_unlock_token(link.id, ttl, security_config.jwt_secret.as_bytes());
 
-	let existing_link = Link::get_by_domain_slug(domain_id, &slug, conn);
+		let cookie = Cookie::build((link_unlock_cookie_name(&link), token))
+			.http_only(true) // Prevent JavaScript access
+			.secure(config.secure_cookies()) // Only send over HTTPS, unless the app is served without it
+			.same_site(SameSite::Lax) // Control cross-site sending
+			.path(format!("/{}", slug)) // Only send the cookie for this link
+			.max_age(ttl)
+			.build();
 
-	if existing_link.is_err() {
-		return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")));
+		jar = jar.add(cookie);
 	}
 
-	let existing_link = existing_link.unwrap();
+	track_click(&link, &config, &click_tracker, &headers, connect_info.map(|ConnectInfo(addr)| addr), &store).await?;
+
+	// 303, so the browser doesn't re-submit the password to the destination
+	Ok((jar, Redirect::to(&link.original_link)).into_response())
+}
+
+#[cfg(test)]
+mod test {
+	use axum::http::Method;
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_checked_links_redirect_uncached() {
+		let app = TestApp::new();
+		let domain = app.add_public_domain("go.to");
+		let mut protected_id = 0;
+
+		for (slug, check) in [
+			("plain", None),
+			("limited", Some(("max_clicks", json!(10)))),
+			("expiring", Some(("expires_at", json!("2099-01-01T00:00:00Z")))),
+			("protected", Some(("password", json!("hunter2")))),
+		] {
+			let mut body = json!({ "domain_id": domain.id, "link": "https://example.com/", "custom_slug": slug });
+
+			if let Some((field, value)) = check {
+				body[field] = value;
+			}
+
+			let response = app.post("/api/link/shorten", body, None).await;
+			assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+
+			protected_id = response.body["id"].as_i64().unwrap() as i32;
+		}
+
+		let response = app.request(Method::GET, "go.to", "/plain", None, None).await;
+		assert_eq!(response.status, StatusCode::PERMANENT_REDIRECT);
+		assert_eq!(response.cache_control, None);
+
+		for slug in ["/limited", "/expiring"] {
+			let response = app.request(Method::GET, "go.to", slug, None, None).await;
+			assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+			assert_eq!(response.cache_control.as_deref(), Some("no-store"));
+		}
 
-	if existing_link.len() == 0 {
-		return Err((StatusCode::NOT_FOUND, GenericMessage::new("Slug not found.")));
+		// The protected link was created last
+		let token = encode_link_unlock_token(protected_id, time::Duration::minutes(5), b"secret");
+		let request = Request::builder()
+			.uri("/protected")
+			.header(header::HOST, "go.to")
+			.header(header::COOKIE, format!("link_unlock_{}={}", protected_id, token))
+			.body(Body::empty())
+			.unwrap();
+
+		let response = app.send(request).await;
+		assert_eq!(response.status, StatusCode::TEMPORARY_REDIRECT);
+		assert_eq!(response.cache_control.as_deref(), Some("no-store"));
 	}
 
-	let link = existing_link.first().unwrap();
+	#[tokio::test]
+	async fn test_unlock_link() {
+		let app = TestApp::new();
+		let domain = app.add_public_domain("go.to");
+		let body = json!({
+			"domain_id": domain.id,
+			"link": "https://example.com/",
+			"custom_slug": "secret",
+			"password": "hunter2",
+		});
+
+		let response = app.post("/api/link/shorten", body, None).await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+
+		let unlock = |password: &str| {
+			Request::builder()
+				.method(Method::POST)
+				.uri("/secret")
+				.header(header::HOST, "go.to")
+				.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+				.body(Body::from(format!("password={}", password)))
+				.unwrap()
+		};
+
+		// The first visit caches the link
+		let response = app.request(Method::GET, "go.to", "/secret", None, None).await;
+		assert_eq!(response.status, StatusCode::OK);
 
-	Ok(Redirect::permanent(&link.original_link))
+		let response = app.send(unlock("wrong")).await;
+		assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+		let response = app.send(unlock("hunter2")).await;
+		assert_eq!(response.status, StatusCode::SEE_OTHER);
+		assert_eq!(response.location.as_deref(), Some("https://example.com/"));
+	}
 }
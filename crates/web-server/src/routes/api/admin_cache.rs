@@ -0,0 +1,21 @@
+use axum::{http::StatusCode, routing::get, Extension, Json, Router};
+
+use crate::{
+	common::APIResponse,
+	extensions::auth::RequirePermission,
+	services::redirect_cache::{RedirectCache, RedirectCacheStats},
+	types::CanManageSettings,
+};
+
+/// Hit and miss counters of the redirect cache since the server started
+async fn get_cache_stats(
+	_: RequirePermission<CanManageSettings>,
+	Extension(cache): Extension<RedirectCache>,
+) -> APIResponse<RedirectCacheStats> {
+	Ok((StatusCode::OK, Json(cache.stats())))
+}
+
+// Starts at /api/admin/cache
+pub fn admin_cache_router() -> Router {
+	Router::new().route("/", get(get_cache_stats))
+}
@@ -0,0 +1,493 @@
+use axum::{
+	extract::{Path, Query},
+	http::StatusCode,
+	routing::{delete, get, post},
+	Extension, Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use db::{
+	models::{NewPasswordResetToken, User, ADMIN_ROLE},
+	repository::{self, Repositories, SharedStore},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	common::{with_repos, APIError, APIResponse, GenericMessage},
+	config::Config,
+	extensions::auth::RequirePermission,
+	services::email::{templates::PasswordResetEmail, Email},
+	types::{CanManageUsers, PaginatedResponse, PaginationQuery},
+	util::{generate_unique_string, hash_token},
+};
+
+const MAX_SUSPENSION_REASON_LENGTH: usize = 512;
+
+#[derive(Deserialize, Debug)]
+struct UserSearchQuery {
+	search: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SuspendRequest {
+	until: DateTime<Utc>,
+	reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct BanRequest {
+	reason: Option<String>,
+}
+
+/// A user as seen by the people managing them
+#[derive(Serialize, Debug)]
+struct AdminUser {
+	id: i32,
+	username: String,
+	email: String,
+	verified_at: Option<NaiveDateTime>,
+	created_at: NaiveDateTime,
+	deleted_at: Option<NaiveDateTime>,
+	two_factor_enabled: bool,
+	suspended: bool,
+	suspended_at: Option<NaiveDateTime>,
+	suspended_until: Option<NaiveDateTime>,
+	suspension_reason: Option<String>,
+	roles: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct PasswordResetLink {
+	reset_url: String,
+	expires_at: NaiveDateTime,
+	/// Whether the link was also sent to the user
+	emailed: bool,
+}
+
+fn internal_error() -> APIError {
+	(StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))
+}
+
+fn admin_user(user: User, repos: &mut dyn Repositories) -> Result<AdminUser, APIError> {
+	let roles = repos.roles().get_for_user(user.id).map_err(|_| internal_error())?;
+
+	Ok(AdminUser {
+		roles: roles.into_iter().map(|role| role.name).collect(),
+		two_factor_enabled: user.has_two_factor(),
+		suspended: user.is_suspended(),
+		id: user.id,
+		username: user.username,
+		email: user.email,
+		verified_at: user.verified_at,
+		created_at: user.created_at,
+		deleted_at: user.deleted_at,
+		suspended_at: user.suspended_at,
+		suspended_until: user.suspended_until,
+		suspension_reason: user.suspension_reason,
+	})
+}
+
+fn get_user(id: i32, repos: &mut dyn Repositories) -> Result<User, APIError> {
+	repos
+		.users()
+		.get_by_id(id)
+		.map_err(|_| internal_error())?
+		.ok_or((StatusCode::NOT_FOUND, GenericMessage::new("User not found")))
+}
+
+/// Keeps admins from locking themselves out
+fn check_not_self(manager: &User, user: &User, action: &str) -> Result<(), APIError> {
+	if manager.id == user.id {
+		return Err((StatusCode::FORBIDDEN, GenericMessage::from_string(format!("You can't {} yourself.", action))));
+	}
+
+	Ok(())
+}
+
+fn parse_reason(reason: Option<String>) -> Result<Option<String>, APIError> {
+	let reason = reason
+		.map(|reason| reason.trim().to_string())
+		.filter(|reason| !reason.is_empty());
+
+	if reason
+		.as_ref()
+		.is_some_and(|reason| reason.len() > MAX_SUSPENSION_REASON_LENGTH)
+	{
+		return Err((
+			StatusCode::BAD_REQUEST,
+			GenericMessage::from_string(format!("Reason must be at most {} characters.", MAX_SUSPENSION_REASON_LENGTH)),
+		));
+	}
+
+	Ok(reason)
+}
+
+async fn get_users(
+	_: RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Query(pagination): Query<PaginationQuery>,
+	Query(query): Query<UserSearchQuery>,
+) -> APIResponse<PaginatedResponse<AdminUser>> {
+	with_repos(&store, move |repos| {
+		let search = query.search.as_deref();
+
+		let users = repos
+			.users()
+			.search_paginated(search, pagination.page, pagination.per_page)
+			.map_err(|_| internal_error())?;
+		let total_count = repos.users().search_count(search).map_err(|_| internal_error())?;
+
+		let items = users
+			.into_iter()
+			.map(|user| admin_user(user, repos))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok((StatusCode::OK, Json(PaginatedResponse { items, total_count })))
+	})
+	.await
+}
+
+async fn get_user_by_id(
+	_: RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<AdminUser> {
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+async fn promote_user(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<AdminUser> {
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+
+		repos
+			.users()
+			.assign_role(ADMIN_ROLE, user.id)
+			.map_err(|_| internal_error())?;
+
+		log::info!("{} made {} an admin", manager.username, user.username);
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+async fn demote_user(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<AdminUser> {
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+		let admin_role = repos.roles().get_by_name(ADMIN_ROLE).map_err(|_| internal_error())?;
+
+		let is_admin = repos
+			.roles()
+			.get_for_user(user.id)
+			.map_err(|_| internal_error())?
+			.iter()
+			.any(|role| role.id == admin_role.id);
+
+		if !is_admin {
+			return Err((StatusCode::NOT_FOUND, GenericMessage::new("The user isn't an admin.")));
+		}
+
+		if repos.roles().count_users(&admin_role).map_err(|_| internal_error())? <= 1 {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The last admin can't lose the admin role.")));
+		}
+
+		repos
+			.roles()
+			.unassign(&admin_role, user.id)
+			.map_err(|_| internal_error())?;
+
+		log::info!("{} removed {} from the admins", manager.username, user.username);
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+/// Suspends a user until the given time and signs them out everywhere
+async fn suspend_user(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Json(payload): Json<SuspendRequest>,
+) -> APIResponse<AdminUser> {
+	if payload.until <= Utc::now() {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("The suspension has to end in the future.")));
+	}
+
+	let reason = parse_reason(payload.reason)?;
+
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+		check_not_self(&manager, &user, "suspend")?;
+
+		repository::transaction(repos, |repos| {
+			repos.users().suspend(&user, Some(payload.until.naive_utc()), reason)?;
+			repos.tokens().revoke_all_sessions(user.id, None)
+		})
+		.map_err(|_| internal_error())?;
+
+		log::info!("{} suspended {} until {}", manager.username, user.username, payload.until);
+
+		let user = get_user(id, repos)?;
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+/// Suspends a user for good
+async fn ban_user(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Json(payload): Json<BanRequest>,
+) -> APIResponse<AdminUser> {
+	let reason = parse_reason(payload.reason)?;
+
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+		check_not_self(&manager, &user, "ban")?;
+
+		repository::transaction(repos, |repos| {
+			repos.users().suspend(&user, None, reason)?;
+			repos.tokens().revoke_all_sessions(user.id, None)
+		})
+		.map_err(|_| internal_error())?;
+
+		log::info!("{} banned {}", manager.username, user.username);
+
+		let user = get_user(id, repos)?;
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+/// Lifts a suspension or a ban
+async fn unsuspend_user(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<AdminUser> {
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+
+		repos.users().unsuspend(&user).map_err(|_| internal_error())?;
+
+		log::info!("{} lifted the suspension of {}", manager.username, user.username);
+
+		let user = get_user(id, repos)?;
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+async fn verify_email(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<AdminUser> {
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+
+		if user.verified_at.is_some() {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The email is already verified.")));
+		}
+
+		repos
+			.users()
+			.set_verified_at(&user, Some(Utc::now().naive_utc()))
+			.map_err(|_| internal_error())?;
+
+		log::info!("{} verified the email of {}", manager.username, user.username);
+
+		let user = get_user(id, repos)?;
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+/// Creates a password reset link for the user. It's emailed to them when possible, and returned either way
+/// so it can be passed on by other means.
+async fn reset_user_password(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Extension(config): Extension<Config>,
+	Extension(email): Extension<Email>,
+	Path(id): Path<i32>,
+) -> APIResponse<PasswordResetLink> {
+	let app_config = config.app.unwrap();
+
+	let reset_token = generate_unique_string(32);
+	let token_hash = hash_token(&reset_token);
+	let expires_at = (Utc::now() + app_config.password_reset_ttl).naive_utc();
+
+	let (user, token) = with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+
+		let new_token = NewPasswordResetToken {
+			user_id: user.id,
+			token_hash,
+			expires_at,
+		};
+
+		// Only the most recently requested link works, so the old ones must not go away without a new one
+		let token = repository::transaction(repos, |repos| {
+			repos.tokens().delete_password_reset_tokens(user.id)?;
+
+			repos.tokens().insert_password_reset_token(&new_token)
+		})
+		.map_err(|_| internal_error())?;
+
+		Ok((user, token))
+	})
+	.await?;
+
+	let emailed = email.is_available();
+
+	log::info!("{} created a password reset link for {}", manager.username, user.username);
+
+	let response = PasswordResetLink {
+		reset_url: format!("{}/dash/reset-password/{}", app_config.base_url, reset_token),
+		expires_at: token.expires_at,
+		emailed,
+	};
+
+	if emailed {
+		tokio::spawn(async move {
+			email
+				.send_template::<PasswordResetEmail>(
+					&user.email,
+					&PasswordResetEmail {
+						base_url: &app_config.base_url,
+						username: &user.username,
+						reset_token: reset_token.as_str(),
+						ttl: format!("{}", app_config.password_reset_ttl).as_str(),
+					},
+				)
+				.await;
+		});
+	}
+
+	Ok((StatusCode::OK, Json(response)))
+}
+
+/// Soft-deletes a user, they can be restored later
+async fn delete_user(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<AdminUser> {
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+		check_not_self(&manager, &user, "delete")?;
+
+		if user.deleted_at.is_some() {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The user is already deleted.")));
+		}
+
+		repository::transaction(repos, |repos| {
+			repos.users().soft_delete(&user)?;
+			repos.tokens().revoke_all_sessions(user.id, None)
+		})
+		.map_err(|_| internal_error())?;
+
+		log::info!("{} deleted {}", manager.username, user.username);
+
+		let user = get_user(id, repos)?;
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+async fn restore_user(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<AdminUser> {
+	with_repos(&store, move |repos| {
+		let user = get_user(id, repos)?;
+
+		if user.deleted_at.is_none() {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The user isn't deleted.")));
+		}
+
+		repos.users().restore(&user).map_err(|_| internal_error())?;
+
+		log::info!("{} restored {}", manager.username, user.username);
+
+		let user = get_user(id, repos)?;
+
+		Ok((StatusCode::OK, Json(admin_user(user, repos)?)))
+	})
+	.await
+}
+
+// Starts at /api/admin/users
+pub fn admin_users_router() -> Router {
+	Router::new()
+		.route("/", get(get_users))
+		.route("/:id", get(get_user_by_id).delete(delete_user))
+		.route("/:id/restore", post(restore_user))
+		.route("/:id/admin", post(promote_user).delete(demote_user))
+		.route("/:id/suspend", post(suspend_user))
+		.route("/:id/ban", post(ban_user))
+		.route("/:id/suspension", delete(unsuspend_user))
+		.route("/:id/verify-email", post(verify_email))
+		.route("/:id/reset-password", post(reset_user_password))
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_ban_user() {
+		let app = TestApp::new();
+		let admin = app.sign_up("alice").await;
+		let user = app.sign_up("bob").await;
+		let user_id = app.user_id("bob");
+
+		assert_eq!(app.get("/api/admin/users?page=1&per_page=10", Some(&user)).await.status, StatusCode::FORBIDDEN);
+
+		let response = app
+			.get("/api/admin/users?page=1&per_page=10&search=BOB", Some(&admin))
+			.await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+		assert_eq!(response.body["total_count"], 1);
+		assert_eq!(response.body["items"][0]["roles"], json!(["user"]));
+
+		let path = format!("/api/admin/users/{}/ban", app.user_id("alice"));
+		let response = app.post(&path, json!({}), Some(&admin)).await;
+		assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+		let path = format!("/api/admin/users/{}/ban", user_id);
+		let response = app.post(&path, json!({ "reason": " Spam " }), Some(&admin)).await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+		assert_eq!(response.body["suspended"], true);
+		assert_eq!(response.body["suspension_reason"], "Spam");
+
+		let path = format!("/api/admin/users/{}/suspension", user_id);
+		let response = app.delete(&path, Some(&admin)).await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+		assert_eq!(response.body["suspended"], false);
+	}
+}
@@ -0,0 +1,249 @@
+use axum::{
+	extract::Path,
+	http::StatusCode,
+	routing::{get, put},
+	Extension, Json, Router,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use db::{
+	models::{ApiKey, NewApiKey, UpdateApiKey, User},
+	repository::{Repositories, SharedStore},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	common::{with_repos, APIError, APIResponse, GenericMessage},
+	extensions::auth::{AuthedUser, Permissions, VerifiedUser},
+	types::{double_option, ApiKeyScope, Permission},
+	util::api_key::{api_key_display_prefix, generate_api_key, hash_api_key},
+};
+
+/// How many keys a single user can have
+const MAX_API_KEYS_PER_USER: usize = 25;
+
+#[derive(Deserialize, Debug)]
+struct CreateApiKey {
+	name: String,
+	scopes: Vec<ApiKeyScope>,
+	expires_at: Option<DateTime<Utc>>,
+}
+
+/// Fields that can be changed on a key. The expiry can be cleared by sending `null`.
+#[derive(Deserialize, Debug)]
+struct UpdateApiKeyRequest {
+	name: Option<String>,
+	scopes: Option<Vec<ApiKeyScope>>,
+	#[serde(default, deserialize_with = "double_option")]
+	expires_at: Option<Option<DateTime<Utc>>>,
+}
+
+#[derive(Serialize, Debug)]
+struct ApiKeyResponse {
+	id: i32,
+	name: String,
+	token_prefix: String,
+	scopes: Vec<ApiKeyScope>,
+	last_used_at: Option<NaiveDateTime>,
+	expires_at: Option<NaiveDateTime>,
+	created_at: NaiveDateTime,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+	fn from(api_key: ApiKey) -> Self {
+		Self {
+			scopes: ApiKeyScope::parse_all(&api_key.scopes()),
+			id: api_key.id,
+			name: api_key.name,
+			token_prefix: api_key.token_prefix,
+			last_used_at: api_key.last_used_at,
+			expires_at: api_key.expires_at,
+			created_at: api_key.created_at,
+		}
+	}
+}
+
+/// A freshly created key. This is the only time the full key is shown.
+#[derive(Serialize, Debug)]
+struct CreatedApiKey {
+	#[serde(flatten)]
+	api_key: ApiKeyResponse,
+	key: String,
+}
+
+fn require_user(user: Option<User>) -> Result<User, APIError> {
+	user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))
+}
+
+fn validate_name(name: &str) -> Result<(), APIError> {
+	if name.trim().is_empty() || name.len() > 255 {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Name must be between 1 and 255 characters.")));
+	}
+
+	Ok(())
+}
+
+fn validate_scopes(scopes: &[ApiKeyScope], permissions: &Permissions) -> Result<(), APIError> {
+	if scopes.is_empty() {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("At least one scope is required.")));
+	}
+
+	if scopes.contains(&ApiKeyScope::DomainsAdmin) && !permissions.has(Permission::DomainManage) {
+		return Err((
+			StatusCode::FORBIDDEN,
+			GenericMessage::new("Only domain managers can create keys with the domains:admin scope."),
+		));
+	}
+
+	Ok(())
+}
+
+fn validate_expiry(expires_at: Option<DateTime<Utc>>) -> Result<(), APIError> {
+	if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Expiry date must be in the future.")));
+	}
+
+	Ok(())
+}
+
+/// Gets one of the user's keys
+fn get_my_api_key(id: i32, user: &User, repos: &mut dyn Repositories) -> Result<ApiKey, APIError> {
+	let not_found = || (StatusCode::NOT_FOUND, GenericMessage::new("API key not found."));
+
+	let api_key = repos.tokens().get_api_key(id).map_err(|_| not_found())?;
+
+	if api_key.user_id != user.id {
+		return Err(not_found());
+	}
+
+	Ok(api_key)
+}
+
+async fn my_api_keys(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<Vec<ApiKeyResponse>> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let api_keys = repos
+			.tokens()
+			.get_api_keys(user.id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		Ok((StatusCode::OK, Json(api_keys.into_iter().map(ApiKeyResponse::from).collect())))
+	})
+	.await
+}
+
+async fn create_api_key(
+	VerifiedUser(user): VerifiedUser,
+	permissions: Permissions,
+	Extension(store): Extension<SharedStore>,
+	Json(payload): Json<CreateApiKey>,
+) -> APIResponse<CreatedApiKey> {
+	let user = require_user(user)?;
+
+	validate_name(&payload.name)?;
+	validate_scopes(&payload.scopes, &permissions)?;
+	validate_expiry(payload.expires_at)?;
+
+	with_repos(&store, move |repos| {
+		let existing = repos
+			.tokens()
+			.get_api_keys(user.id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		if existing.len() >= MAX_API_KEYS_PER_USER {
+			return Err((
+				StatusCode::CONFLICT,
+				GenericMessage::from_string(format!("You can't have more than {} API keys.", MAX_API_KEYS_PER_USER)),
+			));
+		}
+
+		let key = generate_api_key();
+
+		let new_api_key = NewApiKey {
+			user_id: user.id,
+			name: payload.name.trim().to_string(),
+			token_hash: hash_api_key(&key),
+			token_prefix: api_key_display_prefix(&key),
+			scopes: ApiKeyScope::join(&payload.scopes),
+			expires_at: payload.expires_at.map(|expires_at| expires_at.naive_utc()),
+		};
+
+		match repos.tokens().insert_api_key(&new_api_key) {
+			Ok(api_key) => Ok((
+				StatusCode::CREATED,
+				Json(CreatedApiKey {
+					api_key: ApiKeyResponse::from(api_key),
+					key,
+				}),
+			)),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to create API key."))),
+		}
+	})
+	.await
+}
+
+async fn update_api_key(
+	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Json(payload): Json<UpdateApiKeyRequest>,
+) -> APIResponse<ApiKeyResponse> {
+	let user = require_user(user)?;
+
+	if let Some(name) = &payload.name {
+		validate_name(name)?;
+	}
+
+	if let Some(scopes) = &payload.scopes {
+		validate_scopes(scopes, &permissions)?;
+	}
+
+	validate_expiry(payload.expires_at.flatten())?;
+
+	with_repos(&store, move |repos| {
+		let api_key = get_my_api_key(id, &user, repos)?;
+
+		let values = UpdateApiKey {
+			name: payload.name.map(|name| name.trim().to_string()),
+			scopes: payload.scopes.map(|scopes| ApiKeyScope::join(&scopes)),
+			expires_at: payload
+				.expires_at
+				.map(|expires_at| expires_at.map(|expires_at| expires_at.naive_utc())),
+		};
+
+		match repos.tokens().update_api_key(&api_key, values) {
+			Ok(api_key) => Ok((StatusCode::OK, Json(ApiKeyResponse::from(api_key)))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to update API key."))),
+		}
+	})
+	.await
+}
+
+async fn delete_api_key(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let api_key = get_my_api_key(id, &user, repos)?;
+
+		match repos.tokens().delete_api_key(&api_key) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("API key deleted."))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to delete API key."))),
+		}
+	})
+	.await
+}
+
+// Starts at /api/user/me/keys
+pub fn api_keys_router() -> Router {
+	Router::new()
+		.route("/", get(my_api_keys).post(create_api_key))
+		.route("/:id", put(update_api_key).delete(delete_api_key))
+}
@@ -12,9 +12,13 @@ struct ConfigResponse {
 	min_password_strength: Score,
 	base_url: String,
 	setup_done: bool,
+	oidc_enabled: bool,
+	/// Label of the single sign-on button
+	oidc_display_name: Option<String>,
 }
 
 async fn get_config(Extension(config): Extension<Config>) -> APIResponse<ConfigResponse> {
+	let oidc_config = config.enabled_oidc().cloned();
 	let app_config = config.app.unwrap();
 	let security_config = config.security.unwrap();
 
@@ -24,6 +28,8 @@ async fn get_config(Extension(config): Extension<Config>) -> APIResponse<ConfigR
 		min_password_strength: security_config.min_password_strength,
 		base_url: app_config.base_url,
 		setup_done: config.setup.setup_done,
+		oidc_enabled: oidc_config.is_some(),
+		oidc_display_name: oidc_config.map(|oidc| oidc.display_name),
 	};
 
 	Ok((StatusCode::OK, Json(response)))
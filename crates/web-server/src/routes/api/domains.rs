@@ -5,120 +5,186 @@ use axum::{
 	Extension, Json, Router,
 };
 
+use chrono::{Duration, Utc};
 use db::{
 	models::{Domain, NewDomain, UpdateDomain},
-	DbPool,
+	repository::{self, Repositories, SharedStore},
 };
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::teams::get_team_role;
 
 use crate::{
-	common::{APIResponse, GenericMessage}, config::Config, extensions::auth::AuthedUser, types::{PaginatedResponse, PaginationQuery}, util::{is_admin, is_url, strip_protocol}
+	common::{with_repos, APIError, APIResponse, GenericMessage}, config::Config, extensions::auth::{AuthedUser, Permissions, RequirePermission, VerifiedUser}, services::{domain_verification::DomainVerifier, redirect_cache::RedirectCache}, slug::SlugStrategy, types::{ApiKeyScope, CanManageDomains, PaginatedResponse, PaginationQuery, Permission, RedirectType, TeamRole}, util::{generate_unique_string, is_url, strip_protocol}
 };
 
+/// How long an unverified claim holds on to a domain, before it is deleted and others can claim it
+pub const DOMAIN_CLAIM_TTL_DAYS: i64 = 7;
+const VERIFICATION_TOKEN_LENGTH: usize = 32;
+
 #[derive(Serialize, Deserialize, Debug)]
 struct CreateDomain {
 	domain: String,
     public: Option<bool>,
+	slug_strategy: Option<SlugStrategy>,
+	redirect_type: Option<RedirectType>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SetDomainTeam {
+	team_id: Option<i32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ClaimDomain {
+	domain: String,
+}
+
+/// The TXT record that proves control over a domain
+#[derive(Serialize, Debug)]
+struct VerificationRecord {
+	name: String,
+	value: String,
+}
+
+/// A domain brought by a user, with the record to add while it's unverified
+#[derive(Serialize, Debug)]
+struct OwnedDomain {
+	#[serde(flatten)]
+	domain: Domain,
+	verification_record: Option<VerificationRecord>,
+}
+
+impl From<Domain> for OwnedDomain {
+	fn from(domain: Domain) -> Self {
+		let verification_record = match (&domain.verification_token, domain.is_verified()) {
+			(Some(token), false) => Some(VerificationRecord {
+				name: DomainVerifier::record_name(&domain.domain),
+				value: DomainVerifier::record_value(token),
+			}),
+			_ => None,
+		};
+
+		Self {
+			domain,
+			verification_record,
+		}
+	}
 }
 
 async fn create_domain(
-	Extension(pool): Extension<DbPool>,
-	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	_: RequirePermission<CanManageDomains>,
 	Json(payload): Json<CreateDomain>,
 ) -> APIResponse<Domain> {
-	if !is_admin(user) {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
-	}
+	with_repos(&store, move |repos| {
+		if !is_url(&payload.domain) {
+			return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Provided domain is not a valid URL.")));
+		}
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+		let stripped_domain = strip_protocol(&payload.domain)
+			.map_err(|e| (StatusCode::BAD_REQUEST, GenericMessage::from_string(e.to_string())))?;
 
-	if !is_url(&payload.domain) {
-		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Provided domain is not a valid URL.")));
-	}
+		// Check if the domain already exists
+		if repos.domains().get_by_domain(&stripped_domain).is_ok() {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("Domain already exists.")));
+		}
 
-	let stripped_domain = strip_protocol(&payload.domain)
-		.map_err(|e| (StatusCode::BAD_REQUEST, GenericMessage::from_string(e.to_string())))?;
+		let new_domain = NewDomain {
+			domain: stripped_domain,
+	        public: payload.public,
+			slug_strategy: payload.slug_strategy.map(|strategy| strategy.to_string()),
+			redirect_type: payload.redirect_type.map(i32::from),
+			owner_id: None,
+			verification_token: None,
+			// Admins don't have to prove anything
+			verified_at: Some(Utc::now().naive_utc()),
+		};
+
+		match repos.domains().insert(&new_domain) {
+			Ok(domain) => Ok((StatusCode::CREATED, Json(domain))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to create domain."))),
+		}
+	})
+	.await
+}
 
-	// Check if the domain already exists
-	if let Ok(_) = Domain::get_by_domain(stripped_domain.clone(), conn) {
-		return Err((StatusCode::CONFLICT, GenericMessage::new("Domain already exists.")));
+/// Gets a domain the user brought, or any domain for domain managers
+fn get_owned_domain(
+	id: i32,
+	user_id: Option<i32>,
+	permissions: &Permissions,
+	repos: &mut dyn Repositories,
+) -> Result<Domain, APIError> {
+	let domain = repos
+		.domains()
+		.get_by_id(id)
+		.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Domain not found")))?;
+
+	if user_id.is_none() || (domain.owner_id != user_id && !permissions.has(Permission::DomainManage)) {
+		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
 	}
 
-	let new_domain = NewDomain {
-		domain: stripped_domain,
-        public: payload.public,
-	};
-
-	match new_domain.insert(conn) {
-		Ok(domain) => Ok((StatusCode::CREATED, Json(domain))),
-		Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to create domain."))),
-	}
+	Ok(domain)
 }
 
 async fn delete_domain(
-	Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
+	Extension(cache): Extension<RedirectCache>,
 	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
 	Extension(config): Extension<Config>,
 	Path(id): Path<i32>,
 ) -> APIResponse<GenericMessage> {
-	if !is_admin(user) {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
-	}
-
-	let conn = &mut pool
-		.get()
+	let base_url = strip_protocol(&config.app.unwrap().base_url)
 		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
 
-	let domain = Domain::get_by_id(id, conn).map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Domain not found")))?;
+	let domain = with_repos(&store, move |repos| {
+		let domain = get_owned_domain(id, user.map(|user| user.id), &permissions, repos)?;
+
+		if domain.domain == base_url {
+			return Err((StatusCode::FORBIDDEN, GenericMessage::new("You are not allowed to delete the base url.")));
+		}
 
-    let base_url = strip_protocol(&config.app.unwrap().base_url).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+		let _ = repos.domains().delete_by_id(id);
 
-    if domain.domain == base_url {
-        return Err((StatusCode::FORBIDDEN, GenericMessage::new("You are not allowed to delete the base url.")));
-    }
+		Ok(domain)
+	})
+	.await?;
 
-	let _ = Domain::delete_by_id(id, conn);
+	cache.invalidate_domain(&domain).await;
 
 	Ok((StatusCode::OK, GenericMessage::new("Domain deleted.")))
 }
 
 async fn get_paged_domains(
-	Extension(pool): Extension<DbPool>,
-	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	_: RequirePermission<CanManageDomains>,
 	Query(pagination): Query<PaginationQuery>,
 ) -> APIResponse<PaginatedResponse<Domain>> {
-	if !is_admin(user) {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
-	}
-
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
-
-	let items = Domain::get_paginated(pagination.page, pagination.per_page, conn)
-		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
-	let total_count = Domain::get_total_count(conn)
-		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
-
-	Ok((StatusCode::OK, Json(PaginatedResponse::<Domain> { items, total_count })))
+	with_repos(&store, move |repos| {
+		let items = repos
+			.domains()
+			.get_paginated(pagination.page, pagination.per_page)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+		let total_count = repos
+			.domains()
+			.get_total_count()
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		Ok((StatusCode::OK, Json(PaginatedResponse::<Domain> { items, total_count })))
+	})
+	.await
 }
 
 async fn update_domain(
-    Extension(pool): Extension<DbPool>,
-	AuthedUser(user): AuthedUser,
+    Extension(store): Extension<SharedStore>,
+	Extension(cache): Extension<RedirectCache>,
+	_: RequirePermission<CanManageDomains>,
     Path(id): Path<i32>,
 	Json(payload): Json<UpdateDomain>,
 ) -> APIResponse<GenericMessage> {
-    if !is_admin(user) {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
-	}
-
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
-
 	if payload.domain.is_some() && !is_url(&payload.domain.clone().unwrap()) {
 		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Provided domain is not a valid URL.")));
 	}
@@ -128,71 +194,325 @@ async fn update_domain(
             Ok(domain) => Some(domain),
             Err(e) => return Err((StatusCode::BAD_REQUEST, GenericMessage::from_string(e.to_string()))),
         },
-        None => return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Failed to parse domain."))),
+        None => None,
     };
 
+	if let Some(slug_strategy) = &payload.slug_strategy {
+		SlugStrategy::from_str(slug_strategy).map_err(|e| (StatusCode::BAD_REQUEST, GenericMessage::from_string(e)))?;
+	}
+
+	if let Some(redirect_type) = payload.redirect_type {
+		RedirectType::try_from(redirect_type).map_err(|e| (StatusCode::BAD_REQUEST, GenericMessage::from_string(e)))?;
+	}
+
 	let update_values = UpdateDomain {
 		domain: domain.clone(),
 		public: payload.public,
+		slug_strategy: payload.slug_strategy,
+		redirect_type: payload.redirect_type,
 	};
 
-	// Check if the new domain already exists
-	
-	if let Some(domain) = domain.clone() {
-		if let Ok(_) = Domain::get_by_domain(domain, conn) {
-			return Err((StatusCode::CONFLICT, GenericMessage::new("Domain already exists.")));
+	let domain = with_repos(&store, move |repos| {
+		// Check if the new domain already exists
+		if let Some(domain) = domain {
+			if let Ok(existing) = repos.domains().get_by_domain(&domain) {
+				if existing.id != id {
+					return Err((StatusCode::CONFLICT, GenericMessage::new("Domain already exists.")));
+				}
+			}
 		}
+
+		let domain = repos
+			.domains()
+			.get_by_id(id)
+			.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Domain not found")))?;
+
+		repos
+			.domains()
+			.update(&domain, update_values)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		Ok(domain)
+	})
+	.await?;
+
+	cache.invalidate_domain(&domain).await;
+
+	Ok((StatusCode::OK, GenericMessage::new("Updated.")))
+}
+
+/// Claims a domain for the user. It stays private, and can only be used once the user proves they control it.
+async fn claim_domain(
+	Extension(store): Extension<SharedStore>,
+	VerifiedUser(user): VerifiedUser,
+	permissions: Permissions,
+	Json(payload): Json<ClaimDomain>,
+) -> APIResponse<OwnedDomain> {
+	let user = match user {
+		Some(user) if permissions.has(Permission::LinkCreate) => user,
+		_ => return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action."))),
+	};
+
+	if !is_url(&payload.domain) {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Provided domain is not a valid URL.")));
 	}
 
-    let domain = Domain::get_by_id(id, conn).map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Domain not found")))?;
+	let stripped_domain = strip_protocol(&payload.domain)
+		.map_err(|e| (StatusCode::BAD_REQUEST, GenericMessage::from_string(e.to_string())))?;
+
+	with_repos(&store, move |repos| {
+		if let Ok(existing) = repos.domains().get_by_domain(&stripped_domain) {
+			let claim_expired = existing.owner_id.is_some()
+				&& !existing.is_verified()
+				&& existing.created_at < (Utc::now() - Duration::days(DOMAIN_CLAIM_TTL_DAYS)).naive_utc();
 
-    match domain.update(update_values, conn) {
-        Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Updated."))),
-        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
-    }
+			if !claim_expired {
+				return Err((StatusCode::CONFLICT, GenericMessage::new("Domain already exists.")));
+			}
+		}
+
+		let new_domain = NewDomain {
+			domain: stripped_domain,
+			public: Some(false),
+			slug_strategy: None,
+			redirect_type: None,
+			owner_id: Some(user.id),
+			verification_token: Some(generate_unique_string(VERIFICATION_TOKEN_LENGTH)),
+			verified_at: None,
+		};
+
+		// The expired claim only makes way for the new one
+		let claimed = repository::transaction(repos, |repos| {
+			if let Ok(existing) = repos.domains().get_by_domain(&new_domain.domain) {
+				repos.domains().delete_by_id(existing.id)?;
+			}
+
+			repos.domains().insert(&new_domain)
+		});
+
+		match claimed {
+			Ok(domain) => Ok((StatusCode::CREATED, Json(OwnedDomain::from(domain)))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to create domain."))),
+		}
+	})
+	.await
 }
 
-async fn get_public_domains(
-    Extension(pool): Extension<DbPool>,
-) -> APIResponse<Vec<Domain>> {
-    let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+async fn get_my_domains(
+	Extension(store): Extension<SharedStore>,
+	AuthedUser(user): AuthedUser,
+) -> APIResponse<Vec<OwnedDomain>> {
+	let Some(user) = user else {
+		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
+	};
+
+	with_repos(&store, move |repos| match repos.domains().get_by_owner_id(user.id) {
+		Ok(domains) => Ok((StatusCode::OK, Json(domains.into_iter().map(OwnedDomain::from).collect()))),
+		Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+	})
+	.await
+}
+
+/// Looks up the domain's TXT record, and marks the domain as verified if it holds the token
+async fn verify_domain(
+	Extension(store): Extension<SharedStore>,
+	Extension(verifier): Extension<DomainVerifier>,
+	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
+	Path(id): Path<i32>,
+) -> APIResponse<OwnedDomain> {
+	let domain =
+		with_repos(&store, move |repos| get_owned_domain(id, user.map(|user| user.id), &permissions, repos)).await?;
+
+	if domain.is_verified() {
+		return Ok((StatusCode::OK, Json(OwnedDomain::from(domain))));
+	}
+
+	let Some(token) = domain.verification_token.as_deref() else {
+		return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")));
+	};
+
+	let found = verifier.verify(&domain.domain, token).await.map_err(|e| {
+		log::warn!("Failed to verify domain {}: {}", domain.domain, e);
+		(
+			StatusCode::BAD_GATEWAY,
+			GenericMessage::with_reason(
+				"Failed to look up the DNS record, try again later.".to_string(),
+				"dns_lookup_failed",
+			),
+		)
+	})?;
+
+	if !found {
+		return Err((
+			StatusCode::BAD_REQUEST,
+			GenericMessage::with_reason(
+				format!(
+					"No TXT record on {} with the value {} was found. DNS changes can take a while to show up.",
+					DomainVerifier::record_name(&domain.domain),
+					DomainVerifier::record_value(token)
+				),
+				"record_not_found",
+			),
+		));
+	}
+
+	with_repos(&store, move |repos| {
+		repos
+			.domains()
+			.set_verified(&domain)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		let domain = repos
+			.domains()
+			.get_by_id(domain.id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		Ok((StatusCode::OK, Json(OwnedDomain::from(domain))))
+	})
+	.await
+}
+
+/// Gives a private domain to a team, so its members can create links on it. Besides domain managers, the owner
+/// of a verified domain can give it to a team they're an admin of.
+async fn set_domain_team(
+	Extension(store): Extension<SharedStore>,
+	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
+	Path(id): Path<i32>,
+	Json(payload): Json<SetDomainTeam>,
+) -> APIResponse<GenericMessage> {
+	let user_id = user.map(|user| user.id);
+	let can_manage = permissions.has(Permission::DomainManage);
+
+	with_repos(&store, move |repos| {
+		let domain = get_owned_domain(id, user_id, &permissions, repos)?;
 
-    match Domain::get_public(conn) {
-        Ok(domains) => Ok((StatusCode::OK, Json(domains))),
-        Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))
-    }
+		if !can_manage && !domain.is_verified() {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("Verify the domain before giving it to a team.")));
+		}
+
+		if let Some(team_id) = payload.team_id {
+			repos
+				.teams()
+				.get_by_id(team_id)
+				.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Team not found")))?;
+
+			let is_team_admin = match user_id {
+				Some(user_id) => get_team_role(team_id, user_id, repos)?.is_some_and(|role| role >= TeamRole::Admin),
+				None => false,
+			};
+
+			if !can_manage && !is_team_admin {
+				return Err((
+					StatusCode::FORBIDDEN,
+					GenericMessage::new("Domains can only be given to teams you're an admin of."),
+				));
+			}
+		}
+
+		match repos.domains().set_team(&domain, payload.team_id) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Updated."))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+		}
+	})
+	.await
+}
+
+async fn get_public_domains(Extension(store): Extension<SharedStore>) -> APIResponse<Vec<Domain>> {
+	with_repos(&store, |repos| match repos.domains().get_public() {
+		Ok(domains) => Ok((StatusCode::OK, Json(domains))),
+		Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+	})
+	.await
 }
 
 async fn get_all_domains(
-    Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
 	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
 ) -> APIResponse<Vec<Domain>> {
-    let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
-    
-    if is_admin(user) {
-		match Domain::get_all(conn) {
-            Ok(domains) => Ok((StatusCode::OK, Json(domains))),
-            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))
-        }
-	} else {
-        match Domain::get_public(conn) {
-            Ok(domains) => Ok((StatusCode::OK, Json(domains))),
-            Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))
-        }
-    }
+	with_repos(&store, move |repos| {
+		if permissions.has(Permission::DomainManage) {
+			match repos.domains().get_all() {
+				Ok(domains) => Ok((StatusCode::OK, Json(domains))),
+				Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+			}
+		} else {
+			let domains = match user {
+				Some(user) => repos.domains().get_usable_by_user(user.id),
+				None => repos.domains().get_public(),
+			};
+
+			match domains {
+				Ok(domains) => Ok((StatusCode::OK, Json(domains))),
+				Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+			}
+		}
+	})
+	.await
 }
 
 // Starts at /api/domains
 pub fn domains_router() -> Router {
 	Router::new()
-		.route("/", get(get_paged_domains))
+		.route("/", get(get_paged_domains).layer(Extension(ApiKeyScope::DomainsAdmin)))
 		.route("/public", get(get_public_domains))
-		.route("/all", get(get_all_domains))
-		.route("/create", post(create_domain))
-		.route("/:id", delete(delete_domain))
-		.route("/:id", put(update_domain))
+		.route("/all", get(get_all_domains).layer(Extension(ApiKeyScope::LinksRead)))
+		.route("/create", post(create_domain).layer(Extension(ApiKeyScope::DomainsAdmin)))
+		.route("/claim", post(claim_domain))
+		.route("/mine", get(get_my_domains))
+		.route("/:id/verify", post(verify_domain))
+		.route("/:id", delete(delete_domain).layer(Extension(ApiKeyScope::DomainsAdmin)))
+		.route("/:id", put(update_domain).layer(Extension(ApiKeyScope::DomainsAdmin)))
+		.route("/:id/team", put(set_domain_team).layer(Extension(ApiKeyScope::DomainsAdmin)))
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_unverified_claim_does_not_block_links() {
+		let app = TestApp::new();
+		let domain = app.add_public_domain("go.to");
+		let claimer = app.sign_up("alice").await;
+		let other = app.sign_up("bob").await;
+
+		let response = app
+			.post("/api/domains/claim", json!({ "domain": "https://example.org" }), Some(&claimer))
+			.await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+
+		let response = app
+			.post("/api/link/shorten", json!({ "domain_id": domain.id, "link": "https://example.org/" }), Some(&other))
+			.await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+	}
+
+	#[tokio::test]
+	async fn test_set_domain_team() {
+		let app = TestApp::new();
+		// The first user is an admin, who could give any domain to any team
+		app.sign_up("root").await;
+		let owner = app.sign_up("alice").await;
+		let other = app.sign_up("bob").await;
+		let domain = app.add_owned_domain("alice.dev", app.user_id("alice"));
+		let admin_team = app.create_team(&other, "Marketing", &[("alice", "admin")]).await;
+		let member_team = app.create_team(&other, "Sales", &[("alice", "member")]).await;
+		let path = format!("/api/domains/{}/team", domain.id);
+
+		let response = app.put(&path, json!({ "team_id": member_team }), Some(&owner)).await;
+		assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+		// Only the owner of the domain can give it away, even to a team they run
+		let response = app.put(&path, json!({ "team_id": admin_team }), Some(&other)).await;
+		assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+
+		let response = app.put(&path, json!({ "team_id": admin_team }), Some(&owner)).await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+	}
 }
@@ -1,144 +1,779 @@
 use crate::{
-	common::{APIResponse, GenericMessage},
+	common::{with_repos, APIError, APIResponse, GenericMessage},
 	config::Config,
 	constants,
-	extensions::auth::AuthedUser,
-	util::{self, is_admin, is_url, starts_with_any},
+	extensions::auth::{effective_permissions, granted_permissions, AuthedUser, Permissions, VerifiedUser},
+	services::redirect_cache::RedirectCache,
+	slug::{SlugGenerator, SlugStrategy},
+	types::{double_option, ApiKeyScope, Permission, RedirectType, TeamRole},
+	url_policy::UrlPolicy,
+	util::{password::hash_password, starts_with_any},
 };
 use axum::{
-	extract::Path,
+	extract::{Path, Query},
 	http::StatusCode,
-	routing::{delete, post},
+	routing::{delete, get, post, put},
 	Extension, Json, Router,
 };
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use db::{
-	models::{Domain, Link, LinkWithDomain, NewLink},
-	DbPool,
+	is_unique_violation,
+	models::{ClickBucket, Domain, Link, LinkWithDomain, NewLink, ReferrerCount, UpdateLink, User},
+	repository::{Repositories, SharedStore},
+	DbError,
 };
 
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use super::teams::get_team_role;
 
 #[derive(Serialize, Deserialize, Debug)]
 struct CreateLink {
 	custom_slug: Option<String>,
 	domain_id: i32,
 	link: String,
+	expires_at: Option<DateTime<Utc>>,
+	max_clicks: Option<i32>,
+	password: Option<String>,
+	redirect_type: Option<RedirectType>,
+	/// Creates the link for a team the user is a member of, instead of for the user
+	team_id: Option<i32>,
+}
+
+/// Fields that can be changed on a link. Nullable fields can be cleared by sending `null`.
+#[derive(Deserialize, Debug)]
+struct UpdateLinkRequest {
+	link: Option<String>,
+	#[serde(default, deserialize_with = "double_option")]
+	custom_slug: Option<Option<String>>,
+	domain_id: Option<i32>,
+	#[serde(default, deserialize_with = "double_option")]
+	expires_at: Option<Option<DateTime<Utc>>>,
+	#[serde(default, deserialize_with = "double_option")]
+	max_clicks: Option<Option<i32>>,
+	#[serde(default, deserialize_with = "double_option")]
+	password: Option<Option<String>>,
+	#[serde(default, deserialize_with = "double_option")]
+	redirect_type: Option<Option<RedirectType>>,
+}
+
+/// The new owner of a link, either a user or a team
+#[derive(Deserialize, Debug)]
+struct TransferLinkRequest {
+	user_id: Option<i32>,
+	team_id: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+struct LinkStats {
+	link_id: i32,
+	total_clicks: i64,
+	unique_visitors: i64,
+	last_clicked_at: Option<NaiveDateTime>,
+	top_referrers: Vec<ReferrerCount>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum StatsInterval {
+	Hour,
+	Day,
+}
+
+impl StatsInterval {
+	fn as_str(&self) -> &'static str {
+		match self {
+			StatsInterval::Hour => "hour",
+			StatsInterval::Day => "day",
+		}
+	}
+}
+
+#[derive(Deserialize, Debug)]
+struct TimeSeriesQuery {
+	interval: Option<StatsInterval>,
+	days: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+struct LinkTimeSeries {
+	link_id: i32,
+	interval: StatsInterval,
+	since: NaiveDateTime,
+	buckets: Vec<ClickBucket>,
+}
+
+/// How many referrers are returned in the link stats
+const TOP_REFERRERS_LIMIT: i64 = 10;
+/// The maximum range of a time series, in days
+const MAX_TIME_SERIES_DAYS: i64 = 365;
+/// How many times slug generation is retried on collisions
+const MAX_SLUG_ATTEMPTS: u32 = 5;
+
+/// Checks the destination against the URL policy, rejections carry a machine readable reason
+fn validate_url(link: &str, config: &Config, repos: &mut dyn Repositories) -> Result<(), APIError> {
+	let policy = UrlPolicy::load(&config.url_policy, repos.domains())
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+
+	policy
+		.check(link)
+		.map_err(|rejection| (StatusCode::BAD_REQUEST, GenericMessage::with_reason(rejection.to_string(), rejection.reason())))
+}
+
+fn validate_expiry(expires_at: Option<DateTime<Utc>>, max_clicks: Option<i32>) -> Result<(), APIError> {
+	if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Expiry date must be in the future.")));
+	}
+
+	if max_clicks.is_some_and(|max_clicks| max_clicks < 1) {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Max clicks must be at least 1.")));
+	}
+
+	Ok(())
+}
+
+fn hash_link_password(password: &str) -> Result<String, APIError> {
+	if password.is_empty() {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Password can't be empty.")));
+	}
+
+	hash_password(password).map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))
+}
+
+/// Checks if a slug is used on a domain by another link than `link_id`
+fn is_slug_taken(
+	slug: &str,
+	domain_id: i32,
+	link_id: Option<i32>,
+	repos: &mut dyn Repositories,
+) -> Result<bool, APIError> {
+	let existing_link = repos
+		.links()
+		.get_by_domain_slug_with_trashed(domain_id, slug)
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+
+	Ok(existing_link.iter().any(|link| Some(link.id) != link_id))
+}
+
+/// Checks that a custom slug isn't reserved or used on the domain by another link than `link_id`
+fn validate_custom_slug(
+	custom_slug: &str,
+	domain_id: i32,
+	link_id: Option<i32>,
+	repos: &mut dyn Repositories,
+) -> Result<(), APIError> {
+	// TODO: Improve this. Maybe make it reject if only matches exacly and with /*, instead of starts with.
+	// TODO: Also ONLY reject on domains that are NOT BASE_URL in config
+	if starts_with_any(custom_slug, &constants::RESERVED_SLUGS) {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Custom slug contains prohibited value")));
+	}
+
+	if is_slug_taken(custom_slug, domain_id, link_id, repos)? {
+		return Err((StatusCode::CONFLICT, GenericMessage::new("Slug already exists.")));
+	}
+
+	Ok(())
+}
+
+/// Generates a slug, growing it if the domain is crowded or earlier attempts collided
+fn generate_slug(
+	generator: &dyn SlugGenerator,
+	domain_id: i32,
+	attempt: u32,
+	repos: &mut dyn Repositories,
+) -> Result<String, APIError> {
+	let internal_error = |_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error"));
+
+	let (used, sequence) = match generator.uses_sequence() {
+		true => {
+			let sequence = repos.links().next_slug_sequence().map_err(internal_error)? as u64;
+			(sequence, sequence)
+		}
+		false => (repos.links().get_domain_count(domain_id).map_err(internal_error)? as u64, 0),
+	};
+
+	let length = generator.length_for(used) + (attempt as usize - 1);
+
+	Ok(generator.generate(length, sequence))
+}
+
+/// Checks if the user is a member of the team
+fn is_team_member(team_id: i32, user_id: i32, repos: &mut dyn Repositories) -> Result<bool, APIError> {
+	repos
+		.teams()
+		.get_member(team_id, user_id)
+		.map(|member| member.is_some())
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))
+}
+
+/// Maps a failed link write to a response, unique violations mean the slug got taken in the meantime
+fn link_write_error(error: DbError) -> APIError {
+	match is_unique_violation(&error) {
+		true => (StatusCode::CONFLICT, GenericMessage::new("Slug already exists.")),
+		false => (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")),
+	}
+}
+
+/// Gets a domain the user is allowed to create links on. Private ones can only be used by domain managers,
+/// members of the team the domain belongs to, and the user who brought it once they verified it.
+fn get_usable_domain(
+	domain_id: i32,
+	user_id: Option<i32>,
+	permissions: &Permissions,
+	repos: &mut dyn Repositories,
+) -> Result<Domain, APIError> {
+	let domain = repos
+		.domains()
+		.get_by_id(domain_id)
+		.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Domain not found")))?;
+
+	if domain.public || permissions.has(Permission::DomainManage) {
+		return Ok(domain);
+	}
+
+	let is_team_domain = match (domain.team_id, user_id) {
+		(Some(team_id), Some(user_id)) => is_team_member(team_id, user_id, repos)?,
+		_ => false,
+	};
+	let is_owned_domain = user_id.is_some() && domain.owner_id == user_id;
+
+	if !domain.is_verified() || !(is_team_domain || is_owned_domain) {
+		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
+	}
+
+	Ok(domain)
 }
 
 async fn create_link(
 	Extension(config): Extension<Config>,
-	Extension(pool): Extension<DbPool>,
-	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	VerifiedUser(user): VerifiedUser,
+	permissions: Permissions,
 	Json(payload): Json<CreateLink>,
 ) -> APIResponse<LinkWithDomain> {
-	let app_config = config.app.unwrap();
+	let app_config = config.app.clone().unwrap();
 	let owner_id: Option<i32> = user.clone().map(|u| u.id);
 
 	if !app_config.allow_anonymous_shorten && owner_id.is_none() {
 		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
 	}
 
+	if owner_id.is_some() && !permissions.has(Permission::LinkCreate) {
+		return Err((StatusCode::FORBIDDEN, GenericMessage::new("You are not allowed to create links.")));
+	}
+
 	// Validate before even getting the db
 
-	if !is_url(&payload.link) {
-		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Provided link is not a valid URL.")));
-	}
+	validate_expiry(payload.expires_at, payload.max_clicks)?;
+
+	let password_hash = match payload.password.as_deref() {
+		Some(password) => Some(hash_link_password(password)?),
+		None => None,
+	};
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+	with_repos(&store, move |repos| {
+		validate_url(&payload.link, &config, repos)?;
 
-	if payload.custom_slug.clone().is_some() {
-		let custom_slug = payload.custom_slug.clone().unwrap();
+		// Team links belong to the team alone, so they stay when the member who created them leaves
+		let (owner_id, team_id) = match (payload.team_id, owner_id) {
+			(Some(team_id), Some(user_id)) => {
+				if !is_team_member(team_id, user_id, repos)? {
+					return Err((StatusCode::NOT_FOUND, GenericMessage::new("Team not found")));
+				}
 
-		// Validate custom slug
-		// TODO: Improve this. Maybe make it reject if only matches exacly and with /*, instead of starts with.
-		// TODO: Also ONLY reject on domains that are NOT BASE_URL in config
-		if starts_with_any(&custom_slug, &constants::RESERVED_SLUGS) {
-			return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Custom slug contains prohibited value")));
+				(None, Some(team_id))
+			}
+			(Some(_), None) => {
+				return Err((
+					StatusCode::UNAUTHORIZED,
+					GenericMessage::new("You are not allowed to perform this action."),
+				))
+			}
+			(None, owner_id) => (owner_id, None),
+		};
+
+		let domain = get_usable_domain(payload.domain_id, user.as_ref().map(|user| user.id), &permissions, repos)?;
+
+		if let Some(custom_slug) = &payload.custom_slug {
+			validate_custom_slug(custom_slug, domain.id, None, repos)?;
 		}
 
-		let existing_link = Link::get_by_slug(&payload.custom_slug.clone().unwrap(), conn);
+		let mut new_link = NewLink {
+			custom_slug: payload.custom_slug,
+			original_link: payload.link,
+			domain_id: domain.id,
+			owner_id,
+			slug: String::new(),
+			expires_at: payload.expires_at.map(|expires_at| expires_at.naive_utc()),
+			max_clicks: payload.max_clicks,
+			password_hash,
+			redirect_type: payload.redirect_type.map(i32::from),
+			team_id,
+		};
+
+		let strategy = match &domain.slug_strategy {
+			Some(strategy) => SlugStrategy::from_str(strategy)
+				.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e)))?,
+			None => app_config.slug_strategy,
+		};
+		let generator = strategy.generator(&config);
+
+		let mut attempts = 0;
+
+		// Generated slugs can collide with existing ones, so keep generating until one sticks
+		let link = loop {
+			attempts += 1;
+
+			new_link.slug = generate_slug(generator.as_ref(), domain.id, attempts, repos)?;
+
+			if is_slug_taken(&new_link.slug, domain.id, None, repos)? {
+				if attempts >= MAX_SLUG_ATTEMPTS {
+					return Err((StatusCode::CONFLICT, GenericMessage::new("Failed to generate a unique slug.")));
+				}
+
+				continue;
+			}
+
+			match repos.links().insert(&new_link) {
+				Ok(link) => break link,
+				// Lost a race for the generated slug, try another one
+				Err(e) if is_unique_violation(&e) && new_link.custom_slug.is_none() && attempts < MAX_SLUG_ATTEMPTS => {
+					continue
+				}
+				Err(e) => return Err(link_write_error(e)),
+			}
+		};
+
+		let created_link = LinkWithDomain::new(link, domain.domain);
 
-		if existing_link.is_err() {
-			return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")));
+		// Return a response
+		Ok((StatusCode::CREATED, Json(created_link)))
+	})
+	.await
+}
+
+async fn update_link(
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+	Extension(cache): Extension<RedirectCache>,
+	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
+	Path(id): Path<i32>,
+	Json(payload): Json<UpdateLinkRequest>,
+) -> APIResponse<LinkWithDomain> {
+	validate_expiry(payload.expires_at.flatten(), payload.max_clicks.flatten())?;
+
+	let password_hash = match payload.password.as_ref() {
+		Some(Some(password)) => Some(Some(hash_link_password(password)?)),
+		Some(None) => Some(None),
+		None => None,
+	};
+
+	let (link, updated, domain) = with_repos(&store, move |repos| {
+		let user_id = user.as_ref().map(|user| user.id);
+		let link = get_managed_link(id, user, &permissions, repos)?;
+
+		if let Some(original_link) = &payload.link {
+			validate_url(original_link, &config, repos)?;
 		}
 
-		let existing_link = existing_link.unwrap();
+		let domain = match payload.domain_id {
+			Some(domain_id) if domain_id != link.domain_id => {
+				get_usable_domain(domain_id, user_id, &permissions, repos)?
+			}
+			_ => repos
+				.domains()
+				.get_by_id(link.domain_id)
+				.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?,
+		};
+
+		match &payload.custom_slug {
+			Some(Some(custom_slug)) => validate_custom_slug(custom_slug, domain.id, Some(link.id), repos)?,
+			// Keeping the custom slug, but it has to be free on the new domain too
+			None if domain.id != link.domain_id => {
+				if let Some(custom_slug) = &link.custom_slug {
+					if is_slug_taken(custom_slug, domain.id, Some(link.id), repos)? {
+						return Err((StatusCode::CONFLICT, GenericMessage::new("Slug already exists.")));
+					}
+				}
+			}
+			_ => {}
+		}
 
-		if existing_link.len() > 0 {
+		if domain.id != link.domain_id && is_slug_taken(&link.slug, domain.id, Some(link.id), repos)? {
 			return Err((StatusCode::CONFLICT, GenericMessage::new("Slug already exists.")));
 		}
+
+		// Changing the limits revives an archived link, the scheduler archives it again if it's still expired
+		let archived_at = match payload.expires_at.is_some() || payload.max_clicks.is_some() {
+			true => Some(None),
+			false => None,
+		};
+
+		let values = UpdateLink {
+			original_link: payload.link,
+			custom_slug: payload.custom_slug,
+			domain_id: payload.domain_id,
+			expires_at: payload
+				.expires_at
+				.map(|expires_at| expires_at.map(|expires_at| expires_at.naive_utc())),
+			max_clicks: payload.max_clicks,
+			password_hash,
+			archived_at,
+			redirect_type: payload.redirect_type.map(|redirect_type| redirect_type.map(i32::from)),
+		};
+
+		let updated = repos.links().update(&link, values).map_err(link_write_error)?;
+
+		Ok((link, updated, domain))
+	})
+	.await?;
+
+	// The old slugs may not point to it anymore
+	cache.invalidate_link(&link).await;
+	cache.invalidate_link(&updated).await;
+
+	Ok((StatusCode::OK, Json(LinkWithDomain::new(updated, domain.domain))))
+}
+
+/// Gets a link that the user or one of their teams owns, or any link if the user can manage every link
+fn get_managed_link(
+	id: i32,
+	user: Option<User>,
+	permissions: &Permissions,
+	repos: &mut dyn Repositories,
+) -> Result<Link, APIError> {
+	let user = match user {
+		Some(user) => user,
+		None => {
+			return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))
+		}
+	};
+
+	let link = repos
+		.links()
+		.get_by_id(id)
+		.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Link not found")))?;
+
+	if link.owner_id == Some(user.id) || permissions.has(Permission::LinkManageAny) {
+		return Ok(link);
 	}
-	
-	let domain = Domain::get_by_id(payload.domain_id, conn).map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
 
-	if !domain.public && !is_admin(user) {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action."))); 
+	let is_team_link = match link.team_id {
+		Some(team_id) => is_team_member(team_id, user.id, repos)?,
+		None => false,
+	};
+
+	if !is_team_link {
+		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
 	}
 
-	let slug = util::generate_unique_string(app_config.shortened_link_length);
+	Ok(link)
+}
 
-	let new_link = NewLink {
-		custom_slug: payload.custom_slug,
-		original_link: payload.link,
-		domain_id: payload.domain_id,
-		owner_id,
-		slug: slug,
-	};
+/// Hands a link over to a user or a team. Links can be given to teams the user is in, and to users they
+/// share a team with. Only the owner of the link, admins of its team and link managers can transfer it, and
+/// only to someone who can use its domain.
+async fn transfer_link(
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
+	Path(id): Path<i32>,
+	Json(payload): Json<TransferLinkRequest>,
+) -> APIResponse<LinkWithDomain> {
+	with_repos(&store, move |repos| {
+		let user_id = user.as_ref().map(|user| user.id).unwrap_or_default();
+		let link = get_managed_link(id, user, &permissions, repos)?;
+		let can_manage_any = permissions.has(Permission::LinkManageAny);
+
+		let internal_error = |_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."));
+
+		let is_team_admin = match link.team_id {
+			Some(team_id) => get_team_role(team_id, user_id, repos)?.is_some_and(|role| role >= TeamRole::Admin),
+			None => false,
+		};
+
+		if link.owner_id != Some(user_id) && !is_team_admin && !can_manage_any {
+			return Err((
+				StatusCode::FORBIDDEN,
+				GenericMessage::new("Only the owner of the link or an admin of its team can transfer it."),
+			));
+		}
+
+		let unusable_domain =
+			|| (StatusCode::BAD_REQUEST, GenericMessage::new("The domain of the link can't be used by the new owner."));
+
+		let domain = match (payload.user_id, payload.team_id) {
+			(Some(new_owner_id), None) => {
+				let new_owner = repos
+					.users()
+					.get_by_id(new_owner_id)
+					.map_err(internal_error)?
+					.ok_or((StatusCode::NOT_FOUND, GenericMessage::new("User not found")))?;
+
+				let can_give = new_owner_id == user_id
+					|| can_manage_any
+					|| repos
+						.teams()
+						.share_team(user_id, new_owner_id)
+						.map_err(internal_error)?;
+
+				if !can_give {
+					return Err((
+						StatusCode::FORBIDDEN,
+						GenericMessage::new("Links can only be given to users you share a team with."),
+					));
+				}
+
+				let granted = granted_permissions(new_owner_id, repos).map_err(internal_error)?;
+				let new_owner_permissions = Permissions(effective_permissions(granted, &new_owner, &config));
+
+				match get_usable_domain(link.domain_id, Some(new_owner_id), &new_owner_permissions, repos) {
+					Ok(domain) => domain,
+					Err((StatusCode::UNAUTHORIZED, _)) => return Err(unusable_domain()),
+					Err(e) => return Err(e),
+				}
+			}
+			(None, Some(team_id)) => {
+				if repos.teams().get_by_id(team_id).is_err() {
+					return Err((StatusCode::NOT_FOUND, GenericMessage::new("Team not found")));
+				}
 
-	let link = new_link.insert(conn);
+				if !can_manage_any && !is_team_member(team_id, user_id, repos)? {
+					return Err((StatusCode::NOT_FOUND, GenericMessage::new("Team not found")));
+				}
 
-	let created_link = LinkWithDomain::new(link, domain.domain);
+				let domain = repos.domains().get_by_id(link.domain_id).map_err(internal_error)?;
 
-	// Return a response
-	Ok((StatusCode::CREATED, Json(created_link)))
+				if !domain.public && domain.team_id != Some(team_id) {
+					return Err(unusable_domain());
+				}
+
+				domain
+			}
+			_ => {
+				return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Either a user or a team has to be given.")))
+			}
+		};
+
+		match repos.links().transfer(&link, payload.user_id, payload.team_id) {
+			Ok(link) => Ok((StatusCode::OK, Json(LinkWithDomain::new(link, domain.domain)))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+		}
+	})
+	.await
 }
 
 async fn delete_link(
-	Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
+	Extension(cache): Extension<RedirectCache>,
 	AuthedUser(user): AuthedUser,
-	Path(slug): Path<String>,
+	permissions: Permissions,
+	Path(id): Path<i32>,
 ) -> APIResponse<GenericMessage> {
-	// TODO: Check link ownership
+	let existing_link = with_repos(&store, move |repos| {
+		let existing_link = get_managed_link(id, user, &permissions, repos)?;
 
-	let owner_id: Option<i32> = user.map(|u| u.id);
+		repos
+			.links()
+			.delete(&existing_link)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
 
-	if owner_id.is_none() {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
-	}
+		Ok(existing_link)
+	})
+	.await?;
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+	cache.invalidate_link(&existing_link).await;
 
-	let existing_link = Link::get_by_slug(&slug, conn);
+	Ok((StatusCode::OK, GenericMessage::new("Link moved to trash.")))
+}
 
-	if existing_link.is_err() {
-		return Err((StatusCode::NOT_FOUND, GenericMessage::new("Slug not found")));
-	}
+async fn link_stats(
+	Extension(store): Extension<SharedStore>,
+	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
+	Path(id): Path<i32>,
+) -> APIResponse<LinkStats> {
+	with_repos(&store, move |repos| {
+		let link = get_managed_link(id, user, &permissions, repos)?;
 
-	let existing_link = existing_link.unwrap();
+		let internal_error = |_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."));
 
-	if existing_link.len() == 0 {
-		return Err((StatusCode::NOT_FOUND, GenericMessage::new("Slug not found")));
-	}
+		let stats = LinkStats {
+			link_id: link.id,
+			total_clicks: repos.links().get_click_count(link.id).map_err(internal_error)?,
+			unique_visitors: repos.links().get_unique_click_count(link.id).map_err(internal_error)?,
+			last_clicked_at: repos.links().get_last_clicked_at(link.id).map_err(internal_error)?,
+			top_referrers: repos
+				.links()
+				.get_top_referrers(link.id, TOP_REFERRERS_LIMIT)
+				.map_err(internal_error)?,
+		};
+
+		Ok((StatusCode::OK, Json(stats)))
+	})
+	.await
+}
 
-	let existing_link = existing_link.first().unwrap();
+async fn link_time_series(
+	Extension(store): Extension<SharedStore>,
+	AuthedUser(user): AuthedUser,
+	permissions: Permissions,
+	Path(id): Path<i32>,
+	Query(query): Query<TimeSeriesQuery>,
+) -> APIResponse<LinkTimeSeries> {
+	let interval = query.interval.unwrap_or(StatsInterval::Day);
+	let days = query.days.unwrap_or(30);
 
-	if existing_link.owner_id != owner_id {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
+	if !(1..=MAX_TIME_SERIES_DAYS).contains(&days) {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Days must be between 1 and 365.")));
 	}
 
-	return match existing_link.delete(conn) {
-		Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Slug deleted."))),
-		Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
-	};
+	with_repos(&store, move |repos| {
+		let link = get_managed_link(id, user, &permissions, repos)?;
+
+		let since = (Utc::now() - Duration::days(days)).naive_utc();
+
+		let buckets = repos
+			.links()
+			.get_time_series(link.id, interval.as_str(), since)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		Ok((
+			StatusCode::OK,
+			Json(LinkTimeSeries {
+				link_id: link.id,
+				interval,
+				since,
+				buckets,
+			}),
+		))
+	})
+	.await
 }
 
 // Starts at /api/link
 pub fn links_router() -> Router {
 	Router::new()
-		.route("/shorten", post(create_link))
-		.route("/:slug", delete(delete_link))
+		.route("/shorten", post(create_link).layer(Extension(ApiKeyScope::LinksWrite)))
+		.route("/:id", delete(delete_link).layer(Extension(ApiKeyScope::LinksWrite)))
+		.route("/:id", put(update_link).layer(Extension(ApiKeyScope::LinksWrite)))
+		.route("/:id/transfer", post(transfer_link).layer(Extension(ApiKeyScope::LinksWrite)))
+		.route("/:id/stats", get(link_stats).layer(Extension(ApiKeyScope::LinksRead)))
+		.route("/:id/stats/timeseries", get(link_time_series).layer(Extension(ApiKeyScope::LinksRead)))
+}
+
+#[cfg(test)]
+mod test {
+	use axum::http::Method;
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_shorten_and_redirect() {
+		let app = TestApp::new();
+		let domain = app.add_public_domain("go.to");
+		let token = app.sign_up("alice").await;
+
+		let response = app
+			.post("/api/link/shorten", json!({ "domain_id": domain.id, "link": "https://example.com/" }), Some(&token))
+			.await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+
+		let slug = response.body["slug"].as_str().unwrap().to_string();
+		let response = app
+			.request(Method::GET, "go.to", &format!("/{}", slug), None, None)
+			.await;
+		assert!(response.status.is_redirection());
+		assert_eq!(response.location.as_deref(), Some("https://example.com/"));
+
+		let response = app.get("/api/user/me/links?page=1&per_page=10", Some(&token)).await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+		assert!(response.body.to_string().contains(&slug));
+	}
+
+	#[tokio::test]
+	async fn test_custom_slug_conflict() {
+		let app = TestApp::new();
+		let domain = app.add_public_domain("go.to");
+		let link = json!({ "domain_id": domain.id, "link": "https://example.com/", "custom_slug": "docs" });
+
+		let response = app.post("/api/link/shorten", link.clone(), None).await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+
+		let response = app.post("/api/link/shorten", link, None).await;
+		assert_eq!(response.status, StatusCode::CONFLICT);
+	}
+
+	#[tokio::test]
+	async fn test_delete_needs_owner() {
+		let app = TestApp::new();
+		let domain = app.add_public_domain("go.to");
+		let owner = app.sign_up("alice").await;
+		let other = app.sign_up("bob").await;
+
+		let response = app
+			.post("/api/link/shorten", json!({ "domain_id": domain.id, "link": "https://example.com/" }), Some(&owner))
+			.await;
+		let path = format!("/api/link/{}", response.body["id"]);
+
+		let response = app.request(Method::DELETE, "sho.rt", &path, None, Some(&other)).await;
+		assert!(response.status.is_client_error());
+
+		let response = app.request(Method::DELETE, "sho.rt", &path, None, Some(&owner)).await;
+		assert_eq!(response.status, StatusCode::OK);
+	}
+
+	#[tokio::test]
+	async fn test_transfer_link() {
+		let app = TestApp::new();
+		let public_domain = app.add_public_domain("go.to");
+		// The first user is an admin, who could transfer any link
+		app.sign_up("root").await;
+		let owner = app.sign_up("alice").await;
+		let member = app.sign_up("bob").await;
+		app.sign_up("carol").await;
+		let private_domain = app.add_owned_domain("alice.dev", app.user_id("alice"));
+		let team_id = app
+			.create_team(&owner, "Marketing", &[("bob", "member"), ("carol", "member")])
+			.await;
+
+		let shorten = |domain_id: i32, team_id: Option<i64>| {
+			app.post(
+				"/api/link/shorten",
+				json!({ "domain_id": domain_id, "link": "https://example.com/", "team_id": team_id }),
+				Some(&owner),
+			)
+		};
+
+		// Members can't give away the team's links
+		let response = shorten(public_domain.id, Some(team_id)).await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+		let path = format!("/api/link/{}/transfer", response.body["id"]);
+		let response = app
+			.post(&path, json!({ "user_id": app.user_id("carol") }), Some(&member))
+			.await;
+		assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+		// Bob can't create links on Alice's own domain, so he can't be given one either
+		let response = shorten(private_domain.id, None).await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+		let path = format!("/api/link/{}/transfer", response.body["id"]);
+		let response = app
+			.post(&path, json!({ "user_id": app.user_id("bob") }), Some(&owner))
+			.await;
+		assert_eq!(response.status, StatusCode::BAD_REQUEST);
+
+		let response = shorten(public_domain.id, None).await;
+		let path = format!("/api/link/{}/transfer", response.body["id"]);
+		let response = app
+			.post(&path, json!({ "user_id": app.user_id("bob") }), Some(&owner))
+			.await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+		assert_eq!(response.body["owner_id"], app.user_id("bob"));
+	}
 }
@@ -1,10 +1,19 @@
 use axum::{http::StatusCode, response::IntoResponse, Router, routing::get};
 
+pub mod admin_cache;
+pub mod admin_users;
+pub mod api_keys;
 pub mod config;
 pub mod domains;
 pub mod links;
+pub mod oidc;
+pub mod roles;
 pub mod user;
+pub mod sessions;
 pub mod setup;
+pub mod teams;
+pub mod two_factor;
+pub mod url_rules;
 
 async fn health_check() -> impl IntoResponse {
     StatusCode::OK
@@ -19,4 +28,9 @@ pub fn api_router() -> Router {
 		.nest("/config", config::config_router())
 		.nest("/domains", domains::domains_router())
 		.nest("/setup", setup::setup_router())
+		.nest("/url-rules", url_rules::url_rules_router())
+		.nest("/roles", roles::roles_router())
+		.nest("/teams", teams::teams_router())
+		.nest("/admin/users", admin_users::admin_users_router())
+		.nest("/admin/cache", admin_cache::admin_cache_router())
 }
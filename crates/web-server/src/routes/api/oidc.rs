@@ -0,0 +1,426 @@
+use axum::{
+	extract::Query,
+	http::{HeaderMap, StatusCode},
+	response::Redirect,
+	routing::get,
+	Extension, Router,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::Utc;
+use db::{
+	models::{NewUser, NewUserIdentity, User, ADMIN_ROLE, USER_ROLE},
+	repository::{self, Repositories, SharedStore},
+	DbError,
+};
+use serde::Deserialize;
+
+use super::{sessions::start_session, user::TWO_FACTOR_LOGIN_TTL_MINUTES};
+use crate::{
+	common::{APIError, GenericMessage},
+	config::{Config, OidcConfig},
+	services::oidc::{OidcIdentity, OidcProvider},
+	util::{
+		generate_unique_string,
+		jwt::{decode_oidc_login_token, encode_oidc_login_token, encode_two_factor_token},
+		password::hash_password,
+	},
+};
+
+/// Holds the state, nonce and PKCE verifier while the user is at the provider
+const OIDC_LOGIN_COOKIE: &str = "oidc_login";
+/// Only sent back to the callback
+const OIDC_LOGIN_COOKIE_PATH: &str = "/api/user/oidc";
+/// How long the user can take to sign in at the provider
+const OIDC_LOGIN_TTL_MINUTES: i64 = 10;
+const MAX_USERNAME_LENGTH: usize = 32;
+
+#[derive(Deserialize, Debug)]
+struct CallbackQuery {
+	code: Option<String>,
+	state: Option<String>,
+	error: Option<String>,
+	error_description: Option<String>,
+}
+
+fn login_cookie(value: String, max_age: time::Duration, config: &Config) -> Cookie<'static> {
+	Cookie::build((OIDC_LOGIN_COOKIE, value))
+		.http_only(true)
+		.secure(config.secure_cookies())
+		// Lax, so the cookie is sent along when the provider redirects back
+		.same_site(SameSite::Lax)
+		.path(OIDC_LOGIN_COOKIE_PATH)
+		.max_age(max_age)
+		.build()
+}
+
+fn redirect_url(oidc_config: &OidcConfig, config: &Config) -> String {
+	oidc_config.redirect_url.clone().unwrap_or_else(|| {
+		format!("{}/api/user/oidc/callback", config.app.as_ref().unwrap().base_url.trim_end_matches('/'))
+	})
+}
+
+/// Sends the user back to the login page, which shows the message
+fn login_error_redirect(message: &str) -> Redirect {
+	let message: String = url::form_urlencoded::byte_serialize(message.as_bytes()).collect();
+
+	Redirect::to(&format!("/dash/login?error={}", message))
+}
+
+/// Turns the provider's username (or the email's local part) into one that's free here
+fn available_username(identity: &OidcIdentity, repos: &mut dyn Repositories) -> String {
+	let base = username_candidate(identity);
+
+	if !repos.users().username_exists(&base) {
+		return base;
+	}
+
+	loop {
+		let username = format!("{}-{}", base, generate_unique_string(4).to_lowercase());
+
+		if !repos.users().username_exists(&username) {
+			return username;
+		}
+	}
+}
+
+fn username_candidate(identity: &OidcIdentity) -> String {
+	let source = identity
+		.preferred_username
+		.as_deref()
+		.or_else(|| identity.email.as_deref().and_then(|email| email.split('@').next()))
+		.unwrap_or_default();
+
+	let username: String = source
+		.chars()
+		.filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+		.take(MAX_USERNAME_LENGTH)
+		.collect();
+
+	match username.is_empty() {
+		true => "user".to_string(),
+		false => username,
+	}
+}
+
+/// Finds the user linked to the identity. Otherwise links the user with the same (verified) email,
+/// or creates a new one if auto provisioning is on.
+fn find_or_provision_user(
+	identity: &OidcIdentity,
+	oidc_config: &OidcConfig,
+	repos: &mut dyn Repositories,
+) -> Result<User, String> {
+	let internal_error = |e: DbError| {
+		log::error!("Failed to find OIDC user: {:#?}", e);
+		"Internal server error.".to_string()
+	};
+
+	if let Some((linked_identity, user)) = repos
+		.users()
+		.get_identity(&identity.issuer, &identity.subject)
+		.map_err(internal_error)?
+	{
+		if let Err(e) = repos
+			.users()
+			.touch_identity(&linked_identity, identity.email.as_deref())
+		{
+			log::error!("Failed to update identity: {:#?}", e);
+		}
+
+		return Ok(user);
+	}
+
+	let email = identity
+		.email
+		.as_deref()
+		.ok_or("The identity provider didn't share your email address.")?;
+
+	let new_identity = |user_id| NewUserIdentity {
+		user_id,
+		issuer: identity.issuer.clone(),
+		subject: identity.subject.clone(),
+		email: identity.email.clone(),
+	};
+
+	let existing_user = repos.users().get_by_email(email).map_err(internal_error)?;
+
+	let user = match existing_user {
+		// Only take the provider's word that it's the same person if it checked the address
+		Some(user) if identity.email_verified => user,
+		Some(_) => {
+			return Err("An account with this email already exists, please sign in with your password.".to_string())
+		}
+		None if !oidc_config.auto_provision => {
+			return Err("There's no account for you yet, ask an admin to create one.".to_string())
+		}
+		None if repos.users().email_exists(email) => {
+			return Err("An account with this email already exists, please sign in with your password.".to_string())
+		}
+		None => {
+			// Nobody knows the password, but it can be reset by email to also sign in without the provider
+			let password_hash = hash_password(&generate_unique_string(64)).map_err(|_| "Internal server error.")?;
+
+			let new_user = NewUser {
+				username: available_username(identity, repos),
+				password_hash,
+				email: email.to_string(),
+			};
+
+			// The account only exists along with its role and the identity to sign in with
+			return repository::transaction(repos, |repos| {
+				let user = repos.users().insert(&new_user)?;
+				repos.users().assign_role(USER_ROLE, user.id)?;
+
+				if identity.email_verified {
+					repos.users().set_verified_at(&user, Some(Utc::now().naive_utc()))?;
+				}
+
+				repos.users().insert_identity(&new_identity(user.id))?;
+
+				Ok(user)
+			})
+			.map_err(internal_error);
+		}
+	};
+
+	repos
+		.users()
+		.insert_identity(&new_identity(user.id))
+		.map_err(internal_error)?;
+
+	Ok(user)
+}
+
+/// Checks the provider's response and gets the user that signed in
+async fn authenticate(
+	query: CallbackQuery,
+	login_token: Option<String>,
+	config: &Config,
+	store: &SharedStore,
+) -> Result<User, String> {
+	let oidc_config = config.enabled_oidc().ok_or("Single sign-on is not enabled.")?;
+	let jwt_secret = config.security.as_ref().unwrap().jwt_secret.as_bytes();
+
+	if let Some(error) = query.error {
+		log::info!("OIDC provider returned an error: {} {:?}", error, query.error_description);
+		return Err(format!("The identity provider refused the sign in: {}", query.error_description.unwrap_or(error)));
+	}
+
+	let login_state = login_token
+		.and_then(|token| decode_oidc_login_token(&token, jwt_secret))
+		.ok_or("Sign in expired, please try again.")?;
+
+	if query.state.as_deref() != Some(login_state.csrf_state.as_str()) {
+		return Err("Invalid sign in state, please try again.".to_string());
+	}
+
+	let code = query
+		.code
+		.ok_or("The identity provider didn't send an authorization code.")?;
+
+	let provider = OidcProvider::discover(oidc_config, redirect_url(oidc_config, config))
+		.await
+		.map_err(|e| {
+			log::error!("{}", e);
+			"Couldn't reach the identity provider."
+		})?;
+
+	let identity = provider.exchange(code, &login_state).await.map_err(|e| {
+		log::warn!("{}", e);
+		"Couldn't verify the sign in with the identity provider."
+	})?;
+
+	let oidc_config = oidc_config.clone();
+
+	db::repository::run(store, move |repos| {
+		let user = find_or_provision_user(&identity, &oidc_config, repos)?;
+
+		if user.deleted_at.is_some() {
+			return Err("This account has been deleted.".to_string());
+		}
+
+		if user.is_suspended() {
+			return Err("This account has been suspended.".to_string());
+		}
+
+		if let Some(admin_group) = &oidc_config.admin_group {
+			let admin_role = repos
+				.roles()
+				.get_by_name(ADMIN_ROLE)
+				.map_err(|_| "Internal server error.")?;
+
+			let synced = match identity.groups.contains(admin_group) {
+				true => repos.roles().assign(&admin_role, user.id),
+				false => repos.roles().unassign(&admin_role, user.id),
+			};
+
+			synced.map_err(|_| "Internal server error.")?;
+		}
+
+		Ok(user)
+	})
+	.await
+	.map_err(|e| {
+		log::error!("{}", e);
+		"Internal server error.".to_string()
+	})?
+}
+
+/// Sends the user to the provider to sign in
+async fn oidc_login(jar: CookieJar, Extension(config): Extension<Config>) -> Result<(CookieJar, Redirect), APIError> {
+	let oidc_config = config
+		.enabled_oidc()
+		.ok_or((StatusCode::NOT_FOUND, GenericMessage::new("Single sign-on is not enabled.")))?;
+
+	let provider = OidcProvider::discover(oidc_config, redirect_url(oidc_config, &config))
+		.await
+		.map_err(|e| {
+			log::error!("{}", e);
+			(StatusCode::BAD_GATEWAY, GenericMessage::new("Couldn't reach the identity provider."))
+		})?;
+
+	let (authorize_url, login_state) = provider.authorize_url();
+
+	let ttl = time::Duration::minutes(OIDC_LOGIN_TTL_MINUTES);
+	let token = encode_oidc_login_token(&login_state, ttl, config.security.as_ref().unwrap().jwt_secret.as_bytes());
+
+	Ok((jar.add(login_cookie(token, ttl, &config)), Redirect::to(authorize_url.as_str())))
+}
+
+/// Where the provider sends the user back to. Starts a session, or the 2FA step if the user has it enabled.
+async fn oidc_callback(
+	jar: CookieJar,
+	headers: HeaderMap,
+	Query(query): Query<CallbackQuery>,
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+) -> (CookieJar, Redirect) {
+	let login_token = jar.get(OIDC_LOGIN_COOKIE).map(|cookie| cookie.value().to_string());
+
+	// The login state can only be used once
+	let mut expired_cookie = login_cookie("deleted".to_string(), time::Duration::ZERO, &config);
+	expired_cookie.set_expires(time::OffsetDateTime::from_unix_timestamp(0).unwrap());
+	let jar = jar.add(expired_cookie);
+
+	let user = match authenticate(query, login_token, &config, &store).await {
+		Ok(user) => user,
+		Err(message) => return (jar, login_error_redirect(&message)),
+	};
+
+	if user.has_two_factor() {
+		let two_factor_token = encode_two_factor_token(
+			user.id,
+			time::Duration::minutes(TWO_FACTOR_LOGIN_TTL_MINUTES),
+			config.security.as_ref().unwrap().jwt_secret.as_bytes(),
+		);
+
+		return (jar, Redirect::to(&format!("/dash/login?two_factor_token={}", two_factor_token)));
+	}
+
+	let session_jar = jar.clone();
+	let session =
+		db::repository::run(&store, move |repos| start_session(session_jar, user.id, &headers, &config, repos)).await;
+
+	match session {
+		Ok(Ok((jar, _))) => (jar, Redirect::to("/dash/links")),
+		Ok(Err(_)) => (jar, login_error_redirect("Failed to create session.")),
+		Err(e) => {
+			log::error!("{}", e);
+			(jar, login_error_redirect("Internal server error."))
+		}
+	}
+}
+
+// Starts at /api/user/oidc
+pub fn oidc_router() -> Router {
+	Router::new()
+		.route("/login", get(oidc_login))
+		.route("/callback", get(oidc_callback))
+}
+
+#[cfg(test)]
+mod test {
+	use db::repository::{MemoryStore, Store};
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	fn identity(preferred_username: Option<&str>, email: Option<&str>) -> OidcIdentity {
+		OidcIdentity {
+			issuer: "https://idp.example.com".to_string(),
+			subject: "1234".to_string(),
+			email: email.map(|email| email.to_string()),
+			email_verified: true,
+			preferred_username: preferred_username.map(|username| username.to_string()),
+			groups: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn username_candidate_test() {
+		assert_eq!(username_candidate(&identity(Some("jane.doe"), Some("jd@example.com"))), "jane.doe");
+		assert_eq!(username_candidate(&identity(None, Some("jd+links@example.com"))), "jdlinks");
+		assert_eq!(username_candidate(&identity(Some("Jane Doe <>"), None)), "JaneDoe");
+		assert_eq!(username_candidate(&identity(None, None)), "user");
+		assert_eq!(username_candidate(&identity(Some(&"a".repeat(100)), None)).len(), MAX_USERNAME_LENGTH);
+	}
+
+	#[test]
+	fn find_or_provision_user_test() {
+		let oidc_config: OidcConfig = toml::from_str(
+			r#"
+			enabled = true
+			issuer_url = "https://idp.example.com"
+			client_id = "shurlix"
+			"#,
+		)
+		.unwrap();
+		let store = MemoryStore::new();
+		let mut repos = store.open().unwrap();
+
+		let existing = repos
+			.users()
+			.insert(&NewUser {
+				username: "jane".to_string(),
+				password_hash: "hash".to_string(),
+				email: "jd@example.com".to_string(),
+			})
+			.unwrap();
+
+		let unverified = OidcIdentity {
+			email_verified: false,
+			..identity(Some("jane.doe"), Some("jd@example.com"))
+		};
+		assert!(find_or_provision_user(&unverified, &oidc_config, repos.as_mut()).is_err());
+
+		let user =
+			find_or_provision_user(&identity(Some("jane.doe"), Some("jd@example.com")), &oidc_config, repos.as_mut())
+				.unwrap();
+		assert_eq!(user.id, existing.id);
+
+		// Linked by now, so a changed email doesn't matter
+		let user =
+			find_or_provision_user(&identity(None, Some("jane@example.org")), &oidc_config, repos.as_mut()).unwrap();
+		assert_eq!(user.id, existing.id);
+
+		let provisioned = OidcIdentity {
+			subject: "5678".to_string(),
+			..identity(Some("jane"), Some("other@example.com"))
+		};
+		let user = find_or_provision_user(&provisioned, &oidc_config, repos.as_mut()).unwrap();
+		assert_ne!(user.id, existing.id);
+		assert!(user.username.starts_with("jane-"));
+		assert_eq!(repos.users().get_permissions(user.id).unwrap(), vec!["link.create".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn test_callback_without_oidc() {
+		let app = TestApp::new();
+
+		let response = app.get("/api/user/oidc/callback?code=abc&state=def", None).await;
+		assert_eq!(response.status, StatusCode::SEE_OTHER);
+		assert!(response
+			.location
+			.unwrap()
+			.starts_with("/dash/login?error=Single+sign-on+is+not+enabled."));
+	}
+}
@@ -0,0 +1,317 @@
+use axum::{
+	extract::Path,
+	http::StatusCode,
+	routing::{get, put},
+	Extension, Json, Router,
+};
+use chrono::NaiveDateTime;
+use db::{
+	is_unique_violation,
+	models::{NewRole, Role, ADMIN_ROLE},
+	repository::{self, Repositories, SharedStore},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	common::{with_repos, APIError, APIResponse, GenericMessage},
+	extensions::auth::RequirePermission,
+	types::{double_option, CanManageUsers, Permission},
+};
+
+const MAX_ROLE_NAME_LENGTH: usize = 64;
+
+#[derive(Deserialize, Debug)]
+struct CreateRole {
+	name: String,
+	description: Option<String>,
+	permissions: Vec<Permission>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UpdateRoleRequest {
+	#[serde(default, deserialize_with = "double_option")]
+	description: Option<Option<String>>,
+	permissions: Option<Vec<Permission>>,
+}
+
+#[derive(Serialize, Debug)]
+struct RoleResponse {
+	id: i32,
+	name: String,
+	description: Option<String>,
+	built_in: bool,
+	permissions: Vec<String>,
+	user_count: i64,
+	created_at: NaiveDateTime,
+}
+
+fn internal_error() -> APIError {
+	(StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))
+}
+
+fn role_not_found() -> APIError {
+	(StatusCode::NOT_FOUND, GenericMessage::new("Role not found"))
+}
+
+fn role_response(role: Role, repos: &mut dyn Repositories) -> Result<RoleResponse, APIError> {
+	Ok(RoleResponse {
+		permissions: repos.roles().get_permissions(&role).map_err(|_| internal_error())?,
+		user_count: repos.roles().count_users(&role).map_err(|_| internal_error())?,
+		id: role.id,
+		name: role.name,
+		description: role.description,
+		built_in: role.built_in,
+		created_at: role.created_at,
+	})
+}
+
+fn permission_names(permissions: &[Permission]) -> Vec<String> {
+	let mut names: Vec<String> = permissions.iter().map(|permission| permission.to_string()).collect();
+	names.sort();
+	names.dedup();
+	names
+}
+
+async fn get_roles(
+	_: RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<Vec<RoleResponse>> {
+	with_repos(&store, move |repos| {
+		let roles = repos.roles().get_all().map_err(|_| internal_error())?;
+		let roles = roles
+			.into_iter()
+			.map(|role| role_response(role, repos))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok((StatusCode::OK, Json(roles)))
+	})
+	.await
+}
+
+/// Every permission a role can have
+async fn get_permissions(_: RequirePermission<CanManageUsers>) -> APIResponse<Vec<Permission>> {
+	Ok((StatusCode::OK, Json(Permission::ALL.to_vec())))
+}
+
+async fn create_role(
+	_: RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Json(payload): Json<CreateRole>,
+) -> APIResponse<RoleResponse> {
+	let name = payload.name.trim().to_lowercase();
+
+	if name.is_empty() || name.len() > MAX_ROLE_NAME_LENGTH {
+		return Err((
+			StatusCode::BAD_REQUEST,
+			GenericMessage::from_string(format!("Name must be between 1 and {} characters.", MAX_ROLE_NAME_LENGTH)),
+		));
+	}
+
+	with_repos(&store, move |repos| {
+		let new_role = NewRole {
+			name,
+			description: payload.description.filter(|description| !description.trim().is_empty()),
+		};
+
+		let role = repository::transaction(repos, |repos| {
+			let role = repos.roles().insert(&new_role)?;
+			repos
+				.roles()
+				.set_permissions(&role, &permission_names(&payload.permissions))?;
+
+			Ok(role)
+		});
+
+		let role = match role {
+			Ok(role) => role,
+			Err(e) if is_unique_violation(&e) => {
+				return Err((StatusCode::CONFLICT, GenericMessage::new("Role already exists.")))
+			}
+			Err(_) => return Err(internal_error()),
+		};
+
+		Ok((StatusCode::CREATED, Json(role_response(role, repos)?)))
+	})
+	.await
+}
+
+async fn update_role(
+	_: RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Json(payload): Json<UpdateRoleRequest>,
+) -> APIResponse<RoleResponse> {
+	with_repos(&store, move |repos| {
+		let role = repos.roles().get_by_id(id).map_err(|_| role_not_found())?;
+
+		// Nobody could get their permissions back if the admin role lost them
+		if payload.permissions.is_some() && role.name == ADMIN_ROLE {
+			return Err((
+				StatusCode::FORBIDDEN,
+				GenericMessage::new("The permissions of the admin role can't be changed."),
+			));
+		}
+
+		repository::transaction(repos, |repos| {
+			if let Some(permissions) = &payload.permissions {
+				repos.roles().set_permissions(&role, &permission_names(permissions))?;
+			}
+
+			if let Some(description) = payload.description {
+				repos.roles().update_description(&role, description)?;
+			}
+
+			Ok(())
+		})
+		.map_err(|_| internal_error())?;
+
+		let role = repos.roles().get_by_id(id).map_err(|_| internal_error())?;
+
+		Ok((StatusCode::OK, Json(role_response(role, repos)?)))
+	})
+	.await
+}
+
+async fn delete_role(
+	_: RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let role = repos.roles().get_by_id(id).map_err(|_| role_not_found())?;
+
+		if role.built_in {
+			return Err((StatusCode::FORBIDDEN, GenericMessage::new("Built-in roles can't be deleted.")));
+		}
+
+		match repos.roles().delete(&role) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Role deleted."))),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+async fn get_user_roles(
+	_: RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path(user_id): Path<i32>,
+) -> APIResponse<Vec<Role>> {
+	with_repos(&store, move |repos| match repos.roles().get_for_user(user_id) {
+		Ok(roles) => Ok((StatusCode::OK, Json(roles))),
+		Err(_) => Err(internal_error()),
+	})
+	.await
+}
+
+async fn assign_role(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path((id, user_id)): Path<(i32, i32)>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let role = repos.roles().get_by_id(id).map_err(|_| role_not_found())?;
+
+		let user_exists = repos
+			.users()
+			.get_by_id(user_id)
+			.map_err(|_| internal_error())?
+			.is_some();
+
+		if !user_exists {
+			return Err((StatusCode::NOT_FOUND, GenericMessage::new("User not found")));
+		}
+
+		repos.roles().assign(&role, user_id).map_err(|_| internal_error())?;
+
+		log::info!("{} gave the {} role to user {}", manager.username, role.name, user_id);
+
+		Ok((StatusCode::OK, GenericMessage::new("Role assigned.")))
+	})
+	.await
+}
+
+async fn unassign_role(
+	RequirePermission(manager, _): RequirePermission<CanManageUsers>,
+	Extension(store): Extension<SharedStore>,
+	Path((id, user_id)): Path<(i32, i32)>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let role = repos.roles().get_by_id(id).map_err(|_| role_not_found())?;
+
+		let has_role = repos
+			.roles()
+			.get_for_user(user_id)
+			.map_err(|_| internal_error())?
+			.iter()
+			.any(|user_role| user_role.id == role.id);
+
+		if !has_role {
+			return Err((StatusCode::NOT_FOUND, GenericMessage::new("The user doesn't have this role.")));
+		}
+
+		if role.name == ADMIN_ROLE && repos.roles().count_users(&role).map_err(|_| internal_error())? <= 1 {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The last admin can't lose the admin role.")));
+		}
+
+		repos.roles().unassign(&role, user_id).map_err(|_| internal_error())?;
+
+		log::info!("{} took the {} role from user {}", manager.username, role.name, user_id);
+
+		Ok((StatusCode::OK, GenericMessage::new("Role removed.")))
+	})
+	.await
+}
+
+// Starts at /api/roles
+pub fn roles_router() -> Router {
+	Router::new()
+		.route("/", get(get_roles).post(create_role))
+		.route("/permissions", get(get_permissions))
+		.route("/:id", put(update_role).delete(delete_role))
+		.route("/users/:user_id", get(get_user_roles))
+		.route("/:id/users/:user_id", put(assign_role).delete(unassign_role))
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_assign_role() {
+		let app = TestApp::new();
+		let admin = app.sign_up("alice").await;
+		let user = app.sign_up("bob").await;
+
+		assert_eq!(app.get("/api/roles", Some(&user)).await.status, StatusCode::FORBIDDEN);
+
+		let response = app
+			.post(
+				"/api/roles",
+				json!({ "name": "Support", "permissions": ["user.manage", "user.manage"] }),
+				Some(&admin),
+			)
+			.await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+		assert_eq!(response.body["name"], "support");
+		assert_eq!(response.body["permissions"], json!(["user.manage"]));
+		let role_id = response.body["id"].as_i64().unwrap();
+
+		let response = app
+			.put(&format!("/api/roles/{}/users/{}", role_id, app.user_id("bob")), json!({}), Some(&admin))
+			.await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+
+		let response = app.get("/api/roles", Some(&user)).await;
+		assert_eq!(response.status, StatusCode::OK);
+		assert_eq!(response.body.as_array().unwrap().len(), 3);
+
+		// Someone has to keep the admin role
+		let admin_role_id = response.body[0]["id"].as_i64().unwrap();
+		let path = format!("/api/roles/{}/users/{}", admin_role_id, app.user_id("alice"));
+		assert_eq!(app.delete(&path, Some(&user)).await.status, StatusCode::CONFLICT);
+	}
+}
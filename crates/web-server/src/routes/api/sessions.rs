@@ -0,0 +1,300 @@
+use axum::{
+	extract::Path,
+	http::{header, HeaderMap, StatusCode},
+	routing::delete,
+	Extension, Json, Router,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::{NaiveDateTime, Utc};
+use db::{
+	models::{NewSession, Session},
+	repository::{Repositories, SharedStore},
+};
+use serde::Serialize;
+
+use crate::{
+	common::{with_repos, APIError, APIResponse, CookiedAPIResponse, GenericMessage},
+	config::Config,
+	extensions::auth::AuthedUser,
+	util::{
+		generate_unique_string, hash_token,
+		jwt::{decode_user_token, encode_user_token},
+	},
+};
+
+pub const AUTH_COOKIE: &str = "auth_token";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+/// The refresh token is only sent to the endpoints that need it (refresh and logout)
+const REFRESH_COOKIE_PATH: &str = "/api/user";
+const REFRESH_TOKEN_LENGTH: usize = 64;
+
+#[derive(Serialize, Debug)]
+pub struct RefreshResponse {
+	token: String,
+}
+
+#[derive(Serialize, Debug)]
+struct SessionResponse {
+	id: i32,
+	user_agent: Option<String>,
+	created_at: NaiveDateTime,
+	last_seen_at: NaiveDateTime,
+	expires_at: NaiveDateTime,
+	/// Whether this is the session making the request
+	current: bool,
+}
+
+fn auth_cookie(value: String, max_age: time::Duration, config: &Config) -> Cookie<'static> {
+	Cookie::build((AUTH_COOKIE, value))
+		.http_only(true) // Prevent JavaScript access
+		.secure(config.secure_cookies()) // Only send over HTTPS, unless the app is served without it
+		.same_site(SameSite::Lax) // Control cross-site sending
+		.path("/") // Path for which the cookie is valid
+		.max_age(max_age)
+		.build()
+}
+
+fn refresh_cookie(value: String, max_age: time::Duration, config: &Config) -> Cookie<'static> {
+	Cookie::build((REFRESH_COOKIE, value))
+		.http_only(true)
+		.secure(config.secure_cookies())
+		.same_site(SameSite::Strict)
+		.path(REFRESH_COOKIE_PATH)
+		.max_age(max_age)
+		.build()
+}
+
+/// Creates a session for the user and sets the access and refresh token cookies. Returns the access token.
+pub fn start_session(
+	jar: CookieJar,
+	user_id: i32,
+	headers: &HeaderMap,
+	config: &Config,
+	repos: &mut dyn Repositories,
+) -> Result<(CookieJar, String), APIError> {
+	let security_config = config.security.clone().unwrap();
+	let refresh_token = generate_unique_string(REFRESH_TOKEN_LENGTH);
+
+	let new_session = NewSession {
+		user_id,
+		refresh_token_hash: hash_token(&refresh_token),
+		user_agent: headers
+			.get(header::USER_AGENT)
+			.and_then(|value| value.to_str().ok())
+			.map(|value| value.to_string()),
+		expires_at: (Utc::now() + security_config.session_ttl).naive_utc(),
+	};
+
+	let session = repos
+		.tokens()
+		.insert_session(&new_session)
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to create session.")))?;
+
+	Ok(set_session_cookies(jar, &session, refresh_token, config))
+}
+
+fn set_session_cookies(
+	jar: CookieJar,
+	session: &Session,
+	refresh_token: String,
+	config: &Config,
+) -> (CookieJar, String) {
+	let security_config = config.security.clone().unwrap();
+
+	let access_token_ttl = time::Duration::seconds(security_config.access_token_ttl.0.num_seconds());
+	let session_ttl = time::Duration::seconds(security_config.session_ttl.0.num_seconds());
+
+	let token = encode_user_token(session.user_id, session.id, access_token_ttl, security_config.jwt_secret.as_bytes());
+
+	let jar = jar
+		.add(auth_cookie(token.clone(), access_token_ttl, config))
+		.add(refresh_cookie(refresh_token, session_ttl, config));
+
+	(jar, token)
+}
+
+/// Removes the session cookies
+pub fn clear_session_cookies(jar: CookieJar, config: &Config) -> CookieJar {
+	let expired = time::OffsetDateTime::from_unix_timestamp(0).unwrap();
+
+	let mut auth = auth_cookie("deleted".to_string(), time::Duration::ZERO, config);
+	auth.set_expires(expired);
+
+	let mut refresh = refresh_cookie("deleted".to_string(), time::Duration::ZERO, config);
+	refresh.set_expires(expired);
+
+	jar.add(auth).add(refresh)
+}
+
+/// Gets the ID of the session making the request, from the access token cookie
+pub fn current_session_id(jar: &CookieJar, config: &Config) -> Option<i32> {
+	let cookie = jar.get(AUTH_COOKIE)?;
+
+	decode_user_token(&cookie.value().to_string(), config.security.clone().unwrap().jwt_secret.as_bytes())
+		.map(|token| token.session_id)
+}
+
+/// Revokes the session making the request, using the access token or the refresh token
+pub fn end_session(jar: &CookieJar, config: &Config, repos: &mut dyn Repositories) {
+	let session = match current_session_id(jar, config) {
+		Some(session_id) => repos.tokens().get_session(session_id).ok(),
+		None => jar.get(REFRESH_COOKIE).and_then(|cookie| {
+			repos
+				.tokens()
+				.get_session_by_refresh_token_hash(&hash_token(cookie.value()))
+				.ok()
+		}),
+	};
+
+	if let Some(session) = session {
+		if let Err(e) = repos.tokens().revoke_session(&session) {
+			log::error!("Failed to revoke session: {:#?}", e);
+		}
+	}
+}
+
+/// Swaps the refresh token for a new access token and refresh token
+pub async fn refresh_session(
+	jar: CookieJar,
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+) -> CookiedAPIResponse<RefreshResponse> {
+	let refresh_token = match jar.get(REFRESH_COOKIE) {
+		Some(cookie) => cookie.value().to_string(),
+		None => return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Session expired."))),
+	};
+
+	with_repos(&store, move |repos| {
+		let refresh_token_hash = hash_token(&refresh_token);
+
+		let session = match repos.tokens().get_session_by_refresh_token_hash(&refresh_token_hash) {
+			Ok(session) if session.is_active() => session,
+			Ok(_) => return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Session expired."))),
+			Err(_) => {
+				// A token that was already rotated away is being reused, so it has probably been stolen
+				if let Ok(session) = repos.tokens().get_session_by_previous_token_hash(&refresh_token_hash) {
+					log::warn!("Refresh token of session {} was reused, revoking it.", session.id);
+
+					if let Err(e) = repos.tokens().revoke_session(&session) {
+						log::error!("Failed to revoke session: {:#?}", e);
+					}
+				}
+
+				return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Session expired.")));
+			}
+		};
+
+		let security_config = config.security.clone().unwrap();
+		let new_refresh_token = generate_unique_string(REFRESH_TOKEN_LENGTH);
+
+		let rotated = repos
+			.tokens()
+			.rotate_session(
+				&session,
+				hash_token(&new_refresh_token),
+				(Utc::now() + security_config.session_ttl).naive_utc(),
+			)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to refresh session.")))?;
+
+		// Another request rotated the same token in the meantime, so it's being used twice
+		let Some(session) = rotated else {
+			log::warn!("Refresh token of session {} was used concurrently, revoking it.", session.id);
+
+			if let Err(e) = repos.tokens().revoke_session(&session) {
+				log::error!("Failed to revoke session: {:#?}", e);
+			}
+
+			return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Session expired.")));
+		};
+
+		let (jar, token) = set_session_cookies(jar, &session, new_refresh_token, &config);
+
+		Ok((jar, Json(RefreshResponse { token })))
+	})
+	.await
+}
+
+async fn my_sessions(
+	AuthedUser(user): AuthedUser,
+	jar: CookieJar,
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<Vec<SessionResponse>> {
+	let user =
+		user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))?;
+
+	with_repos(&store, move |repos| {
+		let current_session_id = current_session_id(&jar, &config);
+
+		let sessions = repos
+			.tokens()
+			.get_active_sessions(user.id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		let sessions = sessions
+			.into_iter()
+			.map(|session| SessionResponse {
+				current: Some(session.id) == current_session_id,
+				id: session.id,
+				user_agent: session.user_agent,
+				created_at: session.created_at,
+				last_seen_at: session.last_seen_at,
+				expires_at: session.expires_at,
+			})
+			.collect();
+
+		Ok((StatusCode::OK, Json(sessions)))
+	})
+	.await
+}
+
+async fn revoke_session(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<GenericMessage> {
+	let user =
+		user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))?;
+
+	with_repos(&store, move |repos| {
+		let session = match repos.tokens().get_session(id) {
+			Ok(session) if session.user_id == user.id && session.is_active() => session,
+			_ => return Err((StatusCode::NOT_FOUND, GenericMessage::new("Session not found."))),
+		};
+
+		match repos.tokens().revoke_session(&session) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Session revoked."))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to revoke session."))),
+		}
+	})
+	.await
+}
+
+/// Revokes every session of the user, except the one making the request
+async fn revoke_other_sessions(
+	AuthedUser(user): AuthedUser,
+	jar: CookieJar,
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<GenericMessage> {
+	let user =
+		user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))?;
+
+	with_repos(&store, move |repos| {
+		match repos
+			.tokens()
+			.revoke_all_sessions(user.id, current_session_id(&jar, &config))
+		{
+			Ok(count) => Ok((StatusCode::OK, GenericMessage::from_string(format!("Revoked {} sessions.", count)))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to revoke sessions."))),
+		}
+	})
+	.await
+}
+
+// Starts at /api/user/me/sessions
+pub fn sessions_router() -> Router {
+	Router::new()
+		.route("/", delete(revoke_other_sessions).get(my_sessions))
+		.route("/:id", delete(revoke_session))
+}
@@ -0,0 +1,580 @@
+use std::str::FromStr;
+
+use axum::{
+	extract::{Path, Query},
+	http::StatusCode,
+	routing::{delete, get, post, put},
+	Extension, Json, Router,
+};
+use chrono::NaiveDateTime;
+use db::{
+	models::{Link, LinkWithDomain, NewTeam, NewTeamMember, Team, TeamMember, User},
+	repository::{Repositories, SharedStore},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	common::{with_repos, APIError, APIResponse, GenericMessage},
+	extensions::auth::{AuthedUser, VerifiedUser},
+	types::{ApiKeyScope, PaginatedResponse, PaginationQuery, TeamRole},
+};
+
+const MAX_TEAM_NAME_LENGTH: usize = 64;
+
+#[derive(Deserialize, Debug)]
+struct TeamRequest {
+	name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct AddMemberRequest {
+	email: String,
+	role: Option<TeamRole>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UpdateMemberRequest {
+	role: TeamRole,
+}
+
+#[derive(Serialize, Debug)]
+struct TeamResponse {
+	id: i32,
+	name: String,
+	/// The role of the requesting user
+	role: TeamRole,
+	member_count: i64,
+	created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Debug)]
+struct TeamMemberResponse {
+	user_id: i32,
+	username: String,
+	email: String,
+	role: TeamRole,
+	joined_at: NaiveDateTime,
+}
+
+fn require_user(user: Option<User>) -> Result<User, APIError> {
+	user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))
+}
+
+fn internal_error() -> APIError {
+	(StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))
+}
+
+fn team_not_found() -> APIError {
+	(StatusCode::NOT_FOUND, GenericMessage::new("Team not found"))
+}
+
+fn validate_team_name(name: &str) -> Result<String, APIError> {
+	let name = name.trim();
+
+	if name.is_empty() || name.chars().count() > MAX_TEAM_NAME_LENGTH {
+		return Err((
+			StatusCode::BAD_REQUEST,
+			GenericMessage::from_string(format!("Name must be between 1 and {} characters.", MAX_TEAM_NAME_LENGTH)),
+		));
+	}
+
+	Ok(name.to_string())
+}
+
+/// Gets the role of a user in a team, or `None` if they're not a member
+pub fn get_team_role(team_id: i32, user_id: i32, repos: &mut dyn Repositories) -> Result<Option<TeamRole>, APIError> {
+	let member = repos
+		.teams()
+		.get_member(team_id, user_id)
+		.map_err(|_| internal_error())?;
+
+	Ok(member.and_then(|member| TeamRole::from_str(&member.role).ok()))
+}
+
+/// Gets a team the user is a member of with at least the given role. Teams of others are hidden.
+fn get_member_team(
+	team_id: i32,
+	user: &User,
+	min_role: TeamRole,
+	repos: &mut dyn Repositories,
+) -> Result<(Team, TeamRole), APIError> {
+	let role = get_team_role(team_id, user.id, repos)?.ok_or_else(team_not_found)?;
+
+	if role < min_role {
+		return Err((StatusCode::FORBIDDEN, GenericMessage::new("You are not allowed to perform this action.")));
+	}
+
+	let team = repos.teams().get_by_id(team_id).map_err(|_| team_not_found())?;
+
+	Ok((team, role))
+}
+
+fn team_response(team: Team, role: TeamRole, repos: &mut dyn Repositories) -> Result<TeamResponse, APIError> {
+	Ok(TeamResponse {
+		member_count: repos.teams().count_members(&team).map_err(|_| internal_error())?,
+		id: team.id,
+		name: team.name,
+		role,
+		created_at: team.created_at,
+	})
+}
+
+/// Checks if the member is the only owner left, teams always need one
+fn is_last_owner(team: &Team, member: &TeamMember, repos: &mut dyn Repositories) -> Result<bool, APIError> {
+	if member.role != TeamRole::Owner.as_str() {
+		return Ok(false);
+	}
+
+	let owners = repos
+		.teams()
+		.count_members_with_role(team, TeamRole::Owner.as_str())
+		.map_err(|_| internal_error())?;
+
+	Ok(owners <= 1)
+}
+
+async fn get_my_teams(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<Vec<TeamResponse>> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let teams = repos.teams().get_for_user(user.id).map_err(|_| internal_error())?;
+
+		let teams = teams
+			.into_iter()
+			.filter_map(|(team, member)| TeamRole::from_str(&member.role).ok().map(|role| (team, role)))
+			.map(|(team, role)| team_response(team, role, repos))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok((StatusCode::OK, Json(teams)))
+	})
+	.await
+}
+
+async fn create_team(
+	VerifiedUser(user): VerifiedUser,
+	Extension(store): Extension<SharedStore>,
+	Json(payload): Json<TeamRequest>,
+) -> APIResponse<TeamResponse> {
+	let user = require_user(user)?;
+	let name = validate_team_name(&payload.name)?;
+
+	with_repos(&store, move |repos| {
+		let team = repos
+			.teams()
+			.insert_with_member(&NewTeam { name }, user.id, TeamRole::Owner.as_str())
+			.map_err(|_| internal_error())?;
+
+		Ok((StatusCode::CREATED, Json(team_response(team, TeamRole::Owner, repos)?)))
+	})
+	.await
+}
+
+async fn get_team(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<TeamResponse> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let (team, role) = get_member_team(id, &user, TeamRole::Member, repos)?;
+
+		Ok((StatusCode::OK, Json(team_response(team, role, repos)?)))
+	})
+	.await
+}
+
+async fn rename_team(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Json(payload): Json<TeamRequest>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+	let name = validate_team_name(&payload.name)?;
+
+	with_repos(&store, move |repos| {
+		let (team, _) = get_member_team(id, &user, TeamRole::Admin, repos)?;
+
+		match repos.teams().rename(&team, &name) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Updated."))),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+async fn delete_team(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let (team, _) = get_member_team(id, &user, TeamRole::Owner, repos)?;
+
+		// The team's links would be deleted with it
+		if repos
+			.links()
+			.get_team_total_count(team.id)
+			.map_err(|_| internal_error())?
+			> 0
+		{
+			return Err((
+				StatusCode::CONFLICT,
+				GenericMessage::new("Transfer or delete the team's links before deleting it."),
+			));
+		}
+
+		// Trashed links still belong to the team until the trash is emptied
+		let trashed = repos
+			.links()
+			.get_trashed_team_total_count(team.id)
+			.map_err(|_| internal_error())?;
+
+		if trashed > 0 {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("Empty the team's trash before deleting it.")));
+		}
+
+		match repos.teams().delete(&team) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Team deleted."))),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+async fn get_members(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<Vec<TeamMemberResponse>> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let (team, _) = get_member_team(id, &user, TeamRole::Member, repos)?;
+
+		let members = repos
+			.teams()
+			.get_members(&team)
+			.map_err(|_| internal_error())?
+			.into_iter()
+			.filter_map(|(member, user)| {
+				Some(TeamMemberResponse {
+					role: TeamRole::from_str(&member.role).ok()?,
+					user_id: user.id,
+					username: user.username,
+					email: user.email,
+					joined_at: member.created_at,
+				})
+			})
+			.collect();
+
+		Ok((StatusCode::OK, Json(members)))
+	})
+	.await
+}
+
+async fn add_member(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Json(payload): Json<AddMemberRequest>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+	let role = payload.role.unwrap_or(TeamRole::Member);
+
+	with_repos(&store, move |repos| {
+		let (team, own_role) = get_member_team(id, &user, TeamRole::Admin, repos)?;
+
+		if role > own_role {
+			return Err((
+				StatusCode::FORBIDDEN,
+				GenericMessage::new("You can't give others a higher role than your own."),
+			));
+		}
+
+		// Unknown emails get the same answer, so the endpoint can't be used to find out who has an account
+		let added = || {
+			(StatusCode::OK, GenericMessage::new("If the email belongs to an account, it's been added to the team."))
+		};
+
+		let Some(new_member) = repos
+			.users()
+			.get_by_email(payload.email.trim())
+			.map_err(|_| internal_error())?
+		else {
+			return Ok(added());
+		};
+
+		if get_team_role(team.id, new_member.id, repos)?.is_some() {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The user is already a member of the team.")));
+		}
+
+		let member = NewTeamMember {
+			team_id: team.id,
+			user_id: new_member.id,
+			role: role.to_string(),
+		};
+
+		match repos.teams().insert_member(&member) {
+			Ok(_) => Ok(added()),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+async fn update_member(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path((id, user_id)): Path<(i32, i32)>,
+	Json(payload): Json<UpdateMemberRequest>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let (team, _) = get_member_team(id, &user, TeamRole::Owner, repos)?;
+
+		let member = repos
+			.teams()
+			.get_member(team.id, user_id)
+			.map_err(|_| internal_error())?
+			.ok_or((StatusCode::NOT_FOUND, GenericMessage::new("Member not found")))?;
+
+		if payload.role != TeamRole::Owner && is_last_owner(&team, &member, repos)? {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The team needs at least one owner.")));
+		}
+
+		match repos.teams().set_member_role(&member, payload.role.as_str()) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Updated."))),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+/// Removes a member. Anyone can leave, admins can remove members with a lower role than their own.
+async fn remove_member(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path((id, user_id)): Path<(i32, i32)>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let (team, own_role) = get_member_team(id, &user, TeamRole::Member, repos)?;
+
+		let member = repos
+			.teams()
+			.get_member(team.id, user_id)
+			.map_err(|_| internal_error())?
+			.ok_or((StatusCode::NOT_FOUND, GenericMessage::new("Member not found")))?;
+
+		let member_role = TeamRole::from_str(&member.role).map_err(|_| internal_error())?;
+
+		let can_remove = user_id == user.id
+			|| own_role == TeamRole::Owner
+			|| (own_role >= TeamRole::Admin && member_role < own_role);
+
+		if !can_remove {
+			return Err((StatusCode::FORBIDDEN, GenericMessage::new("You are not allowed to perform this action.")));
+		}
+
+		if is_last_owner(&team, &member, repos)? {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("The team needs at least one owner.")));
+		}
+
+		match repos.teams().delete_member(&member) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Member removed."))),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+async fn team_links(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Query(pagination): Query<PaginationQuery>,
+) -> APIResponse<PaginatedResponse<LinkWithDomain>> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let (team, _) = get_member_team(id, &user, TeamRole::Member, repos)?;
+
+		let items = repos
+			.links()
+			.get_by_team_id_paginated(team.id, pagination.page, pagination.per_page)
+			.map_err(|_| internal_error())?
+			.into_iter()
+			.map(|(link, domain)| LinkWithDomain::new(link, domain))
+			.collect();
+
+		let total_count = repos
+			.links()
+			.get_team_total_count(team.id)
+			.map_err(|_| internal_error())?;
+
+		Ok((StatusCode::OK, Json(PaginatedResponse::<LinkWithDomain> { items, total_count })))
+	})
+	.await
+}
+
+async fn team_trashed_links(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+	Query(pagination): Query<PaginationQuery>,
+) -> APIResponse<PaginatedResponse<LinkWithDomain>> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let (team, _) = get_member_team(id, &user, TeamRole::Member, repos)?;
+
+		let items = repos
+			.links()
+			.get_trashed_by_team_id_paginated(team.id, pagination.page, pagination.per_page)
+			.map_err(|_| internal_error())?
+			.into_iter()
+			.map(|(link, domain)| LinkWithDomain::new(link, domain))
+			.collect();
+
+		let total_count = repos
+			.links()
+			.get_trashed_team_total_count(team.id)
+			.map_err(|_| internal_error())?;
+
+		Ok((StatusCode::OK, Json(PaginatedResponse::<LinkWithDomain> { items, total_count })))
+	})
+	.await
+}
+
+/// Gets a link from the team's trash
+fn get_team_trashed_link(
+	team_id: i32,
+	link_id: i32,
+	user: &User,
+	repos: &mut dyn Repositories,
+) -> Result<Link, APIError> {
+	let (team, _) = get_member_team(team_id, user, TeamRole::Member, repos)?;
+
+	let link = repos
+		.links()
+		.get_trashed_by_id(link_id)
+		.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Link not found in trash.")))?;
+
+	if link.team_id != Some(team.id) {
+		return Err((StatusCode::NOT_FOUND, GenericMessage::new("Link not found in trash.")));
+	}
+
+	Ok(link)
+}
+
+async fn restore_team_trashed_link(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path((id, link_id)): Path<(i32, i32)>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let link = get_team_trashed_link(id, link_id, &user, repos)?;
+
+		match repos.links().restore(&link) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Link restored."))),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+async fn purge_team_trashed_link(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path((id, link_id)): Path<(i32, i32)>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let link = get_team_trashed_link(id, link_id, &user, repos)?;
+
+		match repos.links().purge(&link) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Link permanently deleted."))),
+			Err(_) => Err(internal_error()),
+		}
+	})
+	.await
+}
+
+// Starts at /api/teams
+pub fn teams_router() -> Router {
+	Router::new()
+		.route("/", get(get_my_teams).post(create_team))
+		.route("/:id", get(get_team).put(rename_team).delete(delete_team))
+		.route("/:id/members", get(get_members).post(add_member))
+		.route("/:id/members/:user_id", put(update_member).delete(remove_member))
+		.route("/:id/links", get(team_links).layer(Extension(ApiKeyScope::LinksRead)))
+		.route("/:id/links/trash", get(team_trashed_links).layer(Extension(ApiKeyScope::LinksRead)))
+		.route("/:id/links/trash/:link_id", delete(purge_team_trashed_link).layer(Extension(ApiKeyScope::LinksWrite)))
+		.route(
+			"/:id/links/trash/:link_id/restore",
+			post(restore_team_trashed_link).layer(Extension(ApiKeyScope::LinksWrite)),
+		)
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[test]
+	fn test_validate_team_name() {
+		assert_eq!(validate_team_name("  Marketing ").unwrap(), "Marketing");
+		assert!(validate_team_name("   ").is_err());
+		assert!(validate_team_name(&"a".repeat(MAX_TEAM_NAME_LENGTH + 1)).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_team_members() {
+		let app = TestApp::new();
+		let owner = app.sign_up("alice").await;
+		let member = app.sign_up("bob").await;
+
+		let response = app
+			.post("/api/teams", json!({ "name": "Marketing" }), Some(&owner))
+			.await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+		let team_id = response.body["id"].as_i64().unwrap();
+
+		let response = app
+			.post(&format!("/api/teams/{}/members", team_id), json!({ "email": "bob@example.com" }), Some(&owner))
+			.await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+
+		let response = app.get("/api/teams", Some(&member)).await;
+		assert_eq!(response.body[0]["member_count"], 2);
+		assert_eq!(response.body[0]["role"], "member");
+
+		// Members can't manage the team
+		let response = app
+			.put(&format!("/api/teams/{}", team_id), json!({ "name": "Sales" }), Some(&member))
+			.await;
+		assert_eq!(response.status, StatusCode::FORBIDDEN);
+
+		// The last owner can't leave
+		let path = format!("/api/teams/{}/members/{}", team_id, app.user_id("alice"));
+		assert_eq!(app.delete(&path, Some(&owner)).await.status, StatusCode::CONFLICT);
+
+		let response = app.delete(&format!("/api/teams/{}", team_id), Some(&owner)).await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+		assert_eq!(app.get("/api/teams", Some(&member)).await.body, json!([]));
+	}
+}
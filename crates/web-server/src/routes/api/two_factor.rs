@@ -0,0 +1,327 @@
+use axum::{
+	http::StatusCode,
+	routing::{get, post},
+	Extension, Json, Router,
+};
+use chrono::{Duration, Utc};
+use db::{
+	models::User,
+	repository::{self, Repositories, SharedStore},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	common::{with_repos, APIError, APIResponse, GenericMessage},
+	extensions::auth::AuthedUser,
+	util::{
+		password::verify_password,
+		totp::{
+			build_totp, generate_recovery_codes, generate_totp_secret, hash_recovery_code, totp_qr_code_svg,
+			verify_totp_code,
+		},
+	},
+};
+
+/// Invalid codes a user can enter before they're locked out
+const MAX_CODE_ATTEMPTS: i32 = 5;
+const CODE_LOCKOUT_MINUTES: i64 = 15;
+
+#[derive(Deserialize, Debug)]
+struct CodeRequest {
+	code: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct DisableTwoFactorRequest {
+	password: String,
+	code: String,
+}
+
+#[derive(Serialize, Debug)]
+struct TwoFactorStatus {
+	enabled: bool,
+	recovery_codes_left: i64,
+}
+
+#[derive(Serialize, Debug)]
+struct TwoFactorSetupResponse {
+	secret: String,
+	otpauth_uri: String,
+	qr_code_svg: String,
+}
+
+/// The recovery codes in plain text. This is the only time they're shown.
+#[derive(Serialize, Debug)]
+struct RecoveryCodesResponse {
+	recovery_codes: Vec<String>,
+}
+
+fn require_user(user: Option<User>) -> Result<User, APIError> {
+	user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))
+}
+
+fn internal_error() -> APIError {
+	(StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))
+}
+
+fn invalid_code() -> APIError {
+	(StatusCode::UNAUTHORIZED, GenericMessage::with_reason("Invalid code.".to_string(), "invalid_code"))
+}
+
+fn too_many_attempts() -> APIError {
+	(
+		StatusCode::TOO_MANY_REQUESTS,
+		GenericMessage::with_reason("Too many invalid codes, try again later.".to_string(), "too_many_attempts"),
+	)
+}
+
+/// Checks a TOTP code, or else a recovery code, of a user with 2FA enabled. Either can only be used once.
+/// Codes are short enough to be guessed, so users get locked out for a while after too many invalid ones.
+pub fn verify_second_factor(user: &User, code: &str, repos: &mut dyn Repositories) -> Result<(), APIError> {
+	let locked_until = (Utc::now() + Duration::minutes(CODE_LOCKOUT_MINUTES)).naive_utc();
+
+	if !repos
+		.users()
+		.claim_totp_attempt(user, MAX_CODE_ATTEMPTS, locked_until)
+		.map_err(|_| internal_error())?
+	{
+		return Err(too_many_attempts());
+	}
+
+	check_second_factor(user, code, repos)?;
+
+	repos.users().reset_totp_attempts(user).map_err(|_| internal_error())?;
+
+	Ok(())
+}
+
+fn check_second_factor(user: &User, code: &str, repos: &mut dyn Repositories) -> Result<(), APIError> {
+	let totp = user
+		.totp_secret
+		.as_deref()
+		.and_then(|secret| build_totp(secret, &user.email))
+		.ok_or_else(internal_error)?;
+
+	let now = Utc::now().timestamp() as u64;
+
+	if let Some(step) = verify_totp_code(&totp, code, now, user.totp_last_step) {
+		return match repos.users().claim_totp_step(user, step) {
+			Ok(true) => Ok(()),
+			Ok(false) => Err(invalid_code()),
+			Err(_) => Err(internal_error()),
+		};
+	}
+
+	match repos.tokens().redeem_recovery_code(user.id, &hash_recovery_code(code)) {
+		Ok(true) => Ok(()),
+		Ok(false) => Err(invalid_code()),
+		Err(_) => Err(internal_error()),
+	}
+}
+
+/// Generates a new set of recovery codes, along with the hashes that get stored
+fn new_recovery_codes() -> (Vec<String>, Vec<String>) {
+	let recovery_codes = generate_recovery_codes();
+	let code_hashes = recovery_codes.iter().map(|code| hash_recovery_code(code)).collect();
+
+	(recovery_codes, code_hashes)
+}
+
+async fn two_factor_status(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<TwoFactorStatus> {
+	let user = require_user(user)?;
+
+	with_repos(&store, move |repos| {
+		let recovery_codes_left = match user.has_two_factor() {
+			true => repos
+				.tokens()
+				.count_unused_recovery_codes(user.id)
+				.map_err(|_| internal_error())?,
+			false => 0,
+		};
+
+		Ok((
+			StatusCode::OK,
+			Json(TwoFactorStatus {
+				enabled: user.has_two_factor(),
+				recovery_codes_left,
+			}),
+		))
+	})
+	.await
+}
+
+/// Starts enrollment with a new secret. 2FA is only enabled once a code from it is confirmed.
+async fn setup_two_factor(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<TwoFactorSetupResponse> {
+	let user = require_user(user)?;
+
+	if user.has_two_factor() {
+		return Err((StatusCode::CONFLICT, GenericMessage::new("2FA is already enabled.")));
+	}
+
+	with_repos(&store, move |repos| {
+		let secret = generate_totp_secret();
+		let totp = build_totp(&secret, &user.email).ok_or_else(internal_error)?;
+		let otpauth_uri = totp.get_url();
+		let qr_code_svg = totp_qr_code_svg(&otpauth_uri).ok_or_else(internal_error)?;
+
+		repos
+			.users()
+			.set_pending_totp_secret(&user, &secret)
+			.map_err(|_| internal_error())?;
+
+		Ok((
+			StatusCode::OK,
+			Json(TwoFactorSetupResponse {
+				secret,
+				otpauth_uri,
+				qr_code_svg,
+			}),
+		))
+	})
+	.await
+}
+
+async fn enable_two_factor(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Json(payload): Json<CodeRequest>,
+) -> APIResponse<RecoveryCodesResponse> {
+	let user = require_user(user)?;
+
+	if user.has_two_factor() {
+		return Err((StatusCode::CONFLICT, GenericMessage::new("2FA is already enabled.")));
+	}
+
+	let totp = match user.totp_secret.as_deref() {
+		Some(secret) => build_totp(secret, &user.email).ok_or_else(internal_error)?,
+		None => return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Start the 2FA setup first."))),
+	};
+
+	let step = verify_totp_code(&totp, &payload.code, Utc::now().timestamp() as u64, None).ok_or_else(invalid_code)?;
+
+	let (recovery_codes, code_hashes) = new_recovery_codes();
+
+	with_repos(&store, move |repos| {
+		// 2FA only gets turned on along with the codes to get back in without the authenticator
+		repository::transaction(repos, |repos| {
+			repos.users().claim_totp_step(&user, step)?;
+			repos.users().enable_totp(&user)?;
+			repos.tokens().replace_recovery_codes(user.id, &code_hashes)
+		})
+		.map_err(|_| internal_error())?;
+
+		Ok((StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes })))
+	})
+	.await
+}
+
+async fn disable_two_factor(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Json(payload): Json<DisableTwoFactorRequest>,
+) -> APIResponse<GenericMessage> {
+	let user = require_user(user)?;
+
+	if !user.has_two_factor() {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("2FA is not enabled.")));
+	}
+
+	let is_valid = verify_password(&payload.password, &user.password_hash).map_err(|_| internal_error())?;
+
+	if !is_valid {
+		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials.")));
+	}
+
+	with_repos(&store, move |repos| {
+		verify_second_factor(&user, &payload.code, repos)?;
+
+		repository::transaction(repos, |repos| {
+			repos.users().disable_totp(&user)?;
+			repos.tokens().delete_recovery_codes(user.id)
+		})
+		.map_err(|_| internal_error())?;
+
+		Ok((StatusCode::OK, GenericMessage::new("2FA disabled.")))
+	})
+	.await
+}
+
+async fn regenerate_recovery_codes(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Json(payload): Json<CodeRequest>,
+) -> APIResponse<RecoveryCodesResponse> {
+	let user = require_user(user)?;
+
+	if !user.has_two_factor() {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("2FA is not enabled.")));
+	}
+
+	with_repos(&store, move |repos| {
+		verify_second_factor(&user, &payload.code, repos)?;
+
+		let (recovery_codes, code_hashes) = new_recovery_codes();
+
+		repos
+			.tokens()
+			.replace_recovery_codes(user.id, &code_hashes)
+			.map_err(|_| internal_error())?;
+
+		Ok((StatusCode::OK, Json(RecoveryCodesResponse { recovery_codes })))
+	})
+	.await
+}
+
+// Starts at /api/user/me/2fa
+pub fn two_factor_router() -> Router {
+	Router::new()
+		.route("/", get(two_factor_status))
+		.route("/setup", post(setup_two_factor))
+		.route("/enable", post(enable_two_factor))
+		.route("/disable", post(disable_two_factor))
+		.route("/recovery-codes", post(regenerate_recovery_codes))
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_invalid_codes_lock_out() {
+		let app = TestApp::new();
+		let token = app.sign_up("alice").await;
+
+		let response = app.post("/api/user/me/2fa/setup", json!({}), Some(&token)).await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+		let totp = build_totp(response.body["secret"].as_str().unwrap(), "alice@example.com").unwrap();
+
+		let code = totp.generate_current().unwrap();
+		let response = app
+			.post("/api/user/me/2fa/enable", json!({ "code": code }), Some(&token))
+			.await;
+		assert_eq!(response.status, StatusCode::OK, "{:?}", response.body);
+
+		for _ in 0..MAX_CODE_ATTEMPTS {
+			let response = app
+				.post("/api/user/me/2fa/recovery-codes", json!({ "code": "000000" }), Some(&token))
+				.await;
+			assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+		}
+
+		// Even a valid code isn't checked anymore
+		let response = app
+			.post("/api/user/me/2fa/recovery-codes", json!({ "code": code }), Some(&token))
+			.await;
+		assert_eq!(response.status, StatusCode::TOO_MANY_REQUESTS);
+		assert_eq!(response.body["reason"], "too_many_attempts");
+	}
+}
@@ -0,0 +1,129 @@
+use axum::{
+	extract::Path,
+	http::StatusCode,
+	routing::{delete, get},
+	Extension, Json, Router,
+};
+use db::{
+	is_unique_violation,
+	models::{NewUrlRule, UrlRule, URL_RULE_ALLOW, URL_RULE_BLOCK},
+	repository::SharedStore,
+};
+use serde::Deserialize;
+
+use crate::{
+	common::{with_repos, APIResponse, GenericMessage},
+	extensions::auth::RequirePermission,
+	types::CanManageSettings,
+};
+
+#[derive(Deserialize, Debug)]
+struct CreateUrlRule {
+	domain: String,
+	kind: String,
+}
+
+async fn get_url_rules(
+	Extension(store): Extension<SharedStore>,
+	_: RequirePermission<CanManageSettings>,
+) -> APIResponse<Vec<UrlRule>> {
+	with_repos(&store, move |repos| match repos.domains().get_url_rules() {
+		Ok(rules) => Ok((StatusCode::OK, Json(rules))),
+		Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+	})
+	.await
+}
+
+async fn create_url_rule(
+	Extension(store): Extension<SharedStore>,
+	_: RequirePermission<CanManageSettings>,
+	Json(payload): Json<CreateUrlRule>,
+) -> APIResponse<UrlRule> {
+	if payload.kind != URL_RULE_BLOCK && payload.kind != URL_RULE_ALLOW {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Rule kind must be either block or allow.")));
+	}
+
+	let domain = payload.domain.trim().to_lowercase();
+
+	if domain.is_empty() || domain.contains('/') {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Provided domain is not valid.")));
+	}
+
+	with_repos(&store, move |repos| {
+		let new_rule = NewUrlRule {
+			domain,
+			kind: payload.kind,
+		};
+
+		match repos.domains().insert_url_rule(&new_rule) {
+			Ok(rule) => Ok((StatusCode::CREATED, Json(rule))),
+			Err(e) if is_unique_violation(&e) => {
+				Err((StatusCode::CONFLICT, GenericMessage::new("Rule already exists.")))
+			}
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to create rule."))),
+		}
+	})
+	.await
+}
+
+async fn delete_url_rule(
+	Extension(store): Extension<SharedStore>,
+	_: RequirePermission<CanManageSettings>,
+	Path(id): Path<i32>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let rule = repos
+			.domains()
+			.get_url_rule(id)
+			.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Rule not found")))?;
+
+		match repos.domains().delete_url_rule(&rule) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Rule deleted."))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to delete rule."))),
+		}
+	})
+	.await
+}
+
+// Starts at /api/url-rules
+pub fn url_rules_router() -> Router {
+	Router::new()
+		.route("/", get(get_url_rules).post(create_url_rule))
+		.route("/:id", delete(delete_url_rule))
+}
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_block_rule() {
+		let app = TestApp::new();
+		let domain = app.add_public_domain("go.to");
+		let admin = app.sign_up("alice").await;
+		let user = app.sign_up("bob").await;
+		let shorten = json!({ "domain_id": domain.id, "link": "https://evil.com/" });
+
+		let rule = json!({ "domain": "Evil.com", "kind": "block" });
+		assert_eq!(app.post("/api/url-rules", rule.clone(), Some(&user)).await.status, StatusCode::FORBIDDEN);
+
+		let response = app.post("/api/url-rules", rule.clone(), Some(&admin)).await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+		assert_eq!(response.body["domain"], "evil.com");
+		let rule_id = response.body["id"].as_i64().unwrap();
+		assert_eq!(app.post("/api/url-rules", rule, Some(&admin)).await.status, StatusCode::CONFLICT);
+
+		let response = app.post("/api/link/shorten", shorten.clone(), Some(&user)).await;
+		assert_eq!(response.status, StatusCode::BAD_REQUEST);
+		assert_eq!(response.body["reason"], "domain_blocked");
+
+		let response = app.delete(&format!("/api/url-rules/{}", rule_id), Some(&admin)).await;
+		assert_eq!(response.status, StatusCode::OK);
+
+		let response = app.post("/api/link/shorten", shorten, Some(&user)).await;
+		assert_eq!(response.status, StatusCode::CREATED, "{:?}", response.body);
+	}
+}
@@ -1,37 +1,55 @@
 use axum::{
 	extract::{Path, Query},
-	http::StatusCode,
+	http::{HeaderMap, StatusCode},
 	routing::{delete, get, post},
 	Extension, Json, Router,
 };
 use chrono::Utc;
 use db::{
-	models::{Link, LinkWithDomain, NewUser, NewVerificationToken, SanitizedUser, UpdateUser, User, VerificationToken},
-	DbPool,
+	is_unique_violation,
+	models::{
+		Link, LinkWithDomain, NewEmailChangeToken, NewPasswordResetToken, NewUser, NewVerificationToken, SanitizedUser,
+		UpdateUser, User, ADMIN_ROLE, USER_ROLE,
+	},
+	repository::{self, Repositories, SharedStore},
 };
 use email_address::EmailAddress;
 use serde::{Deserialize, Serialize};
 
-use axum_extra::extract::cookie::{Cookie, CookieJar};
+use axum_extra::extract::cookie::CookieJar;
 
-use argon2::{
-	password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-	Argon2,
-};
 use zxcvbn::{
 	feedback::{Suggestion, Warning},
 	zxcvbn, Entropy, Score,
 };
 
+use super::{
+	api_keys, oidc,
+	two_factor::{self, verify_second_factor},
+	sessions::{self, clear_session_cookies, current_session_id, end_session, refresh_session, start_session},
+};
 use crate::{
-	common::{APIResponse, CookiedAPIResponse, GenericMessage},
-	config::Config,
-	extensions::auth::AuthedUser,
-	services::email::{templates::VerificationEmail, Email},
-	types::{PaginatedResponse, PaginationQuery},
-	util::{generate_unique_string, jwt::encode_user_token},
+	common::{with_repos, APIError, APIResponse, CookiedAPIResponse, GenericMessage},
+	config::{AppConfig, Config},
+	extensions::auth::{check_not_suspended, effective_permissions, granted_permissions, AuthedUser, Permissions, VerifiedUser},
+	services::{
+		email::{
+			templates::{EmailChangeConfirmationEmail, EmailChangeNoticeEmail, PasswordResetEmail, VerificationEmail},
+			Email,
+		},
+		redirect_cache::RedirectCache,
+	},
+	types::{ApiKeyScope, PaginatedResponse, PaginationQuery, TeamRole},
+	util::{
+		generate_unique_string, hash_token,
+		jwt::{decode_two_factor_token, encode_two_factor_token},
+		password::{hash_password, verify_password},
+	},
 };
 
+/// How long the second step of a 2FA login can take, for password and OIDC logins
+pub(crate) const TWO_FACTOR_LOGIN_TTL_MINUTES: i64 = 5;
+
 #[derive(Deserialize)]
 struct RegisterRequest {
 	username: String,
@@ -59,10 +77,32 @@ struct RegisteredUser {
 	email: String,
 }
 
+#[derive(Deserialize)]
+struct TwoFactorLoginRequest {
+	two_factor_token: String,
+	code: String,
+}
+
 #[derive(Serialize)]
 struct LoginResponse {
 	token: String,
 	user: SanitizedUser,
+	/// Set for admins that need to enable 2FA before they get their admin rights
+	two_factor_setup_required: bool,
+}
+
+/// Sent instead of a session when the password was right, but the user still has to enter a 2FA code
+#[derive(Serialize)]
+struct TwoFactorChallenge {
+	two_factor_required: bool,
+	two_factor_token: String,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum LoginResult {
+	LoggedIn(LoginResponse),
+	TwoFactorRequired(TwoFactorChallenge),
 }
 
 #[derive(Serialize)]
@@ -85,6 +125,17 @@ struct ChangePasswordRequest {
 	confirm_password: String,
 }
 
+#[derive(Deserialize)]
+struct ForgotPasswordRequest {
+	email: String,
+}
+
+#[derive(Deserialize)]
+struct ResetPasswordRequest {
+	password: String,
+	confirm_password: String,
+}
+
 #[derive(Deserialize)]
 struct UserUpdateRequest {
 	username: Option<String>,
@@ -118,8 +169,45 @@ impl From<Entropy> for CheckPasswordResponse {
 	}
 }
 
+/// Creates a verification token for the user and emails them the link, if SMTP is configured
+fn send_verification_email(user: &User, email: Email, app_config: AppConfig, repos: &mut dyn Repositories) {
+	let verification_token = generate_unique_string(32);
+
+	let new_token = NewVerificationToken {
+		user_id: user.id,
+		token: verification_token.clone(),
+		expires_at: (Utc::now() + app_config.email_verification_ttl).naive_utc(),
+	};
+
+	if let Err(e) = repos.tokens().insert_verification_token(&new_token) {
+		log::error!("Failed to create verification token: {:#?}", e);
+		return;
+	}
+
+	if !email.is_available() {
+		return;
+	}
+
+	let to = user.email.clone();
+	let username = user.username.clone();
+
+	tokio::spawn(async move {
+		email
+			.send_template::<VerificationEmail>(
+				&to,
+				&VerificationEmail {
+					base_url: &app_config.base_url,
+					username: &username,
+					verification_token: verification_token.as_str(),
+					ttl: format!("{}", app_config.email_verification_ttl).as_str(),
+				},
+			)
+			.await;
+	});
+}
+
 async fn register_user(
-	Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
 	Extension(config): Extension<Config>,
 	Extension(email): Extension<Email>,
 	Json(payload): Json<RegisterRequest>,
@@ -145,148 +233,185 @@ async fn register_user(
 		return Err((StatusCode::CONFLICT, GenericMessage::new("Password is not strong enough.")));
 	}
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
-
-	if User::email_exists(&payload.email, conn) {
-		return Err((StatusCode::CONFLICT, GenericMessage::new("Email already in use")));
-	}
-
-	if User::username_exists(&payload.username, conn) {
-		return Err((StatusCode::CONFLICT, GenericMessage::new("Username already in use")));
-	}
+	with_repos(&store, move |repos| {
+		if repos.users().email_exists(&payload.email) {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("Email already in use")));
+		}
 
-	let salt = SaltString::generate(&mut OsRng);
+		if repos.users().username_exists(&payload.username) {
+			return Err((StatusCode::CONFLICT, GenericMessage::new("Username already in use")));
+		}
 
-	let argon2 = Argon2::default();
+		let password_hash = match hash_password(&payload.password) {
+			Ok(hash) => hash,
+			Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error"))),
+		};
 
-	// Hash password to PHC string ($argon2id$v=19$...)
-	let password_hash = match argon2.hash_password(&payload.password.into_bytes(), &salt) {
-		Ok(hash) => hash.to_string(),
-		Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error"))),
-	};
+		let user_count = repos
+			.users()
+			.get_total_count()
+			.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
 
-	let user_count = User::get_total_count(conn).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+		let new_user = NewUser {
+			email: payload.email.clone(),
+			password_hash,
+			username: payload.username.clone(),
+		};
 
+		// The first user sets up the instance, so they get every permission
+		let roles = match user_count {
+			0 => vec![USER_ROLE, ADMIN_ROLE],
+			_ => vec![USER_ROLE],
+		};
 
-	let new_user = NewUser {
-		email: payload.email.clone(),
-		password_hash,
-		username: payload.username.clone(),
-		is_admin: user_count == 0,
-	};
+		// A user without their roles couldn't do anything, so they're only created along with them
+		let user = repository::transaction(repos, |repos| {
+			let user = repos.users().insert(&new_user)?;
 
-	let user = new_user.insert(conn);
+			for role in roles {
+				repos.users().assign_role(role, user.id)?;
+			}
 
-	// TODO: Send validation link (if REQUIRE_EMAIL_VALIDATION & SMTP configured)
+			Ok(user)
+		})
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to create user.")))?;
 
-	if app_config.enable_email_verification {
-		let verification_token = generate_unique_string(32);
+		if app_config.enable_email_verification {
+			send_verification_email(&user, email, app_config, repos);
+		}
 
-		let new_token = NewVerificationToken {
-			user_id: user.id,
-			token: verification_token.clone(),
-			expires_at: (Utc::now() + app_config.email_verification_ttl).naive_utc(),
+		let registered_user = RegisteredUser {
+			email: user.email,
+			id: user.id,
+			username: user.username,
 		};
 
-		new_token.insert(conn);
-
-		if email.is_available() {
-			tokio::spawn(async move {
-				let _ = email
-					.send_template::<VerificationEmail>(
-						&payload.email,
-						&VerificationEmail {
-							base_url: &app_config.base_url,
-							username: &payload.username,
-							verification_token: verification_token.as_str(),
-							ttl: format!(
-								"{}",
-								app_config.email_verification_ttl
-							)
-							.as_str(),
-						},
-					)
-					.await;
-			});
-		}
-	}
-
-	let registered_user = RegisteredUser {
-		email: user.email,
-		id: user.id,
-		username: user.username,
-	};
+		Ok((StatusCode::CREATED, Json(registered_user)))
+	})
+	.await
+}
 
-	Ok((StatusCode::CREATED, Json(registered_user)))
+/// Starts a session for a user that passed every login step
+fn finish_login(
+	jar: CookieJar,
+	user: &User,
+	headers: &HeaderMap,
+	config: &Config,
+	repos: &mut dyn Repositories,
+) -> CookiedAPIResponse<LoginResult> {
+	check_not_suspended(user)?;
+
+	let granted = granted_permissions(user.id, repos)
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+	let permissions = effective_permissions(granted.clone(), user, config);
+
+	let (jar2, token) = start_session(jar, user.id, headers, config, repos)?;
+
+	Ok((
+		jar2,
+		Json(LoginResult::LoggedIn(LoginResponse {
+			token,
+			// Admin permissions are withheld until 2FA is enabled
+			two_factor_setup_required: permissions.len() < granted.len(),
+			user: user.sanitize(permissions.iter().map(|permission| permission.to_string()).collect()),
+		})),
+	))
 }
 
 async fn login_user(
 	jar: CookieJar,
+	headers: HeaderMap,
 	Extension(config): Extension<Config>,
-	Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
 	Json(payload): Json<LoginRequest>,
-) -> CookiedAPIResponse<LoginResponse> {
-	let security_config = config.security.unwrap();
-
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
-
-	let users = match User::get_by_email(&payload.email, conn) {
-		Ok(users) => users,
-		Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
-	};
-
-	if users.len() == 0 {
-		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials.")));
-	}
-
-	let user = users.first().unwrap();
-
-	let argon2 = Argon2::default();
-	let parsed_hash = PasswordHash::new(&user.password_hash)
-		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+) -> CookiedAPIResponse<LoginResult> {
+	with_repos(&store, move |repos| {
+		let user = match repos.users().get_by_email(&payload.email) {
+			Ok(Some(user)) => user,
+			Ok(None) => return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials."))),
+			Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+		};
 
-	// Verify the password
-	match argon2.verify_password(&payload.password.as_bytes(), &parsed_hash) {
-		Ok(_) => {
-			let token = encode_user_token(user.id, security_config.jwt_secret.as_bytes());
+		// Deleted accounts can't sign in, but look like any unknown email
+		if user.deleted_at.is_some() {
+			return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials.")));
+		}
 
-			let cookie = Cookie::build(("auth_token", token.clone()))
-				.http_only(true) // Prevent JavaScript access
-				.secure(false) // Only send over HTTPS
-				.same_site(axum_extra::extract::cookie::SameSite::Lax) // Control cross-site sending
-				.path("/") // Path for which the cookie is valid
-				.max_age(time::Duration::hours(1))
-				.build();
+		let is_valid = verify_password(&payload.password, &user.password_hash)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+
+		// Verify the password
+		match is_valid {
+			true if user.has_two_factor() => {
+				check_not_suspended(&user)?;
+
+				let two_factor_token = encode_two_factor_token(
+					user.id,
+					time::Duration::minutes(TWO_FACTOR_LOGIN_TTL_MINUTES),
+					config.security.unwrap().jwt_secret.as_bytes(),
+				);
+
+				Ok((
+					jar,
+					Json(LoginResult::TwoFactorRequired(TwoFactorChallenge {
+						two_factor_required: true,
+						two_factor_token,
+					})),
+				))
+			}
+			true => finish_login(jar, &user, &headers, &config, repos),
+			false => Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials."))),
+		}
+	})
+	.await
+}
 
-			let jar2 = jar.add(cookie);
+/// Second login step for users with 2FA, trades the token from the first step and a code for a session
+async fn login_two_factor(
+	jar: CookieJar,
+	headers: HeaderMap,
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+	Json(payload): Json<TwoFactorLoginRequest>,
+) -> CookiedAPIResponse<LoginResult> {
+	let expired = || (StatusCode::UNAUTHORIZED, GenericMessage::new("Login expired, please sign in again."));
+
+	let user_id = decode_two_factor_token(&payload.two_factor_token, config.security.clone().unwrap().jwt_secret.as_bytes())
+		.ok_or_else(expired)?;
+
+	with_repos(&store, move |repos| {
+		let user = match repos.users().get_by_id(user_id) {
+			Ok(user) => user.filter(|user| user.deleted_at.is_none()).ok_or_else(expired)?,
+			Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+		};
 
-			Ok((
-				jar2,
-				Json(LoginResponse {
-					token,
-					user: user.sanitize(),
-				}),
-			))
+		// 2FA may have been disabled since the first step, in which case the password alone would have been enough
+		if user.has_two_factor() {
+			verify_second_factor(&user, &payload.code, repos)?;
 		}
-		Err(_) => Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials."))),
-	}
+
+		finish_login(jar, &user, &headers, &config, repos)
+	})
+	.await
 }
 
-async fn user_profile(AuthedUser(user): AuthedUser) -> APIResponse<SanitizedUser> {
+async fn user_profile(
+	AuthedUser(user): AuthedUser,
+	Permissions(permissions): Permissions,
+) -> APIResponse<SanitizedUser> {
 	match user {
 		None => Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials."))),
-		Some(user) => Ok((StatusCode::OK, Json(SanitizedUser::from(&user)))),
+		Some(user) => Ok((
+			StatusCode::OK,
+			Json(user.sanitize(permissions.iter().map(|permission| permission.to_string()).collect())),
+		)),
 	}
 }
 
 async fn my_links(
 	AuthedUser(user): AuthedUser,
 	Query(pagination): Query<PaginationQuery>,
-	Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
 ) -> APIResponse<PaginatedResponse<LinkWithDomain>> {
 	let owner_id: Option<i32> = user.map(|u| u.id);
 
@@ -294,38 +419,127 @@ async fn my_links(
 		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
 	}
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+	with_repos(&store, move |repos| {
+		let items = repos
+			.links()
+			.get_by_owner_id_paginated(owner_id.unwrap(), pagination.page, pagination.per_page)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
 
-	let items = Link::get_by_owner_id_paginated(owner_id.unwrap(), pagination.page, pagination.per_page, conn)
-		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+		let items = items.iter().map(|(link, domain)| {
+			LinkWithDomain::new(link.clone(), domain.clone())
+		}).collect();
 
-	let items = items.iter().map(|(link, domain)| {
-		LinkWithDomain::new(link.clone(), domain.clone())
-	}).collect();
+		let total_count = repos
+			.links()
+			.get_total_count(owner_id.unwrap())
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
 
-	let total_count = Link::get_total_count(owner_id.unwrap(), conn)
-		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
 
 
+		Ok((StatusCode::OK, Json(PaginatedResponse::<LinkWithDomain> { items, total_count })))
+	})
+	.await
+}
+
+async fn my_trashed_links(
+	AuthedUser(user): AuthedUser,
+	Query(pagination): Query<PaginationQuery>,
+	Extension(store): Extension<SharedStore>,
+) -> APIResponse<PaginatedResponse<LinkWithDomain>> {
+	let owner_id = match user {
+		Some(user) => user.id,
+		None => {
+			return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))
+		}
+	};
+
+	with_repos(&store, move |repos| {
+		let items = repos
+			.links()
+			.get_trashed_by_owner_id_paginated(owner_id, pagination.page, pagination.per_page)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		let items = items
+			.into_iter()
+			.map(|(link, domain)| LinkWithDomain::new(link, domain))
+			.collect();
+
+		let total_count = repos
+			.links()
+			.get_trashed_total_count(owner_id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		Ok((StatusCode::OK, Json(PaginatedResponse::<LinkWithDomain> { items, total_count })))
+	})
+	.await
+}
+
+/// Gets a link from the user's trash
+fn get_my_trashed_link(id: i32, user: Option<User>, repos: &mut dyn Repositories) -> Result<Link, APIError> {
+	let owner_id = match user {
+		Some(user) => user.id,
+		None => {
+			return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))
+		}
+	};
+
+	let link = repos
+		.links()
+		.get_trashed_by_id(id)
+		.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Link not found in trash.")))?;
+
+	if link.owner_id != Some(owner_id) {
+		return Err((StatusCode::NOT_FOUND, GenericMessage::new("Link not found in trash.")));
+	}
+
+	Ok(link)
+}
+
+async fn restore_trashed_link(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let link = get_my_trashed_link(id, user, repos)?;
 
-	Ok((StatusCode::OK, Json(PaginatedResponse::<LinkWithDomain> { items, total_count })))
+		match repos.links().restore(&link) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Link restored."))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+		}
+	})
+	.await
 }
 
-async fn logout_user(jar: CookieJar) -> CookiedAPIResponse<GenericMessage> {
-	let cookie = Cookie::build(("auth_token", "deleted"))
-		.http_only(true) // Prevent JavaScript access
-		.secure(false) // Only send over HTTPS
-		.same_site(axum_extra::extract::cookie::SameSite::Lax) // Control cross-site sending
-		.path("/") // Path for which the cookie is valid
-		.max_age(time::Duration::hours(1)) // Set cookie expiration
-		.expires(time::OffsetDateTime::from_unix_timestamp(0).unwrap())
-		.build();
+async fn purge_trashed_link(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Path(id): Path<i32>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let link = get_my_trashed_link(id, user, repos)?;
 
-	let jar2 = jar.add(cookie);
+		match repos.links().purge(&link) {
+			Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Link permanently deleted."))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+		}
+	})
+	.await
+}
 
-	Ok((jar2, GenericMessage::new("Logged out")))
+async fn logout_user(
+	jar: CookieJar,
+	Extension(config): Extension<Config>,
+	Extension(store): Extension<SharedStore>,
+) -> CookiedAPIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		end_session(&jar, &config, repos);
+
+		let jar2 = clear_session_cookies(jar, &config);
+
+		Ok((jar2, GenericMessage::new("Logged out")))
+	})
+	.await
 }
 
 async fn check_password(Json(payload): Json<CheckPasswordRequest>) -> APIResponse<CheckPasswordResponse> {
@@ -336,11 +550,12 @@ async fn check_password(Json(payload): Json<CheckPasswordRequest>) -> APIRespons
 
 async fn update_password(
 	AuthedUser(user): AuthedUser,
+	jar: CookieJar,
 	Extension(config): Extension<Config>,
-	Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
 	Json(payload): Json<ChangePasswordRequest>,
 ) -> APIResponse<GenericMessage> {
-	let security_config = config.security.unwrap();
+	let security_config = config.security.clone().unwrap();
 	let owner_id: Option<i32> = user.clone().map(|u| u.id);
 
 	if owner_id.is_none() {
@@ -349,205 +564,597 @@ async fn update_password(
 
 	let user = user.unwrap();
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+	with_repos(&store, move |repos| {
+		// Confirm password
 
-	// Confirm password
+		let is_valid = verify_password(&payload.password, &user.password_hash)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
 
-	let argon2 = Argon2::default();
-	let parsed_hash = PasswordHash::new(&user.password_hash)
-		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+		// Verify the password
+		match is_valid {
+			true => {
+				if payload.new_password != payload.confirm_password {
+					return Err((StatusCode::CONFLICT, GenericMessage::new("Passwords do not match.")));
+				}
 
-	// Verify the password
-	match argon2.verify_password(&payload.password.as_bytes(), &parsed_hash) {
-		Ok(_) => {
-			if payload.new_password != payload.confirm_password {
-				return Err((StatusCode::CONFLICT, GenericMessage::new("Passwords do not match.")));
-			}
+				let password_estimate = zxcvbn(&payload.new_password, &[]);
 
-			let password_estimate = zxcvbn(&payload.new_password, &[]);
+				if password_estimate.score().lt(&security_config.min_password_strength) {
+					return Err((StatusCode::CONFLICT, GenericMessage::new("Password is not strong enough.")));
+				}
 
-			if password_estimate.score().lt(&security_config.min_password_strength) {
-				return Err((StatusCode::CONFLICT, GenericMessage::new("Password is not strong enough.")));
-			}
+				// TODO: Send validation link (if REQUIRE_EMAIL_VALIDATION & SMTP configured)
 
-			let salt = SaltString::generate(&mut OsRng);
+				let password_hash = match hash_password(&payload.new_password) {
+					Ok(hash) => hash,
+					Err(_) => {
+						return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))
+					}
+				};
 
-			let argon2 = Argon2::default();
+				let current_session_id = current_session_id(&jar, &config);
 
-			// TODO: Send validation link (if REQUIRE_EMAIL_VALIDATION & SMTP configured)
+				let updated = repository::transaction(repos, |repos| {
+					repos.users().update_password_hash(&user, password_hash)?;
 
-			// Hash password to PHC string ($argon2id$v=19$...)
-			let password_hash = match argon2.hash_password(&payload.new_password.into_bytes(), &salt) {
-				Ok(hash) => hash.to_string(),
-				Err(_) => {
-					return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))
-				}
-			};
+					// Log out everywhere else, in case the old password was compromised
+					repos.tokens().revoke_all_sessions(user.id, current_session_id)
+				});
 
-			match user.update_password_hash(password_hash, conn) {
-				Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Password updated."))),
-				Err(_) => {
-					return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))
+				match updated {
+					Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Password updated."))),
+					Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error"))),
 				}
 			}
+			false => Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials."))),
 		}
-		Err(_) => Err((StatusCode::UNAUTHORIZED, GenericMessage::new("Invalid credentials."))),
-	}
+	})
+	.await
 }
 
-async fn validate_email(Extension(pool): Extension<DbPool>, Path(token): Path<String>) -> APIResponse<GenericMessage> {
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+/// Sends a password reset link. Responds the same whether or not the email belongs to an account.
+async fn forgot_password(
+	Extension(store): Extension<SharedStore>,
+	Extension(config): Extension<Config>,
+	Extension(email): Extension<Email>,
+	Json(payload): Json<ForgotPasswordRequest>,
+) -> APIResponse<GenericMessage> {
+	let app_config = config.app.unwrap();
+	let response = (
+		StatusCode::OK,
+		GenericMessage::new("If an account with that email exists, a password reset link has been sent to it."),
+	);
+
+	let email_available = email.is_available();
+	let reset_token = generate_unique_string(32);
+	let token_hash = hash_token(&reset_token);
+	let expires_at = (Utc::now() + app_config.password_reset_ttl).naive_utc();
+
+	let user = with_repos(&store, move |repos| {
+		let user = match repos.users().get_by_email(&payload.email) {
+			Ok(user) => user,
+			Err(_) => return Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."))),
+		};
 
-	let records = VerificationToken::get_by_token(token, conn)
-		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to get token.")))?;
+		let user = match user {
+			Some(user) => user,
+			None => return Ok(None),
+		};
 
-	if records.len() == 0 {
-		return Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")));
+		if !email_available {
+			log::warn!("Password reset requested for user {}, but SMTP is not configured.", user.id);
+			return Ok(None);
+		}
+
+		let new_token = NewPasswordResetToken {
+			user_id: user.id,
+			token_hash,
+			expires_at,
+		};
+
+		// Only the most recently requested link works
+		repository::transaction(repos, |repos| {
+			repos.tokens().delete_password_reset_tokens(user.id)?;
+
+			repos.tokens().insert_password_reset_token(&new_token)
+		})
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		Ok(Some(user))
+	})
+	.await?;
+
+	let Some(user) = user else {
+		return Ok(response);
+	};
+
+	tokio::spawn(async move {
+		email
+			.send_template::<PasswordResetEmail>(
+				&user.email,
+				&PasswordResetEmail {
+					base_url: &app_config.base_url,
+					username: &user.username,
+					reset_token: reset_token.as_str(),
+					ttl: format!("{}", app_config.password_reset_ttl).as_str(),
+				},
+			)
+			.await;
+	});
+
+	Ok(response)
+}
+
+async fn reset_password(
+	Extension(store): Extension<SharedStore>,
+	Extension(config): Extension<Config>,
+	Path(token): Path<String>,
+	Json(payload): Json<ResetPasswordRequest>,
+) -> APIResponse<GenericMessage> {
+	let security_config = config.security.unwrap();
+
+	if payload.password != payload.confirm_password {
+		return Err((StatusCode::CONFLICT, GenericMessage::new("Passwords do not match.")));
 	}
 
-	let (token, user) = records.get(0).unwrap();
+	let password_estimate = zxcvbn(&payload.password, &[]);
 
-	if token.is_expired() {
-		return Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")));
+	if password_estimate.score().lt(&security_config.min_password_strength) {
+		return Err((StatusCode::CONFLICT, GenericMessage::new("Password is not strong enough.")));
 	}
 
-	let _ = token.delete(conn);
+	with_repos(&store, move |repos| {
+		let (token, user) = repos
+			.tokens()
+			.get_password_reset_token(&hash_token(&token))
+			.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")))?;
+
+		if token.is_expired() {
+			let _ = repos.tokens().delete_password_reset_token(&token);
+			return Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")));
+		}
+
+		let password_hash = hash_password(&payload.password)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error")))?;
+
+		// All or nothing, so the token is never used up without the password changing or the other way around
+		let is_reset = repository::transaction(repos, |repos| {
+			// Another request used the token in the meantime
+			if repos.tokens().delete_password_reset_token(&token)? == 0 {
+				return Ok(false);
+			}
+
+			repos.tokens().delete_password_reset_tokens(user.id)?;
+			repos.users().update_password_hash(&user, password_hash)?;
+
+			// Whoever knew the old password shouldn't stay logged in
+			repos.tokens().revoke_all_sessions(user.id, None)?;
+
+			Ok(true)
+		});
+
+		match is_reset {
+			Ok(true) => Ok((StatusCode::OK, GenericMessage::new("Password reset."))),
+			Ok(false) => Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid."))),
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal Server Error"))),
+		}
+	})
+	.await
+}
+
+async fn validate_email(
+	Extension(store): Extension<SharedStore>,
+	Path(token): Path<String>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let record = repos
+			.tokens()
+			.get_verification_token(&token)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to get token.")))?;
+
+		let Some((token, user)) = record else {
+			return Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")));
+		};
+
+		if token.is_expired() {
+			return Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")));
+		}
 
-	user.set_verified_at(Some(Utc::now().naive_utc()), conn)
+		repository::transaction(repos, |repos| {
+			repos.tokens().delete_verification_token(&token)?;
+			repos.users().set_verified_at(&user, Some(Utc::now().naive_utc()))
+		})
 		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to set verified at.")))?;
 
-	Ok((StatusCode::OK, GenericMessage::new("Email verified.")))
+		Ok((StatusCode::OK, GenericMessage::new("Email verified.")))
+	})
+	.await
+}
+
+async fn resend_verification(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Extension(config): Extension<Config>,
+	Extension(email): Extension<Email>,
+) -> APIResponse<GenericMessage> {
+	let user =
+		user.ok_or((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")))?;
+	let app_config = config.app.unwrap();
+
+	if !app_config.enable_email_verification {
+		return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Email verification is disabled.")));
+	}
+
+	if user.verified_at.is_some() {
+		return Err((StatusCode::CONFLICT, GenericMessage::new("Email already verified.")));
+	}
+
+	if !email.is_available() {
+		return Err((StatusCode::SERVICE_UNAVAILABLE, GenericMessage::new("Emails can't be sent right now.")));
+	}
+
+	with_repos(&store, move |repos| {
+		let latest_token = repos
+			.tokens()
+			.get_latest_verification_token(user.id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to get token.")))?;
+
+		if let Some(token) = latest_token {
+			let available_at = token.created_at + app_config.verification_resend_cooldown.0;
+			let now = Utc::now().naive_utc();
+
+			if available_at > now {
+				return Err((
+					StatusCode::TOO_MANY_REQUESTS,
+					GenericMessage::from_string(format!(
+						"Please wait {} seconds before requesting another verification email.",
+						(available_at - now).num_seconds() + 1
+					)),
+				));
+			}
+		}
+
+		// Links from earlier emails stop working
+		if let Err(e) = repos.tokens().delete_verification_tokens(user.id) {
+			log::error!("Failed to delete old verification tokens: {:#?}", e);
+		}
+
+		send_verification_email(&user, email, app_config, repos);
+
+		Ok((StatusCode::OK, GenericMessage::new("Verification email sent.")))
+	})
+	.await
 }
 
-async fn delete_me(AuthedUser(user): AuthedUser, Extension(pool): Extension<DbPool>) -> APIResponse<GenericMessage> {
+/// Checks if the user is the only owner of any of their teams
+fn owns_team_alone(user: &User, repos: &mut dyn Repositories) -> Result<bool, APIError> {
+	let internal_error = |_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error."));
+
+	for (team, member) in repos.teams().get_for_user(user.id).map_err(internal_error)? {
+		if member.role == TeamRole::Owner.as_str()
+			&& repos
+				.teams()
+				.count_members_with_role(&team, TeamRole::Owner.as_str())
+				.map_err(internal_error)?
+				<= 1
+		{
+			return Ok(true);
+		}
+	}
+
+	Ok(false)
+}
+
+async fn delete_me(
+	AuthedUser(user): AuthedUser,
+	Extension(store): Extension<SharedStore>,
+	Extension(cache): Extension<RedirectCache>,
+) -> APIResponse<GenericMessage> {
 	if user.is_none() {
 		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
 	}
 
 	let user = user.unwrap();
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+	let (owned_links, owned_domains) = with_repos(&store, move |repos| {
+		// Teams would be left without an owner
+		if owns_team_alone(&user, repos)? {
+			return Err((
+				StatusCode::CONFLICT,
+				GenericMessage::new(
+					"Hand over the ownership of your teams, or delete them, before deleting your account.",
+				),
+			));
+		}
+
+		// Their links and domains are deleted along with them
+		let owned_links: Vec<Link> = repos
+			.links()
+			.get_by_owner_id(user.id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+		let owned_domains = repos
+			.domains()
+			.get_by_owner_id(user.id)
+			.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Internal server error.")))?;
+
+		repository::transaction(repos, |repos| {
+			repos.users().delete(&user)?;
+			repos.tokens().revoke_all_sessions(user.id, None)
+		})
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to delete user.")))?;
+
+		Ok((owned_links, owned_domains))
+	})
+	.await?;
+
+	for link in &owned_links {
+		cache.invalidate_link(link).await;
+	}
 
-	match user.delete(conn) {
-		Ok(_) => Ok((StatusCode::OK, GenericMessage::new("Deleted."))),
-		Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to delete user."))),
+	for domain in &owned_domains {
+		cache.invalidate_domain(domain).await;
 	}
+
+	Ok((StatusCode::OK, GenericMessage::new("Deleted.")))
 }
 
 // TODO: Ask for password?
 async fn update_user(
-	AuthedUser(user): AuthedUser,
+	VerifiedUser(user): VerifiedUser,
 	Extension(email): Extension<Email>,
 	Extension(config): Extension<Config>,
-	Extension(pool): Extension<DbPool>,
+	Extension(store): Extension<SharedStore>,
 	Json(payload): Json<UserUpdateRequest>,
 ) -> APIResponse<GenericMessage> {
 	let app_config = config.app.unwrap();
-	
+
 	if user.is_none() {
 		return Err((StatusCode::UNAUTHORIZED, GenericMessage::new("You are not allowed to perform this action.")));
 	}
 
 	let user = user.unwrap();
 
-	let conn = &mut pool
-		.get()
-		.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::from_string(e.to_string())))?;
+	let email_available = email.is_available();
+	let confirmation_token = generate_unique_string(32);
+	let token_hash = hash_token(&confirmation_token);
+	let expires_at = (Utc::now() + app_config.email_verification_ttl).naive_utc();
 
-	let mut update_user = UpdateUser {
-		email: None,
-		username: None,
-		verified_at: None,
-	};
+	let email_change = with_repos(&store, move |repos| {
+		let mut update_user = UpdateUser {
+			email: None,
+			username: None,
+			verified_at: None,
+		};
+
+		// The email is only changed once the new address is confirmed
+		let new_email = match payload.email {
+			Some(new_email) => {
+				if repos.users().email_exists(&new_email) {
+					return Err((StatusCode::CONFLICT, GenericMessage::new("Email already in use")));
+				}
+
+				if !EmailAddress::is_valid(&new_email) {
+					return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Invalid email")));
+				}
+
+				if !email_available {
+					return Err((
+						StatusCode::SERVICE_UNAVAILABLE,
+						GenericMessage::new("Emails can't be sent right now, so your email can't be changed."),
+					));
+				}
 
-	match payload.email {
-		Some(email) => {
-			if User::email_exists(&email, conn) {
-				return Err((StatusCode::CONFLICT, GenericMessage::new("Email already in use")));
+				Some(new_email)
 			}
+			None => None,
+		};
 
-			if !EmailAddress::is_valid(&email) {
-				return Err((StatusCode::BAD_REQUEST, GenericMessage::new("Invalid email")));
+		if let Some(username) = payload.username {
+			if repos.users().username_exists(&username) {
+				return Err((StatusCode::CONFLICT, GenericMessage::new("Username already in use")));
 			}
 
-			update_user.email = Some(email.clone());
+			update_user.username = Some(username);
 		}
-		None => {}
-	}
 
-	match payload.username {
-		Some(username) => {
-			if User::username_exists(&username, conn) {
-				return Err((StatusCode::CONFLICT, GenericMessage::new("Username already in use")));
-			}
+		if update_user.username.is_some() {
+			repos
+				.users()
+				.update(&user, update_user.clone())
+				.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to update user.")))?;
+		}
+
+		let new_email = match new_email {
+			Some(new_email) => new_email,
+			None => return Ok(None),
+		};
+
+		let new_token = NewEmailChangeToken {
+			user_id: user.id,
+			new_email: new_email.clone(),
+			token_hash,
+			expires_at,
+		};
+
+		// Only the most recently requested change can be confirmed
+		repository::transaction(repos, |repos| {
+			repos.tokens().delete_email_change_tokens(user.id)?;
+
+			repos.tokens().insert_email_change_token(&new_token)
+		})
+		.map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to update user.")))?;
+
+		let username = update_user.username.unwrap_or(user.username);
+
+		Ok(Some((new_email, username, user.email)))
+	})
+	.await?;
 
-			update_user.username = Some(username.clone());
+	let Some((new_email, username, old_email)) = email_change else {
+		return Ok((StatusCode::OK, GenericMessage::new("Updated.")));
+	};
+
+	let message = format!("Updated. Please confirm your new email with the link we sent to {}.", new_email);
+
+	tokio::spawn(async move {
+		email
+			.send_template::<EmailChangeConfirmationEmail>(
+				&new_email,
+				&EmailChangeConfirmationEmail {
+					base_url: &app_config.base_url,
+					username: &username,
+					confirmation_token: confirmation_token.as_str(),
+					ttl: format!("{}", app_config.email_verification_ttl).as_str(),
+				},
+			)
+			.await;
+
+		email
+			.send_template::<EmailChangeNoticeEmail>(
+				&old_email,
+				&EmailChangeNoticeEmail {
+					username: &username,
+					new_email: &new_email,
+				},
+			)
+			.await;
+	});
+
+	Ok((StatusCode::OK, GenericMessage::from_string(message)))
+}
+
+async fn confirm_email_change(
+	Extension(store): Extension<SharedStore>,
+	Path(token): Path<String>,
+) -> APIResponse<GenericMessage> {
+	with_repos(&store, move |repos| {
+		let (token, user) = repos
+			.tokens()
+			.get_email_change_token(&hash_token(&token))
+			.map_err(|_| (StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")))?;
+
+		if token.is_expired() {
+			let _ = repos.tokens().delete_email_change_tokens(user.id);
+			return Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid.")));
 		}
-		None => {}
-	}
 
-	// TODO: Make this less ugly
-
-	match user.update(update_user.clone(), conn) {
-		Ok(_) => {
-			let email_username = match update_user.username.clone() {
-				Some(username) => username,
-				None => user.username,
-			};
-
-			let verification_token = generate_unique_string(32);
-
-			let new_token = NewVerificationToken {
-				user_id: user.id,
-				token: verification_token.clone(),
-				expires_at: (Utc::now() + app_config.email_verification_ttl).naive_utc(),
-			};
-
-			new_token.insert(conn);
-
-			if update_user.email.is_some() && email.is_available() {
-				tokio::spawn(async move {
-					let _ = email
-						.send_template::<VerificationEmail>(
-							&update_user.email.unwrap(),
-							&VerificationEmail {
-								base_url: &app_config.base_url,
-								username: &email_username,
-								verification_token: verification_token.as_str(),
-								ttl: format!(
-									"{}",
-									app_config.email_verification_ttl,
-								)
-								.as_str(),
-							},
-						)
-						.await;
-				});
+		// Someone may have taken the address while the change was pending
+		if repos.users().email_exists(&token.new_email) {
+			let _ = repos.tokens().delete_email_change_tokens(user.id);
+			return Err((StatusCode::CONFLICT, GenericMessage::new("Email already in use")));
+		}
+
+		let values = UpdateUser {
+			username: None,
+			email: Some(token.new_email.clone()),
+			// Clicking the link proves the user owns the new address
+			verified_at: Some(Utc::now().naive_utc()),
+		};
+
+		// All or nothing, so the token is never used up without the email changing or the other way around
+		let is_changed = repository::transaction(repos, |repos| {
+			// Another request used the token in the meantime
+			if repos.tokens().delete_email_change_tokens(user.id)? == 0 {
+				return Ok(false);
 			}
 
-			Ok((StatusCode::OK, GenericMessage::new("Updated.")))
+			repos.users().update(&user, values)?;
+
+			// Verification links sent to the old address shouldn't verify the new one
+			repos.tokens().delete_verification_tokens(user.id)?;
+
+			Ok(true)
+		});
+
+		match is_changed {
+			Ok(true) => Ok((StatusCode::OK, GenericMessage::new("Email changed."))),
+			Ok(false) => Err((StatusCode::NOT_FOUND, GenericMessage::new("Token expired or invalid."))),
+			Err(e) if is_unique_violation(&e) => {
+				Err((StatusCode::CONFLICT, GenericMessage::new("Email already in use")))
+			}
+			Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to update user."))),
 		}
-		Err(_) => Err((StatusCode::INTERNAL_SERVER_ERROR, GenericMessage::new("Failed to update user."))),
-	}
+	})
+	.await
 }
 
 // Starts at /api/user
 pub fn user_router() -> Router {
 	Router::new()
 		.route("/password", post(check_password))
+		.route("/password/forgot", post(forgot_password))
+		.route("/password/reset/:token", post(reset_password))
 		.route("/register", post(register_user))
 		.route("/login", post(login_user))
+		.route("/login/2fa", post(login_two_factor))
+		.nest("/oidc", oidc::oidc_router())
 		.route("/logout", post(logout_user))
+		.route("/refresh", post(refresh_session))
 		.route("/me", get(user_profile))
 		.route("/me", delete(delete_me))
-		.route("/me/links", get(my_links))
+		.route("/me/links", get(my_links).layer(Extension(ApiKeyScope::LinksRead)))
+		.route("/me/links/trash", get(my_trashed_links).layer(Extension(ApiKeyScope::LinksRead)))
+		.route("/me/links/trash/:id", delete(purge_trashed_link).layer(Extension(ApiKeyScope::LinksWrite)))
+		.route("/me/links/trash/:id/restore", post(restore_trashed_link).layer(Extension(ApiKeyScope::LinksWrite)))
+		.nest("/me/keys", api_keys::api_keys_router())
+		.nest("/me/sessions", sessions::sessions_router())
+		.nest("/me/2fa", two_factor::two_factor_router())
 		.route("/me/update", post(update_user))
 		.route("/me/password", post(update_password))
+		.route("/email/confirm/:token", get(confirm_email_change))
+		.route("/verify/resend", post(resend_verification))
 		.route("/verify/:token", get(validate_email))
 }
+
+#[cfg(test)]
+mod test {
+	use serde_json::json;
+
+	use super::*;
+	use crate::test_app::TestApp;
+
+	#[tokio::test]
+	async fn test_login_with_wrong_password() {
+		let app = TestApp::new();
+		app.sign_up("alice").await;
+
+		let response = app
+			.post("/api/user/login", json!({ "email": "alice@example.com", "password": "wrong" }), None)
+			.await;
+		assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+		assert!(response.auth_token.is_none());
+	}
+
+	#[tokio::test]
+	async fn test_register_taken_email() {
+		let app = TestApp::new();
+		app.sign_up("alice").await;
+
+		let response = app
+			.post(
+				"/api/user/register",
+				json!({
+					"username": "bob",
+					"password": "correct horse battery staple",
+					"confirm_password": "correct horse battery staple",
+					"email": "alice@example.com",
+					"confirm_email": "alice@example.com",
+				}),
+				None,
+			)
+			.await;
+		assert_eq!(response.status, StatusCode::CONFLICT);
+	}
+
+	#[tokio::test]
+	async fn test_profile_needs_session() {
+		let app = TestApp::new();
+		let token = app.sign_up("alice").await;
+
+		assert_eq!(app.get("/api/user/me", None).await.status, StatusCode::UNAUTHORIZED);
+
+		let response = app.get("/api/user/me", Some(&token)).await;
+		assert_eq!(response.status, StatusCode::OK);
+		assert_eq!(response.body["username"], "alice");
+	}
+}
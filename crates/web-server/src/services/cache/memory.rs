@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use moka::sync::Cache;
+
+use super::{CacheBackend, CacheError};
+
+/// Keeps values in this process. Least useful entries are evicted first once it's full.
+pub struct MemoryBackend {
+	cache: Cache<String, String>,
+}
+
+impl MemoryBackend {
+	pub fn new(max_entries: u64, ttl: Duration) -> Self {
+		Self {
+			cache: Cache::builder()
+				.max_capacity(max_entries)
+				.time_to_live(ttl)
+				.support_invalidation_closures()
+				.build(),
+		}
+	}
+
+	pub fn get_local(&self, key: &str) -> Option<String> {
+		self.cache.get(key)
+	}
+
+	pub fn set_local(&self, key: &str, value: String) {
+		self.cache.insert(key.to_string(), value);
+	}
+
+	pub fn invalidate_local(&self, key: &str) {
+		self.cache.invalidate(key);
+	}
+
+	pub fn invalidate_prefix_local(&self, prefix: &str) {
+		let prefix = prefix.to_string();
+
+		if let Err(e) = self.cache.invalidate_entries_if(move |key, _| key.starts_with(&prefix)) {
+			log::error!("Failed to invalidate cached entries: {}", e);
+			self.clear();
+		}
+	}
+
+	pub fn clear(&self) {
+		self.cache.invalidate_all();
+	}
+}
+
+#[async_trait]
+impl CacheBackend for MemoryBackend {
+	fn name(&self) -> &'static str {
+		"memory"
+	}
+
+	async fn get(&self, key: &str) -> Result<Option<String>, CacheError> {
+		Ok(self.get_local(key))
+	}
+
+	async fn set(&self, key: &str, value: String) -> Result<(), CacheError> {
+		self.set_local(key, value);
+		Ok(())
+	}
+
+	async fn invalidate(&self, keys: &[String]) -> Result<(), CacheError> {
+		for key in keys {
+			self.invalidate_local(key);
+		}
+
+		Ok(())
+	}
+
+	async fn invalidate_prefix(&self, prefix: &str) -> Result<(), CacheError> {
+		self.invalidate_prefix_local(prefix);
+		Ok(())
+	}
+}
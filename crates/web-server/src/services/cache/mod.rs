@@ -0,0 +1,38 @@
+pub mod memory;
+pub mod redis;
+#[cfg(test)]
+mod redis_stand_in;
+
+use std::fmt;
+
+use axum::async_trait;
+
+pub use memory::MemoryBackend;
+pub use redis::RedisBackend;
+
+#[derive(Debug)]
+pub struct CacheError(String);
+
+impl fmt::Display for CacheError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Cache error: {}", self.0)
+	}
+}
+
+/// Where cached values are kept. Values are opaque strings, callers take care of serializing them.
+#[async_trait]
+pub trait CacheBackend: Send + Sync {
+	/// Shown in the cache stats
+	fn name(&self) -> &'static str;
+
+	async fn get(&self, key: &str) -> Result<Option<String>, CacheError>;
+
+	/// Stores a value, it expires after the configured TTL
+	async fn set(&self, key: &str, value: String) -> Result<(), CacheError>;
+
+	/// Removes the keys on every instance sharing the cache
+	async fn invalidate(&self, keys: &[String]) -> Result<(), CacheError>;
+
+	/// Removes every key starting with the prefix on every instance sharing the cache
+	async fn invalidate_prefix(&self, prefix: &str) -> Result<(), CacheError>;
+}
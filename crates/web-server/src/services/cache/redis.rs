@@ -0,0 +1,286 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use futures_util::StreamExt;
+use redis::{aio::ConnectionManager, aio::PubSub, Client, RedisError};
+
+use super::{CacheBackend, CacheError, MemoryBackend};
+
+/// Invalidations are announced here, so every instance drops its local copies
+const INVALIDATION_CHANNEL: &str = "shurlix:cache:invalidate";
+/// Keeps our keys apart from anything else in the same database
+const KEY_PREFIX: &str = "shurlix:cache:";
+/// How long to wait before subscribing again after losing the connection
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+impl From<RedisError> for CacheError {
+	fn from(e: RedisError) -> Self {
+		CacheError(e.to_string())
+	}
+}
+
+/// An invalidation announced to the other instances
+#[derive(Debug, PartialEq)]
+enum Invalidation {
+	Key(String),
+	Prefix(String),
+}
+
+impl Invalidation {
+	fn encode(&self) -> String {
+		match self {
+			Invalidation::Key(key) => format!("key:{}", key),
+			Invalidation::Prefix(prefix) => format!("prefix:{}", prefix),
+		}
+	}
+
+	fn decode(message: &str) -> Option<Self> {
+		if let Some(key) = message.strip_prefix("key:") {
+			return Some(Invalidation::Key(key.to_string()));
+		}
+
+		message
+			.strip_prefix("prefix:")
+			.map(|prefix| Invalidation::Prefix(prefix.to_string()))
+	}
+
+	fn apply(&self, local: &MemoryBackend) {
+		match self {
+			Invalidation::Key(key) => local.invalidate_local(key),
+			Invalidation::Prefix(prefix) => local.invalidate_prefix_local(prefix),
+		}
+	}
+}
+
+/// Shares cached values between instances through a server speaking the Redis protocol.
+///
+/// Each instance also keeps a local copy of what it read, so hot keys don't need a round trip. Invalidations are
+/// published to every instance, which then drop their local copies.
+pub struct RedisBackend {
+	connection: ConnectionManager,
+	local: Arc<MemoryBackend>,
+	ttl: Duration,
+}
+
+impl RedisBackend {
+	/// Connects to the server, and starts listening for invalidations from other instances
+	pub async fn connect(url: &str, max_entries: u64, ttl: Duration) -> Result<Self, CacheError> {
+		let client = Client::open(url)?;
+		let connection = client.get_connection_manager().await?;
+		let local = Arc::new(MemoryBackend::new(max_entries, ttl));
+
+		let pubsub = subscribe(&client).await?;
+		tokio::spawn(listen_for_invalidations(client, pubsub, local.clone()));
+
+		Ok(Self { connection, local, ttl })
+	}
+
+	async fn publish(&self, invalidation: Invalidation) -> Result<(), CacheError> {
+		redis::cmd("PUBLISH")
+			.arg(INVALIDATION_CHANNEL)
+			.arg(invalidation.encode())
+			.query_async::<()>(&mut self.connection.clone())
+			.await?;
+
+		Ok(())
+	}
+}
+
+async fn subscribe(client: &Client) -> Result<PubSub, CacheError> {
+	let mut pubsub = client.get_async_pubsub().await?;
+	pubsub.subscribe(INVALIDATION_CHANNEL).await?;
+
+	Ok(pubsub)
+}
+
+async fn listen_for_invalidations(client: Client, mut pubsub: PubSub, local: Arc<MemoryBackend>) {
+	loop {
+		let mut messages = pubsub.into_on_message();
+
+		while let Some(message) = messages.next().await {
+			match message
+				.get_payload::<String>()
+				.ok()
+				.and_then(|payload| Invalidation::decode(&payload))
+			{
+				Some(invalidation) => invalidation.apply(&local),
+				None => log::warn!("Ignoring malformed cache invalidation message"),
+			}
+		}
+
+		log::warn!("Lost the cache invalidation subscription, resubscribing");
+
+		pubsub = loop {
+			tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+
+			match subscribe(&client).await {
+				Ok(pubsub) => break pubsub,
+				Err(e) => log::warn!("Failed to resubscribe to cache invalidations: {}", e),
+			}
+		};
+
+		// Invalidations sent in the meantime were missed
+		local.clear();
+	}
+}
+
+#[async_trait]
+impl CacheBackend for RedisBackend {
+	fn name(&self) -> &'static str {
+		"redis"
+	}
+
+	async fn get(&self, key: &str) -> Result<Option<String>, CacheError> {
+		if let Some(value) = self.local.get_local(key) {
+			return Ok(Some(value));
+		}
+
+		let value: Option<String> = redis::cmd("GET")
+			.arg(format!("{}{}", KEY_PREFIX, key))
+			.query_async(&mut self.connection.clone())
+			.await?;
+
+		if let Some(value) = &value {
+			self.local.set_local(key, value.clone());
+		}
+
+		Ok(value)
+	}
+
+	async fn set(&self, key: &str, value: String) -> Result<(), CacheError> {
+		redis::cmd("SET")
+			.arg(format!("{}{}", KEY_PREFIX, key))
+			.arg(&value)
+			.arg("EX")
+			.arg(self.ttl.as_secs().max(1))
+			.query_async::<()>(&mut self.connection.clone())
+			.await?;
+
+		self.local.set_local(key, value);
+
+		Ok(())
+	}
+
+	async fn invalidate(&self, keys: &[String]) -> Result<(), CacheError> {
+		if keys.is_empty() {
+			return Ok(());
+		}
+
+		for key in keys {
+			self.local.invalidate_local(key);
+		}
+
+		let prefixed: Vec<String> = keys.iter().map(|key| format!("{}{}", KEY_PREFIX, key)).collect();
+
+		redis::cmd("DEL")
+			.arg(prefixed)
+			.query_async::<()>(&mut self.connection.clone())
+			.await?;
+
+		for key in keys {
+			self.publish(Invalidation::Key(key.clone())).await?;
+		}
+
+		Ok(())
+	}
+
+	async fn invalidate_prefix(&self, prefix: &str) -> Result<(), CacheError> {
+		self.local.invalidate_prefix_local(prefix);
+
+		let mut connection = self.connection.clone();
+
+		// Prefixes are built from hosts and ids, which don't contain glob characters
+		let keys: Vec<String> = {
+			let mut scan = redis::cmd("SCAN");
+			scan.cursor_arg(0)
+				.arg("MATCH")
+				.arg(format!("{}{}*", KEY_PREFIX, prefix))
+				.arg("COUNT")
+				.arg(1000);
+
+			let mut iter = scan.iter_async::<String>(&mut connection).await?;
+
+			let mut keys = Vec::new();
+
+			while let Some(key) = iter.next_item().await {
+				keys.push(key);
+			}
+
+			keys
+		};
+
+		if !keys.is_empty() {
+			redis::cmd("DEL").arg(keys).query_async::<()>(&mut connection).await?;
+		}
+
+		self.publish(Invalidation::Prefix(prefix.to_string())).await
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::{super::redis_stand_in::RedisStandIn, *};
+
+	const TTL: Duration = Duration::from_secs(60);
+
+	/// Waits for an invalidation to reach the other instance
+	async fn eventually_missing(backend: &RedisBackend, key: &str) -> bool {
+		for _ in 0..50 {
+			if backend.local.get_local(key).is_none() {
+				return true;
+			}
+
+			tokio::time::sleep(Duration::from_millis(20)).await;
+		}
+
+		false
+	}
+
+	#[test]
+	fn test_invalidation_encoding() {
+		for invalidation in [
+			Invalidation::Key("host:sho.rt".to_string()),
+			Invalidation::Prefix("link:1:".to_string()),
+		] {
+			assert_eq!(Invalidation::decode(&invalidation.encode()), Some(invalidation));
+		}
+
+		assert_eq!(Invalidation::decode("nonsense"), None);
+	}
+
+	#[tokio::test]
+	async fn test_shared_between_instances() {
+		let server = RedisStandIn::start().await;
+		let first = RedisBackend::connect(&server.url(), 100, TTL).await.unwrap();
+		let second = RedisBackend::connect(&server.url(), 100, TTL).await.unwrap();
+
+		first.set("link:1:abc", "cached".to_string()).await.unwrap();
+
+		assert_eq!(second.get("link:1:abc").await.unwrap().as_deref(), Some("cached"));
+		assert_eq!(second.get("link:1:missing").await.unwrap(), None);
+	}
+
+	#[tokio::test]
+	async fn test_invalidation_reaches_other_instances() {
+		let server = RedisStandIn::start().await;
+		let first = RedisBackend::connect(&server.url(), 100, TTL).await.unwrap();
+		let second = RedisBackend::connect(&server.url(), 100, TTL).await.unwrap();
+
+		first.set("link:1:abc", "cached".to_string()).await.unwrap();
+		first.set("link:2:abc", "other domain".to_string()).await.unwrap();
+		// Both end up in the second instance's local copy
+		second.get("link:1:abc").await.unwrap();
+		second.get("link:2:abc").await.unwrap();
+
+		first.invalidate(&["link:1:abc".to_string()]).await.unwrap();
+
+		assert!(eventually_missing(&second, "link:1:abc").await);
+		assert_eq!(second.get("link:1:abc").await.unwrap(), None);
+		assert!(second.get("link:2:abc").await.unwrap().is_some());
+
+		first.invalidate_prefix("link:2:").await.unwrap();
+
+		assert!(eventually_missing(&second, "link:2:abc").await);
+		assert_eq!(second.get("link:2:abc").await.unwrap(), None);
+	}
+}
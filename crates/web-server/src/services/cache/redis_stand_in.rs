@@ -0,0 +1,170 @@
+//! A tiny server speaking just enough of the Redis protocol for the cache, so tests don't need a real one
+
+use std::{
+	collections::HashMap,
+	net::SocketAddr,
+	sync::{Arc, Mutex},
+};
+
+use tokio::{
+	io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+	net::{TcpListener, TcpStream},
+	sync::mpsc::{unbounded_channel, UnboundedSender},
+};
+
+#[derive(Default)]
+struct State {
+	values: HashMap<String, String>,
+	subscribers: Vec<(String, UnboundedSender<Vec<u8>>)>,
+}
+
+pub struct RedisStandIn {
+	addr: SocketAddr,
+}
+
+impl RedisStandIn {
+	pub async fn start() -> Self {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let state = Arc::new(Mutex::new(State::default()));
+
+		tokio::spawn(async move {
+			while let Ok((stream, _)) = listener.accept().await {
+				tokio::spawn(serve(stream, state.clone()));
+			}
+		});
+
+		Self { addr }
+	}
+
+	pub fn url(&self) -> String {
+		format!("redis://{}", self.addr)
+	}
+}
+
+fn simple(value: &str) -> Vec<u8> {
+	format!("+{}\r\n", value).into_bytes()
+}
+
+fn integer(value: usize) -> Vec<u8> {
+	format!(":{}\r\n", value).into_bytes()
+}
+
+fn bulk(value: Option<&str>) -> Vec<u8> {
+	match value {
+		Some(value) => format!("${}\r\n{}\r\n", value.len(), value).into_bytes(),
+		None => b"$-1\r\n".to_vec(),
+	}
+}
+
+fn array(items: Vec<Vec<u8>>) -> Vec<u8> {
+	let mut reply = format!("*{}\r\n", items.len()).into_bytes();
+	reply.extend(items.into_iter().flatten());
+	reply
+}
+
+/// Reads a command, sent as an array of bulk strings
+async fn read_command(reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>) -> Option<Vec<String>> {
+	let mut line = String::new();
+	reader.read_line(&mut line).await.ok().filter(|read| *read > 0)?;
+	let count: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+
+	let mut args = Vec::with_capacity(count);
+
+	for _ in 0..count {
+		line.clear();
+		reader.read_line(&mut line).await.ok()?;
+		let length: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+
+		let mut arg = vec![0; length + 2];
+		reader.read_exact(&mut arg).await.ok()?;
+		arg.truncate(length);
+		args.push(String::from_utf8(arg).ok()?);
+	}
+
+	Some(args)
+}
+
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+	let (reader, mut writer) = stream.into_split();
+	let mut reader = BufReader::new(reader);
+	let (replies, mut outgoing) = unbounded_channel::<Vec<u8>>();
+
+	// Subscribers get messages pushed at any time, so everything is written from one place
+	tokio::spawn(async move {
+		while let Some(reply) = outgoing.recv().await {
+			if writer.write_all(&reply).await.is_err() {
+				break;
+			}
+		}
+	});
+
+	while let Some(args) = read_command(&mut reader).await {
+		let reply = {
+			let mut state = state.lock().unwrap();
+			let arg = |i: usize| args.get(i).map(String::as_str).unwrap_or_default();
+
+			match arg(0).to_uppercase().as_str() {
+				"PING" => simple("PONG"),
+				// Handshake, the client ignores the result
+				"CLIENT" => simple("OK"),
+				"GET" => bulk(state.values.get(arg(1)).map(String::as_str)),
+				"SET" => {
+					state.values.insert(arg(1).to_string(), arg(2).to_string());
+					simple("OK")
+				}
+				"DEL" => integer(
+					args[1..]
+						.iter()
+						.filter(|key| state.values.remove(*key).is_some())
+						.count(),
+				),
+				"SCAN" => {
+					// Only trailing wildcards are supported, and everything is returned at once
+					let pattern = args
+						.iter()
+						.position(|arg| arg.eq_ignore_ascii_case("MATCH"))
+						.map(|i| arg(i + 1));
+					let prefix = pattern.unwrap_or("*").trim_end_matches('*');
+
+					let keys = state
+						.values
+						.keys()
+						.filter(|key| key.starts_with(prefix))
+						.map(|key| bulk(Some(key)))
+						.collect();
+
+					array(vec![bulk(Some("0")), array(keys)])
+				}
+				"PUBLISH" => {
+					let message = array(vec![bulk(Some("message")), bulk(Some(arg(1))), bulk(Some(arg(2)))]);
+
+					state.subscribers.retain(|(_, subscriber)| !subscriber.is_closed());
+
+					let receivers = state
+						.subscribers
+						.iter()
+						.filter(|(channel, subscriber)| channel == arg(1) && subscriber.send(message.clone()).is_ok())
+						.count();
+
+					integer(receivers)
+				}
+				"SUBSCRIBE" => {
+					let mut confirmations = Vec::new();
+
+					for (i, channel) in args[1..].iter().enumerate() {
+						state.subscribers.push((channel.clone(), replies.clone()));
+						confirmations.extend(array(vec![bulk(Some("subscribe")), bulk(Some(channel)), integer(i + 1)]));
+					}
+
+					confirmations
+				}
+				command => format!("-ERR unknown command '{}'\r\n", command).into_bytes(),
+			}
+		};
+
+		if replies.send(reply).is_err() {
+			break;
+		}
+	}
+}
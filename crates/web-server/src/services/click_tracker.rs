@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use db::{models::NewLinkClick, repository::SharedStore};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::util::hash_ip;
+
+/// How many clicks can be queued before new ones get dropped
+const CLICK_QUEUE_SIZE: usize = 10_000;
+/// How many clicks get written in a single insert
+const CLICK_BATCH_SIZE: usize = 500;
+/// How often pending clicks get written, even if the batch isn't full
+const CLICK_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Records link clicks in the background, so redirects don't wait on the database.
+#[derive(Clone, Debug)]
+pub struct ClickTracker {
+	sender: mpsc::Sender<NewLinkClick>,
+	ip_salt: String,
+}
+
+impl ClickTracker {
+	/// Creates the tracker and spawns the task writing the batches. Must be called from within the runtime.
+	pub fn new(store: SharedStore, ip_salt: String) -> Self {
+		let (sender, receiver) = mpsc::channel(CLICK_QUEUE_SIZE);
+
+		tokio::spawn(Self::run(store, receiver));
+
+		Self { sender, ip_salt }
+	}
+
+	/// Queues a click. Never blocks; if the queue is full the click is dropped.
+	pub fn record(
+		&self,
+		link_id: i32,
+		domain_id: i32,
+		referrer: Option<String>,
+		user_agent: Option<String>,
+		ip: Option<String>,
+	) {
+		let click = NewLinkClick {
+			link_id,
+			domain_id,
+			referrer,
+			user_agent,
+			ip_hash: ip.map(|ip| hash_ip(&ip, self.ip_salt.as_bytes())),
+			clicked_at: Utc::now().naive_utc(),
+		};
+
+		match self.sender.try_send(click) {
+			Ok(_) => {}
+			Err(TrySendError::Full(_)) => log::warn!("Click queue is full, dropping click."),
+			Err(TrySendError::Closed(_)) => log::error!("Click queue is closed, dropping click."),
+		}
+	}
+
+	async fn run(store: SharedStore, mut receiver: mpsc::Receiver<NewLinkClick>) {
+		let mut buffer: Vec<NewLinkClick> = Vec::with_capacity(CLICK_BATCH_SIZE);
+		let mut interval = tokio::time::interval(CLICK_FLUSH_INTERVAL);
+
+		loop {
+			tokio::select! {
+				click = receiver.recv() => match click {
+					Some(click) => {
+						buffer.push(click);
+
+						if buffer.len() >= CLICK_BATCH_SIZE {
+							Self::flush(&store, &mut buffer).await;
+						}
+					}
+					None => {
+						Self::flush(&store, &mut buffer).await;
+						break;
+					}
+				},
+				_ = interval.tick() => Self::flush(&store, &mut buffer).await,
+			}
+		}
+	}
+
+	async fn flush(store: &SharedStore, buffer: &mut Vec<NewLinkClick>) {
+		if buffer.is_empty() {
+			return;
+		}
+
+		let clicks = std::mem::take(buffer);
+
+		let result = db::repository::run(store, move |repos| repos.links().insert_clicks(&clicks)).await;
+
+		match result {
+			Ok(Ok(count)) => log::debug!("Recorded {} clicks.", count),
+			Ok(Err(e)) => log::error!("Failed to record clicks: {:#?}", e),
+			Err(e) => log::error!("Failed to record clicks: {}", e),
+		}
+	}
+}
@@ -0,0 +1,146 @@
+use std::{fmt, sync::Arc};
+
+use axum::async_trait;
+use hickory_resolver::{
+	config::{ResolverConfig, ResolverOpts},
+	error::ResolveErrorKind,
+	system_conf::read_system_conf,
+	TokioAsyncResolver,
+};
+
+/// Subdomain the challenge record has to be published on
+const CHALLENGE_RECORD_PREFIX: &str = "_shurlix-challenge";
+/// Prefix of the challenge record's value, followed by the domain's token
+const CHALLENGE_VALUE_PREFIX: &str = "shurlix-verification=";
+
+#[derive(Debug)]
+pub struct DnsError(String);
+
+impl fmt::Display for DnsError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "DNS lookup failed: {}", self.0)
+	}
+}
+
+/// Looks up TXT records. Lets tests swap the DNS for records they control.
+#[async_trait]
+pub trait TxtResolver: Send + Sync {
+	/// Gets the TXT records of a name, or nothing if it has none
+	async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, DnsError>;
+}
+
+/// Resolves TXT records over DNS, using the system's resolver configuration
+pub struct DnsTxtResolver {
+	resolver: TokioAsyncResolver,
+}
+
+impl DnsTxtResolver {
+	pub fn from_system_conf() -> Self {
+		let (config, mut options) = read_system_conf().unwrap_or_else(|e| {
+			log::warn!("Failed to read the system DNS config, using the default resolvers: {}", e);
+			(ResolverConfig::default(), ResolverOpts::default())
+		});
+
+		// The record was likely just added, a cached miss would hide it
+		options.cache_size = 0;
+
+		Self {
+			resolver: TokioAsyncResolver::tokio(config, options),
+		}
+	}
+}
+
+#[async_trait]
+impl TxtResolver for DnsTxtResolver {
+	async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, DnsError> {
+		match self.resolver.txt_lookup(name).await {
+			Ok(lookup) => Ok(lookup.iter().map(|txt| txt.to_string()).collect()),
+			Err(e) if matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. }) => Ok(Vec::new()),
+			Err(e) => Err(DnsError(e.to_string())),
+		}
+	}
+}
+
+/// Checks that users control the domains they bring, through a TXT record holding the domain's token
+#[derive(Clone)]
+pub struct DomainVerifier {
+	resolver: Arc<dyn TxtResolver>,
+}
+
+impl DomainVerifier {
+	pub fn new(resolver: impl TxtResolver + 'static) -> Self {
+		Self {
+			resolver: Arc::new(resolver),
+		}
+	}
+
+	/// The name the TXT record has to be added to. Ports are ignored, DNS doesn't know about them.
+	pub fn record_name(domain: &str) -> String {
+		let host = domain.split(':').next().unwrap_or(domain);
+
+		format!("{}.{}", CHALLENGE_RECORD_PREFIX, host)
+	}
+
+	pub fn record_value(token: &str) -> String {
+		format!("{}{}", CHALLENGE_VALUE_PREFIX, token)
+	}
+
+	/// Checks if the domain has a TXT record with the token
+	pub async fn verify(&self, domain: &str, token: &str) -> Result<bool, DnsError> {
+		let expected = Self::record_value(token);
+		let records = self.resolver.lookup_txt(&Self::record_name(domain)).await?;
+
+		Ok(records.iter().any(|record| record.trim() == expected))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashMap;
+
+	use super::*;
+
+	/// Stand-in DNS that answers from a fixed set of records
+	struct StaticTxtResolver(HashMap<String, Vec<String>>);
+
+	#[async_trait]
+	impl TxtResolver for StaticTxtResolver {
+		async fn lookup_txt(&self, name: &str) -> Result<Vec<String>, DnsError> {
+			Ok(self.0.get(name).cloned().unwrap_or_default())
+		}
+	}
+
+	struct FailingTxtResolver;
+
+	#[async_trait]
+	impl TxtResolver for FailingTxtResolver {
+		async fn lookup_txt(&self, _name: &str) -> Result<Vec<String>, DnsError> {
+			Err(DnsError("timed out".to_string()))
+		}
+	}
+
+	#[test]
+	fn test_record_name() {
+		assert_eq!(DomainVerifier::record_name("sho.rt"), "_shurlix-challenge.sho.rt");
+		assert_eq!(DomainVerifier::record_name("localhost:3000"), "_shurlix-challenge.localhost");
+	}
+
+	#[tokio::test]
+	async fn test_verify() {
+		let verifier = DomainVerifier::new(StaticTxtResolver(HashMap::from([(
+			"_shurlix-challenge.sho.rt".to_string(),
+			vec!["v=spf1 -all".to_string(), DomainVerifier::record_value("token")],
+		)])));
+
+		assert!(verifier.verify("sho.rt", "token").await.unwrap());
+		assert!(!verifier.verify("sho.rt", "other-token").await.unwrap());
+		assert!(!verifier.verify("example.com", "token").await.unwrap());
+	}
+
+	#[tokio::test]
+	async fn test_verify_lookup_failure() {
+		let verifier = DomainVerifier::new(FailingTxtResolver);
+
+		assert!(verifier.verify("sho.rt", "token").await.is_err());
+	}
+}
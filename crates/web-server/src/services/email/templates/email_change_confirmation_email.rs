@@ -0,0 +1,30 @@
+use super::EmailTemplate;
+
+pub struct EmailChangeConfirmationEmail<'a> {
+	pub username: &'a str,
+	pub confirmation_token: &'a str,
+	pub base_url: &'a str,
+	pub ttl: &'a str,
+}
+
+impl<'a> EmailTemplate for EmailChangeConfirmationEmail<'a> {
+	fn subject(&self) -> String {
+		"Please confirm your new email".to_string()
+	}
+
+	fn body(&self) -> String {
+		format!(
+			r#"Hello {},
+            You asked to change the email address of your account to this one. Please confirm it to complete the change.
+
+            Confirmation Link:
+            {}/api/user/email/confirm/{}
+
+            Until you click the link above, your account keeps using your old address. This link is valid for the next {}.
+
+            If you didn't request this change, you can safely ignore this email.
+            "#,
+			self.username, self.base_url, self.confirmation_token, self.ttl
+		)
+	}
+}
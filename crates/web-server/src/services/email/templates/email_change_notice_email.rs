@@ -0,0 +1,26 @@
+use super::EmailTemplate;
+
+/// Sent to the old address when someone asks to move the account to a new one
+pub struct EmailChangeNoticeEmail<'a> {
+	pub username: &'a str,
+	pub new_email: &'a str,
+}
+
+impl<'a> EmailTemplate for EmailChangeNoticeEmail<'a> {
+	fn subject(&self) -> String {
+		"Your email is being changed".to_string()
+	}
+
+	fn body(&self) -> String {
+		format!(
+			r#"Hello {},
+            A request was made to change the email address of your account to {}.
+
+            The change only takes effect once the new address is confirmed. Until then, nothing changes.
+
+            If you didn't request this change, please change your password right away.
+            "#,
+			self.username, self.new_email
+		)
+	}
+}
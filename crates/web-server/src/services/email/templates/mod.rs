@@ -1,5 +1,11 @@
+pub mod email_change_confirmation_email;
+pub mod email_change_notice_email;
+pub mod password_reset_email;
 pub mod verification_email;
 
+pub use email_change_confirmation_email::*;
+pub use email_change_notice_email::*;
+pub use password_reset_email::*;
 pub use verification_email::*;
 
 pub trait EmailTemplate {
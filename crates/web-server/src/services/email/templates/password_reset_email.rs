@@ -0,0 +1,30 @@
+use super::EmailTemplate;
+
+pub struct PasswordResetEmail<'a> {
+	pub username: &'a str,
+	pub reset_token: &'a str,
+	pub base_url: &'a str,
+	pub ttl: &'a str,
+}
+
+impl<'a> EmailTemplate for PasswordResetEmail<'a> {
+	fn subject(&self) -> String {
+		"Reset your password".to_string()
+	}
+
+	fn body(&self) -> String {
+		format!(
+			r#"Hello {},
+            We received a request to reset the password of your account.
+
+            Reset Link:
+            {}/dash/reset-password/{}
+
+            Clicking the link above lets you choose a new password. This link is valid for the next {} and can only be used once.
+
+            If you didn't request a password reset, you can safely ignore this email. Your password won't be changed.
+            "#,
+			self.username, self.base_url, self.reset_token, self.ttl
+		)
+	}
+}
@@ -1 +1,6 @@
+pub mod cache;
+pub mod click_tracker;
+pub mod domain_verification;
 pub mod email;
+pub mod oidc;
+pub mod redirect_cache;
@@ -0,0 +1,400 @@
+use std::{
+	future::Future,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use db::models::{Domain, Link};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::config::{CacheBackendKind, CacheConfig};
+
+use super::cache::{CacheBackend, CacheError, MemoryBackend, RedisBackend};
+
+#[derive(Default)]
+struct Counter {
+	hits: AtomicU64,
+	misses: AtomicU64,
+}
+
+impl Counter {
+	fn record<T>(&self, value: Option<T>) -> Option<T> {
+		match value {
+			Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+			None => self.misses.fetch_add(1, Ordering::Relaxed),
+		};
+
+		value
+	}
+
+	fn stats(&self) -> CacheStats {
+		CacheStats {
+			hits: self.hits.load(Ordering::Relaxed),
+			misses: self.misses.load(Ordering::Relaxed),
+		}
+	}
+}
+
+/// Counters of this instance, since it started
+#[derive(Serialize, Debug, PartialEq)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct RedirectCacheStats {
+	pub enabled: bool,
+	pub backend: Option<&'static str>,
+	pub domains: CacheStats,
+	pub links: CacheStats,
+}
+
+// The models skip fields when serialized for responses, so the cache has its own definitions. Secrets are left
+// out, as the backend may be shared with other services.
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Domain")]
+struct DomainDef {
+	id: i32,
+	domain: String,
+	public: bool,
+	created_at: NaiveDateTime,
+	updated_at: NaiveDateTime,
+	slug_strategy: Option<String>,
+	redirect_type: Option<i32>,
+	team_id: Option<i32>,
+	owner_id: Option<i32>,
+	/// Only needed to verify the domain, which doesn't go through the cache
+	#[serde(skip)]
+	verification_token: Option<String>,
+	verified_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedDomain(#[serde(with = "DomainDef")] Domain);
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Link")]
+struct LinkDef {
+	id: i32,
+	domain_id: i32,
+	slug: String,
+	custom_slug: Option<String>,
+	original_link: String,
+	owner_id: Option<i32>,
+	created_at: NaiveDateTime,
+	updated_at: NaiveDateTime,
+	deleted_at: Option<NaiveDateTime>,
+	expires_at: Option<NaiveDateTime>,
+	max_clicks: Option<i32>,
+	click_count: i32,
+	archived_at: Option<NaiveDateTime>,
+	/// Kept out of the cache, [`RedirectLink::has_password`] says if there is one
+	#[serde(skip)]
+	password_hash: Option<String>,
+	redirect_type: Option<i32>,
+	team_id: Option<i32>,
+}
+
+/// A link as redirects see it. The password hash is left out, unlocking the link loads it from the database.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RedirectLink {
+	/// Always has no password hash
+	#[serde(with = "LinkDef")]
+	pub link: Link,
+	pub has_password: bool,
+}
+
+impl From<Link> for RedirectLink {
+	fn from(mut link: Link) -> Self {
+		let has_password = link.password_hash.take().is_some();
+
+		Self { link, has_password }
+	}
+}
+
+fn domain_key(host: &str) -> String {
+	format!("host:{}", host)
+}
+
+/// Keys of a domain's links all start with this
+fn domain_links_prefix(domain_id: i32) -> String {
+	format!("link:{}:", domain_id)
+}
+
+fn link_key(domain_id: i32, slug: &str) -> String {
+	format!("{}{}", domain_links_prefix(domain_id), slug)
+}
+
+/// Keeps the lookups behind redirects in a cache, so hot links don't need the database.
+///
+/// Entries live for a limited time, in case an invalidation gets lost. Handlers that change links or domains
+/// invalidate them right away, on every instance if the backend is shared. Cache failures are logged and fall
+/// back to the database.
+#[derive(Clone)]
+pub struct RedirectCache {
+	backend: Option<Arc<dyn CacheBackend>>,
+	domain_counter: Arc<Counter>,
+	link_counter: Arc<Counter>,
+}
+
+impl RedirectCache {
+	pub fn new(backend: Option<Arc<dyn CacheBackend>>) -> Self {
+		Self {
+			backend,
+			domain_counter: Arc::default(),
+			link_counter: Arc::default(),
+		}
+	}
+
+	/// Sets up the configured backend, connecting to it if it's shared
+	pub async fn from_config(config: &CacheConfig) -> Result<Self, CacheError> {
+		if !config.enabled {
+			return Ok(Self::disabled());
+		}
+
+		let ttl = Duration::from_secs(config.ttl.0.num_seconds().max(1) as u64);
+
+		let backend: Arc<dyn CacheBackend> = match config.backend {
+			CacheBackendKind::Memory => Arc::new(MemoryBackend::new(config.max_entries, ttl)),
+			CacheBackendKind::Redis => {
+				let url = config.redis_url.as_deref().unwrap_or_default();
+				Arc::new(RedisBackend::connect(url, config.max_entries, ttl).await?)
+			}
+		};
+
+		Ok(Self::new(Some(backend)))
+	}
+
+	/// A cache that never holds anything
+	pub fn disabled() -> Self {
+		Self::new(None)
+	}
+
+	async fn read<T: DeserializeOwned>(backend: &dyn CacheBackend, key: &str) -> Option<T> {
+		let value = match backend.get(key).await {
+			Ok(value) => value?,
+			Err(e) => {
+				log::warn!("Failed to read {} from the cache: {}", key, e);
+				return None;
+			}
+		};
+
+		// Entries written by an older version may not fit anymore
+		serde_json::from_str(&value).ok()
+	}
+
+	async fn write<T: Serialize>(backend: &dyn CacheBackend, key: &str, value: &T) {
+		let value = match serde_json::to_string(value) {
+			Ok(value) => value,
+			Err(e) => return log::error!("Failed to serialize {} for the cache: {}", key, e),
+		};
+
+		if let Err(e) = backend.set(key, value).await {
+			log::warn!("Failed to write {} to the cache: {}", key, e);
+		}
+	}
+
+	/// Gets the domain for a host, loading it on a miss. Lookups that fail aren't cached.
+	pub async fn get_domain<E>(&self, host: &str, load: impl Future<Output = Result<Domain, E>>) -> Result<Domain, E> {
+		let Some(backend) = &self.backend else {
+			return load.await;
+		};
+
+		let key = domain_key(host);
+
+		if let Some(CachedDomain(domain)) = self.domain_counter.record(Self::read(backend.as_ref(), &key).await) {
+			return Ok(domain);
+		}
+
+		let domain = load.await?;
+		let cached = CachedDomain(domain);
+		Self::write(backend.as_ref(), &key, &cached).await;
+
+		Ok(cached.0)
+	}
+
+	/// Gets the link for a slug on a domain, loading it on a miss. Slugs without a link aren't cached, so new
+	/// links work right away.
+	pub async fn get_link<E>(
+		&self,
+		domain_id: i32,
+		slug: &str,
+		load: impl Future<Output = Result<Option<Link>, E>>,
+	) -> Result<Option<RedirectLink>, E> {
+		let Some(backend) = &self.backend else {
+			return Ok(load.await?.map(RedirectLink::from));
+		};
+
+		let key = link_key(domain_id, slug);
+
+		if let Some(link) = self.link_counter.record(Self::read(backend.as_ref(), &key).await) {
+			return Ok(Some(link));
+		}
+
+		let Some(link) = load.await? else {
+			return Ok(None);
+		};
+
+		let link = RedirectLink::from(link);
+		Self::write(backend.as_ref(), &key, &link).await;
+
+		Ok(Some(link))
+	}
+
+	/// Forgets a link under both of its slugs
+	pub async fn invalidate_link(&self, link: &Link) {
+		let Some(backend) = &self.backend else {
+			return;
+		};
+
+		let mut keys = vec![link_key(link.domain_id, &link.slug)];
+		keys.extend(
+			link.custom_slug
+				.iter()
+				.map(|custom_slug| link_key(link.domain_id, custom_slug)),
+		);
+
+		if let Err(e) = backend.invalidate(&keys).await {
+			log::error!("Failed to invalidate cached link {}: {}", link.id, e);
+		}
+	}
+
+	/// Forgets a domain along with its links
+	pub async fn invalidate_domain(&self, domain: &Domain) {
+		let Some(backend) = &self.backend else {
+			return;
+		};
+
+		let invalidated = match backend.invalidate(&[domain_key(&domain.domain)]).await {
+			Ok(_) => backend.invalidate_prefix(&domain_links_prefix(domain.id)).await,
+			Err(e) => Err(e),
+		};
+
+		if let Err(e) = invalidated {
+			log::error!("Failed to invalidate cached domain {}: {}", domain.id, e);
+		}
+	}
+
+	pub fn stats(&self) -> RedirectCacheStats {
+		RedirectCacheStats {
+			enabled: self.backend.is_some(),
+			backend: self.backend.as_ref().map(|backend| backend.name()),
+			domains: self.domain_counter.stats(),
+			links: self.link_counter.stats(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	fn cache() -> RedirectCache {
+		RedirectCache::new(Some(Arc::new(MemoryBackend::new(100, Duration::from_secs(60)))))
+	}
+
+	fn link(id: i32, slug: &str, custom_slug: Option<&str>) -> Link {
+		Link {
+			id,
+			original_link: "https://example.com".to_string(),
+			slug: slug.to_string(),
+			custom_slug: custom_slug.map(str::to_string),
+			owner_id: None,
+			domain_id: 1,
+			created_at: NaiveDateTime::default(),
+			updated_at: NaiveDateTime::default(),
+			deleted_at: None,
+			expires_at: None,
+			max_clicks: None,
+			click_count: 0,
+			password_hash: Some("hash".to_string()),
+			archived_at: None,
+			redirect_type: None,
+			team_id: None,
+		}
+	}
+
+	async fn load(link: Link) -> Result<Option<Link>, ()> {
+		Ok(Some(link))
+	}
+
+	async fn fail() -> Result<Option<Link>, ()> {
+		Err(())
+	}
+
+	#[tokio::test]
+	async fn test_link_hits_and_misses() {
+		let cache = cache();
+
+		assert!(cache
+			.get_link(1, "abc", load(link(1, "abc", None)))
+			.await
+			.unwrap()
+			.is_some());
+		let cached = cache.get_link(1, "abc", fail()).await.unwrap().unwrap();
+		// Only whether there is a password is cached, not its hash
+		assert_eq!((cached.link.id, cached.has_password, cached.link.password_hash), (1, true, None));
+		// Missing slugs are looked up every time
+		assert!(cache
+			.get_link(1, "nope", async { Ok::<_, ()>(None) })
+			.await
+			.unwrap()
+			.is_none());
+		assert!(cache.get_link(1, "nope", fail()).await.is_err());
+
+		let stats = cache.stats().links;
+		assert_eq!((stats.hits, stats.misses), (1, 3));
+	}
+
+	#[tokio::test]
+	async fn test_domain_secrets_not_cached() {
+		let cache = cache();
+		let domain = Domain {
+			id: 1,
+			domain: "sho.rt".to_string(),
+			public: false,
+			created_at: NaiveDateTime::default(),
+			updated_at: NaiveDateTime::default(),
+			slug_strategy: None,
+			redirect_type: None,
+			team_id: None,
+			owner_id: Some(1),
+			verification_token: Some("token".to_string()),
+			verified_at: Some(NaiveDateTime::default()),
+		};
+
+		cache.get_domain("sho.rt", async { Ok::<_, ()>(domain) }).await.unwrap();
+		let cached = cache.get_domain("sho.rt", async { Err(()) }).await.unwrap();
+
+		assert_eq!((cached.id, cached.verification_token), (1, None));
+	}
+
+	#[tokio::test]
+	async fn test_invalidate_link() {
+		let cache = cache();
+		let cached = link(1, "abc", Some("custom"));
+
+		cache.get_link(1, "abc", load(cached.clone())).await.unwrap();
+		cache.get_link(1, "custom", load(cached.clone())).await.unwrap();
+		cache.invalidate_link(&cached).await;
+
+		assert!(cache.get_link(1, "abc", fail()).await.is_err());
+		assert!(cache.get_link(1, "custom", fail()).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn test_disabled() {
+		let cache = RedirectCache::disabled();
+
+		cache.get_link(1, "abc", load(link(1, "abc", None))).await.unwrap();
+
+		assert!(cache.get_link(1, "abc", fail()).await.is_err());
+		assert!(!cache.stats().enabled);
+	}
+}
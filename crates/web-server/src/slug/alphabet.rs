@@ -0,0 +1,73 @@
+use rand::{thread_rng, Rng};
+
+use super::SlugGenerator;
+
+const BASE62_ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Base62 without 0/O/o and 1/l/I
+const UNAMBIGUOUS_ALPHABET: &str = "23456789abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// Generates random slugs from a fixed alphabet
+pub struct AlphabetGenerator {
+	alphabet: Vec<char>,
+	min_length: usize,
+}
+
+impl AlphabetGenerator {
+	pub fn new(alphabet: &str, min_length: usize) -> Self {
+		Self {
+			alphabet: alphabet.chars().collect(),
+			min_length,
+		}
+	}
+
+	pub fn base62(min_length: usize) -> Self {
+		Self::new(BASE62_ALPHABET, min_length)
+	}
+
+	pub fn unambiguous(min_length: usize) -> Self {
+		Self::new(UNAMBIGUOUS_ALPHABET, min_length)
+	}
+}
+
+impl SlugGenerator for AlphabetGenerator {
+	fn min_length(&self) -> usize {
+		self.min_length
+	}
+
+	fn keyspace(&self, length: usize) -> f64 {
+		(self.alphabet.len() as f64).powi(length as i32)
+	}
+
+	fn generate(&self, length: usize, _sequence: u64) -> String {
+		let mut rng = thread_rng();
+
+		(0..length)
+			.map(|_| self.alphabet[rng.gen_range(0..self.alphabet.len())])
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn base62_test() {
+		let slug = AlphabetGenerator::base62(8).generate(8, 0);
+
+		assert_eq!(slug.len(), 8);
+		assert!(slug.chars().all(|c| c.is_ascii_alphanumeric()));
+	}
+
+	#[test]
+	fn unambiguous_test() {
+		let generator = AlphabetGenerator::unambiguous(8);
+
+		for _ in 0..100 {
+			let slug = generator.generate(16, 0);
+
+			assert_eq!(slug.len(), 16);
+			assert!(!slug.contains(['0', 'O', 'o', '1', 'l', 'I']));
+		}
+	}
+}
@@ -0,0 +1,174 @@
+mod alphabet;
+mod sequential;
+mod words;
+
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+pub use alphabet::*;
+pub use sequential::*;
+pub use words::*;
+
+use crate::config::Config;
+
+/// The share of the keyspace that may be used before slugs grow longer
+const MAX_KEYSPACE_FILL: f64 = 0.01;
+
+/// Generates slugs for new links
+pub trait SlugGenerator: Send + Sync {
+	/// The length slugs start at. What a unit of length is depends on the strategy.
+	fn min_length(&self) -> usize;
+
+	/// How many distinct slugs exist at the given length
+	fn keyspace(&self, length: usize) -> f64;
+
+	/// Generates a slug of the given length. `sequence` is a unique, increasing number, only passed to
+	/// strategies that use one.
+	fn generate(&self, length: usize, sequence: u64) -> String;
+
+	/// Whether `generate` needs a number from the database sequence
+	fn uses_sequence(&self) -> bool {
+		false
+	}
+
+	/// Gets the length to generate at, growing it while `used` slugs fill too much of the keyspace
+	fn length_for(&self, used: u64) -> usize {
+		let mut length = self.min_length().max(1);
+
+		while used as f64 / self.keyspace(length) > MAX_KEYSPACE_FILL {
+			length += 1;
+		}
+
+		length
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugStrategy {
+	/// Random base62 characters
+	#[default]
+	Random,
+	/// Sequential numbers encoded with a shuffled alphabet
+	Sequential,
+	/// Random words joined with dashes
+	Pronounceable,
+	/// Random characters without look-alikes such as 0/O and 1/l/I
+	Unambiguous,
+}
+
+impl SlugStrategy {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			SlugStrategy::Random => "random",
+			SlugStrategy::Sequential => "sequential",
+			SlugStrategy::Pronounceable => "pronounceable",
+			SlugStrategy::Unambiguous => "unambiguous",
+		}
+	}
+
+	pub fn generator(&self, config: &Config) -> Box<dyn SlugGenerator> {
+		let app_config = config.app.as_ref().unwrap();
+
+		match self {
+			SlugStrategy::Random => Box::new(AlphabetGenerator::base62(app_config.shortened_link_length)),
+			SlugStrategy::Sequential => {
+				Box::new(SequentialGenerator::new(app_config.shortened_link_length, sequential_salt(config).as_bytes()))
+			}
+			SlugStrategy::Pronounceable => Box::new(WordGenerator::new(app_config.slug_word_count)),
+			SlugStrategy::Unambiguous => Box::new(AlphabetGenerator::unambiguous(app_config.shortened_link_length)),
+		}
+	}
+}
+
+/// Without a salt every install would shuffle the alphabet the same way, and anyone could decode slugs back to
+/// sequence numbers. Installs that didn't set one get a salt derived from the JWT secret instead.
+fn sequential_salt(config: &Config) -> String {
+	let app_config = config.app.as_ref().unwrap();
+
+	if !app_config.slug_salt.is_empty() {
+		return app_config.slug_salt.clone();
+	}
+
+	format!("slug-salt:{}", config.security.as_ref().unwrap().jwt_secret)
+}
+
+impl FromStr for SlugStrategy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"random" => Ok(SlugStrategy::Random),
+			"sequential" => Ok(SlugStrategy::Sequential),
+			"pronounceable" => Ok(SlugStrategy::Pronounceable),
+			"unambiguous" => Ok(SlugStrategy::Unambiguous),
+			_ => Err(format!("Unknown slug strategy '{}'", s)),
+		}
+	}
+}
+
+impl fmt::Display for SlugStrategy {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn strategy_round_trip_test() {
+		for strategy in [
+			SlugStrategy::Random,
+			SlugStrategy::Sequential,
+			SlugStrategy::Pronounceable,
+			SlugStrategy::Unambiguous,
+		] {
+			assert_eq!(SlugStrategy::from_str(strategy.as_str()), Ok(strategy));
+		}
+
+		assert!(SlugStrategy::from_str("invalid").is_err());
+	}
+
+	#[test]
+	fn sequential_salt_falls_back_to_secret_test() {
+		let config = |slug_salt: &str, jwt_secret: &str| Config {
+			app: Some(
+				toml::from_str(&format!(
+					r#"
+					shortened_link_length = 6
+					allow_anonymous_shorten = true
+					allow_registering = true
+					base_url = ""
+					enable_email_verification = false
+					email_verification_ttl = "1h"
+					slug_salt = "{}"
+					"#,
+					slug_salt
+				))
+				.unwrap(),
+			),
+			security: Some(
+				toml::from_str(&format!("jwt_secret = \"{}\"\nmin_password_strength = 0", jwt_secret)).unwrap(),
+			),
+			..Config::new()
+		};
+		let slug = |config: &Config| SlugStrategy::Sequential.generator(config).generate(6, 1);
+
+		assert_ne!(slug(&config("", "one secret")), slug(&config("", "another secret")));
+		assert_eq!(slug(&config("salt", "one secret")), slug(&config("salt", "another secret")));
+	}
+
+	#[test]
+	fn length_grows_when_crowded_test() {
+		let generator = AlphabetGenerator::base62(2);
+
+		// 62^2 = 3844, so 1% is about 38 slugs
+		assert_eq!(generator.length_for(0), 2);
+		assert_eq!(generator.length_for(38), 2);
+		assert_eq!(generator.length_for(39), 3);
+		assert_eq!(generator.length_for(10_000), 4);
+	}
+}
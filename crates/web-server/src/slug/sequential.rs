@@ -0,0 +1,107 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use sha2::{Digest, Sha256};
+
+use super::SlugGenerator;
+
+const BASE62_ALPHABET: &str = "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Odd and not divisible by 31, so multiplying by it is a bijection modulo any power of 62
+const SCRAMBLE_MULTIPLIER: u128 = 0x9E37_79B9_7F4A_7C15;
+/// Any i64 sequence fits in 11 base62 digits, this keeps the keyspace within u128
+const MAX_LENGTH: usize = 20;
+
+/// Encodes sequence numbers with an alphabet shuffled by a salt, similar to hashids/sqids.
+/// Slugs of the same length never collide, and consecutive numbers don't look consecutive.
+pub struct SequentialGenerator {
+	alphabet: Vec<char>,
+	min_length: usize,
+}
+
+impl SequentialGenerator {
+	pub fn new(min_length: usize, salt: &[u8]) -> Self {
+		let seed = Sha256::digest(salt);
+		let mut rng = StdRng::from_seed(seed.into());
+
+		let mut alphabet: Vec<char> = BASE62_ALPHABET.chars().collect();
+		alphabet.shuffle(&mut rng);
+
+		Self {
+			alphabet,
+			min_length: min_length.min(MAX_LENGTH),
+		}
+	}
+}
+
+impl SlugGenerator for SequentialGenerator {
+	fn min_length(&self) -> usize {
+		self.min_length
+	}
+
+	fn keyspace(&self, length: usize) -> f64 {
+		(self.alphabet.len() as f64).powi(length as i32)
+	}
+
+	fn uses_sequence(&self) -> bool {
+		true
+	}
+
+	/// Sequence numbers are unique, so the length only has to grow once they don't fit anymore
+	fn length_for(&self, used: u64) -> usize {
+		let mut length = self.min_length.max(1);
+
+		while used as f64 >= self.keyspace(length) && length < MAX_LENGTH {
+			length += 1;
+		}
+
+		length
+	}
+
+	fn generate(&self, length: usize, sequence: u64) -> String {
+		let length = length.min(MAX_LENGTH);
+		let base = self.alphabet.len() as u128;
+		let keyspace = base.pow(length as u32);
+
+		let mut value = (sequence as u128).wrapping_mul(SCRAMBLE_MULTIPLIER) % keyspace;
+		let mut slug = Vec::with_capacity(length);
+
+		for _ in 0..length {
+			slug.push(self.alphabet[(value % base) as usize]);
+			value /= base;
+		}
+
+		slug.into_iter().rev().collect()
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use std::collections::HashSet;
+
+	use super::*;
+
+	#[test]
+	fn sequential_is_unique_test() {
+		let generator = SequentialGenerator::new(3, b"salt");
+
+		let slugs: HashSet<String> = (0..10_000).map(|n| generator.generate(3, n)).collect();
+
+		assert_eq!(slugs.len(), 10_000);
+		assert!(slugs.iter().all(|slug| slug.len() == 3));
+	}
+
+	#[test]
+	fn sequential_depends_on_salt_test() {
+		let a = SequentialGenerator::new(6, b"salt");
+		let b = SequentialGenerator::new(6, b"other");
+
+		assert_eq!(a.generate(6, 42), SequentialGenerator::new(6, b"salt").generate(6, 42));
+		assert_ne!(a.generate(6, 42), b.generate(6, 42));
+	}
+
+	#[test]
+	fn sequential_grows_when_exhausted_test() {
+		let generator = SequentialGenerator::new(2, b"salt");
+
+		assert_eq!(generator.length_for(3843), 2);
+		assert_eq!(generator.length_for(3844), 3);
+	}
+}
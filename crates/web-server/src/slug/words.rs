@@ -0,0 +1,70 @@
+use rand::{seq::SliceRandom, thread_rng};
+
+use super::SlugGenerator;
+
+/// Short, common words. Keep this at 256 entries, existing slugs don't depend on it but the keyspace does.
+const WORDS: [&str; 256] = [
+	"able", "acid", "aged", "also", "area", "army", "away", "baby", "back", "ball", "band", "bank", "base",
+	"bath", "bear", "beat", "bell", "belt", "best", "bird", "blue", "boat", "body", "bold", "bone", "book",
+	"boot", "born", "both", "bowl", "bulk", "burn", "bush", "busy", "cafe", "cake", "calm", "camp", "card",
+	"care", "cart", "case", "cash", "cast", "cave", "chef", "chip", "city", "clay", "club", "coal", "coat",
+	"code", "cold", "cook", "cool", "copy", "cord", "corn", "cost", "crew", "crop", "cube", "cup", "dark", "dawn",
+	"days", "deal", "deep", "deer", "desk", "dial", "dish", "dock", "door", "dove", "down", "draw", "drop",
+	"drum", "duck", "dust", "duty", "each", "earl", "earn", "east", "easy", "echo", "edge", "epic", "even",
+	"ever", "face", "fact", "fair", "fall", "farm", "fast", "fern", "file", "film", "fine", "fire", "firm",
+	"fish", "five", "flag", "flat", "flow", "foam", "fold", "folk", "font", "food", "foot", "fork", "form",
+	"fort", "four", "free", "frog", "fuel", "full", "fund", "gain", "game", "gate", "gear", "gift", "glad",
+	"glow", "goal", "gold", "golf", "good", "gray", "grid", "grow", "gulf", "hair", "half", "hall", "hand",
+	"harp", "hawk", "head", "heat", "herb", "hero", "high", "hill", "hint", "hive", "home", "hope", "horn",
+	"host", "hour", "huge", "idea", "inch", "iron", "isle", "jade", "jazz", "join", "joke", "jump", "keen",
+	"keep", "kind", "king", "kite", "knot", "lake", "lamp", "land", "lane", "last", "lava", "lawn", "leaf",
+	"lean", "left", "lens", "life", "lift", "lily", "lime", "line", "link", "lion", "list", "loaf", "lock",
+	"loft", "long", "loop", "lord", "loud", "love", "luck", "lush", "main", "mall", "many", "maple", "mark",
+	"mask", "mast", "meal", "mild", "milk", "mill", "mind", "mint", "mist", "mode", "moon", "moss", "most",
+	"moth", "much", "nest", "news", "next", "nice", "nine", "noon", "nose", "note", "oak", "oasis", "open",
+	"oval", "oven", "pace", "pack", "page", "palm", "park", "path", "peak", "pear", "pine", "pink", "plan",
+	"play", "plot", "plum", "poem", "poet", "pond", "pool", "port",
+];
+
+/// Generates slugs from random words joined with dashes, e.g. `calm-otter-lake`. The length is the number of words.
+pub struct WordGenerator {
+	min_words: usize,
+}
+
+impl WordGenerator {
+	pub fn new(min_words: usize) -> Self {
+		Self { min_words }
+	}
+}
+
+impl SlugGenerator for WordGenerator {
+	fn min_length(&self) -> usize {
+		self.min_words
+	}
+
+	fn keyspace(&self, length: usize) -> f64 {
+		(WORDS.len() as f64).powi(length as i32)
+	}
+
+	fn generate(&self, length: usize, _sequence: u64) -> String {
+		let mut rng = thread_rng();
+
+		(0..length)
+			.map(|_| *WORDS.choose(&mut rng).unwrap())
+			.collect::<Vec<_>>()
+			.join("-")
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn words_test() {
+		let slug = WordGenerator::new(3).generate(3, 0);
+
+		assert_eq!(slug.split('-').count(), 3);
+		assert!(slug.split('-').all(|word| WORDS.contains(&word)));
+	}
+}
@@ -0,0 +1,279 @@
+//! Builds the whole app on an in-memory store, so the HTTP API can be tested without a database
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+	async_trait,
+	body::{to_bytes, Body},
+	http::{header, Method, Request, StatusCode},
+	Router,
+};
+use chrono::Utc;
+use db::{
+	models::{Domain, NewDomain},
+	repository::{MemoryStore, SharedStore, Store},
+};
+use serde_json::Value;
+use tower::ServiceExt;
+
+use crate::{
+	app_router,
+	config::{AppConfig, Config, SecurityConfig},
+	hostname_router::HostnameRouter,
+	services::{
+		cache::MemoryBackend,
+		click_tracker::ClickTracker,
+		domain_verification::{DnsError, DomainVerifier, TxtResolver},
+		email::Email,
+		redirect_cache::RedirectCache,
+	},
+	slug_router, Services,
+};
+
+pub const BASE_HOST: &str = "sho.rt";
+
+/// DNS without any records
+struct EmptyTxtResolver;
+
+#[async_trait]
+impl TxtResolver for EmptyTxtResolver {
+	async fn lookup_txt(&self, _name: &str) -> Result<Vec<String>, DnsError> {
+		Ok(Vec::new())
+	}
+}
+
+pub struct TestResponse {
+	pub status: StatusCode,
+	pub location: Option<String>,
+	pub cache_control: Option<String>,
+	/// The `auth_token` cookie, if the response set one
+	pub auth_token: Option<String>,
+	pub body: Value,
+}
+
+pub struct TestApp {
+	router: Router,
+	pub store: Arc<MemoryStore>,
+}
+
+impl TestApp {
+	pub fn new() -> Self {
+		let app: AppConfig = toml::from_str(&format!(
+			r#"
+			shortened_link_length = 8
+			allow_anonymous_shorten = true
+			allow_registering = true
+			base_url = "http://{}"
+			enable_email_verification = false
+			email_verification_ttl = "1h"
+			"#,
+			BASE_HOST
+		))
+		.unwrap();
+		let security: SecurityConfig = toml::from_str(
+			r#"
+			jwt_secret = "secret"
+			min_password_strength = 0
+			"#,
+		)
+		.unwrap();
+
+		let config = Config {
+			app: Some(app),
+			security: Some(security),
+			..Config::new()
+		};
+
+		let store = Arc::new(MemoryStore::new());
+		let shared_store: SharedStore = store.clone();
+
+		let services = Services {
+			config: config.clone(),
+			email: Email::default(),
+			click_tracker: ClickTracker::new(shared_store.clone(), "salt".to_string()),
+			store: shared_store,
+			redirect_cache: RedirectCache::new(Some(Arc::new(MemoryBackend::new(1000, Duration::from_secs(60))))),
+			domain_verifier: DomainVerifier::new(EmptyTxtResolver),
+		};
+
+		let hostname_router = HostnameRouter::new(app_router(&services), slug_router(&services), config);
+
+		Self {
+			router: Router::new().fallback(hostname_router),
+			store,
+		}
+	}
+
+	pub async fn request(
+		&self,
+		method: Method,
+		host: &str,
+		path: &str,
+		body: Option<Value>,
+		auth_token: Option<&str>,
+	) -> TestResponse {
+		let mut request = Request::builder().method(method).uri(path).header(header::HOST, host);
+
+		if let Some(token) = auth_token {
+			request = request.header(header::COOKIE, format!("auth_token={}", token));
+		}
+
+		let request = match body {
+			Some(body) => request
+				.header(header::CONTENT_TYPE, "application/json")
+				.body(Body::from(body.to_string())),
+			None => request.body(Body::empty()),
+		}
+		.unwrap();
+
+		self.send(request).await
+	}
+
+	pub async fn send(&self, request: Request<Body>) -> TestResponse {
+		let response = self.router.clone().oneshot(request).await.unwrap();
+
+		let header_value = |name: header::HeaderName| -> Vec<String> {
+			response
+				.headers()
+				.get_all(name)
+				.iter()
+				.filter_map(|value| value.to_str().ok().map(|value| value.to_string()))
+				.collect()
+		};
+
+		let location = header_value(header::LOCATION).into_iter().next();
+		let cache_control = header_value(header::CACHE_CONTROL).into_iter().next();
+		let auth_token = header_value(header::SET_COOKIE).into_iter().find_map(|cookie| {
+			cookie
+				.strip_prefix("auth_token=")
+				.map(|rest| rest.split(';').next().unwrap().to_string())
+		});
+		let status = response.status();
+
+		let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+		let body = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+		TestResponse {
+			status,
+			location,
+			cache_control,
+			auth_token,
+			body,
+		}
+	}
+
+	pub async fn post(&self, path: &str, body: Value, auth_token: Option<&str>) -> TestResponse {
+		self.request(Method::POST, BASE_HOST, path, Some(body), auth_token)
+			.await
+	}
+
+	pub async fn get(&self, path: &str, auth_token: Option<&str>) -> TestResponse {
+		self.request(Method::GET, BASE_HOST, path, None, auth_token).await
+	}
+
+	pub async fn put(&self, path: &str, body: Value, auth_token: Option<&str>) -> TestResponse {
+		self.request(Method::PUT, BASE_HOST, path, Some(body), auth_token).await
+	}
+
+	pub async fn delete(&self, path: &str, auth_token: Option<&str>) -> TestResponse {
+		self.request(Method::DELETE, BASE_HOST, path, None, auth_token).await
+	}
+
+	pub fn add_public_domain(&self, domain: &str) -> Domain {
+		let mut repos = self.store.open().unwrap();
+
+		repos
+			.domains()
+			.insert(&NewDomain {
+				domain: domain.to_string(),
+				public: Some(true),
+				slug_strategy: None,
+				redirect_type: None,
+				owner_id: None,
+				verification_token: None,
+				verified_at: None,
+			})
+			.unwrap()
+	}
+
+	/// Adds a private domain, as if the user claimed and verified it
+	pub fn add_owned_domain(&self, domain: &str, owner_id: i32) -> Domain {
+		let mut repos = self.store.open().unwrap();
+
+		repos
+			.domains()
+			.insert(&NewDomain {
+				domain: domain.to_string(),
+				public: Some(false),
+				slug_strategy: None,
+				redirect_type: None,
+				owner_id: Some(owner_id),
+				verification_token: None,
+				verified_at: Some(Utc::now().naive_utc()),
+			})
+			.unwrap()
+	}
+
+	/// Creates a team owned by the signed in user and adds the users registered through [`TestApp::sign_up`] with
+	/// the given roles, returning the team's ID
+	pub async fn create_team(&self, auth_token: &str, name: &str, members: &[(&str, &str)]) -> i64 {
+		let response = self
+			.post("/api/teams", serde_json::json!({ "name": name }), Some(auth_token))
+			.await;
+		assert_eq!(response.status, StatusCode::CREATED, "creating the team failed: {:?}", response.body);
+		let team_id = response.body["id"].as_i64().unwrap();
+
+		for (username, role) in members {
+			let response = self
+				.post(
+					&format!("/api/teams/{}/members", team_id),
+					serde_json::json!({ "email": format!("{}@example.com", username), "role": role }),
+					Some(auth_token),
+				)
+				.await;
+			assert!(response.status.is_success(), "adding {} failed: {:?}", username, response.body);
+		}
+
+		team_id
+	}
+
+	/// Gets the ID of a user registered through [`TestApp::sign_up`]
+	pub fn user_id(&self, username: &str) -> i32 {
+		let mut repos = self.store.open().unwrap();
+
+		repos
+			.users()
+			.get_by_email(&format!("{}@example.com", username))
+			.unwrap()
+			.expect("user not found")
+			.id
+	}
+
+	/// Registers a user and signs them in, returning their access token. The first user becomes an admin.
+	pub async fn sign_up(&self, username: &str) -> String {
+		let email = format!("{}@example.com", username);
+		let password = "correct horse battery staple";
+
+		let response = self
+			.post(
+				"/api/user/register",
+				serde_json::json!({
+					"username": username,
+					"password": password,
+					"confirm_password": password,
+					"email": email,
+					"confirm_email": email,
+				}),
+				None,
+			)
+			.await;
+		assert!(response.status.is_success(), "register failed: {:?}", response.body);
+
+		let response = self
+			.post("/api/user/login", serde_json::json!({ "email": email, "password": password }), None)
+			.await;
+		assert_eq!(response.status, StatusCode::OK, "login failed: {:?}", response.body);
+
+		response.auth_token.expect("login didn't set the auth cookie")
+	}
+}
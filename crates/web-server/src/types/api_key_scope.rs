@@ -0,0 +1,77 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// What an API key is allowed to do. Routes that accept API keys declare the scope they need
+/// with an `Extension(ApiKeyScope)` layer; routes without one only accept the auth cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiKeyScope {
+	#[serde(rename = "links:read")]
+	LinksRead,
+	#[serde(rename = "links:write")]
+	LinksWrite,
+	#[serde(rename = "domains:admin")]
+	DomainsAdmin,
+}
+
+impl ApiKeyScope {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ApiKeyScope::LinksRead => "links:read",
+			ApiKeyScope::LinksWrite => "links:write",
+			ApiKeyScope::DomainsAdmin => "domains:admin",
+		}
+	}
+
+	/// Joins scopes the way they're stored in the database
+	pub fn join(scopes: &[ApiKeyScope]) -> String {
+		scopes.iter().map(ApiKeyScope::as_str).collect::<Vec<_>>().join(" ")
+	}
+
+	/// Parses stored scopes, skipping any that are no longer known
+	pub fn parse_all(scopes: &[&str]) -> Vec<ApiKeyScope> {
+		scopes
+			.iter()
+			.filter_map(|scope| ApiKeyScope::from_str(scope).ok())
+			.collect()
+	}
+}
+
+impl FromStr for ApiKeyScope {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"links:read" => Ok(ApiKeyScope::LinksRead),
+			"links:write" => Ok(ApiKeyScope::LinksWrite),
+			"domains:admin" => Ok(ApiKeyScope::DomainsAdmin),
+			_ => Err(format!("Unknown API key scope '{}'", s)),
+		}
+	}
+}
+
+impl fmt::Display for ApiKeyScope {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_scopes_round_trip() {
+		let scopes = [ApiKeyScope::LinksRead, ApiKeyScope::DomainsAdmin];
+		let joined = ApiKeyScope::join(&scopes);
+
+		assert_eq!(joined, "links:read domains:admin");
+		assert_eq!(ApiKeyScope::parse_all(&joined.split_whitespace().collect::<Vec<_>>()), scopes);
+	}
+
+	#[test]
+	fn test_unknown_scope() {
+		assert!(ApiKeyScope::from_str("links:delete").is_err());
+		assert_eq!(ApiKeyScope::parse_all(&["links:delete", "links:write"]), [ApiKeyScope::LinksWrite]);
+	}
+}
@@ -1,4 +1,4 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Deserialize)]
 pub struct PaginationQuery {
@@ -10,4 +10,14 @@ pub struct PaginationQuery {
 pub struct PaginatedResponse<T> {
 	pub items: Vec<T>,
 	pub total_count: i64,
-}
\ No newline at end of file
+}
+
+/// Deserializes a field that distinguishes between missing (`None`) and `null` (`Some(None)`).
+/// Use together with `#[serde(default)]`.
+pub fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+	T: Deserialize<'de>,
+	D: Deserializer<'de>,
+{
+	Option::<T>::deserialize(deserializer).map(Some)
+}
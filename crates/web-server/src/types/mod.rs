@@ -1,5 +1,13 @@
+mod api_key_scope;
 mod generic;
+mod permission;
+mod redirect_type;
+mod team_role;
 mod wrapped_duration;
 
+pub use api_key_scope::*;
 pub use generic::*;
+pub use permission::*;
+pub use redirect_type::*;
+pub use team_role::*;
 pub use wrapped_duration::*;
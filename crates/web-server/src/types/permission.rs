@@ -0,0 +1,115 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// What a user is allowed to do. Users get permissions through their roles, which are stored in the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Permission {
+	/// Shorten links as a logged in user
+	#[serde(rename = "link.create")]
+	LinkCreate,
+	/// Edit and delete links of other users
+	#[serde(rename = "link.manage_any")]
+	LinkManageAny,
+	/// Manage domains, and create links on the ones that aren't public
+	#[serde(rename = "domain.manage")]
+	DomainManage,
+	/// Manage roles and who has them
+	#[serde(rename = "user.manage")]
+	UserManage,
+	/// Manage instance wide settings, like the URL rules
+	#[serde(rename = "settings.manage")]
+	SettingsManage,
+}
+
+impl Permission {
+	pub const ALL: [Permission; 5] = [
+		Permission::LinkCreate,
+		Permission::LinkManageAny,
+		Permission::DomainManage,
+		Permission::UserManage,
+		Permission::SettingsManage,
+	];
+
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			Permission::LinkCreate => "link.create",
+			Permission::LinkManageAny => "link.manage_any",
+			Permission::DomainManage => "domain.manage",
+			Permission::UserManage => "user.manage",
+			Permission::SettingsManage => "settings.manage",
+		}
+	}
+
+	/// Whether the permission is an admin one, which needs 2FA when `security.require_admin_two_factor` is set
+	pub fn is_privileged(&self) -> bool {
+		*self != Permission::LinkCreate
+	}
+
+	/// Parses stored permissions, skipping any that are no longer known
+	pub fn parse_all(permissions: &[String]) -> Vec<Permission> {
+		permissions
+			.iter()
+			.filter_map(|permission| Permission::from_str(permission).ok())
+			.collect()
+	}
+}
+
+impl FromStr for Permission {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Permission::ALL
+			.into_iter()
+			.find(|permission| permission.as_str() == s)
+			.ok_or_else(|| format!("Unknown permission '{}'", s))
+	}
+}
+
+impl fmt::Display for Permission {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+/// Ties a type to a permission, so routes can require it with `RequirePermission<CanManageDomains>`
+pub trait PermissionMarker: Send + Sync {
+	const PERMISSION: Permission;
+}
+
+pub struct CanManageDomains;
+pub struct CanManageUsers;
+pub struct CanManageSettings;
+
+impl PermissionMarker for CanManageDomains {
+	const PERMISSION: Permission = Permission::DomainManage;
+}
+
+impl PermissionMarker for CanManageUsers {
+	const PERMISSION: Permission = Permission::UserManage;
+}
+
+impl PermissionMarker for CanManageSettings {
+	const PERMISSION: Permission = Permission::SettingsManage;
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_permissions_round_trip() {
+		for permission in Permission::ALL {
+			assert_eq!(Permission::from_str(permission.as_str()), Ok(permission));
+		}
+	}
+
+	#[test]
+	fn test_unknown_permission() {
+		assert!(Permission::from_str("link.delete").is_err());
+		assert_eq!(
+			Permission::parse_all(&["link.delete".to_string(), "domain.manage".to_string()]),
+			[Permission::DomainManage]
+		);
+	}
+}
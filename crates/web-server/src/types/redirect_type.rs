@@ -0,0 +1,139 @@
+use std::fmt;
+
+use axum::{
+	http::{header, HeaderValue, StatusCode},
+	response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+/// The status code used when redirecting to a link's destination
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "i32", into = "i32")]
+pub enum RedirectType {
+	/// 301, cached by browsers
+	MovedPermanently,
+	/// 302, not cached, so edits to the destination apply right away
+	Found,
+	/// 307, like 302 but keeps the request method
+	TemporaryRedirect,
+	/// 308, like 301 but keeps the request method
+	#[default]
+	PermanentRedirect,
+}
+
+impl RedirectType {
+	pub fn status_code(&self) -> StatusCode {
+		match self {
+			RedirectType::MovedPermanently => StatusCode::MOVED_PERMANENTLY,
+			RedirectType::Found => StatusCode::FOUND,
+			RedirectType::TemporaryRedirect => StatusCode::TEMPORARY_REDIRECT,
+			RedirectType::PermanentRedirect => StatusCode::PERMANENT_REDIRECT,
+		}
+	}
+
+	/// Picks the first redirect type that is set, from most to least specific
+	pub fn resolve(link: Option<i32>, domain: Option<i32>, default: RedirectType) -> RedirectType {
+		link.or(domain)
+			.and_then(|code| RedirectType::try_from(code).ok())
+			.unwrap_or(default)
+	}
+
+	/// The temporary counterpart of permanent redirects, which browsers would otherwise cache
+	pub fn temporary(self) -> RedirectType {
+		match self {
+			RedirectType::MovedPermanently => RedirectType::Found,
+			RedirectType::PermanentRedirect => RedirectType::TemporaryRedirect,
+			redirect_type => redirect_type,
+		}
+	}
+
+	pub fn redirect(&self, url: &str) -> Response {
+		match HeaderValue::from_str(url) {
+			Ok(location) => (self.status_code(), [(header::LOCATION, location)]).into_response(),
+			Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+		}
+	}
+
+	/// Redirects without letting browsers cache it, for links that have to be checked on every visit
+	pub fn redirect_uncached(&self, url: &str) -> Response {
+		let mut response = self.temporary().redirect(url);
+		response
+			.headers_mut()
+			.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+
+		response
+	}
+}
+
+impl TryFrom<i32> for RedirectType {
+	type Error = String;
+
+	fn try_from(code: i32) -> Result<Self, Self::Error> {
+		match code {
+			301 => Ok(RedirectType::MovedPermanently),
+			302 => Ok(RedirectType::Found),
+			307 => Ok(RedirectType::TemporaryRedirect),
+			308 => Ok(RedirectType::PermanentRedirect),
+			_ => Err(format!("Invalid redirect type {}, expected 301, 302, 307 or 308", code)),
+		}
+	}
+}
+
+impl From<RedirectType> for i32 {
+	fn from(redirect_type: RedirectType) -> Self {
+		redirect_type.status_code().as_u16() as i32
+	}
+}
+
+impl fmt::Display for RedirectType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", i32::from(*self))
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use serde_test::{assert_de_tokens_error, assert_tokens, Token};
+
+	#[test]
+	fn test_redirect_type_serialize() {
+		assert_tokens(&RedirectType::Found, &[Token::I32(302)]);
+		assert_tokens(&RedirectType::PermanentRedirect, &[Token::I32(308)]);
+	}
+
+	#[test]
+	fn test_redirect_type_deserialize_invalid() {
+		assert_de_tokens_error::<RedirectType>(
+			&[Token::I32(303)],
+			"Invalid redirect type 303, expected 301, 302, 307 or 308",
+		);
+	}
+
+	#[test]
+	fn test_redirect_type_resolve() {
+		let default = RedirectType::PermanentRedirect;
+
+		assert_eq!(RedirectType::resolve(Some(302), Some(301), default), RedirectType::Found);
+		assert_eq!(RedirectType::resolve(None, Some(301), default), RedirectType::MovedPermanently);
+		assert_eq!(RedirectType::resolve(None, None, default), default);
+	}
+
+	#[test]
+	fn test_redirect_type_response() {
+		let response = RedirectType::Found.redirect("https://example.com");
+
+		assert_eq!(response.status(), StatusCode::FOUND);
+		assert_eq!(response.headers()[header::LOCATION], "https://example.com");
+	}
+
+	#[test]
+	fn test_redirect_type_uncached() {
+		let response = RedirectType::PermanentRedirect.redirect_uncached("https://example.com");
+
+		assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+		assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+		assert_eq!(RedirectType::MovedPermanently.temporary(), RedirectType::Found);
+		assert_eq!(RedirectType::Found.temporary(), RedirectType::Found);
+	}
+}
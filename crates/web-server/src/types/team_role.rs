@@ -0,0 +1,57 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The role of a member in a team, ordered from least to most rights
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TeamRole {
+	/// Can create and manage the team's links
+	Member,
+	/// Can also add and remove members
+	Admin,
+	/// Can also rename and delete the team, and change the roles of members
+	Owner,
+}
+
+impl TeamRole {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			TeamRole::Member => "member",
+			TeamRole::Admin => "admin",
+			TeamRole::Owner => "owner",
+		}
+	}
+}
+
+impl FromStr for TeamRole {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"member" => Ok(TeamRole::Member),
+			"admin" => Ok(TeamRole::Admin),
+			"owner" => Ok(TeamRole::Owner),
+			_ => Err(format!("Unknown team role '{}'", s)),
+		}
+	}
+}
+
+impl fmt::Display for TeamRole {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_team_role_order() {
+		assert!(TeamRole::Owner > TeamRole::Admin);
+		assert!(TeamRole::Admin > TeamRole::Member);
+		assert_eq!(TeamRole::from_str("admin"), Ok(TeamRole::Admin));
+		assert!(TeamRole::from_str("guest").is_err());
+	}
+}
@@ -0,0 +1,39 @@
+use axum::response::Html;
+
+/// Renders the page asking for the password of a protected link. The form posts back to the slug itself.
+pub fn render(error: Option<&str>) -> Html<String> {
+	let error = match error {
+		Some(error) => format!(r#"<p class="error">{}</p>"#, error),
+		None => String::new(),
+	};
+
+	Html(format!(
+		r#"<!DOCTYPE html>
+<html lang="en">
+	<head>
+		<meta charset="UTF-8" />
+		<meta name="viewport" content="width=device-width, initial-scale=1.0" />
+		<meta name="robots" content="noindex" />
+		<title>Protected Link</title>
+		<style>
+			body {{ font-family: sans-serif; display: flex; justify-content: center; align-items: center; min-height: 100vh; margin: 0; background: #f3f4f6; }}
+			form {{ background: #fff; padding: 2rem; border-radius: 0.5rem; box-shadow: 0 1px 3px rgba(0, 0, 0, 0.1); width: 100%; max-width: 20rem; }}
+			input, button {{ width: 100%; box-sizing: border-box; padding: 0.5rem; margin-top: 0.5rem; border-radius: 0.375rem; }}
+			input {{ border: 1px solid #d1d5db; }}
+			button {{ border: none; background: #9333ea; color: #fff; font-weight: bold; cursor: pointer; }}
+			.error {{ color: #b91c1c; }}
+		</style>
+	</head>
+	<body>
+		<form method="post">
+			<h1>Protected Link</h1>
+			<p>This link is password protected. Please enter the password to continue.</p>
+			{}
+			<input type="password" name="password" placeholder="Password" required autofocus />
+			<button type="submit">Continue</button>
+		</form>
+	</body>
+</html>"#,
+		error
+	))
+}
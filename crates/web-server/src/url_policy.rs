@@ -0,0 +1,299 @@
+use std::fmt;
+
+use db::{
+	models::{Domain, UrlRule},
+	repository::DomainRepository,
+	DbError,
+};
+use url::Url;
+
+use crate::config::UrlPolicyConfig;
+
+/// Why a destination URL was rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrlRejection {
+	InvalidUrl,
+	TooLong { max_length: usize },
+	SchemeNotAllowed { scheme: String },
+	MissingHost,
+	DomainBlocked { domain: String },
+	DomainNotAllowed { domain: String },
+	RedirectLoop { domain: String },
+}
+
+impl UrlRejection {
+	/// Machine readable reason, sent next to the message
+	pub fn reason(&self) -> &'static str {
+		match self {
+			UrlRejection::InvalidUrl => "invalid_url",
+			UrlRejection::TooLong { .. } => "too_long",
+			UrlRejection::SchemeNotAllowed { .. } => "scheme_not_allowed",
+			UrlRejection::MissingHost => "missing_host",
+			UrlRejection::DomainBlocked { .. } => "domain_blocked",
+			UrlRejection::DomainNotAllowed { .. } => "domain_not_allowed",
+			UrlRejection::RedirectLoop { .. } => "redirect_loop",
+		}
+	}
+}
+
+impl fmt::Display for UrlRejection {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			UrlRejection::InvalidUrl => write!(f, "Provided link is not a valid URL."),
+			UrlRejection::TooLong { max_length } => write!(f, "Link is longer than {} characters.", max_length),
+			UrlRejection::SchemeNotAllowed { scheme } => {
+				write!(f, "Links using the {} scheme are not allowed.", scheme)
+			}
+			UrlRejection::MissingHost => write!(f, "Link has no domain."),
+			UrlRejection::DomainBlocked { domain } => write!(f, "Links to {} are not allowed.", domain),
+			UrlRejection::DomainNotAllowed { domain } => write!(f, "Links to {} are not allowed.", domain),
+			UrlRejection::RedirectLoop { domain } => {
+				write!(f, "Links can't point to {}, as it's one of our own domains.", domain)
+			}
+		}
+	}
+}
+
+/// Decides which destinations links may point to
+#[derive(Debug, Clone)]
+pub struct UrlPolicy {
+	allowed_schemes: Vec<String>,
+	blocked_domains: Vec<String>,
+	allowed_domains: Vec<String>,
+	max_length: usize,
+	/// Our own short domains, links pointing to them would redirect in a loop
+	own_domains: Vec<String>,
+}
+
+impl UrlPolicy {
+	pub fn new(config: &UrlPolicyConfig, rules: &[UrlRule], domains: &[Domain]) -> Self {
+		let blocked_domains = config
+			.blocked_domains
+			.iter()
+			.map(String::as_str)
+			.chain(
+				rules
+					.iter()
+					.filter(|rule| rule.is_block())
+					.map(|rule| rule.domain.as_str()),
+			)
+			.map(normalize_domain)
+			.collect();
+
+		let allowed_domains = config
+			.allowed_domains
+			.iter()
+			.map(String::as_str)
+			.chain(
+				rules
+					.iter()
+					.filter(|rule| rule.is_allow())
+					.map(|rule| rule.domain.as_str()),
+			)
+			.map(normalize_domain)
+			.collect();
+
+		Self {
+			allowed_schemes: config
+				.allowed_schemes
+				.iter()
+				.map(|scheme| scheme.to_lowercase())
+				.collect(),
+			blocked_domains,
+			allowed_domains,
+			max_length: config.max_url_length,
+			// Anyone can claim a domain, it only becomes ours once it's verified
+			own_domains: domains
+				.iter()
+				.filter(|domain| domain.owner_id.is_none() || domain.is_verified())
+				.map(|domain| domain.domain.to_lowercase())
+				.collect(),
+		}
+	}
+
+	/// Loads the policy from the config, the `url_rules` table and every row in `domains`
+	pub fn load(config: &UrlPolicyConfig, repo: &mut dyn DomainRepository) -> Result<Self, DbError> {
+		let rules = repo.get_url_rules()?;
+		let domains = repo.get_all()?;
+
+		Ok(Self::new(config, &rules, &domains))
+	}
+
+	pub fn check(&self, link: &str) -> Result<(), UrlRejection> {
+		if link.len() > self.max_length {
+			return Err(UrlRejection::TooLong {
+				max_length: self.max_length,
+			});
+		}
+
+		let url = Url::parse(link).map_err(|_| UrlRejection::InvalidUrl)?;
+
+		if !self.allowed_schemes.iter().any(|scheme| scheme == url.scheme()) {
+			return Err(UrlRejection::SchemeNotAllowed {
+				scheme: url.scheme().to_string(),
+			});
+		}
+
+		let host = match url.host_str() {
+			Some(host) => host.trim_end_matches('.').to_lowercase(),
+			// Hostless schemes (e.g. mailto) can't be matched against an allowlist
+			None if !self.allowed_domains.is_empty() => return Err(UrlRejection::MissingHost),
+			None => return Ok(()),
+		};
+
+		let host_with_port = match url.port() {
+			Some(port) => format!("{}:{}", host, port),
+			None => host.clone(),
+		};
+
+		if self
+			.own_domains
+			.iter()
+			.any(|domain| *domain == host || *domain == host_with_port)
+		{
+			return Err(UrlRejection::RedirectLoop { domain: host });
+		}
+
+		if self.blocked_domains.iter().any(|domain| matches_domain(&host, domain)) {
+			return Err(UrlRejection::DomainBlocked { domain: host });
+		}
+
+		if !self.allowed_domains.is_empty() && !self.allowed_domains.iter().any(|domain| matches_domain(&host, domain))
+		{
+			return Err(UrlRejection::DomainNotAllowed { domain: host });
+		}
+
+		Ok(())
+	}
+}
+
+/// Lowercases a domain rule and strips wildcard prefixes, as rules always match subdomains
+fn normalize_domain(domain: &str) -> String {
+	domain
+		.trim()
+		.trim_start_matches("*.")
+		.trim_start_matches('.')
+		.to_lowercase()
+}
+
+/// Checks if the host is the domain or one of its subdomains
+fn matches_domain(host: &str, domain: &str) -> bool {
+	host == domain || host.strip_suffix(domain).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use chrono::NaiveDateTime;
+
+	fn rule(domain: &str, kind: &str) -> UrlRule {
+		UrlRule {
+			id: 1,
+			domain: domain.to_string(),
+			kind: kind.to_string(),
+			created_at: NaiveDateTime::default(),
+		}
+	}
+
+	fn own_domain(domain: &str) -> Domain {
+		Domain {
+			id: 1,
+			domain: domain.to_string(),
+			public: true,
+			created_at: NaiveDateTime::default(),
+			updated_at: NaiveDateTime::default(),
+			slug_strategy: None,
+			redirect_type: None,
+			team_id: None,
+			owner_id: None,
+			verification_token: None,
+			verified_at: None,
+		}
+	}
+
+	#[test]
+	fn test_default_policy_schemes() {
+		let policy = UrlPolicy::new(&UrlPolicyConfig::default(), &[], &[]);
+
+		assert_eq!(policy.check("https://example.com"), Ok(()));
+		assert_eq!(policy.check("not a url"), Err(UrlRejection::InvalidUrl));
+		assert_eq!(
+			policy.check("javascript:alert(1)"),
+			Err(UrlRejection::SchemeNotAllowed {
+				scheme: "javascript".to_string()
+			})
+		);
+		assert_eq!(policy.check("file:///etc/passwd").unwrap_err().reason(), "scheme_not_allowed");
+	}
+
+	#[test]
+	fn test_max_length() {
+		let config = UrlPolicyConfig {
+			max_url_length: 24,
+			..Default::default()
+		};
+		let policy = UrlPolicy::new(&config, &[], &[]);
+
+		assert_eq!(policy.check("https://example.com"), Ok(()));
+		assert_eq!(policy.check("https://example.com/a/long/path"), Err(UrlRejection::TooLong { max_length: 24 }));
+	}
+
+	#[test]
+	fn test_blocklist_matches_subdomains() {
+		let config = UrlPolicyConfig {
+			blocked_domains: vec!["evil.com".to_string()],
+			..Default::default()
+		};
+		let policy = UrlPolicy::new(&config, &[rule("*.Bad.org", "block")], &[]);
+
+		assert_eq!(policy.check("https://notevil.com"), Ok(()));
+		assert_eq!(policy.check("https://cdn.evil.com/x").unwrap_err().reason(), "domain_blocked");
+		assert_eq!(
+			policy.check("https://bad.org"),
+			Err(UrlRejection::DomainBlocked {
+				domain: "bad.org".to_string()
+			})
+		);
+	}
+
+	#[test]
+	fn test_allowlist() {
+		let policy = UrlPolicy::new(&UrlPolicyConfig::default(), &[rule("example.com", "allow")], &[]);
+
+		assert_eq!(policy.check("https://docs.example.com"), Ok(()));
+		assert_eq!(policy.check("https://other.com").unwrap_err().reason(), "domain_not_allowed");
+	}
+
+	#[test]
+	fn test_redirect_loop() {
+		let policy =
+			UrlPolicy::new(&UrlPolicyConfig::default(), &[], &[own_domain("sho.rt"), own_domain("localhost:3000")]);
+
+		assert_eq!(
+			policy.check("https://SHO.RT/abc"),
+			Err(UrlRejection::RedirectLoop {
+				domain: "sho.rt".to_string()
+			})
+		);
+		assert_eq!(policy.check("http://localhost:3000/abc").unwrap_err().reason(), "redirect_loop");
+		assert_eq!(policy.check("http://localhost:8080/abc"), Ok(()));
+		assert_eq!(policy.check("https://sub.sho.rt/abc"), Ok(()));
+	}
+
+	#[test]
+	fn test_unverified_claim_is_not_own_domain() {
+		let claimed = Domain {
+			owner_id: Some(1),
+			..own_domain("example.com")
+		};
+		let verified = Domain {
+			owner_id: Some(1),
+			verified_at: Some(NaiveDateTime::default()),
+			..own_domain("go.to")
+		};
+		let policy = UrlPolicy::new(&UrlPolicyConfig::default(), &[], &[claimed, verified]);
+
+		assert_eq!(policy.check("https://example.com/abc"), Ok(()));
+		assert_eq!(policy.check("https://go.to/abc").unwrap_err().reason(), "redirect_loop");
+	}
+}
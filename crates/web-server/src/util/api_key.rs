@@ -0,0 +1,45 @@
+use super::{generate_unique_string, hash_token};
+
+/// Prefix of every API key, makes leaked keys easy to spot
+pub const API_KEY_PREFIX: &str = "shx_";
+const API_KEY_LENGTH: usize = 40;
+/// How much of the key is stored in plain text, so users can tell their keys apart
+const API_KEY_DISPLAY_LENGTH: usize = 12;
+
+/// Generates a new random API key
+pub fn generate_api_key() -> String {
+	format!("{}{}", API_KEY_PREFIX, generate_unique_string(API_KEY_LENGTH))
+}
+
+/// Hashes an API key for storage, keys are looked up by their hash
+pub fn hash_api_key(key: &str) -> String {
+	hash_token(key)
+}
+
+/// Gets the part of the key that is safe to show
+pub fn api_key_display_prefix(key: &str) -> String {
+	key.chars().take(API_KEY_DISPLAY_LENGTH).collect()
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_generate_api_key() {
+		let key = generate_api_key();
+
+		assert!(key.starts_with(API_KEY_PREFIX));
+		assert_eq!(key.len(), API_KEY_PREFIX.len() + API_KEY_LENGTH);
+		assert_ne!(key, generate_api_key());
+	}
+
+	#[test]
+	fn test_hash_api_key() {
+		let key = generate_api_key();
+
+		assert_eq!(hash_api_key(&key).len(), 64);
+		assert_eq!(hash_api_key(&key), hash_api_key(&key));
+		assert_eq!(api_key_display_prefix(&key).len(), 12);
+	}
+}
@@ -5,24 +5,66 @@ use time::{Duration, OffsetDateTime};
 #[derive(Serialize, Deserialize, Debug)]
 struct JwtClaims {
 	sub: String, // User ID
+	sid: i32,    // Session ID
 	iat: usize,  // Issued at (timestamp)
 	exp: usize,  // Expiration (timestamp)
 }
 
-pub fn encode_user_token(id: i32, jwt_secret: &[u8]) -> String {
+/// A decoded access token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserToken {
+	pub user_id: i32,
+	pub session_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LinkUnlockClaims {
+	link_id: i32, // Unlocked link ID
+	iat: usize,   // Issued at (timestamp)
+	exp: usize,   // Expiration (timestamp)
+}
+
+/// Issued after the password check of a 2FA login, traded for a session along with a code
+#[derive(Serialize, Deserialize, Debug)]
+struct TwoFactorClaims {
+	two_factor_user_id: i32, // User that still has to enter a code
+	iat: usize,              // Issued at (timestamp)
+	exp: usize,              // Expiration (timestamp)
+}
+
+/// Kept in a cookie while the user signs in at the OIDC provider, to check the response belongs to this browser
+#[derive(Serialize, Deserialize, Debug)]
+struct OidcLoginClaims {
+	oidc_csrf_state: String,    // State sent to the provider, has to come back unchanged
+	oidc_nonce: String,         // Nonce the ID token has to contain
+	oidc_pkce_verifier: String, // PKCE verifier for the code exchange
+	iat: usize,                 // Issued at (timestamp)
+	exp: usize,                 // Expiration (timestamp)
+}
+
+/// The values of an OIDC login that have to survive the trip to the provider and back
+#[derive(Debug, Clone, PartialEq)]
+pub struct OidcLoginState {
+	pub csrf_state: String,
+	pub nonce: String,
+	pub pkce_verifier: String,
+}
+
+pub fn encode_user_token(id: i32, session_id: i32, ttl: Duration, jwt_secret: &[u8]) -> String {
 	let now = OffsetDateTime::now_utc();
 
 	let claims = JwtClaims {
 		sub: id.to_string(),
+		sid: session_id,
 		iat: now.unix_timestamp() as usize,
-		exp: (now + Duration::hours(1)).unix_timestamp() as usize, // 1 hour
+		exp: (now + ttl).unix_timestamp() as usize,
 	};
 	let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret)).unwrap();
 
 	token
 }
 
-pub fn decode_user_token(token: &String, jwt_secret: &[u8]) -> Option<i32> {
+pub fn decode_user_token(token: &String, jwt_secret: &[u8]) -> Option<UserToken> {
 	let decoded_token =
 		match decode::<JwtClaims>(&token, &DecodingKey::from_secret(jwt_secret.as_ref()), &Validation::default()) {
 			Ok(token) => token,
@@ -40,5 +82,144 @@ pub fn decode_user_token(token: &String, jwt_secret: &[u8]) -> Option<i32> {
 		}
 	};
 
-	Some(user_id)
+	Some(UserToken {
+		user_id,
+		session_id: decoded_token.claims.sid,
+	})
+}
+
+pub fn encode_link_unlock_token(link_id: i32, ttl: Duration, jwt_secret: &[u8]) -> String {
+	let now = OffsetDateTime::now_utc();
+
+	let claims = LinkUnlockClaims {
+		link_id,
+		iat: now.unix_timestamp() as usize,
+		exp: (now + ttl).unix_timestamp() as usize,
+	};
+
+	encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret)).unwrap()
+}
+
+/// Checks if the token unlocks the given link
+pub fn verify_link_unlock_token(token: &str, link_id: i32, jwt_secret: &[u8]) -> bool {
+	match decode::<LinkUnlockClaims>(token, &DecodingKey::from_secret(jwt_secret), &Validation::default()) {
+		Ok(token) => token.claims.link_id == link_id,
+		Err(e) => {
+			log::debug!("Failed to decode link unlock JWT: {}", e);
+			false
+		}
+	}
+}
+
+pub fn encode_two_factor_token(user_id: i32, ttl: Duration, jwt_secret: &[u8]) -> String {
+	let now = OffsetDateTime::now_utc();
+
+	let claims = TwoFactorClaims {
+		two_factor_user_id: user_id,
+		iat: now.unix_timestamp() as usize,
+		exp: (now + ttl).unix_timestamp() as usize,
+	};
+
+	encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret)).unwrap()
+}
+
+/// Gets the user ID from a 2FA login token
+pub fn decode_two_factor_token(token: &str, jwt_secret: &[u8]) -> Option<i32> {
+	match decode::<TwoFactorClaims>(token, &DecodingKey::from_secret(jwt_secret), &Validation::default()) {
+		Ok(token) => Some(token.claims.two_factor_user_id),
+		Err(e) => {
+			log::debug!("Failed to decode 2FA JWT: {}", e);
+			None
+		}
+	}
+}
+
+pub fn encode_oidc_login_token(state: &OidcLoginState, ttl: Duration, jwt_secret: &[u8]) -> String {
+	let now = OffsetDateTime::now_utc();
+
+	let claims = OidcLoginClaims {
+		oidc_csrf_state: state.csrf_state.clone(),
+		oidc_nonce: state.nonce.clone(),
+		oidc_pkce_verifier: state.pkce_verifier.clone(),
+		iat: now.unix_timestamp() as usize,
+		exp: (now + ttl).unix_timestamp() as usize,
+	};
+
+	encode(&Header::default(), &claims, &EncodingKey::from_secret(jwt_secret)).unwrap()
+}
+
+pub fn decode_oidc_login_token(token: &str, jwt_secret: &[u8]) -> Option<OidcLoginState> {
+	match decode::<OidcLoginClaims>(token, &DecodingKey::from_secret(jwt_secret), &Validation::default()) {
+		Ok(token) => Some(OidcLoginState {
+			csrf_state: token.claims.oidc_csrf_state,
+			nonce: token.claims.oidc_nonce,
+			pkce_verifier: token.claims.oidc_pkce_verifier,
+		}),
+		Err(e) => {
+			log::debug!("Failed to decode OIDC login JWT: {}", e);
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn link_unlock_token_test() {
+		let token = encode_link_unlock_token(1, Duration::minutes(5), b"secret");
+
+		assert!(verify_link_unlock_token(&token, 1, b"secret"));
+		assert!(!verify_link_unlock_token(&token, 2, b"secret"));
+		assert!(!verify_link_unlock_token(&token, 1, b"other"));
+	}
+
+	#[test]
+	fn user_token_test() {
+		let token = encode_user_token(1, 7, Duration::minutes(5), b"secret");
+
+		assert_eq!(
+			decode_user_token(&token, b"secret"),
+			Some(UserToken {
+				user_id: 1,
+				session_id: 7
+			})
+		);
+		assert_eq!(decode_user_token(&token, b"other"), None);
+		assert_eq!(decode_user_token(&encode_user_token(1, 7, Duration::minutes(-5), b"secret"), b"secret"), None);
+	}
+
+	#[test]
+	fn user_token_does_not_unlock_link_test() {
+		let token = encode_user_token(1, 1, Duration::hours(1), b"secret");
+
+		assert!(!verify_link_unlock_token(&token, 1, b"secret"));
+		assert_eq!(decode_user_token(&encode_link_unlock_token(1, Duration::minutes(5), b"secret"), b"secret"), None);
+	}
+
+	#[test]
+	fn two_factor_token_test() {
+		let token = encode_two_factor_token(3, Duration::minutes(5), b"secret");
+
+		assert_eq!(decode_two_factor_token(&token, b"secret"), Some(3));
+		assert_eq!(decode_two_factor_token(&token, b"other"), None);
+		// A pending 2FA login isn't a session
+		assert_eq!(decode_user_token(&token, b"secret"), None);
+		assert_eq!(decode_two_factor_token(&encode_user_token(3, 1, Duration::hours(1), b"secret"), b"secret"), None);
+	}
+
+	#[test]
+	fn oidc_login_token_test() {
+		let state = OidcLoginState {
+			csrf_state: "state".to_string(),
+			nonce: "nonce".to_string(),
+			pkce_verifier: "verifier".to_string(),
+		};
+		let token = encode_oidc_login_token(&state, Duration::minutes(10), b"secret");
+
+		assert_eq!(decode_oidc_login_token(&token, b"secret"), Some(state));
+		assert_eq!(decode_oidc_login_token(&token, b"other"), None);
+		assert_eq!(decode_oidc_login_token(&encode_two_factor_token(3, Duration::minutes(5), b"secret"), b"secret"), None);
+	}
 }
@@ -1,8 +1,14 @@
+pub mod api_key;
 pub mod jwt;
+pub mod password;
+pub mod totp;
 
-use db::models::User;
+use std::net::SocketAddr;
+
+use axum::http::HeaderMap;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
 use url::Url;
 
 /// Generates a unique alphanumeric string of given length
@@ -41,15 +47,38 @@ pub fn starts_with_any(input: &str, patterns: &[String]) -> bool {
 	patterns.iter().any(|pattern| input.starts_with(pattern))
 }
 
-pub fn is_admin(user: Option<User>) -> bool {
-	user.map_or(false, |u| u.is_admin)
+/// Hashes a client IP with a salt, so raw addresses are never stored
+pub fn hash_ip(ip: &str, salt: &[u8]) -> String {
+	let mut hasher = Sha256::new();
+	hasher.update(salt);
+	hasher.update(ip.as_bytes());
+
+	format!("{:x}", hasher.finalize())
+}
+
+/// Hashes a long random token (API key, refresh token) for storage. They can't be guessed, so no salt is needed.
+pub fn hash_token(token: &str) -> String {
+	format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Gets the client IP. Behind a reverse proxy that's the last `X-Forwarded-For` entry, the one the proxy added, as
+/// clients can send the header with anything in it. Otherwise it's the socket address.
+pub fn client_ip(headers: &HeaderMap, remote_addr: Option<SocketAddr>, behind_proxy: bool) -> Option<String> {
+	let forwarded = headers
+		.get("x-forwarded-for")
+		.filter(|_| behind_proxy)
+		.and_then(|v| v.to_str().ok())
+		.and_then(|v| v.rsplit(',').next())
+		.map(|v| v.trim().to_string())
+		.filter(|v| !v.is_empty());
+
+	forwarded.or_else(|| remote_addr.map(|addr| addr.ip().to_string()))
 }
 
 #[cfg(test)]
 mod test {
 	use super::*;
 	use crate::util::strip_protocol;
-	use chrono::NaiveDateTime;
 
 	#[test]
 	fn strip_localhost_test() {
@@ -62,38 +91,32 @@ mod test {
 	}
 
 	#[test]
-	fn test_is_admin_when_user_is_admin() {
-		let user = Some(User {
-			id: 1,
-			username: "admin".to_string(),
-			email: "admin@example.com".to_string(),
-			password_hash: "hash".to_string(),
-			verified_at: None,
-			is_admin: true,
-			created_at: NaiveDateTime::from_timestamp(0, 0),
-			deleted_at: None,
-		});
-		assert!(is_admin(user));
+	fn hash_ip_is_salted_test() {
+		let hash = hash_ip("127.0.0.1", b"salt");
+
+		assert_eq!(hash.len(), 64);
+		assert_eq!(hash, hash_ip("127.0.0.1", b"salt"));
+		assert_ne!(hash, hash_ip("127.0.0.1", b"other"));
 	}
 
 	#[test]
-	fn test_is_admin_when_user_is_not_admin() {
-		let user = Some(User {
-			id: 2,
-			username: "user".to_string(),
-			email: "user@example.com".to_string(),
-			password_hash: "hash".to_string(),
-			verified_at: None,
-			is_admin: false,
-			created_at: NaiveDateTime::from_timestamp(0, 0),
-			deleted_at: None,
-		});
-		assert!(!is_admin(user));
+	fn client_ip_behind_proxy_test() {
+		let mut headers = HeaderMap::new();
+		headers.insert("x-forwarded-for", "198.51.100.1, 203.0.113.7".parse().unwrap());
+
+		let remote = "10.0.0.2:1234".parse().ok();
+
+		assert_eq!(client_ip(&headers, remote, true), Some("203.0.113.7".to_string()));
+		assert_eq!(client_ip(&HeaderMap::new(), remote, true), Some("10.0.0.2".to_string()));
 	}
 
 	#[test]
-	fn test_is_admin_when_user_is_none() {
-		let user: Option<User> = None;
-		assert!(!is_admin(user));
+	fn client_ip_ignores_forwarded_for_without_proxy_test() {
+		let mut headers = HeaderMap::new();
+		headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+
+		let remote = "10.0.0.2:1234".parse().ok();
+
+		assert_eq!(client_ip(&headers, remote, false), Some("10.0.0.2".to_string()));
 	}
 }
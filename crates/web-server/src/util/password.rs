@@ -0,0 +1,38 @@
+use argon2::{
+	password_hash::{rand_core::OsRng, Error, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+	Argon2,
+};
+
+/// Hashes a password to a PHC string ($argon2id$v=19$...)
+pub fn hash_password(password: &str) -> Result<String, Error> {
+	let salt = SaltString::generate(&mut OsRng);
+
+	let argon2 = Argon2::default();
+
+	argon2.hash_password(password.as_bytes(), &salt).map(|hash| hash.to_string())
+}
+
+/// Verifies a password against a PHC string. Errors if the stored hash can't be parsed.
+pub fn verify_password(password: &str, password_hash: &str) -> Result<bool, Error> {
+	let parsed_hash = PasswordHash::new(password_hash)?;
+
+	Ok(Argon2::default().verify_password(password.as_bytes(), &parsed_hash).is_ok())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn hash_and_verify_test() {
+		let hash = hash_password("correct horse battery staple").unwrap();
+
+		assert!(verify_password("correct horse battery staple", &hash).unwrap());
+		assert!(!verify_password("wrong", &hash).unwrap());
+	}
+
+	#[test]
+	fn verify_invalid_hash_test() {
+		assert!(verify_password("password", "not a hash").is_err());
+	}
+}
@@ -0,0 +1,117 @@
+use qrcode::{render::svg, QrCode};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use super::{generate_unique_string, hash_token};
+
+/// Shown as the account's provider in authenticator apps
+const TOTP_ISSUER: &str = "Shurlix";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+/// How many steps before or after the current one are accepted, to allow for clock drift
+const TOTP_SKEW_STEPS: i64 = 1;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Generates a new base32 encoded TOTP secret
+pub fn generate_totp_secret() -> String {
+	Secret::generate_secret().to_encoded().to_string()
+}
+
+/// Builds the TOTP generator for a secret, labeled with the user's email
+pub fn build_totp(secret: &str, account_name: &str) -> Option<TOTP> {
+	let secret = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+
+	TOTP::new(
+		Algorithm::SHA1,
+		TOTP_DIGITS,
+		TOTP_SKEW_STEPS as u8,
+		TOTP_STEP_SECONDS,
+		secret,
+		Some(TOTP_ISSUER.to_string()),
+		// Colons separate the issuer from the account in otpauth URIs
+		account_name.replace(':', ""),
+	)
+	.ok()
+}
+
+/// Renders the otpauth URI as a QR code SVG
+pub fn totp_qr_code_svg(uri: &str) -> Option<String> {
+	let code = QrCode::new(uri.as_bytes()).ok()?;
+
+	Some(code.render::<svg::Color>().min_dimensions(200, 200).build())
+}
+
+/// Checks a code against the steps around `now`, skipping steps up to and including `last_step`
+/// so codes can't be replayed. Returns the step the code belongs to.
+pub fn verify_totp_code(totp: &TOTP, code: &str, now: u64, last_step: Option<i64>) -> Option<i64> {
+	let code = code.trim();
+	let current_step = (now / TOTP_STEP_SECONDS) as i64;
+
+	(current_step - TOTP_SKEW_STEPS..=current_step + TOTP_SKEW_STEPS)
+		.filter(|step| *step >= 0 && last_step.is_none_or(|last_step| *step > last_step))
+		.find(|step| constant_time_eq(&totp.generate(*step as u64 * TOTP_STEP_SECONDS), code))
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+	a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Generates recovery codes, formatted like `abcde-12345`
+pub fn generate_recovery_codes() -> Vec<String> {
+	(0..RECOVERY_CODE_COUNT)
+		.map(|_| {
+			let code = generate_unique_string(10).to_lowercase();
+			format!("{}-{}", &code[..5], &code[5..])
+		})
+		.collect()
+}
+
+/// Hashes a recovery code, ignoring case, dashes and spaces
+pub fn hash_recovery_code(code: &str) -> String {
+	let normalized: String = code
+		.chars()
+		.filter(|c| !c.is_whitespace() && *c != '-')
+		.collect::<String>()
+		.to_lowercase();
+
+	hash_token(&normalized)
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+	#[test]
+	fn test_verify_totp_code() {
+		let secret = generate_totp_secret();
+		let totp = build_totp(&secret, "user@example.com").unwrap();
+		let now = 1_700_000_000;
+		let step = (now / TOTP_STEP_SECONDS) as i64;
+
+		let code = totp.generate(now);
+		assert_eq!(verify_totp_code(&totp, &code, now, None), Some(step));
+		// Codes from the previous step are still accepted
+		assert_eq!(verify_totp_code(&totp, &code, now + TOTP_STEP_SECONDS, None), Some(step));
+		// But not once they've been used
+		assert_eq!(verify_totp_code(&totp, &code, now, Some(step)), None);
+		assert_eq!(verify_totp_code(&totp, "000000x", now, None), None);
+	}
+
+	#[test]
+	fn test_otpauth_uri() {
+		let totp = build_totp(&generate_totp_secret(), "a:b@example.com").unwrap();
+		let uri = totp.get_url();
+
+		assert!(uri.starts_with("otpauth://totp/Shurlix:ab%40example.com?"));
+		assert!(totp_qr_code_svg(&uri).unwrap().contains("<svg"));
+	}
+
+	#[test]
+	fn test_recovery_codes() {
+		let codes = generate_recovery_codes();
+
+		assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+		assert_eq!(codes[0].len(), 11);
+		assert_eq!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[0].replace('-', "").to_uppercase()));
+	}
+}